@@ -0,0 +1,149 @@
+// Measures how long `biver commit` takes on a large image compared to running its steps one after another.
+// Run with `cargo bench --bench commit`. BIVER_BENCH_MEGAPIXELS controls the image size (default 100, about 300 MB).
+
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ImageEncoder, RgbImage};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use std::{env, fs};
+use xxhash_rust::xxh3::Xxh3;
+
+const BIVER: &str = env!("CARGO_BIN_EXE_biver");
+
+fn main() {
+    let megapixels: u32 = env::var("BIVER_BENCH_MEGAPIXELS").ok().and_then(|v| v.parse().ok()).unwrap_or(100);
+    let side = ((megapixels as f64 * 1_000_000.0).sqrt()) as u32;
+
+    let bench_dir = env::temp_dir().join(format!("biver-bench-{}", std::process::id()));
+    fs::create_dir_all(&bench_dir).unwrap();
+
+    let versioned_file = bench_dir.join("bench.png");
+    let previous_content = bench_dir.join("previous.png");
+
+    println!("Generating a {}x{} image", side, side);
+    write_noise_image(&versioned_file, side, 0);
+    fs::copy(&versioned_file, &previous_content).unwrap();
+    println!("Image size: {} MB", fs::metadata(&versioned_file).unwrap().len() / 1_000_000);
+
+    let init_time = time(|| run_biver(&versioned_file, "init"));
+    println!("init:   {:>8.2?}", init_time);
+
+    write_noise_image(&versioned_file, side, side / 4);
+
+    let commit_time = time(|| run_biver(&versioned_file, "commit"));
+    println!("commit: {:>8.2?}", commit_time);
+
+    let hash_time = time(|| {
+        hash_file(&versioned_file);
+    });
+    println!("Sequential steps for reference:");
+    println!("  hash:    {:>8.2?}", hash_time);
+
+    let mut sequential_time = hash_time;
+
+    let preview_output = bench_dir.join("preview.jpg");
+    let preview_time = time_tool(|| {
+        run_tool(
+            env::var_os("BIVER_IMAGE_MAGICK_PATH").map(PathBuf::from).unwrap_or("magick".into()),
+            &[
+                versioned_file.as_os_str(),
+                "-flatten".as_ref(),
+                "-thumbnail".as_ref(),
+                "1024x1024>".as_ref(),
+                preview_output.as_os_str(),
+            ],
+        )
+    });
+    report_tool_time("preview", preview_time, &mut sequential_time);
+
+    let patch_output = bench_dir.join("patch.xdelta");
+    let patch_time = time_tool(|| {
+        run_tool(
+            env::var_os("BIVER_XDELTA3_PATH").map(PathBuf::from).unwrap_or("xdelta3".into()),
+            &[
+                "-e".as_ref(),
+                "-s".as_ref(),
+                previous_content.as_os_str(),
+                versioned_file.as_os_str(),
+                patch_output.as_os_str(),
+            ],
+        )
+    });
+    report_tool_time("patch", patch_time, &mut sequential_time);
+
+    println!("  total:   {:>8.2?}", sequential_time);
+    println!("Speedup: {:.2}x", sequential_time.as_secs_f64() / commit_time.as_secs_f64());
+
+    fs::remove_dir_all(&bench_dir).unwrap();
+}
+
+fn write_noise_image(path: &Path, side: u32, changed_rows: u32) {
+    let mut state: u32 = 0x9E3779B9;
+    let image = RgbImage::from_fn(side, side, |_, y| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let value = if y < changed_rows { !state } else { state };
+        image::Rgb([value as u8, (value >> 8) as u8, (value >> 16) as u8])
+    });
+
+    let writer = BufWriter::new(File::create(path).unwrap());
+    PngEncoder::new_with_quality(writer, CompressionType::Fast, FilterType::NoFilter)
+        .write_image(&image, side, side, image::ExtendedColorType::Rgb8)
+        .unwrap();
+}
+
+fn hash_file(path: &Path) -> u128 {
+    let mut reader = BufReader::new(File::open(path).unwrap());
+    let mut hasher = Xxh3::new();
+    let mut buffer = [0; 8192];
+
+    loop {
+        let bytes_read = reader.read(&mut buffer).unwrap();
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    hasher.digest128()
+}
+
+fn run_biver(versioned_file: &Path, command: &str) {
+    let status = Command::new(BIVER).arg(command).arg("-f").arg(versioned_file).stdout(Stdio::null()).status().unwrap();
+    assert!(status.success(), "biver {} failed", command);
+}
+
+fn run_tool(program: PathBuf, args: &[&std::ffi::OsStr]) -> bool {
+    Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn time_tool(f: impl FnOnce() -> bool) -> Option<Duration> {
+    let start = Instant::now();
+    let succeeded = f();
+    succeeded.then(|| start.elapsed())
+}
+
+fn report_tool_time(name: &str, tool_time: Option<Duration>, sequential_time: &mut Duration) {
+    match tool_time {
+        Some(tool_time) => {
+            println!("  {:<8} {:>8.2?}", format!("{}:", name), tool_time);
+            *sequential_time += tool_time;
+        }
+        None => println!("  {:<8} skipped (tool not available)", format!("{}:", name)),
+    }
+}
@@ -0,0 +1,69 @@
+use crate::biver_result::{BiverResult, error};
+use crate::command_line_arguments::CommandLineArguments;
+use crate::config::Alias;
+use clap::CommandFactory;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+
+// The alias is replaced with its words and the arguments after it follow them, so the alias works as a command
+// with default arguments. Aliases are expanded once, an alias cannot refer to another alias.
+pub fn expand(arguments: Vec<OsString>, aliases: &BTreeMap<String, Alias>) -> BiverResult<Vec<OsString>> {
+    let command = CommandLineArguments::command();
+
+    for (name, alias) in aliases {
+        if name.is_empty() || name.starts_with('-') || name.contains(char::is_whitespace) {
+            return error(format!("Invalid alias name \"{}\"", name));
+        }
+
+        if command.find_subcommand(name).is_some() {
+            return error(format!("Alias {} has the name of a command", name));
+        }
+
+        if alias.words().is_empty() {
+            return error(format!("Alias {} is empty", name));
+        }
+    }
+
+    let Some(subcommand_index) = subcommand_index(&arguments) else {
+        return Ok(arguments);
+    };
+
+    let Some(alias) = arguments[subcommand_index].to_str().and_then(|name| aliases.get(name)) else {
+        return Ok(arguments);
+    };
+
+    let mut expanded = arguments[..subcommand_index].to_vec();
+    expanded.extend(alias.words().into_iter().map(OsString::from));
+    expanded.extend_from_slice(&arguments[subcommand_index + 1..]);
+
+    Ok(expanded)
+}
+
+// Global options may come before the command, those with a separate value are skipped along with it
+fn subcommand_index(arguments: &[OsString]) -> Option<usize> {
+    let command = CommandLineArguments::command();
+
+    let takes_separate_value = |argument: &str| {
+        command
+            .get_arguments()
+            .filter(|a| a.get_action().takes_values())
+            .any(|a| a.get_long().is_some_and(|long| argument.strip_prefix("--") == Some(long)) || a.get_short().is_some_and(|short| argument == format!("-{}", short)))
+    };
+
+    let mut index = 1;
+    while let Some(argument) = arguments.get(index) {
+        let argument = argument.to_str()?;
+
+        if argument == "--" {
+            return None;
+        }
+
+        if !argument.starts_with('-') {
+            return Some(index);
+        }
+
+        index += if takes_separate_value(argument) { 2 } else { 1 };
+    }
+
+    None
+}
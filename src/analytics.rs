@@ -0,0 +1,124 @@
+use crate::repository_data::{ContentBlob, RepositoryData, Version};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, TimeDelta, Utc, Weekday};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct HistoryStats<'a> {
+    pub version_count: usize,
+    pub branch_count: usize,
+    pub first_version_time: DateTime<Utc>,
+    pub last_version_time: DateTime<Utc>,
+    // None with a single version
+    pub average_seconds_between_versions: Option<i64>,
+    pub full_version_count: usize,
+    pub patch_version_count: usize,
+    pub weeks: Vec<WeekStats>,
+    pub weekdays: Vec<WeekdayStats>,
+    pub sizes: Vec<SizeStats<'a>>,
+}
+
+// Weeks start on Monday in local time. Weeks without versions are included, so the series can be plotted as is.
+#[derive(Serialize)]
+pub struct WeekStats {
+    pub week_start: NaiveDate,
+    pub version_count: usize,
+    // Size of the newest version created up to the end of the week
+    pub size: u64,
+}
+
+#[derive(Serialize)]
+pub struct WeekdayStats {
+    pub weekday: Weekday,
+    pub version_count: usize,
+}
+
+#[derive(Serialize)]
+pub struct SizeStats<'a> {
+    pub creation_time: DateTime<Utc>,
+    pub nickname: &'a str,
+    pub size: u64,
+}
+
+impl HistoryStats<'_> {
+    pub fn average_time_between_versions(&self) -> Option<TimeDelta> {
+        self.average_seconds_between_versions.map(TimeDelta::seconds)
+    }
+}
+
+pub fn history_stats(repo_data: &RepositoryData) -> HistoryStats<'_> {
+    let mut versions: Vec<&Version> = repo_data.versions.iter().collect();
+    versions.sort_by_key(|v| v.creation_time);
+
+    let first_version = versions.first().expect("Repository must have at least one version");
+    let last_version = versions.last().expect("Repository must have at least one version");
+
+    let average_seconds_between_versions = (versions.len() > 1).then(|| (last_version.creation_time - first_version.creation_time).num_seconds() / (versions.len() as i64 - 1));
+
+    let patch_version_count = versions.iter().filter(|v| matches!(v.content_blob, ContentBlob::Patch { .. })).count();
+
+    HistoryStats {
+        version_count: versions.len(),
+        branch_count: repo_data.branches.len(),
+        first_version_time: first_version.creation_time,
+        last_version_time: last_version.creation_time,
+        average_seconds_between_versions,
+        full_version_count: versions.len() - patch_version_count,
+        patch_version_count,
+        weeks: week_stats(&versions),
+        weekdays: weekday_stats(&versions),
+        sizes: versions
+            .iter()
+            .map(|v| SizeStats {
+                creation_time: v.creation_time,
+                nickname: &v.nickname,
+                size: v.versioned_file_length,
+            })
+            .collect(),
+    }
+}
+
+// Versions must be sorted by creation time
+fn week_stats(versions: &[&Version]) -> Vec<WeekStats> {
+    let week_start = |version: &Version| {
+        let date = version.creation_time.with_timezone(&Local).date_naive();
+        date - Days::new(date.weekday().num_days_from_monday() as u64)
+    };
+
+    let last_week_start = week_start(versions[versions.len() - 1]);
+
+    let mut weeks = Vec::new();
+    let mut current_week_start = week_start(versions[0]);
+    let mut remaining_versions = versions.iter().peekable();
+    let mut size = 0;
+
+    while current_week_start <= last_week_start {
+        let mut version_count = 0;
+
+        while let Some(version) = remaining_versions.next_if(|v| week_start(v) == current_week_start) {
+            version_count += 1;
+            size = version.versioned_file_length;
+        }
+
+        weeks.push(WeekStats {
+            week_start: current_week_start,
+            version_count,
+            size,
+        });
+
+        current_week_start = current_week_start + Days::new(7);
+    }
+
+    weeks
+}
+
+fn weekday_stats(versions: &[&Version]) -> Vec<WeekdayStats> {
+    let weekdays = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun];
+
+    weekdays
+        .into_iter()
+        .map(|weekday| WeekdayStats {
+            weekday,
+            version_count: versions.iter().filter(|v| v.creation_time.with_timezone(&Local).weekday() == weekday).count(),
+        })
+        .collect()
+}
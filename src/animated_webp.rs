@@ -0,0 +1,104 @@
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageResult, RgbImage};
+use std::io;
+use std::io::{Seek, SeekFrom, Write};
+
+const RIFF_HEADER_SIZE: usize = 12;
+const ANIMATION_FLAG: u8 = 0x02;
+const DO_NOT_BLEND_FLAG: u8 = 0x02;
+const WHITE_BACKGROUND: [u8; 4] = [255, 255, 255, 255];
+const LOOP_FOREVER: u16 = 0;
+
+// The image crate writes WebP stills only. Each frame is encoded as a lossless still, whose image chunk is moved into
+// a frame chunk of the animation. The frames cover the whole canvas, so nothing is blended or disposed.
+pub struct AnimatedWebPEncoder<W: Write + Seek> {
+    writer: W,
+    riff_payload_size: u32,
+}
+
+impl<W: Write + Seek> AnimatedWebPEncoder<W> {
+    pub fn new(mut writer: W, width: u32, height: u32) -> io::Result<Self> {
+        // The RIFF size is filled in by finish, once every frame is written
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"WEBP")?;
+
+        let mut encoder = AnimatedWebPEncoder { writer, riff_payload_size: 4 };
+
+        let mut vp8x = vec![ANIMATION_FLAG, 0, 0, 0];
+        vp8x.extend_from_slice(&u24(width - 1));
+        vp8x.extend_from_slice(&u24(height - 1));
+        encoder.write_chunk(b"VP8X", &vp8x)?;
+
+        let mut anim = WHITE_BACKGROUND.to_vec();
+        anim.extend_from_slice(&LOOP_FOREVER.to_le_bytes());
+        encoder.write_chunk(b"ANIM", &anim)?;
+
+        Ok(encoder)
+    }
+
+    pub fn encode_frame(&mut self, frame: &RgbImage, duration_ms: u32) -> ImageResult<()> {
+        let mut still = Vec::new();
+        WebPEncoder::new_lossless(&mut still).encode(frame.as_raw(), frame.width(), frame.height(), ExtendedColorType::Rgb8)?;
+        let image_chunk = image_chunk(&still).ok_or_else(|| io::Error::other("The WebP encoder wrote no image chunk"))?;
+
+        let mut anmf = Vec::with_capacity(16 + image_chunk.len());
+        anmf.extend_from_slice(&u24(0));
+        anmf.extend_from_slice(&u24(0));
+        anmf.extend_from_slice(&u24(frame.width() - 1));
+        anmf.extend_from_slice(&u24(frame.height() - 1));
+        anmf.extend_from_slice(&u24(duration_ms));
+        anmf.push(DO_NOT_BLEND_FLAG);
+        anmf.extend_from_slice(image_chunk);
+        self.write_chunk(b"ANMF", &anmf)?;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&self.riff_payload_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    // Chunks of odd size are padded to keep the next one aligned
+    fn write_chunk(&mut self, fourcc: &[u8; 4], payload: &[u8]) -> io::Result<()> {
+        let padding = payload.len() % 2;
+        let chunk_size = 8 + payload.len() + padding;
+        self.riff_payload_size = u32::try_from(self.riff_payload_size as usize + chunk_size).map_err(|_| io::Error::other("The animation is too large for WebP"))?;
+
+        self.writer.write_all(fourcc)?;
+        self.writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+        self.writer.write_all(payload)?;
+        self.writer.write_all(&[0; 1][..padding])?;
+
+        Ok(())
+    }
+}
+
+// The VP8L chunk of a still, along with its header and padding
+fn image_chunk(still: &[u8]) -> Option<&[u8]> {
+    let mut offset = RIFF_HEADER_SIZE;
+
+    while offset + 8 <= still.len() {
+        let fourcc = &still[offset..offset + 4];
+        let payload_size = u32::from_le_bytes(still[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let chunk_end = (offset + 8 + payload_size + payload_size % 2).min(still.len());
+
+        if fourcc == b"VP8L" || fourcc == b"VP8 " {
+            return Some(&still[offset..chunk_end]);
+        }
+
+        offset = chunk_end;
+    }
+
+    None
+}
+
+fn u24(value: u32) -> [u8; 3] {
+    let [b0, b1, b2, _] = value.to_le_bytes();
+    [b0, b1, b2]
+}
@@ -0,0 +1,129 @@
+use crate::biver_result::{BiverError, BiverErrorCategory, BiverErrorSeverity, BiverResult};
+use crate::env::Env;
+use crate::repository_io::RepositoryDataResult;
+use crate::repository_operations::{CommitResult, StashResult};
+use crate::repository_paths::RepositoryPaths;
+use crate::{formatting, repository_io, repository_operations};
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant, SystemTime};
+
+pub enum SaveAction<'a> {
+    Commit,
+    Stash { branch: &'a str },
+}
+
+// Applications rarely write the file in place. Photoshop and Krita write a temporary file next to it and rename it
+// over the original, sometimes after moving the original to a backup name. The parent directory is watched instead
+// of the file, and a save is handled once the file has existed unchanged for the settle time.
+pub fn run(env: &Env, repo_paths: &RepositoryPaths, save_action: SaveAction, settle_time: Duration) -> BiverResult<()> {
+    let Some(versioned_file_name) = repo_paths.versioned_file.file_name() else {
+        return Err(watch_error("The versioned file path has no file name"));
+    };
+
+    let watched_dir = match repo_paths.versioned_file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(|e| watch_error(&e.to_string()))?;
+    watcher.watch(watched_dir, RecursiveMode::NonRecursive).map_err(|e| watch_error(&e.to_string()))?;
+
+    println!("Watching {} for saves. Press Ctrl+C to stop.", repo_paths.versioned_file.display());
+
+    let mut save_deadline: Option<Instant> = None;
+
+    loop {
+        let timeout = match save_deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => Duration::from_secs(3600),
+        };
+
+        match receiver.recv_timeout(timeout) {
+            Ok(Ok(event)) => {
+                if is_save_event(&event, versioned_file_name) {
+                    save_deadline = Some(Instant::now() + settle_time);
+                }
+            }
+            Ok(Err(e)) => return Err(watch_error(&e.to_string())),
+            Err(RecvTimeoutError::Disconnected) => return Err(watch_error("The file system watcher stopped")),
+            Err(RecvTimeoutError::Timeout) => {
+                if save_deadline.is_none() {
+                    continue;
+                };
+
+                // The application may still be in the middle of replacing the file
+                if !settled(repo_paths, settle_time)? {
+                    save_deadline = Some(Instant::now() + settle_time);
+                    continue;
+                }
+
+                save_deadline = None;
+
+                if let Err(e) = handle_save(env, repo_paths, &save_action) {
+                    formatting::print_autosave_error(&e.error_message);
+                }
+            }
+        }
+    }
+}
+
+fn is_save_event(event: &Event, versioned_file_name: &OsStr) -> bool {
+    let relevant_kind = match event.kind {
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => true,
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+        // Reads, including biver's own hashing, are not saves
+        EventKind::Access(_) | EventKind::Any | EventKind::Other => false,
+    };
+
+    relevant_kind && event.paths.iter().any(|path| path.file_name() == Some(versioned_file_name))
+}
+
+fn settled(repo_paths: &RepositoryPaths, settle_time: Duration) -> BiverResult<bool> {
+    let Ok(metadata) = fs::metadata(&repo_paths.versioned_file) else {
+        return Ok(false);
+    };
+
+    let modified = metadata.modified()?;
+    Ok(modified + settle_time <= SystemTime::now())
+}
+
+fn handle_save(env: &Env, repo_paths: &RepositoryPaths, save_action: &SaveAction) -> BiverResult<()> {
+    // The data file is read on every save because other biver commands may have changed it in the meantime
+    let RepositoryDataResult::Initialized(mut repo_data) = repository_io::read_data(repo_paths)? else {
+        return Err(watch_error("Not initialized"));
+    };
+
+    match save_action {
+        // Every save is recorded, also one that returns to content committed before
+        SaveAction::Commit => match repository_operations::commit_version(env, repo_paths, &mut repo_data, None, None, false, true)? {
+            CommitResult::Ok => formatting::print_autosave("Committed", repo_data.head_version()),
+            CommitResult::NothingToCommit => {}
+            CommitResult::HeadMustBeOnBranch => formatting::print_autosave_error("Save not committed: head must be on a branch"),
+            CommitResult::BranchProtected => formatting::print_autosave_error("Save not committed: head branch is protected"),
+            CommitResult::BranchAlreadyExists | CommitResult::InvalidBranchName => unreachable!("No new branch is created"),
+            CommitResult::Duplicate(_) => unreachable!("Duplicates are allowed"),
+        },
+        SaveAction::Stash { branch } => match repository_operations::stash_version(env, repo_paths, &mut repo_data, branch)? {
+            StashResult::Ok(version_id) => formatting::print_autosave("Stashed", repo_data.version(version_id).expect("Stashed version must exist")),
+            StashResult::NothingToStash => {}
+            StashResult::InvalidBranchName => return Err(watch_error("Invalid stash branch name")),
+        },
+    }
+
+    Ok(())
+}
+
+fn watch_error(message: &str) -> BiverError {
+    BiverError {
+        error_message: message.to_string(),
+        severity: BiverErrorSeverity::Error,
+        category: BiverErrorCategory::Io,
+    }
+}
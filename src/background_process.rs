@@ -0,0 +1,87 @@
+use crate::object_store;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::thread::JoinHandle;
+use std::time::Instant;
+use std::{fs, io};
+
+// What writes the output: an external tool, or code of biver running on a thread of its own, like the bundled codec
+// or the stand-ins for external tools in tests
+pub enum Worker {
+    Process(Child),
+    Thread(JoinHandle<io::Result<()>>),
+}
+
+impl Worker {
+    // A process is waited for by the tool that spawned it, which knows what its exit status means
+    pub fn wait(self, wait_for_process: fn(&mut Child) -> io::Result<()>) -> io::Result<()> {
+        match self {
+            Worker::Process(mut child) => wait_for_process(&mut child),
+            Worker::Thread(thread) => thread.join().unwrap_or_else(|_| Err(io::Error::other("Background thread panicked."))),
+        }
+    }
+}
+
+// An external tool writing its output into a temporary file while the caller keeps working.
+// Dropping an unfinished process kills it and removes the temporary file. A thread cannot be killed, so it is waited for.
+pub struct BackgroundProcess {
+    worker: Option<Worker>,
+    output_path: PathBuf,
+    wait: fn(&mut Child) -> io::Result<()>,
+    started: Instant,
+}
+
+impl BackgroundProcess {
+    pub fn start(output_path: PathBuf, spawn: impl FnOnce(&Path) -> io::Result<Worker>, wait: fn(&mut Child) -> io::Result<()>) -> io::Result<Self> {
+        if fs::exists(&output_path)? {
+            fs::remove_file(&output_path)?;
+        }
+
+        let worker = spawn(&output_path)?;
+
+        Ok(Self {
+            worker: Some(worker),
+            output_path,
+            wait,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn wait(&mut self) -> io::Result<&Path> {
+        if let Some(worker) = self.worker.take() {
+            worker.wait(self.wait)?;
+            tracing::debug!("{} written after {:?}", self.output_path.display(), self.started.elapsed());
+        }
+
+        Ok(&self.output_path)
+    }
+
+    pub fn keep_as(mut self, destination_path: &Path) -> io::Result<()> {
+        self.wait()?;
+
+        object_store::prepare_object_path(destination_path)?;
+        fs::rename(&self.output_path, destination_path)?;
+        tracing::trace!("Moved {} to {}", self.output_path.display(), destination_path.display());
+
+        Ok(())
+    }
+}
+
+impl Drop for BackgroundProcess {
+    fn drop(&mut self) {
+        match self.worker.take() {
+            Some(Worker::Process(mut child)) => {
+                tracing::debug!("Killing unfinished process {}", child.id());
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            Some(worker @ Worker::Thread(_)) => {
+                tracing::debug!("Waiting for unfinished thread writing {}", self.output_path.display());
+                let _ = worker.wait(|_| Ok(()));
+            }
+            None => {}
+        }
+
+        let _ = fs::remove_file(&self.output_path);
+    }
+}
@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::io;
+
+// What was copied to each backup destination, kept in the repository directory. Version records are rewritten when
+// notes are added, so objects are compared by size and modification time rather than by name alone.
+#[derive(Default, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub destinations: BTreeMap<String, BackupRecord>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct BackupRecord {
+    pub last_backup_time: Option<DateTime<Utc>>,
+    pub objects: BTreeMap<String, ObjectStat>,
+}
+
+#[derive(PartialEq, Serialize, Deserialize)]
+pub struct ObjectStat {
+    pub length: u64,
+    pub modified_time: DateTime<Utc>,
+}
+
+impl ObjectStat {
+    pub fn of(metadata: &Metadata) -> io::Result<Self> {
+        Ok(ObjectStat {
+            length: metadata.len(),
+            modified_time: metadata.modified()?.into(),
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use crate::repository_data::{Head, RepositoryData};
+use crate::version_id::VersionId;
+use serde::{Deserialize, Serialize};
+
+// Kept in the repository directory between bisect commands
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BisectState {
+    // Where head was when bisecting started, reset returns to it
+    pub original_head: Head,
+    pub good: Option<VersionId>,
+    pub bad: Option<VersionId>,
+}
+
+pub enum BisectStep {
+    WaitingForGood,
+    WaitingForBad,
+    Test {
+        version_id: VersionId,
+        remaining_versions: usize,
+        remaining_steps: u32,
+    },
+    Found(VersionId),
+}
+
+// Versions strictly between good and bad, newest first. None when good is not an ancestor of bad.
+fn candidates(repo_data: &RepositoryData, good: VersionId, bad: VersionId) -> Option<Vec<VersionId>> {
+    let mut candidates = Vec::new();
+
+    for version in repo_data.iter_version_and_ancestors(bad).skip(1) {
+        if version.id == good {
+            return Some(candidates);
+        }
+
+        candidates.push(version.id);
+    }
+
+    None
+}
+
+// The state outlives the command that wrote it, so the versions it marks may have been reset or pruned away meanwhile
+pub fn is_stale(repo_data: &RepositoryData, state: &BisectState) -> bool {
+    [state.good, state.bad].into_iter().flatten().any(|version_id| repo_data.version(version_id).is_none())
+}
+
+// The version in the middle is tested next, halving the versions left whichever way it turns out.
+// None when good is not an ancestor of bad.
+pub fn next_step(repo_data: &RepositoryData, state: &BisectState) -> Option<BisectStep> {
+    let (good, bad) = match (state.good, state.bad) {
+        (None, _) => return Some(BisectStep::WaitingForGood),
+        (_, None) => return Some(BisectStep::WaitingForBad),
+        (Some(good), Some(bad)) => (good, bad),
+    };
+
+    let candidates = candidates(repo_data, good, bad)?;
+
+    if candidates.is_empty() {
+        return Some(BisectStep::Found(bad));
+    }
+
+    Some(BisectStep::Test {
+        version_id: candidates[candidates.len() / 2],
+        remaining_versions: candidates.len(),
+        remaining_steps: (candidates.len() + 1).next_power_of_two().trailing_zeros(),
+    })
+}
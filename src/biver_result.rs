@@ -1,3 +1,4 @@
+use serde::Serialize;
 use std::fmt::{Debug, Display, Formatter};
 
 pub type BiverResult<T> = Result<T, BiverError>;
@@ -6,14 +7,43 @@ pub type BiverResult<T> = Result<T, BiverError>;
 pub struct BiverError {
     pub error_message: String,
     pub severity: BiverErrorSeverity,
+    pub category: BiverErrorCategory,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BiverErrorSeverity {
     Error,
     Warning,
 }
 
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BiverErrorCategory {
+    General,
+    NotInitialized,
+    InvalidTarget,
+    UncommittedChanges,
+    DependencyMissing,
+    Io,
+    Corruption,
+}
+
+impl BiverErrorCategory {
+    // Scripts rely on these, so existing codes must never change. 2 is taken by clap for invalid arguments.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            BiverErrorCategory::General => 1,
+            BiverErrorCategory::NotInitialized => 3,
+            BiverErrorCategory::InvalidTarget => 4,
+            BiverErrorCategory::UncommittedChanges => 5,
+            BiverErrorCategory::DependencyMissing => 6,
+            BiverErrorCategory::Io => 7,
+            BiverErrorCategory::Corruption => 8,
+        }
+    }
+}
+
 impl Display for BiverError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let severity = match self.severity {
@@ -30,6 +60,17 @@ impl From<eframe::Error> for BiverError {
         Self {
             error_message: format!("eframe/egui failure: {}", value),
             severity: BiverErrorSeverity::Error,
+            category: BiverErrorCategory::General,
+        }
+    }
+}
+
+impl From<arboard::Error> for BiverError {
+    fn from(value: arboard::Error) -> Self {
+        Self {
+            error_message: format!("clipboard failure: {}", value),
+            severity: BiverErrorSeverity::Error,
+            category: BiverErrorCategory::General,
         }
     }
 }
@@ -39,6 +80,7 @@ impl From<image::ImageError> for BiverError {
         Self {
             error_message: format!("image failure: {}", value),
             severity: BiverErrorSeverity::Error,
+            category: BiverErrorCategory::General,
         }
     }
 }
@@ -48,6 +90,7 @@ impl From<serde_json::Error> for BiverError {
         Self {
             error_message: format!("serde_json failure: {}", value),
             severity: BiverErrorSeverity::Error,
+            category: BiverErrorCategory::Corruption,
         }
     }
 }
@@ -57,22 +100,37 @@ impl From<std::io::Error> for BiverError {
         Self {
             error_message: format!("io failure: {}", value),
             severity: BiverErrorSeverity::Error,
+            category: BiverErrorCategory::Io,
+        }
+    }
+}
+
+impl From<ureq::Error> for BiverError {
+    fn from(value: ureq::Error) -> Self {
+        Self {
+            error_message: format!("ureq failure: {}", value),
+            severity: BiverErrorSeverity::Error,
+            category: BiverErrorCategory::Io,
         }
     }
 }
 
 pub fn error<T>(message: impl Into<String>) -> BiverResult<T> {
+    categorized_error(BiverErrorCategory::General, message)
+}
+
+pub fn categorized_error<T>(category: BiverErrorCategory, message: impl Into<String>) -> BiverResult<T> {
     Err(BiverError {
         error_message: message.into(),
         severity: BiverErrorSeverity::Error,
-    }
-    .into())
+        category,
+    })
 }
 
 pub fn warning<T>(message: impl Into<String>) -> BiverResult<T> {
     Err(BiverError {
         error_message: message.into(),
         severity: BiverErrorSeverity::Warning,
-    }
-    .into())
+        category: BiverErrorCategory::General,
+    })
 }
@@ -0,0 +1,136 @@
+use crate::hash::FileHash;
+use crate::repository_data::{RepositoryData, Version};
+use crate::repository_paths::RepositoryPaths;
+use crate::version_id::VersionId;
+use crate::{migrations, object_store, repository_io};
+use std::collections::{HashMap, HashSet};
+use std::{fs, io};
+
+const FULL_BLOB_SUFFIX: &str = "_full";
+const PATCH_BLOB_SUFFIX: &str = "_patch";
+const PREVIEW_BLOB_SUFFIX: &str = "_preview";
+const LEGACY_CONTENT_BLOB_SUFFIX: &str = "_content";
+const VERSION_RECORD_SUFFIX: &str = "_version";
+
+pub fn full_blob_file_name(content_hash: u128) -> String {
+    format!("{:032x}{}", content_hash, FULL_BLOB_SUFFIX)
+}
+
+pub fn patch_blob_file_name(content_hash: u128) -> String {
+    format!("{:032x}{}", content_hash, PATCH_BLOB_SUFFIX)
+}
+
+pub fn preview_blob_file_name(content_hash: u128) -> String {
+    format!("{:032x}{}", content_hash, PREVIEW_BLOB_SUFFIX)
+}
+
+pub fn version_record_file_name(version_id: VersionId) -> String {
+    format!("{}{}", version_id.bs58(), VERSION_RECORD_SUFFIX)
+}
+
+pub fn is_version_record_file_name(file_name: &str) -> bool {
+    file_name.ends_with(VERSION_RECORD_SUFFIX)
+}
+
+pub enum ObjectKind {
+    Full,
+    Patch,
+    Preview,
+    VersionRecord,
+}
+
+pub fn object_kind(object_name: &str) -> Option<ObjectKind> {
+    if object_name.ends_with(FULL_BLOB_SUFFIX) || object_name.ends_with(LEGACY_CONTENT_BLOB_SUFFIX) {
+        Some(ObjectKind::Full)
+    } else if object_name.ends_with(PATCH_BLOB_SUFFIX) {
+        Some(ObjectKind::Patch)
+    } else if object_name.ends_with(PREVIEW_BLOB_SUFFIX) {
+        Some(ObjectKind::Preview)
+    } else if object_name.ends_with(VERSION_RECORD_SUFFIX) {
+        Some(ObjectKind::VersionRecord)
+    } else {
+        None
+    }
+}
+
+pub fn is_blob_file_name(file_name: &str) -> bool {
+    [FULL_BLOB_SUFFIX, PATCH_BLOB_SUFFIX, PREVIEW_BLOB_SUFFIX, LEGACY_CONTENT_BLOB_SUFFIX]
+        .iter()
+        .any(|suffix| file_name.ends_with(suffix))
+}
+
+// The names biver itself gives objects: a content hash in hex, or a version id as a UUID in repositories from before
+// hashes named blobs, followed by the suffix of the kind. Version records are named after the version id in base58.
+pub fn is_valid_object_name(object_name: &str) -> bool {
+    let hash_named = [FULL_BLOB_SUFFIX, PATCH_BLOB_SUFFIX, PREVIEW_BLOB_SUFFIX, LEGACY_CONTENT_BLOB_SUFFIX].iter().any(|suffix| {
+        object_name.strip_suffix(suffix).is_some_and(|stem| match stem.len() {
+            32 => stem.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')),
+            36 => stem.bytes().all(|b| b.is_ascii_hexdigit() || b == b'-'),
+            _ => false,
+        })
+    });
+
+    hash_named || object_name.strip_suffix(VERSION_RECORD_SUFFIX).and_then(VersionId::from_bs58).is_some()
+}
+
+// Full digests are compared where both versions have one, so that content made to collide in the first half of its
+// BLAKE3 hash does not take over the blobs of another version
+pub fn find_identical_content(repo_data: &RepositoryData, file_hash: FileHash, length: u64) -> Option<&Version> {
+    repo_data.versions.iter().find(|v| has_content(v, file_hash, length))
+}
+
+pub fn has_content(version: &Version, file_hash: FileHash, length: u64) -> bool {
+    version.versioned_file_hash == file_hash.hash
+        && version.versioned_file_length == length
+        && (version.versioned_file_digest.is_none() || file_hash.digest.is_none() || version.versioned_file_digest == file_hash.digest)
+}
+
+pub fn reference_counts(repo_data: &RepositoryData) -> HashMap<&str, usize> {
+    let mut reference_counts = HashMap::new();
+
+    for blob_file_name in repo_data.versions_and_orphans().flat_map(|v| v.blob_file_names()) {
+        *reference_counts.entry(blob_file_name).or_insert(0) += 1;
+    }
+
+    reference_counts
+}
+
+pub fn unreferenced_blob_file_names(repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> io::Result<Vec<String>> {
+    let reference_counts = reference_counts(repo_data);
+    let version_record_file_names: HashSet<_> = repo_data.versions_and_orphans().map(|v| version_record_file_name(v.id)).collect();
+    let backup_object_names = backup_referenced_object_names(repo_paths)?;
+
+    let unreferenced = object_store::list_object_names(repo_paths)?
+        .into_iter()
+        .filter(|object_name| reference_counts.get(object_name.as_str()).copied().unwrap_or(0) == 0)
+        .filter(|object_name| !version_record_file_names.contains(object_name))
+        .filter(|object_name| !backup_object_names.contains(object_name))
+        .collect();
+
+    Ok(unreferenced)
+}
+
+// Repair restores data.json from its backups, so the objects a backup uses stay until it rotates out.
+// Backups that cannot be parsed cannot be restored either and keep nothing.
+pub fn backup_referenced_object_names(repo_paths: &RepositoryPaths) -> io::Result<HashSet<String>> {
+    let mut object_names = HashSet::new();
+
+    for backup_path in repository_io::data_backup_paths(repo_paths) {
+        let backup_contents = match fs::read(&backup_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        let Ok(backup_data) = migrations::parse_data(&backup_contents) else {
+            continue;
+        };
+
+        for version in backup_data.versions_and_orphans() {
+            object_names.extend(version.blob_file_names().into_iter().map(str::to_string));
+            object_names.insert(version_record_file_name(version.id));
+        }
+    }
+
+    Ok(object_names)
+}
@@ -0,0 +1,97 @@
+use crate::repository_data::{RepositoryData, Version};
+use crate::version_id::VersionId;
+use chrono::{DateTime, Local, Utc};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+pub enum ChangelogFormat {
+    Markdown,
+    Csv,
+}
+
+const COLUMNS: [&str; 5] = ["Date", "Nickname", "Branch", "Description", "Size change"];
+
+// Oldest first. A version belongs to every branch it is an ancestor of, shared history lists them all.
+pub fn changelog(repo_data: &RepositoryData, format: ChangelogFormat, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> String {
+    let mut versions: Vec<&Version> = repo_data
+        .versions
+        .iter()
+        .filter(|v| since.is_none_or(|since| v.creation_time >= since))
+        .filter(|v| until.is_none_or(|until| v.creation_time < until))
+        .collect();
+    versions.sort_by_key(|v| v.creation_time);
+
+    let version_branches = version_branches(repo_data);
+
+    let rows: Vec<[String; 5]> = versions
+        .iter()
+        .map(|v| {
+            let parent_length = v.parent.and_then(|p| repo_data.version(p)).map_or(0, |p| p.versioned_file_length);
+            let size_change = v.versioned_file_length as i128 - parent_length as i128;
+
+            [
+                v.creation_time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+                v.nickname.clone(),
+                version_branches.get(&v.id).map(|branches| branches.join(", ")).unwrap_or_default(),
+                v.summary().to_string(),
+                if size_change > 0 { format!("+{} B", size_change) } else { format!("{} B", size_change) },
+            ]
+        })
+        .collect();
+
+    match format {
+        ChangelogFormat::Markdown => markdown_table(&rows),
+        ChangelogFormat::Csv => csv_table(&rows),
+    }
+}
+
+fn version_branches(repo_data: &RepositoryData) -> HashMap<VersionId, Vec<&str>> {
+    let mut branches: Vec<(&String, _)> = repo_data.branches.iter().collect();
+    branches.sort_by_key(|(name, _)| name.as_str());
+
+    let mut version_branches: HashMap<VersionId, Vec<&str>> = HashMap::new();
+
+    for (branch, leaf_id) in branches {
+        for version in repo_data.iter_version_and_ancestors(*leaf_id) {
+            version_branches.entry(version.id).or_default().push(branch);
+        }
+    }
+
+    version_branches
+}
+
+fn markdown_table(rows: &[[String; 5]]) -> String {
+    let mut markdown = String::new();
+
+    let _ = writeln!(markdown, "| {} |", COLUMNS.join(" | "));
+    let _ = writeln!(markdown, "|{}", "---|".repeat(COLUMNS.len()));
+
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        let _ = writeln!(markdown, "| {} |", cells.join(" | "));
+    }
+
+    markdown
+}
+
+fn csv_table(rows: &[[String; 5]]) -> String {
+    let mut csv = String::new();
+
+    let _ = writeln!(csv, "{}", COLUMNS.join(","));
+
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|cell| csv_cell(cell)).collect();
+        let _ = writeln!(csv, "{}", cells.join(","));
+    }
+
+    csv
+}
+
+// RFC 4180: fields with separators, quotes or line breaks are quoted, with quotes doubled
+fn csv_cell(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
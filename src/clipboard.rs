@@ -0,0 +1,62 @@
+use crate::biver_result::BiverResult;
+use crate::diff;
+use crate::env::Env;
+use crate::repository_paths::RepositoryPaths;
+use crate::tools::image_magick;
+use arboard::{Clipboard, ImageData};
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+pub enum CopyImageResult {
+    Ok,
+    NotAnImage,
+}
+
+// Formats the image crate cannot decode, such as PSD, are converted with ImageMagick first when it is available
+pub fn copy_image(env: &Env, repo_paths: &RepositoryPaths, image_path: &Path) -> BiverResult<CopyImageResult> {
+    let image = match diff::decode_image(image_path) {
+        Some(image) => Some(image),
+        None if image_magick::ready(env) => {
+            let converted_path = repo_paths.unique_temp_file_path(env, "clipboard.tmp");
+            let converted = image_magick::convert_to_png(env, image_path, &converted_path)
+                .ok()
+                .and_then(|_| diff::decode_image(&converted_path));
+
+            if fs::exists(&converted_path)? {
+                fs::remove_file(&converted_path)?;
+            }
+
+            converted
+        }
+        None => None,
+    };
+
+    let Some(image) = image else {
+        return Ok(CopyImageResult::NotAnImage);
+    };
+
+    let image = image.to_rgba8();
+    let image_data = ImageData {
+        width: image.width() as usize,
+        height: image.height() as usize,
+        bytes: Cow::Owned(image.into_raw()),
+    };
+
+    set_image(&mut Clipboard::new()?, image_data)?;
+
+    Ok(CopyImageResult::Ok)
+}
+
+// On Linux the clipboard content is served by the program that set it, so this waits until a clipboard manager
+// or the next copy takes it over. With a clipboard manager running that happens right away.
+#[cfg(target_os = "linux")]
+fn set_image(clipboard: &mut Clipboard, image_data: ImageData) -> Result<(), arboard::Error> {
+    use arboard::SetExtLinux;
+    clipboard.set().wait().image(image_data)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_image(clipboard: &mut Clipboard, image_data: ImageData) -> Result<(), arboard::Error> {
+    clipboard.set_image(image_data)
+}
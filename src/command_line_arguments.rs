@@ -1,4 +1,9 @@
-use clap::{Parser, Subcommand};
+use crate::perceptual_hash;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use clap::builder::FalseyValueParser;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -7,25 +12,191 @@ pub struct CommandLineArguments {
     #[arg(global(true), long = "xdelta3-path", env = "BIVER_XDELTA3_PATH")]
     pub xdelta3_path: Option<PathBuf>,
 
-    /// Path to ImageMagick executable. If not specified, it will be searched in PATH.
+    /// Path to ImageMagick executable, either magick or the ImageMagick 6 convert. If not specified, magick and then convert will be searched in PATH.
     #[arg(global(true), long = "image-magick-path", env = "BIVER_IMAGE_MAGICK_PATH")]
     pub image_magick_path: Option<PathBuf>,
 
+    /// Path to git executable. If not specified, it will be searched in PATH.
+    #[arg(global(true), long = "git-path", env = "BIVER_GIT_PATH")]
+    pub git_path: Option<PathBuf>,
+
+    /// Path to rclone executable. If not specified, it will be searched in PATH.
+    #[arg(global(true), long = "rclone-path", env = "BIVER_RCLONE_PATH")]
+    pub rclone_path: Option<PathBuf>,
+
+    /// Path to minisign executable. If not specified, it will be searched in PATH.
+    #[arg(global(true), long = "minisign-path", env = "BIVER_MINISIGN_PATH")]
+    pub minisign_path: Option<PathBuf>,
+
+    /// minisign secret key to sign new versions with. The public key is expected next to it with the .pub extension, e.g. minisign.key and minisign.pub.
+    #[arg(global(true), long = "signing-key", env = "BIVER_SIGNING_KEY")]
+    pub signing_key: Option<PathBuf>,
+
+    /// JSON file with "adjectives" and "nouns" lists to make nicknames from instead of the built-in words. A nickname_words.json file in the repository directory takes precedence.
+    #[arg(global(true), long = "nickname-words", env = "BIVER_NICKNAME_WORDS", value_name = "FILE")]
+    pub nickname_words: Option<PathBuf>,
+
+    /// Editor opened to write the description when committing without one. If not specified, VISUAL and then EDITOR are used.
+    #[arg(global(true), long = "editor", env = "BIVER_EDITOR")]
+    pub editor: Option<String>,
+
+    /// Number of bytes hashed at a time. Larger chunks are faster on big files, smaller ones report progress more often.
+    #[arg(global(true), long = "hash-chunk-size", env = "BIVER_HASH_CHUNK_SIZE", value_name = "BYTES", default_value_t = 8 * 1024 * 1024, value_parser = clap::value_parser!(u64).range(1..))]
+    pub hash_chunk_size: u64,
+
+    /// Keep up to this many bytes of versions reconstructed from patches in the repository directory, so restoring them again skips xdelta3.
+    /// 0 turns the cache off.
+    #[arg(global(true), long = "restore-cache-size", env = "BIVER_RESTORE_CACHE_SIZE", value_name = "BYTES", default_value_t = 256 * 1024 * 1024)]
+    pub restore_cache_size: u64,
+
+    /// Keep what discard, reset --hard and delete branch would lose: the overwritten versioned file, or the content of the deleted branch.
+    /// If not specified, the trash setting of the configuration file is used, which defaults to off.
+    #[arg(global(true), long = "trash", env = "BIVER_TRASH", value_name = "WHERE")]
+    pub trash: Option<TrashArgument>,
+
+    /// Print additional diagnostics, such as hashing progress, external tool command lines and timings. Repeat (-vv) to also trace individual file operations.
+    #[arg(global(true), short = 'v', long = "verbose", env = "BIVER_VERBOSE", action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Also append the diagnostics to biver.log in the repository directory, at least at the -v level. A log of 4 MiB is moved aside to biver.log.1.
+    #[arg(global(true), long = "log", env = "BIVER_LOG", value_parser = FalseyValueParser::new())]
+    pub log: bool,
+
+    /// Show what commit, amend, reset, delete branch, gc and prune would change without changing anything
+    #[arg(global(true), long = "dry-run", env = "BIVER_DRY_RUN", value_parser = FalseyValueParser::new())]
+    pub dry_run: bool,
+
+    /// Only run commands that read the repository, and leave its caches, backups and log alone, e.g. for a repository on a read-only share.
+    /// Temporary files go to the system temporary directory instead of the repository directory.
+    #[arg(global(true), long = "read-only", env = "BIVER_READ_ONLY", value_parser = FalseyValueParser::new())]
+    pub read_only: bool,
+
+    /// Print the output of commands that support it, such as stats and list versions, as JSON, and errors and warnings to stderr as JSON objects
+    /// with their exit code instead of colored text.
+    /// Exit codes: 1 general error, 2 invalid arguments, 3 not initialized, 4 invalid target, 5 uncommitted changes, 6 dependency missing, 7 I/O failure, 8 corrupted repository.
+    #[arg(global(true), long = "json", env = "BIVER_JSON", value_parser = FalseyValueParser::new())]
+    pub json: bool,
+
+    /// Print long output, such as status --all and list versions, directly instead of through the pager. The pager is PAGER, or less if it is not set.
+    #[arg(global(true), long = "no-pager", env = "BIVER_NO_PAGER", value_parser = FalseyValueParser::new())]
+    pub no_pager: bool,
+
+    /// When to color the output. In auto mode, colors are used on terminals unless the NO_COLOR environment variable is set.
+    #[arg(global(true), long = "color", env = "BIVER_COLOR", value_name = "WHEN", default_value = "auto")]
+    pub color: ColorArgument,
+
     #[command(subcommand)]
     pub command: Command,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+pub enum StatusFormatArgument {
+    /// Creation time, humanized creation time, ID, nickname, branches, pin, attributes and description
+    Full,
+    /// Like full, without the exact creation time
+    Compact,
+    /// ID, nickname, branches and description
+    Oneline,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum StatusColumnArgument {
+    Time,
+    Humanized,
+    Id,
+    Nickname,
+    /// Head and branches on the version or forking from it
+    Branches,
+    Pinned,
+    Attributes,
+    Review,
+    Description,
+}
+
+#[derive(Clone, Copy)]
+pub enum PatchLimitArgument {
+    Count(u32),
+    Auto,
+    None,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HashAlgorithmArgument {
+    /// Faster, but collisions can be made on purpose
+    Xxh3,
+    /// Resists deliberately made collisions, for repositories whose versions are signed or verified
+    Blake3,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TrashArgument {
+    Off,
+    /// The trash or recycle bin of the operating system
+    System,
+    /// A trash directory in the repository directory, with the time in the file names
+    Repository,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SeriesOrderArgument {
+    /// By the numbers in the file names, so v2 comes before v10
+    Number,
+    /// By modification time
+    Modified,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ColorArgument {
+    Auto,
+    Always,
+    Never,
+}
+
 #[derive(Subcommand)]
 pub enum Command {
     /// Show the current status of the repository
     #[command(alias = "st")]
     Status {
-        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
-        versioned_file_path: PathBuf,
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE", required_unless_present = "workspace")]
+        versioned_file_path: Option<PathBuf>,
+
+        /// Show the status of every file listed in biver.workspace.toml, found in the current directory or its parents
+        #[arg(long = "workspace")]
+        workspace: bool,
 
         /// Show all versions (by default, limited to 20 most recent)
         #[arg(short = 'a', long = "all")]
         all: bool,
+
+        /// Show the versions leading to this branch instead of those leading to head
+        #[arg(long = "branch", value_name = "NAME", conflicts_with = "workspace")]
+        branch: Option<String>,
+
+        /// Show the versions of all branches, newest first, with the branch each one belongs to
+        #[arg(long = "all-branches", conflicts_with = "branch")]
+        all_branches: bool,
+
+        /// Rehash the versioned file instead of trusting its cached size and modification time
+        #[arg(long = "verify")]
+        verify: bool,
+
+        /// When there are uncommitted changes, also show how they compare to head and a thumbnail of the file as it is. Reads both files and may run ImageMagick.
+        #[arg(long = "pending", conflicts_with = "porcelain")]
+        pending: bool,
+
+        /// Set of columns shown for each version
+        #[arg(long = "format", value_name = "FORMAT", default_value = "full")]
+        format: StatusFormatArgument,
+
+        /// Columns shown for each version, separated by commas, instead of those of the format
+        #[arg(long = "columns", value_name = "COLUMNS", value_delimiter = ',', conflicts_with = "format")]
+        columns: Option<Vec<StatusColumnArgument>>,
+
+        /// Print lines of tab-separated fields for scripts, which stay the same between releases.
+        /// First "head", version ID, branch (empty when detached), "clean" or "modified".
+        /// Then for each version shown, by default head and its ancestors: "version", version ID, parent ID (empty for the root), creation time (RFC 3339, UTC), nickname, branches on the version separated by commas, description summary.
+        #[arg(long = "porcelain", conflicts_with_all = ["format", "columns"])]
+        porcelain: bool,
     },
 
     /// Preview a version
@@ -34,23 +205,135 @@ pub enum Command {
         #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
         versioned_file_path: PathBuf,
 
-        /// Target branch or version to preview. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version nickname (adjective-noun, adjectivenoun, an).
+        /// Target branch or version to preview. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+    },
+
+    /// Show the details and the full description of a version
+    Show {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// (Default: head) Target branch or version to show. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: Option<String>,
+    },
+
+    /// Show statistics of the version history: commit frequency, time between versions, size growth and storage
+    Stats {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+    },
+
+    /// Find versions that look nearly identical to a version, on any branch, by comparing perceptual hashes of their previews
+    Similar {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version to find similar versions of. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
         target: String,
+
+        /// Number of the 64 bits of the perceptual hash that may differ. 0 finds only versions that look the same.
+        #[arg(long = "max-distance", value_name = "BITS", default_value_t = perceptual_hash::DEFAULT_MAX_DISTANCE, value_parser = clap::value_parser!(u32).range(0..=64))]
+        max_distance: u32,
     },
 
-    /// Compare two versions using their previews
+    /// Compare up to four versions using their previews.
+    /// Keys: space/J/K cycle through versions, 1-4 show one, G toggles the grid, B toggles blinking, H toggles histograms, F flips, R resets zoom, Q quits. Scroll zooms and dragging pans all versions together.
     #[command(alias = "cmp")]
     Compare {
         #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
         versioned_file_path: PathBuf,
 
-        /// Target branch or version to compare. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version nickname (adjective-noun, adjectivenoun, an).
+        /// Targets branches or versions to compare. Head is added when only one is given. Each may be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        #[arg(required = true, num_args = 1..=4, value_name = "TARGET")]
+        targets: Vec<String>,
+
+        /// Compare with a version of another versioned file, e.g. one forked from this one. The first target is taken from --file, the second (default: head) from this file.
+        #[arg(long = "other-file", value_name = "OTHER_FILE")]
+        other_file: Option<PathBuf>,
+
+        /// Compare with the versioned file as it is now, uncommitted changes included, instead of head. Up to three targets may be given.
+        #[arg(long = "working", conflicts_with = "other_file")]
+        working: bool,
+
+        /// Start blinking: show one version at a time and switch to the next automatically, which makes subtle differences stand out
+        #[arg(long = "blink")]
+        blink: bool,
+
+        /// Time each version is shown while blinking
+        #[arg(long = "blink-interval", value_name = "MILLISECONDS", default_value_t = 500, value_parser = clap::value_parser!(u64).range(50..))]
+        blink_interval: u64,
+
+        /// Write the previews side by side with their descriptions to an image file instead of opening the viewer, e.g. to attach to a review. The format follows the extension.
+        #[arg(short = 'o', long = "output", value_name = "IMAGE_FILE", conflicts_with = "blink")]
+        output: Option<PathBuf>,
+    },
+
+    /// Write the previews of a branch, from its first version to its last, as an animated GIF or WebP
+    Timelapse {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Branch to follow (default: head)
+        #[arg(long = "branch")]
+        branch: Option<String>,
+
+        /// GIF or WebP file to write, following the extension
+        #[arg(short = 'o', long = "output", value_name = "FILE")]
+        output: PathBuf,
+
+        /// Versions shown per second
+        #[arg(long = "fps", default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=50))]
+        frames_per_second: u32,
+
+        /// Largest width or height of the frames in pixels. Previews are not scaled up.
+        #[arg(long = "size", value_name = "PIXELS", default_value_t = 512, value_parser = clap::value_parser!(u32).range(16..))]
+        max_size: u32,
+
+        /// Leave out the commit time and nickname stamped above each frame
+        #[arg(long = "no-dates")]
+        no_dates: bool,
+    },
+
+    /// Report differences between the contents of two versions without opening a viewer
+    Diff {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version to diff. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
         target1: String,
 
-        /// (Default: head) Target branch or version to compare. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version nickname (adjective-noun, adjectivenoun, an).
+        /// (Default: head) Target branch or version to diff. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
         target2: Option<String>,
     },
 
+    /// Export the version history as a standalone HTML page with embedded previews
+    Report {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Output HTML file path
+        #[arg(short = 'o', long = "output")]
+        output: PathBuf,
+    },
+
+    /// Print a chronological table of versions with their date, nickname, branches, description and size change, oldest first
+    Changelog {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        #[arg(long = "format", value_enum, default_value_t = ChangelogFormatArgument::Md)]
+        format: ChangelogFormatArgument,
+
+        /// Only include versions created at or after this time (YYYY-MM-DD or YYYY-MM-DD HH:MM[:SS], local time)
+        #[arg(long = "since", value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only include versions created at or before this time (YYYY-MM-DD or YYYY-MM-DD HH:MM[:SS], local time)
+        #[arg(long = "until", value_parser = parse_until)]
+        until: Option<DateTime<Utc>>,
+    },
+
     /// Initialize a new repository
     Init {
         #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
@@ -60,18 +343,69 @@ pub enum Command {
         #[arg(short = 'b', long = "branch")]
         initial_branch_name: Option<String>,
 
+        /// Hash to identify versions and check blobs with. If not specified, the hash_algorithm setting of the configuration file is used, which defaults to blake3.
+        #[arg(long = "hash", value_name = "ALGORITHM")]
+        hash_algorithm: Option<HashAlgorithmArgument>,
+
         /// Description of the initial version
         #[arg(value_name = "INITIAL_VERSION_DESCRIPTION")]
         initial_version_description: Option<String>,
     },
 
+    /// Create a repository from copies saved by hand, like drawing_v1.psd to drawing_v27_final.psd. The copies are committed oldest first, described by their names, and left as they are. The newest becomes the versioned file.
+    ImportSeries {
+        /// Copies to import. * and ? are wildcards in the file name.
+        #[arg(long = "pattern")]
+        pattern: String,
+
+        /// The new versioned file
+        #[arg(long = "into", value_name = "FILE")]
+        versioned_file_path: PathBuf,
+
+        /// How the copies are put in order
+        #[arg(long = "order", value_name = "ORDER", default_value = "number")]
+        order: SeriesOrderArgument,
+
+        /// Hash to identify versions and check blobs with. If not specified, the hash_algorithm setting of the configuration file is used, which defaults to blake3.
+        #[arg(long = "hash", value_name = "ALGORITHM")]
+        hash_algorithm: Option<HashAlgorithmArgument>,
+    },
+
     /// Commit current changes to a new version
     #[command(alias = "ct")]
     Commit {
-        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
-        versioned_file_path: PathBuf,
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE", required_unless_present = "workspace")]
+        versioned_file_path: Option<PathBuf>,
+
+        /// Commit every file listed in biver.workspace.toml, found in the current directory or its parents. Files without changes are skipped.
+        #[arg(long = "workspace")]
+        workspace: bool,
+
+        /// Create a new branch with the specified name and commit to it
+        #[arg(short = 'b', long = "branch", value_name = "NAME")]
+        new_branch: Option<String>,
+
+        /// If head is not on a branch, create a branch named after the head version nickname without asking
+        #[arg(long = "branch-auto", conflicts_with = "new_branch")]
+        branch_auto: bool,
+
+        /// Commit even if the head branch is protected
+        #[arg(long = "force")]
+        force: bool,
+
+        /// Commit even if the content is that of the version before head, which undoes the last commit
+        #[arg(long = "allow-duplicate")]
+        allow_duplicate: bool,
+
+        /// Replace the versioned file with what is read from standard input and commit that. Nothing is asked, a detached head gets a branch as with --branch-auto.
+        #[arg(long = "stdin", conflicts_with = "workspace")]
+        stdin: bool,
 
-        /// Description of the new version
+        /// Commit even if someone else holds the editing lock, taking it over
+        #[arg(long = "steal")]
+        steal: bool,
+
+        /// Description of the new version. If not specified in an interactive terminal, the pending change is shown and a new branch and the description are asked for, the description in the editor when one is configured.
         #[arg(value_name = "DESCRIPTION")]
         description: Option<String>,
     },
@@ -85,17 +419,48 @@ pub enum Command {
         #[arg(short = 'y', long = "yes")]
         confirmed: bool,
 
+        /// Amend even if the head branch is protected
+        #[arg(long = "force")]
+        force: bool,
+
+        /// Keep the replaced head version recoverable with recover until gc expires it
+        #[arg(long = "recoverable")]
+        recoverable: bool,
+
+        /// Amend even if someone else holds the editing lock, taking it over
+        #[arg(long = "steal")]
+        steal: bool,
+
         /// New description
         #[arg(value_name = "DESCRIPTION")]
         description: Option<String>,
     },
 
+    /// Bring back a head version replaced by amend --recoverable. If it was replaced by the leaf of its branch, the two swap places, otherwise it is put on a new branch.
+    #[command(alias = "undo")]
+    Recover {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// List the recoverable versions instead
+        #[arg(long = "list")]
+        list: bool,
+
+        /// (Default: the most recently replaced) Nickname or ID of the version to recover
+        #[arg(conflicts_with = "list")]
+        target: Option<String>,
+
+        /// Recover even if the version would take the place of the leaf of a protected branch
+        #[arg(long = "force")]
+        force: bool,
+    },
+
     /// Change description of the specified version
     Reword {
         #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
         versioned_file_path: PathBuf,
 
-        /// Target version to reword. Must be a version ID.
+        /// Target version to reword. May be one of the following (in order of precedence): branch name (its leaf), version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
         target: String,
 
         /// New description
@@ -126,16 +491,187 @@ pub enum Command {
         #[arg(short = 'y', long = "yes")]
         confirmed: bool,
 
-        /// Target version to reset to. Must be a version ID.
+        /// Reset even if the current branch is protected
+        #[arg(long = "force")]
+        force: bool,
+
+        /// Keep the blob files of the erased versions, which gc can delete later
+        #[arg(long = "keep-blobs")]
+        keep_blobs: bool,
+
+        /// Target version to reset to. Must be an ancestor of head. May be one of the following (in order of precedence): branch name (its leaf), version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
         target: String,
     },
 
+    /// Delete old versions according to a retention policy. Branch leaves, head, pinned versions and the root version are always kept.
+    Prune {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Keep all versions younger than this many days
+        #[arg(long = "keep-all-days", value_name = "DAYS", default_value_t = 7, env = "BIVER_KEEP_ALL_DAYS")]
+        keep_all_days: u32,
+
+        /// Keep one version per day for versions younger than this many days, and one version per week for older versions
+        #[arg(long = "keep-daily-days", value_name = "DAYS", default_value_t = 30, env = "BIVER_KEEP_DAILY_DAYS")]
+        keep_daily_days: u32,
+
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long = "yes")]
+        confirmed: bool,
+
+        /// Prune even versions of protected branches
+        #[arg(long = "force")]
+        force: bool,
+    },
+
+    /// Delete blob files that are not referenced by any version
+    Gc {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Versions replaced by amend --recoverable stay recoverable for this many days before their blob files are deleted
+        #[arg(long = "grace-period", value_name = "DAYS", default_value_t = 14)]
+        grace_period_days: u32,
+
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long = "yes")]
+        confirmed: bool,
+
+        /// Number of blob files deleted at once. Defaults to the number of CPU cores.
+        #[arg(short = 'j', long = "jobs", value_name = "N")]
+        jobs: Option<NonZeroUsize>,
+    },
+
+    /// Check blob checksums and reconstruct every version to verify repository integrity
+    Verify {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Number of blobs hashed and versions reconstructed at once. Defaults to the number of CPU cores.
+        #[arg(short = 'j', long = "jobs", value_name = "N")]
+        jobs: Option<NonZeroUsize>,
+    },
+
+    /// Check the signature of every signed version and list who signed it
+    VerifySignatures {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// minisign public key file of a signer to trust. May be repeated. Valid signatures by other keys are reported as made by an unknown key.
+        #[arg(long = "trusted-key", value_name = "PUBLIC_KEY_FILE")]
+        trusted_keys: Vec<PathBuf>,
+    },
+
+    /// Check repository invariants, blob files, patch bases and version records, and suggest a fix for every problem found
+    Doctor {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+    },
+
+    /// Recover a corrupt data file from the freshest valid backup or, as a last resort, from version records
+    Repair {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        #[arg(short = 'y', long = "yes")]
+        confirmed: bool,
+    },
+
+    /// Rename the versioned file together with its repository. If the file was already renamed, only the repository is moved.
+    #[command(alias = "mv")]
+    Move {
+        #[arg(value_name = "OLD_FILE")]
+        old_versioned_file_path: PathBuf,
+
+        #[arg(value_name = "NEW_FILE")]
+        new_versioned_file_path: PathBuf,
+    },
+
+    /// Copy the versioned file together with its whole history, e.g. to another project folder. The copy is checked against the blob checksums.
+    Clone {
+        #[arg(value_name = "SOURCE_FILE")]
+        source_versioned_file_path: PathBuf,
+
+        #[arg(value_name = "NEW_FILE")]
+        new_versioned_file_path: PathBuf,
+    },
+
+    /// Upload versions and branches to a remote. The remote must not have versions or branch changes that were not pulled yet.
+    Push {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Overwrite the remote history even if it has versions or branch changes that were not pulled
+        #[arg(long = "force")]
+        force: bool,
+
+        /// Remote to push to. May be omitted if only one remote is configured.
+        remote: Option<String>,
+    },
+
+    /// Copy the repository to a backup destination, replacing the previous backup there. Meant to be run on a schedule, e.g. from cron or Task Scheduler.
+    Backup {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// A remote name, a directory path, s3://bucket/prefix or rclone:remote:path
+        #[arg(long = "to", value_name = "DESTINATION")]
+        destination: String,
+
+        /// Only copy objects that are new or changed since the last backup to the same destination
+        #[arg(long = "incremental")]
+        incremental: bool,
+    },
+
+    /// Download versions and branches from a remote. Branches that diverged are kept as <remote>-<branch>.
+    Pull {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Remote to pull from. May be omitted if only one remote is configured.
+        remote: Option<String>,
+    },
+
+    /// Merge copies of the repository data that a file sync service such as Dropbox kept after conflicting edits from two machines.
+    /// Branches that diverged are kept as conflict-<branch>.
+    Reconcile {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Also recover versions that are only present in data backups. This may bring back deliberately deleted versions.
+        #[arg(long = "backups")]
+        include_backups: bool,
+    },
+
+    /// Bring the versions of another repository into this one, e.g. to reunite two forks of the same file. The other head becomes the given branch, its other branches are kept as <branch>-<name>.
+    ImportHistory {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Versioned file of the repository to import from
+        #[arg(long = "from", value_name = "FILE")]
+        other_versioned_file_path: PathBuf,
+
+        /// Branch for the imported head
+        #[arg(long = "branch", value_name = "BRANCH")]
+        branch: String,
+
+        /// Version the imported root follows. Defaults to the version a fork was made from, otherwise to the root version.
+        #[arg(long = "onto", value_name = "TARGET")]
+        onto: Option<String>,
+    },
+
     /// Check out a specific branch or version
     Checkout {
         #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
         versioned_file_path: PathBuf,
 
-        /// Target branch or version to preview. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version nickname (adjective-noun, adjectivenoun, an).
+        /// Stash uncommitted changes on the autostash branch instead of carrying them over. Checking out the same branch or version again offers to restore them.
+        #[arg(long = "autostash")]
+        autostash: bool,
+
+        /// Target branch or version to preview. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
         target: String,
     },
 
@@ -148,10 +684,114 @@ pub enum Command {
         #[arg(short = 'o', long = "output")]
         output: Option<PathBuf>,
 
-        /// Target branch or version to restore. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version nickname (adjective-noun, adjectivenoun, an).
+        /// Restore to a file in the temporary directory and open it with the default application
+        #[arg(long = "open", conflicts_with = "output")]
+        open: bool,
+
+        /// Put the restored image on the clipboard instead of writing a file. Formats other than PNG, JPEG and the like need ImageMagick.
+        #[arg(long = "clipboard", conflicts_with_all = ["output", "open"])]
+        clipboard: bool,
+
+        /// Write the content to standard output instead of a file, e.g. to pipe it into another program
+        #[arg(long = "stdout", conflicts_with_all = ["output", "open", "clipboard"])]
+        stdout: bool,
+
+        /// Restore what was current at a local time (YYYY-MM-DD HH:MM[:SS]) instead of a target: the newest version created by then. A time without seconds includes the whole minute.
+        #[arg(long = "at", value_name = "TIME", value_parser = parse_until, conflicts_with = "target")]
+        at: Option<DateTime<Utc>>,
+
+        /// Branch to look at with --at (default: head)
+        #[arg(long = "branch", requires = "at")]
+        branch: Option<String>,
+
+        /// Target branch or version to restore. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        #[arg(required_unless_present = "at")]
+        target: Option<String>,
+    },
+
+    /// Write a version to a new file and start a separate repository for it, recording where it was forked from
+    Fork {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Path of the new file. It must not exist yet.
+        #[arg(long = "as", value_name = "NEW_FILE")]
+        new_file: PathBuf,
+
+        /// Description of the initial version of the new repository (default: "Forked from <file> <nickname>")
+        #[arg(short = 'd', long = "description")]
+        description: Option<String>,
+
+        /// Target branch or version to fork. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
         target: String,
     },
 
+    /// Mark a branch read-only. Commit, amend, reset, prune, recover and delete branch refuse to change it unless --force is given.
+    Protect {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        #[arg(value_name = "BRANCH")]
+        branch: String,
+    },
+
+    /// Remove the protection of a branch
+    Unprotect {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        #[arg(value_name = "BRANCH")]
+        branch: String,
+    },
+
+    /// Record that you are editing the file, for teams sharing it on a network drive. Status shows the lock and commits by others are refused until it is released.
+    Lock {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Take over a lock held by someone else
+        #[arg(long = "steal")]
+        steal: bool,
+    },
+
+    /// Release the editing lock
+    Unlock {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Release a lock held by someone else, e.g. one they forgot
+        #[arg(long = "steal")]
+        steal: bool,
+    },
+
+    /// Pin a version so that prune, reset, amend and delete branch never remove it
+    Pin {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target version to pin. May be one of the following (in order of precedence): branch name (pins its leaf), version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+    },
+
+    /// Remove the pin of a version
+    Unpin {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target version to unpin. May be one of the following (in order of precedence): branch name (unpins its leaf), version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+    },
+
+    /// Show or set when a version is stored in full although a patch would do. Without a limit, patches are stored as long as they stay small.
+    PatchLimit {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Number of versions in a row stored as patches, "auto" to store a version in full once the patches since the last full one add up to its size, or "none"
+        #[arg(value_name = "LIMIT", value_parser = parse_patch_limit)]
+        limit: Option<PatchLimitArgument>,
+    },
+
     /// Create commands
     #[command(subcommand)]
     Create(CreateCommand),
@@ -168,12 +808,142 @@ pub enum Command {
     #[command(subcommand)]
     Delete(DeleteCommand),
 
+    /// Version attribute commands, for key-value labels such as ratings or approval status
+    #[command(subcommand)]
+    Attr(AttrCommand),
+
+    /// Version note commands, for review feedback that accumulates without changing the description
+    #[command(subcommand)]
+    Note(NoteCommand),
+
+    /// Version review commands, for a submit and approve or reject loop with a client or lead
+    #[command(subcommand)]
+    Review(ReviewCommand),
+
+    /// Version nickname commands
+    #[command(subcommand)]
+    Nickname(NicknameCommand),
+
+    /// Branch commands: create, list, rename, delete and describe
+    #[command(subcommand)]
+    Branch(BranchCommand),
+
+    /// Find the version that introduced a problem by checking out versions between a good and a bad one, halving the range with every mark
+    #[command(subcommand)]
+    Bisect(BisectCommand),
+
     /// List dependencies and check their statuses
     Dependencies,
+
+    /// Set biver up for first use: install or locate xdelta3 and ImageMagick, save their paths to the user config file
+    /// and optionally install shell completions and the file manager context menu actions
+    Setup,
+
+    /// Print the completion script for a shell, to be loaded from its profile
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Watch the versioned file and commit or stash it every time an application saves it.
+    /// Unlike timed autosaving, versions are only created for actual saves, including temp-file-then-rename saves.
+    AutosaveGuard {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// What to do when a save is detected
+        #[arg(long = "on-save", value_enum, default_value_t = OnSave::Commit)]
+        on_save: OnSave,
+
+        /// Branch that stashed saves are added to, without moving head
+        #[arg(long = "stash-branch", default_value = "autosave")]
+        stash_branch: String,
+
+        /// How long the file must stay untouched before a save is considered complete
+        #[arg(long = "settle", value_name = "MILLISECONDS", default_value_t = 2000)]
+        settle_milliseconds: u64,
+    },
+
+    /// Export the version history to a git repository, one commit per version. Running it again only adds new versions.
+    GitExport {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Git repository to export to. A bare repository is created if the path does not exist or is an empty directory.
+        #[arg(long = "repo")]
+        git_repo: PathBuf,
+
+        /// How the file content is stored in git
+        #[arg(long = "content", value_enum, default_value_t = GitExportContent::Raw)]
+        content: GitExportContent,
+    },
+
+    /// Serve a local HTTP JSON API (status, versions, preview, commit, restore) for editor plugins.
+    /// Every request names the versioned file with the `file` query parameter and carries an `Authorization: Bearer` header
+    /// with the token the daemon writes to daemon_token next to the config file when it starts.
+    Daemon {
+        /// Address to listen on. Requests naming any other host than a loopback one are refused.
+        #[arg(long = "address", env = "BIVER_DAEMON_ADDRESS", default_value = "127.0.0.1:7737")]
+        address: String,
+    },
+
+    /// Serve a read-only web page with the history of the versioned file, its previews and downloads of every version,
+    /// for teammates without biver
+    Serve {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        #[arg(long = "port", default_value_t = 8080)]
+        port: u16,
+
+        /// Interface to listen on. The page has no authentication and offers every version for download, so it is only
+        /// reachable from this computer unless another interface, such as 0.0.0.0 for all of them, is given.
+        #[arg(long = "bind", default_value = "127.0.0.1")]
+        bind: String,
+    },
+
+    /// Read JSON-RPC 2.0 requests (list, status, commit, restore, preview_path) from standard input, one per line, and
+    /// write one response line for each that has an id, for editor extensions and agents running biver as a long-lived
+    /// subprocess
+    Rpc {
+        /// Versioned file of requests without a `file` parameter
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: Option<PathBuf>,
+    },
+
+    /// Add or remove "BiVer: Commit", "BiVer: Status" and "BiVer: History" entries in the file manager context menu
+    #[command(subcommand)]
+    IntegrateShell(IntegrateShellCommand),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OnSave {
+    /// Commit the save to the current branch
+    Commit,
+    /// Add the save to the stash branch
+    Stash,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GitExportContent {
+    /// The file itself
+    Raw,
+    /// A Git LFS pointer, with the file stored among the local LFS objects of the git repository
+    LfsPointer,
+}
+
+#[derive(Subcommand)]
+pub enum IntegrateShellCommand {
+    /// Register the context menu entries for the current user, pointing at this biver executable
+    Install,
+
+    /// Remove the context menu entries
+    Uninstall,
 }
 
 #[derive(Subcommand)]
 pub enum CreateCommand {
+    /// Create a branch, the same as branch create
     Branch {
         #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
         versioned_file_path: PathBuf,
@@ -184,16 +954,101 @@ pub enum CreateCommand {
 
         /// The name of the new branch
         name: String,
+
+        /// (Default: head) Version the branch starts at, such as one on another branch. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: Option<String>,
+    },
+
+    /// Add a remote to push to and pull from. Credentials for S3 remotes are read from AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and AWS_SESSION_TOKEN.
+    Remote {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// The name of the new remote
+        name: String,
+
+        /// A directory path, s3://bucket/prefix for S3-compatible object storage, or rclone:remote:path for any storage configured in rclone
+        location: String,
+
+        /// S3 endpoint URL, e.g. http://localhost:9000 for MinIO. Defaults to AWS for the region.
+        #[arg(long = "endpoint")]
+        endpoint: Option<String>,
+
+        /// S3 region. Defaults to AWS_REGION or us-east-1.
+        #[arg(long = "region")]
+        region: Option<String>,
     },
 }
 
 #[derive(Subcommand)]
 pub enum ListCommand {
-    /// List branches
+    /// List branches. With --verbose, also their descriptions, creation times and creators.
     Branches {
         #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
         versioned_file_path: PathBuf,
     },
+
+    /// List remotes
+    Remotes {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+    },
+
+    /// List every file in the repository directory with its role, size and the versions referencing it
+    Blobs {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+    },
+
+    /// List all versions, including those not on the head branch
+    Versions {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Only list versions on this branch
+        #[arg(short = 'b', long = "branch")]
+        branch: Option<String>,
+
+        /// Only list versions created at or after this time (YYYY-MM-DD or YYYY-MM-DD HH:MM[:SS], local time)
+        #[arg(long = "since", value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only list versions created at or before this time (YYYY-MM-DD or YYYY-MM-DD HH:MM[:SS], local time)
+        #[arg(long = "until", value_parser = parse_until)]
+        until: Option<DateTime<Utc>>,
+
+        /// Only list versions committed by this author
+        #[arg(long = "author")]
+        author: Option<String>,
+
+        /// Only list versions with this attribute value (KEY=VALUE). May be repeated, all must match.
+        #[arg(long = "where", value_name = "KEY=VALUE", value_parser = parse_attribute_filter)]
+        attribute_filters: Vec<(String, String)>,
+
+        /// Order of the listed versions
+        #[arg(long = "sort", value_enum, default_value_t = VersionSort::Date)]
+        sort: VersionSort,
+
+        /// Maximum number of versions to list
+        #[arg(short = 'n', long = "limit")]
+        limit: Option<usize>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ChangelogFormatArgument {
+    /// Markdown table
+    Md,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum VersionSort {
+    /// Newest first
+    Date,
+    /// Largest first
+    Size,
 }
 
 #[derive(Subcommand)]
@@ -209,6 +1064,18 @@ pub enum RenameCommand {
         #[arg(value_name = "NEW_NAME")]
         new_name: String,
     },
+
+    /// Give a version a nickname of your choice, such as final or client-review. Letters, digits and dashes between words are allowed.
+    Version {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+
+        #[arg(value_name = "NEW_NICKNAME")]
+        new_nickname: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -222,7 +1089,306 @@ pub enum DeleteCommand {
         #[arg(short = 'y', long = "yes")]
         confirmed: bool,
 
+        /// Delete even if the branch is protected
+        #[arg(long = "force")]
+        force: bool,
+
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+
+    /// Delete a remote. Nothing is deleted from the remote itself.
+    Remote {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AttrCommand {
+    /// Set an attribute of a version, replacing its previous value
+    Set {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+
+        /// Attribute name, e.g. rating or approved
+        key: String,
+
+        value: String,
+    },
+
+    /// Remove an attribute from a version
+    Unset {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+
+        /// Attribute name
+        key: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum NoteCommand {
+    /// Add a timestamped note to a version
+    Add {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+
+        /// Text of the note
+        text: String,
+    },
+
+    /// List the notes of a version, oldest first
+    List {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// (Default: head) Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ReviewCommand {
+    /// Submit a version for review
+    Submit {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+
+        /// What to look at
+        note: Option<String>,
+
+        /// (Default: the author) Who submits it
+        #[arg(long = "reviewer", value_name = "NAME")]
+        reviewer: Option<String>,
+    },
+
+    /// Approve a submitted version
+    Approve {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+
+        /// Feedback for the approval
+        note: Option<String>,
+
+        /// (Default: the author) Who approved it, e.g. the client who replied by email
+        #[arg(long = "reviewer", value_name = "NAME")]
+        reviewer: Option<String>,
+    },
+
+    /// Reject a submitted version. Once the problems are fixed, submit the new version.
+    Reject {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+
+        /// What has to change
+        note: Option<String>,
+
+        /// (Default: the author) Who rejected it, e.g. the client who replied by email
+        #[arg(long = "reviewer", value_name = "NAME")]
+        reviewer: Option<String>,
+    },
+
+    /// List the review history of a version, oldest first
+    List {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// (Default: head) Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BranchCommand {
+    /// Create a branch at the head version or at any other version, without committing
+    Create {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Check out the new branch after creation
+        #[arg(short = 'c', long = "checkout")]
+        checkout: bool,
+
+        /// The name of the new branch
+        name: String,
+
+        /// (Default: head) Version the branch starts at, such as one on another branch. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: Option<String>,
+    },
+
+    /// List branches. With --verbose, also their descriptions, creation times and creators.
+    List {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+    },
+
+    /// Point a branch at any other version, e.g. to undo a mistaken commit without erasing it. Refused if versions only the branch leads to would be left outside all branches.
+    Set {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Set even if the branch is protected
+        #[arg(long = "force")]
+        force: bool,
+
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Version the branch points at afterwards. May be one of the following (in order of precedence): branch name (its leaf), version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+    },
+
+    /// Rename a branch
+    Rename {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        #[arg(value_name = "OLD_NAME")]
+        old_name: String,
+
+        #[arg(value_name = "NEW_NAME")]
+        new_name: String,
+    },
+
+    /// Delete a branch
+    Delete {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long = "yes")]
+        confirmed: bool,
+
+        /// Delete even if the branch is protected
+        #[arg(long = "force")]
+        force: bool,
+
         #[arg(value_name = "NAME")]
         name: String,
     },
+
+    /// Describe what a branch is for, shown by list branches --verbose and status
+    Describe {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        #[arg(value_name = "BRANCH")]
+        branch: String,
+
+        /// Description of the branch. An empty one removes it.
+        description: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum NicknameCommand {
+    /// Give a version a new nickname from the current word lists, e.g. after switching to custom words. The old nickname stops working as a target.
+    Regenerate {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BisectCommand {
+    /// Start bisecting. Mark a good and a bad version next.
+    Start {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+    },
+
+    /// Mark a version as not having the problem
+    Good {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// (Default: head) Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: Option<String>,
+    },
+
+    /// Mark a version as having the problem
+    Bad {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+
+        /// (Default: head) Target branch or version. May be one of the following (in order of precedence): branch name, version ID, head offset (~, ~1, ~2), version ID prefix (at least 4 characters), version nickname (adjective-noun, adjectivenoun, an).
+        target: Option<String>,
+    },
+
+    /// Stop bisecting and check out what was checked out before it started
+    Reset {
+        #[arg(short = 'f', long = "file", env = "BIVER_VERSIONED_FILE")]
+        versioned_file_path: PathBuf,
+    },
+}
+
+fn parse_patch_limit(value: &str) -> Result<PatchLimitArgument, String> {
+    match value {
+        "auto" => Ok(PatchLimitArgument::Auto),
+        "none" => Ok(PatchLimitArgument::None),
+        _ => value
+            .parse()
+            .map(PatchLimitArgument::Count)
+            .map_err(|_| "expected a number of versions, auto or none".to_string()),
+    }
+}
+
+fn parse_attribute_filter(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("'{}' is not an attribute filter (KEY=VALUE)", value)),
+    }
+}
+
+fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_local_time(value).map(|(time, _)| time)
+}
+
+// Upper bounds are exclusive and include the whole day, minute or second that was given
+fn parse_until(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_local_time(value).map(|(time, precision)| time + precision)
+}
+
+fn parse_local_time(value: &str) -> Result<(DateTime<Utc>, TimeDelta), String> {
+    let (naive, precision) = if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        (date.and_hms_opt(0, 0, 0).expect("Midnight is a valid time"), TimeDelta::days(1))
+    } else if let Ok(date_time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        (date_time, TimeDelta::seconds(1))
+    } else if let Ok(date_time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M") {
+        (date_time, TimeDelta::minutes(1))
+    } else {
+        return Err(format!("'{}' is not a date (YYYY-MM-DD) or time (YYYY-MM-DD HH:MM[:SS])", value));
+    };
+
+    let local = naive
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or_else(|| format!("'{}' does not exist in the local time zone", value))?;
+
+    Ok((local.with_timezone(&Utc), precision))
 }
@@ -0,0 +1,156 @@
+use crate::biver_result::{BiverResult, error};
+use crate::hash::HashAlgorithm;
+use crate::notifications::Webhook;
+use crate::trash::TrashMode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::{env, fs, io};
+use toml_edit::{DocumentMut, Item};
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+// Settings of the user rather than of a repository, e.g.
+// xdelta3_path = "C:/Tools/xdelta3.exe"
+// hash_algorithm = "xxh3"
+// trash = "system"
+// desktop_notifications = true
+// preserve_times = true
+//
+// [aliases]
+// save = "commit -y"
+// back = ["checkout", "~1"]
+//
+// [[webhooks]]
+// url = "https://hooks.slack.com/services/..."
+//
+// Tool paths, the hash algorithm of new repositories and the trash apply when neither the command line nor the
+// environment specifies them. Webhooks and desktop notifications are sent after every commit and amend. With
+// preserve_times, checkout, restore and discard give the file they write the modification time and, on Unix, the
+// permissions recorded with the version.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xdelta3_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_magick_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_algorithm: Option<HashAlgorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash: Option<TrashMode>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Alias>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<Webhook>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub desktop_notifications: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preserve_times: bool,
+}
+
+// A list keeps arguments with spaces in them together
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Alias {
+    Line(String),
+    Words(Vec<String>),
+}
+
+impl Alias {
+    pub fn words(&self) -> Vec<String> {
+        match self {
+            Alias::Line(line) => line.split_whitespace().map(str::to_string).collect(),
+            Alias::Words(words) => words.clone(),
+        }
+    }
+}
+
+// BIVER_CONFIG, otherwise biver/config.toml in the configuration directory of the platform
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(config_path) = env::var_os("BIVER_CONFIG").filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(config_path));
+    }
+
+    let config_dir = if cfg!(target_os = "windows") {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(env::var_os("HOME")?).join("Library").join("Application Support")
+    } else if let Some(config_home) = env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        PathBuf::from(config_home)
+    } else {
+        PathBuf::from(env::var_os("HOME")?).join(".config")
+    };
+
+    Some(config_dir.join("biver").join(CONFIG_FILE_NAME))
+}
+
+pub fn read() -> BiverResult<Config> {
+    let Some(config_path) = config_path() else {
+        return Ok(Config::default());
+    };
+
+    let contents = match fs::read_to_string(&config_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    match toml::from_str(&contents) {
+        Ok(config) => Ok(config),
+        Err(e) => error(format!("Invalid {}: {}", config_path.display(), e.message())),
+    }
+}
+
+pub fn write(config: &Config) -> BiverResult<PathBuf> {
+    let Some(config_path) = config_path() else {
+        return error("No configuration directory, set BIVER_CONFIG to the path of the config file");
+    };
+
+    if let Some(config_dir) = config_path.parent() {
+        fs::create_dir_all(config_dir)?;
+    }
+
+    let contents = toml::to_string_pretty(config).map_err(io::Error::other)?;
+    let contents = match fs::read_to_string(&config_path) {
+        Ok(existing) => merge_into_existing(&existing, &contents)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => contents,
+        Err(e) => return Err(e.into()),
+    };
+    fs::write(&config_path, contents)?;
+
+    Ok(config_path)
+}
+
+// Only the settings that changed are rewritten, so the comments and layout of the existing file survive
+fn merge_into_existing(existing: &str, contents: &str) -> BiverResult<String> {
+    let mut document = match existing.parse::<DocumentMut>() {
+        Ok(document) => document,
+        Err(e) => return error(format!("Invalid config file: {}", e.message())),
+    };
+    let updated = contents.parse::<DocumentMut>().map_err(io::Error::other)?;
+    let existing_values: toml::Table = toml::from_str(existing).map_err(io::Error::other)?;
+    let updated_values: toml::Table = toml::from_str(contents).map_err(io::Error::other)?;
+
+    let removed_keys: Vec<String> = document.iter().map(|(key, _)| key.to_string()).filter(|key| !updated.contains_key(key)).collect();
+    for key in removed_keys {
+        document.remove(&key);
+    }
+
+    for (key, item) in updated.iter() {
+        if existing_values.get(key) == updated_values.get(key) {
+            continue;
+        }
+
+        match (document.get_mut(key), item) {
+            (Some(Item::Value(existing_value)), Item::Value(value)) => {
+                let decor = existing_value.decor().clone();
+                *existing_value = value.clone();
+                *existing_value.decor_mut() = decor;
+            }
+            _ => document[key] = item.clone(),
+        }
+    }
+
+    Ok(document.to_string())
+}
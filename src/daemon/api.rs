@@ -0,0 +1,246 @@
+use crate::biver_result::BiverResult;
+use crate::daemon::repository_cache::RepositoryCache;
+use crate::env::Env;
+use crate::formatting;
+use crate::known_file_types;
+use crate::repository_data::RepositoryData;
+use crate::repository_operations;
+use crate::repository_operations::{CommitResult, ListVersionsResult, PreviewResult, RestoreResult, VersionOrder, VersionQuery, VersionResult};
+use crate::repository_paths::RepositoryPaths;
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use subtle::ConstantTimeEq;
+use tiny_http::{Header, Method};
+
+pub struct ApiResponse {
+    pub status_code: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+// Any web page the user opens can send requests to loopback addresses. It cannot read the token, and the Host check
+// stops pages that make their own host name resolve to 127.0.0.1.
+pub fn check_access(token: &str, headers: &[Header]) -> Option<ApiResponse> {
+    let header = |name: &'static str| headers.iter().find(|h| h.field.equiv(name)).map(|h| h.value.as_str());
+
+    if !header("Host").is_some_and(is_loopback_host) {
+        return Some(error_response(403, "Host must be a loopback address"));
+    }
+
+    let origin_host = |origin: &str| origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://")).map(str::to_string);
+    if let Some(origin) = header("Origin")
+        && !origin_host(origin).is_some_and(|host| is_loopback_host(&host))
+    {
+        return Some(error_response(403, "Requests from web pages are not accepted"));
+    }
+
+    // Compared in constant time, so that response times do not give away how much of a guess was right
+    let token_matches = header("Authorization")
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+        .is_some_and(|provided_token| bool::from(provided_token.as_bytes().ct_eq(token.as_bytes())));
+    if !token_matches {
+        return Some(error_response(401, "Missing or wrong bearer token"));
+    }
+
+    None
+}
+
+fn is_loopback_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or_default(),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+
+    name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+pub fn handle(env: &Env, repository_cache: &mut RepositoryCache, method: &Method, url: &str) -> ApiResponse {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = parse_query(query);
+
+    let Some(versioned_file_path) = params.get("file") else {
+        return error_response(400, "Missing parameter: file");
+    };
+
+    let repo_paths = RepositoryPaths::from_versioned_file_path(PathBuf::from(versioned_file_path));
+
+    let repo_data = match repository_cache.data(&repo_paths) {
+        Err(e) => return error_response(500, &format!("io failure: {}", e)),
+        Ok(None) => return error_response(404, "Not initialized"),
+        Ok(Some(repo_data)) => repo_data,
+    };
+
+    let result = match (method, path) {
+        (Method::Get, "/status") => status(env, &repo_paths, repo_data),
+        (Method::Get, "/versions") => versions(repo_data, &params),
+        (Method::Get, "/preview") => preview(&repo_paths, repo_data, &params),
+        (Method::Post, "/commit") => commit(env, &repo_paths, repo_data, &params),
+        (Method::Post, "/restore") => restore(env, &repo_paths, repo_data, &params),
+        _ => Ok(error_response(404, "Unknown endpoint")),
+    };
+
+    let response = result.unwrap_or_else(|e| error_response(500, &e.error_message));
+
+    // An operation that failed may have changed the data in memory without writing it
+    if response.status_code >= 400 {
+        repository_cache.forget(&repo_paths);
+    }
+
+    response
+}
+
+fn status(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<ApiResponse> {
+    let has_uncommitted_changes = repository_operations::has_uncommitted_changes(env, repo_paths, repo_data, true)?;
+    let head_version = repo_data.head_version();
+
+    let branches: HashMap<&str, String> = repo_data.branches.iter().map(|(name, id)| (name.as_str(), id.bs58())).collect();
+
+    Ok(json_response(
+        200,
+        &json!({
+            "head_branch": repo_data.head.branch(),
+            "head_version": formatting::version_list_entries(repo_data, &[head_version]).pop(),
+            "uncommitted_changes": has_uncommitted_changes,
+            "branches": branches,
+            "version_count": repo_data.versions.len(),
+        }),
+    ))
+}
+
+fn versions(repo_data: &RepositoryData, params: &HashMap<String, String>) -> BiverResult<ApiResponse> {
+    let query = VersionQuery {
+        branch: params.get("branch").map(String::as_str),
+        since: None,
+        until: None,
+        author: params.get("author").map(String::as_str),
+        attributes: &[],
+        order: VersionOrder::NewestFirst,
+        limit: params.get("limit").and_then(|limit| limit.parse().ok()),
+    };
+
+    match repository_operations::list_versions(repo_data, &query) {
+        ListVersionsResult::BranchDoesNotExist => Ok(error_response(404, "Branch does not exist")),
+        ListVersionsResult::Ok(versions) => Ok(json_response(200, &formatting::version_list_entries(repo_data, &versions))),
+    }
+}
+
+fn preview(repo_paths: &RepositoryPaths, repo_data: &RepositoryData, params: &HashMap<String, String>) -> BiverResult<ApiResponse> {
+    let version = match params.get("target") {
+        None => repo_data.head_version(),
+        Some(target) => match repository_operations::version(repo_data, target) {
+            VersionResult::InvalidTarget => return Ok(error_response(404, "Invalid target")),
+            VersionResult::Ok(version) => version,
+        },
+    };
+
+    match repository_operations::preview(repo_paths, version) {
+        PreviewResult::NoPreviewAvailable => Ok(error_response(404, "No preview available")),
+        PreviewResult::Ok(preview_file_path) => {
+            let preview = fs::read(preview_file_path)?;
+            Ok(ApiResponse {
+                status_code: 200,
+                content_type: known_file_types::preview_mime_type(&preview),
+                body: preview,
+            })
+        }
+    }
+}
+
+fn commit(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, params: &HashMap<String, String>) -> BiverResult<ApiResponse> {
+    let description = params.get("description").map(String::as_str);
+    let new_branch = params.get("branch").map(String::as_str);
+    let allow_duplicate = params.get("allow_duplicate").is_some_and(|allow_duplicate| allow_duplicate == "true");
+
+    let result = repository_operations::commit_version(env, repo_paths, repo_data, description, new_branch, false, allow_duplicate)?;
+
+    match result {
+        CommitResult::Ok => Ok(json_response(
+            200,
+            &json!({
+                "result": "ok",
+                "version": formatting::version_list_entries(repo_data, &[repo_data.head_version()]).pop(),
+            }),
+        )),
+        CommitResult::NothingToCommit => Ok(json_response(200, &json!({ "result": "nothing_to_commit" }))),
+        CommitResult::Duplicate(version_id) => Ok(json_response(
+            409,
+            &json!({
+                "result": "duplicate",
+                "version": repo_data.version(version_id).and_then(|v| formatting::version_list_entries(repo_data, &[v]).pop()),
+            }),
+        )),
+        CommitResult::HeadMustBeOnBranch => Ok(error_response(409, "Head must be on a branch")),
+        CommitResult::BranchAlreadyExists => Ok(error_response(409, "Branch already exists")),
+        CommitResult::InvalidBranchName => Ok(error_response(400, "Invalid branch name")),
+        CommitResult::BranchProtected => Ok(error_response(409, "Head branch is protected")),
+    }
+}
+
+fn restore(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, params: &HashMap<String, String>) -> BiverResult<ApiResponse> {
+    let Some(target) = params.get("target") else {
+        return Ok(error_response(400, "Missing parameter: target"));
+    };
+
+    let output = params.get("output").map(Path::new);
+
+    if let Some(output) = output
+        && !allowed_output(env, repo_paths, output)?
+    {
+        return Ok(error_response(403, "Output must be the versioned file or in the temporary directory"));
+    }
+
+    let result = repository_operations::restore(env, repo_paths, repo_data, target, output)?;
+
+    match result {
+        RestoreResult::Ok => Ok(json_response(200, &json!({ "result": "ok" }))),
+        RestoreResult::BlockedByUncommittedChanges => Ok(error_response(409, "Blocked by uncommitted changes")),
+        RestoreResult::InvalidTarget => Ok(error_response(404, "Invalid target")),
+    }
+}
+
+// Not any path the user can write, a request is not the user typing a command
+fn allowed_output(env: &Env, repo_paths: &RepositoryPaths, output: &Path) -> io::Result<bool> {
+    if std::path::absolute(output)? == std::path::absolute(&repo_paths.versioned_file)? {
+        return Ok(true);
+    }
+
+    let output_dir = match output.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    match (output_dir.canonicalize(), env.temp_dir.canonicalize()) {
+        (Ok(output_dir), Ok(temp_dir)) => Ok(output_dir.starts_with(temp_dir)),
+        _ => Ok(false),
+    }
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (decode_query_component(name), decode_query_component(value)))
+        .collect()
+}
+
+fn decode_query_component(component: &str) -> String {
+    percent_decode_str(&component.replace('+', " ")).decode_utf8_lossy().to_string()
+}
+
+fn json_response(status_code: u16, value: &impl Serialize) -> ApiResponse {
+    ApiResponse {
+        status_code,
+        content_type: "application/json",
+        body: serde_json::to_vec_pretty(value).expect("API responses must serialize"),
+    }
+}
+
+fn error_response(status_code: u16, message: &str) -> ApiResponse {
+    json_response(status_code, &json!({ "error": message }))
+}
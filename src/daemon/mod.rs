@@ -0,0 +1,83 @@
+mod api;
+pub mod repository_cache;
+
+use crate::biver_result::{BiverError, BiverErrorCategory, BiverErrorSeverity, BiverResult, error};
+use crate::config;
+use crate::env::Env;
+use repository_cache::RepositoryCache;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use tiny_http::{Header, Response, Server};
+use uuid::Uuid;
+
+const TOKEN_FILE_NAME: &str = "daemon_token";
+
+// Requests are handled one at a time, so operations on the same repository never overlap
+pub fn run(env: &Env, address: &str) -> BiverResult<()> {
+    let server = Server::http(address).map_err(|e| BiverError {
+        error_message: format!("Could not listen on {}: {}", address, e),
+        severity: BiverErrorSeverity::Error,
+        category: BiverErrorCategory::Io,
+    })?;
+
+    let (token, token_path) = write_token()?;
+
+    println!("Listening on http://{}, requests need the bearer token in {}", address, token_path.display());
+
+    let mut repository_cache = RepositoryCache::new(env);
+
+    for request in server.incoming_requests() {
+        let api_response = match api::check_access(&token, request.headers()) {
+            Some(denied) => denied,
+            None => {
+                let api_response = api::handle(env, &mut repository_cache, request.method(), request.url());
+                repository_cache.apply_own_changes();
+                api_response
+            }
+        };
+
+        if env.verbose {
+            eprintln!("{} {} {}", request.method(), request.url(), api_response.status_code);
+        }
+
+        let content_type = Header::from_bytes("Content-Type", api_response.content_type).expect("Content type header must be valid");
+        let response = Response::from_data(api_response.body).with_status_code(api_response.status_code).with_header(content_type);
+
+        // A client that disconnected before reading the response is not the daemon's problem
+        let _ = request.respond(response);
+    }
+
+    Ok(())
+}
+
+// A new token on every start, in a file only the user can read, so only programs running as the user can make requests
+fn write_token() -> BiverResult<(String, PathBuf)> {
+    let Some(token_path) = config::config_path().and_then(|config_path| config_path.parent().map(|dir| dir.join(TOKEN_FILE_NAME))) else {
+        return error("No configuration directory for the daemon token, set BIVER_CONFIG to the path of the config file");
+    };
+
+    if let Some(token_dir) = token_path.parent() {
+        fs::create_dir_all(token_dir)?;
+    }
+
+    let token = Uuid::new_v4().simple().to_string();
+
+    let mut options = File::options();
+    options.write(true).create(true).truncate(true);
+
+    // On Windows the configuration directory is in the user's profile, which other users cannot read
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if token_path.exists() {
+            fs::set_permissions(&token_path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    options.open(&token_path)?.write_all(token.as_bytes())?;
+
+    Ok((token, token_path))
+}
@@ -0,0 +1,99 @@
+use crate::env::Env;
+use crate::repository_data::RepositoryData;
+use crate::repository_io;
+use crate::repository_io::RepositoryDataResult;
+use crate::repository_paths::RepositoryPaths;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use std::{fs, io, mem};
+
+struct CachedData {
+    modified: SystemTime,
+    length: u64,
+    repo_data: RepositoryData,
+}
+
+// Keeps parsed data files between requests. A data file changed by anyone, including the CLI, is read again.
+pub struct RepositoryCache {
+    entries: HashMap<PathBuf, CachedData>,
+    // Of the events operations emit, so their own writes do not make the data files look changed by someone else
+    changed_versioned_files: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl RepositoryCache {
+    pub fn new(env: &Env) -> Self {
+        let changed_versioned_files = Arc::new(Mutex::new(Vec::new()));
+
+        let subscriber_changed_versioned_files = Arc::clone(&changed_versioned_files);
+        env.events.subscribe(move |event| {
+            subscriber_changed_versioned_files
+                .lock()
+                .expect("Cache lock must not be poisoned")
+                .push(event.versioned_file().to_path_buf());
+        });
+
+        RepositoryCache {
+            entries: HashMap::new(),
+            changed_versioned_files,
+        }
+    }
+
+    pub fn data(&mut self, repo_paths: &RepositoryPaths) -> io::Result<Option<&mut RepositoryData>> {
+        let Ok(metadata) = fs::metadata(&repo_paths.data_file) else {
+            self.entries.remove(&repo_paths.data_file);
+            return Ok(None);
+        };
+
+        let modified = metadata.modified()?;
+        let length = metadata.len();
+
+        let fresh = self
+            .entries
+            .get(&repo_paths.data_file)
+            .is_some_and(|cached| cached.modified == modified && cached.length == length);
+
+        if !fresh {
+            let RepositoryDataResult::Initialized(repo_data) = repository_io::read_data(repo_paths)? else {
+                return Ok(None);
+            };
+
+            let cached = CachedData {
+                modified,
+                length,
+                repo_data: *repo_data,
+            };
+            self.entries.insert(repo_paths.data_file.clone(), cached);
+        }
+
+        Ok(self.entries.get_mut(&repo_paths.data_file).map(|cached| &mut cached.repo_data))
+    }
+
+    pub fn forget(&mut self, repo_paths: &RepositoryPaths) {
+        self.entries.remove(&repo_paths.data_file);
+    }
+
+    // Run right after every request. A data file an operation wrote is as fresh as the data it changed in memory, as long
+    // as no one else got to write it in between.
+    pub fn apply_own_changes(&mut self) {
+        let changed_versioned_files = mem::take(&mut *self.changed_versioned_files.lock().expect("Cache lock must not be poisoned"));
+
+        for versioned_file in changed_versioned_files {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file);
+            let Some(cached) = self.entries.get_mut(&repo_paths.data_file) else {
+                continue;
+            };
+
+            match fs::metadata(&repo_paths.data_file).and_then(|metadata| Ok((metadata.modified()?, metadata.len()))) {
+                Ok((modified, length)) => {
+                    cached.modified = modified;
+                    cached.length = length;
+                }
+                Err(_) => {
+                    self.entries.remove(&repo_paths.data_file);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::biver_result::{BiverResult, error};
+use crate::env;
+use crate::env::Env;
+use crate::logging::LoggedCommand;
+use crate::repository_paths::RepositoryPaths;
+use crate::temporary_file::TemporaryFile;
+use std::fs;
+use std::io;
+use std::io::IsTerminal;
+use std::process::Command;
+
+pub const COMMENT_PREFIX: char = '#';
+
+// Scripts and other programs running biver do not get an editor they cannot interact with
+pub fn available(env: &Env) -> bool {
+    env.editor.is_some() && io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+// Opens the editor on the template and returns what was written, without comment lines and surrounding blank lines.
+// None when nothing was written, which cancels like it does in git.
+pub fn edit(env: &Env, repo_paths: &RepositoryPaths, template: &str) -> BiverResult<Option<String>> {
+    let editor_words = env.editor.as_deref().and_then(env::command_words).unwrap_or_default();
+    let Some((program, editor_args)) = editor_words.split_first() else {
+        return error("No editor configured, or its command is not quoted correctly");
+    };
+
+    let description_file = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "description.tmp"));
+    fs::write(description_file.path(), template)?;
+
+    let status = Command::new(program).args(editor_args).arg(description_file.path()).logged_status()?;
+    if !status.success() {
+        return error(format!("Editor {} failed", program));
+    }
+
+    let description = strip_comments(&fs::read_to_string(description_file.path())?);
+    Ok(Some(description).filter(|d| !d.is_empty()))
+}
+
+fn strip_comments(written: &str) -> String {
+    let lines: Vec<&str> = written.lines().filter(|line| !line.starts_with(COMMENT_PREFIX)).map(str::trim_end).collect();
+    lines.join("\n").trim_matches('\n').to_string()
+}
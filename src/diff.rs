@@ -0,0 +1,195 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use xxhash_rust::xxh3::xxh3_64;
+
+const MIN_BLOCK_SIZE: usize = 16;
+const MAX_BLOCK_SIZE: usize = 64 * 1024;
+const PIXEL_CHANNEL_TOLERANCE: u8 = 8;
+
+pub struct ImageDiff {
+    pub width: u32,
+    pub height: u32,
+    pub changed_pixels: u64,
+    pub bounding_box: Option<BoundingBox>,
+}
+
+pub struct BoundingBox {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+pub enum ImageDiffResult {
+    Ok(ImageDiff),
+    DimensionsDiffer { width1: u32, height1: u32, width2: u32, height2: u32 },
+    NotAnImage,
+}
+
+// Counts bytes of the new file that cannot be found as whole blocks of the old file,
+// using an rsync-style rolling checksum so insertions and deletions do not shift the whole comparison.
+pub fn changed_bytes(old: &Path, new: &Path) -> io::Result<u64> {
+    let old_length = old.metadata()?.len();
+    let block_size = ((old_length as f64).sqrt() as usize).clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE);
+
+    let old_blocks = index_blocks(old, block_size)?;
+
+    let mut reader = BufReader::new(File::open(new)?);
+    let mut window = VecDeque::with_capacity(block_size);
+    let mut checksum = RollingChecksum::default();
+    let mut unmatched = 0u64;
+
+    let mut next_byte = || -> io::Result<Option<u8>> {
+        let mut byte = [0u8];
+        let bytes_read = reader.read(&mut byte)?;
+        Ok(if bytes_read == 0 { None } else { Some(byte[0]) })
+    };
+
+    loop {
+        while window.len() < block_size {
+            let Some(byte) = next_byte()? else {
+                break;
+            };
+            window.push_back(byte);
+            checksum.push(byte);
+        }
+
+        if window.len() < block_size {
+            unmatched += window.len() as u64;
+            break;
+        }
+
+        let matched = old_blocks
+            .get(&checksum.value())
+            .is_some_and(|strong_hashes| strong_hashes.contains(&xxh3_64(window.make_contiguous())));
+
+        if matched {
+            window.clear();
+            checksum = RollingChecksum::default();
+            continue;
+        }
+
+        let Some(byte) = next_byte()? else {
+            unmatched += window.len() as u64;
+            break;
+        };
+
+        let removed = window.pop_front().unwrap();
+        window.push_back(byte);
+        checksum.roll(removed, byte, block_size);
+        unmatched += 1;
+    }
+
+    Ok(unmatched)
+}
+
+fn index_blocks(path: &Path, block_size: usize) -> io::Result<HashMap<u32, Vec<u64>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut blocks: HashMap<u32, Vec<u64>> = HashMap::new();
+    let mut buffer = vec![0; block_size];
+
+    loop {
+        let mut filled = 0;
+        while filled < block_size {
+            let bytes_read = reader.read(&mut buffer[filled..])?;
+            if bytes_read == 0 {
+                break;
+            }
+            filled += bytes_read;
+        }
+
+        if filled < block_size {
+            return Ok(blocks);
+        }
+
+        let mut checksum = RollingChecksum::default();
+        for byte in &buffer {
+            checksum.push(*byte);
+        }
+
+        blocks.entry(checksum.value()).or_default().push(xxh3_64(&buffer));
+    }
+}
+
+#[derive(Default)]
+struct RollingChecksum {
+    a: u32,
+    b: u32,
+}
+
+impl RollingChecksum {
+    fn push(&mut self, byte: u8) {
+        self.a = self.a.wrapping_add(byte as u32);
+        self.b = self.b.wrapping_add(self.a);
+    }
+
+    fn roll(&mut self, removed: u8, added: u8, block_size: usize) {
+        self.a = self.a.wrapping_sub(removed as u32).wrapping_add(added as u32);
+        self.b = self.b.wrapping_sub((block_size as u32).wrapping_mul(removed as u32)).wrapping_add(self.a);
+    }
+
+    fn value(&self) -> u32 {
+        (self.a & 0xffff) | (self.b << 16)
+    }
+}
+
+pub fn image_diff(image_path1: &Path, image_path2: &Path) -> ImageDiffResult {
+    let (Some(image1), Some(image2)) = (decode_image(image_path1), decode_image(image_path2)) else {
+        return ImageDiffResult::NotAnImage;
+    };
+
+    let image1 = image1.to_rgba8();
+    let image2 = image2.to_rgba8();
+
+    if image1.dimensions() != image2.dimensions() {
+        return ImageDiffResult::DimensionsDiffer {
+            width1: image1.width(),
+            height1: image1.height(),
+            width2: image2.width(),
+            height2: image2.height(),
+        };
+    }
+
+    let mut changed_pixels = 0;
+    let mut bounding_box: Option<BoundingBox> = None;
+
+    for (x, y, pixel1) in image1.enumerate_pixels() {
+        let pixel2 = image2.get_pixel(x, y);
+        let changed = pixel1.0.iter().zip(pixel2.0.iter()).any(|(c1, c2)| c1.abs_diff(*c2) > PIXEL_CHANNEL_TOLERANCE);
+
+        if !changed {
+            continue;
+        }
+
+        changed_pixels += 1;
+
+        bounding_box = Some(match bounding_box {
+            None => BoundingBox {
+                left: x,
+                top: y,
+                right: x,
+                bottom: y,
+            },
+            Some(b) => BoundingBox {
+                left: b.left.min(x),
+                top: b.top.min(y),
+                right: b.right.max(x),
+                bottom: b.bottom.max(y),
+            },
+        });
+    }
+
+    ImageDiffResult::Ok(ImageDiff {
+        width: image1.width(),
+        height: image1.height(),
+        changed_pixels,
+        bounding_box,
+    })
+}
+
+pub fn decode_image(path: &Path) -> Option<image::DynamicImage> {
+    image::ImageReader::open(path).ok()?.with_guessed_format().ok()?.decode().ok()
+}
@@ -0,0 +1,114 @@
+use crate::blob_store;
+use crate::repository_data::{ContentBlob, DataProblem, RepositoryData};
+use crate::repository_paths::RepositoryPaths;
+use crate::tools::xdelta3::{self, XDelta3Env};
+use crate::version_id::VersionId;
+use std::collections::{BTreeMap, HashSet};
+use std::{fs, io};
+
+pub enum Finding {
+    InvalidData(DataProblem),
+    MissingBlob { blob_file_name: String, version_count: usize },
+    // The base of a patch is no version's full content, it is only kept alive by the patch itself
+    UnreachablePatchBase { version_id: VersionId, base_blob_file_name: String },
+    MissingVersionRecord(VersionId),
+    UnreferencedObjects { count: usize, length: u64 },
+    // Stored by an xdelta3 executable with options the bundled codec in use cannot apply
+    UnreadablePatches { count: usize },
+}
+
+impl Finding {
+    // Unreferenced objects are left behind by everyday operations until gc runs, they are not damage
+    pub fn is_problem(&self) -> bool {
+        !matches!(self, Finding::UnreferencedObjects { .. })
+    }
+}
+
+// Unlike verify, nothing is reconstructed, so this stays fast on large repositories
+pub fn diagnose(env: &impl XDelta3Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> io::Result<Vec<Finding>> {
+    let mut findings: Vec<Finding> = repo_data.problems().into_iter().map(Finding::InvalidData).collect();
+
+    let mut blob_version_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for version in repo_data.versions_and_orphans() {
+        for blob_file_name in version.blob_file_names() {
+            *blob_version_counts.entry(blob_file_name).or_default() += 1;
+        }
+    }
+
+    for (blob_file_name, version_count) in blob_version_counts {
+        if !fs::exists(repo_paths.blob_path(blob_file_name))? {
+            findings.push(Finding::MissingBlob {
+                blob_file_name: blob_file_name.to_string(),
+                version_count,
+            });
+        }
+    }
+
+    let full_blob_file_names: HashSet<&str> = repo_data
+        .versions_and_orphans()
+        .filter_map(|v| match &v.content_blob {
+            ContentBlob::Full { full_blob_file_name } => Some(full_blob_file_name.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    for version in repo_data.versions_and_orphans() {
+        if let ContentBlob::Patch { base_blob_file_name, .. } = &version.content_blob
+            && !full_blob_file_names.contains(base_blob_file_name.as_str())
+        {
+            findings.push(Finding::UnreachablePatchBase {
+                version_id: version.id,
+                base_blob_file_name: base_blob_file_name.clone(),
+            });
+        }
+    }
+
+    for version in &repo_data.versions {
+        if !fs::exists(repo_paths.blob_path(&blob_store::version_record_file_name(version.id)))? {
+            findings.push(Finding::MissingVersionRecord(version.id));
+        }
+    }
+
+    let unreadable_patch_count = unreadable_patch_count(env, repo_paths, repo_data)?;
+    if unreadable_patch_count > 0 {
+        findings.push(Finding::UnreadablePatches { count: unreadable_patch_count });
+    }
+
+    let unreferenced_object_names = blob_store::unreferenced_blob_file_names(repo_paths, repo_data)?;
+    if !unreferenced_object_names.is_empty() {
+        let mut length = 0;
+        for object_name in &unreferenced_object_names {
+            length += fs::metadata(repo_paths.blob_path(object_name))?.len();
+        }
+
+        findings.push(Finding::UnreferencedObjects {
+            count: unreferenced_object_names.len(),
+            length,
+        });
+    }
+
+    Ok(findings)
+}
+
+fn unreadable_patch_count(env: &impl XDelta3Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> io::Result<usize> {
+    let mut count = 0;
+
+    let patch_blob_file_names: HashSet<&str> = repo_data
+        .versions_and_orphans()
+        .filter_map(|v| match &v.content_blob {
+            ContentBlob::Patch { patch_blob_file_name, .. } => Some(patch_blob_file_name.as_str()),
+            ContentBlob::Full { .. } => None,
+        })
+        .collect();
+
+    for patch_blob_file_name in patch_blob_file_names {
+        let patch_blob_file_path = repo_paths.blob_path(patch_blob_file_name);
+
+        // Missing blobs are reported on their own
+        if fs::exists(&patch_blob_file_path)? && !xdelta3::can_apply(env, &patch_blob_file_path)? {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,41 @@
+use crate::env::Env;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::process::Command;
+
+// Kept in the repository directory, so everyone working on the file from a shared drive sees it. It is advisory:
+// nothing stops the file from being saved, only commits by others are refused.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditingLock {
+    pub user: String,
+    pub machine: String,
+    pub lock_time: DateTime<Utc>,
+}
+
+impl EditingLock {
+    pub fn new(env: &Env) -> Self {
+        EditingLock {
+            user: env.author.clone(),
+            machine: current_machine(),
+            lock_time: Utc::now(),
+        }
+    }
+
+    // The machine counts too, a copy of the file open on another computer of the same user may be just as stale
+    pub fn is_own(&self, env: &Env) -> bool {
+        self.user == env.author && self.machine == current_machine()
+    }
+}
+
+fn current_machine() -> String {
+    let from_environment = ["HOSTNAME", "COMPUTERNAME"].iter().find_map(|name| std::env::var(name).ok());
+    let from_file = || fs::read_to_string("/etc/hostname").ok();
+    let from_command = || Command::new("hostname").output().ok().map(|output| String::from_utf8_lossy(&output.stdout).to_string());
+
+    from_environment
+        .or_else(from_file)
+        .or_else(from_command)
+        .map(|machine| machine.trim().to_string())
+        .unwrap_or_default()
+}
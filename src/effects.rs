@@ -0,0 +1,115 @@
+use crate::background_process::BackgroundProcess;
+use crate::env::Env;
+use crate::repository_data::{RepositoryData, Version};
+use crate::repository_io;
+use crate::repository_paths::RepositoryPaths;
+use crate::version_id::VersionId;
+use std::path::Path;
+use std::sync::Mutex;
+use std::{fs, io, mem};
+
+// Disk changes a mutating operation makes go through here, so a dry run can skip them and record what they would have been.
+// Changes to versions, branches and head need no recording, they show in the repository data left in memory.
+pub enum Effect {
+    WriteBlob { blob_file_name: String, length: u64 },
+    DeleteBlob { blob_file_name: String, length: u64 },
+    RestoreVersionedFile { version_id: VersionId },
+}
+
+// Behind a mutex, so the env can be shared with worker threads
+#[derive(Default)]
+pub struct Effects {
+    recorded: Mutex<Vec<Effect>>,
+}
+
+impl Effects {
+    pub fn take(&self) -> Vec<Effect> {
+        mem::take(&mut *self.recorded.lock().expect("Effects lock must not be poisoned"))
+    }
+
+    fn record(&self, effect: Effect) {
+        self.recorded.lock().expect("Effects lock must not be poisoned").push(effect);
+    }
+}
+
+pub fn write_data(env: &Env, paths: &RepositoryPaths, data: &RepositoryData) -> io::Result<()> {
+    if env.dry_run {
+        return Ok(());
+    }
+
+    if env.read_only {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "The repository is opened read-only, nothing was written"));
+    }
+
+    repository_io::write_data(paths, data)
+}
+
+pub fn write_version_record(env: &Env, paths: &RepositoryPaths, version: &Version) -> io::Result<()> {
+    if env.dry_run {
+        return Ok(());
+    }
+
+    repository_io::write_version_record(paths, version)
+}
+
+// In a dry run the output is still produced, since its size decides between a patch and a full copy, and then dropped
+pub fn keep_blob(env: &Env, paths: &RepositoryPaths, mut process: BackgroundProcess, blob_file_name: &str) -> io::Result<()> {
+    if env.dry_run {
+        let length = fs::metadata(process.wait()?)?.len();
+        env.effects.record(Effect::WriteBlob {
+            blob_file_name: blob_file_name.to_string(),
+            length,
+        });
+        return Ok(());
+    }
+
+    process.keep_as(&paths.blob_path(blob_file_name))
+}
+
+pub fn store_full_blob(env: &Env, paths: &RepositoryPaths, content_path: &Path, blob_file_name: &str) -> io::Result<()> {
+    if env.dry_run {
+        env.effects.record(Effect::WriteBlob {
+            blob_file_name: blob_file_name.to_string(),
+            length: fs::metadata(content_path)?.len(),
+        });
+        return Ok(());
+    }
+
+    repository_io::store_version_content_full(&paths.blob_path(blob_file_name), content_path)
+}
+
+pub fn delete_blobs<'a>(env: &Env, paths: &RepositoryPaths, blob_file_names: impl IntoIterator<Item = &'a str>) -> io::Result<usize> {
+    if !env.dry_run {
+        return repository_io::delete_blobs(paths, blob_file_names);
+    }
+
+    let mut deleted_count = 0;
+
+    for blob_file_name in blob_file_names {
+        let blob_file_path = paths.blob_path(blob_file_name);
+        if fs::exists(&blob_file_path)? {
+            env.effects.record(Effect::DeleteBlob {
+                blob_file_name: blob_file_name.to_string(),
+                length: fs::metadata(&blob_file_path)?.len(),
+            });
+            deleted_count += 1;
+        }
+    }
+
+    Ok(deleted_count)
+}
+
+// Caches only save work for later, so a dry run leaves them alone without recording anything, like a read-only repository
+pub fn update_caches(env: &Env) -> bool {
+    !env.dry_run && !env.read_only
+}
+
+// Returns whether the file is to be restored for real
+pub fn restore_versioned_file(env: &Env, version_id: VersionId) -> bool {
+    if env.dry_run {
+        env.effects.record(Effect::RestoreVersionedFile { version_id });
+        return false;
+    }
+
+    true
+}
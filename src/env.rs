@@ -1,20 +1,135 @@
-use crate::image_magick::ImageMagickEnv;
-use crate::xdelta3::XDelta3Env;
+use crate::effects::Effects;
+use crate::events::Events;
+use crate::hash::{HashAlgorithm, HashEnv};
+use crate::notifications::{Webhook, WebhookDeliveries};
+use crate::tools::git::GitEnv;
+use crate::tools::image_magick::{ImageMagickEnv, Invocation, PreviewStandIn};
+use crate::tools::minisign::MinisignEnv;
+use crate::tools::rclone::RcloneEnv;
+use crate::tools::xdelta3::{Codec, XDelta3Env};
+use crate::trash::TrashMode;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 pub struct Env {
     pub xdelta3_path: Option<PathBuf>,
     pub image_magick_path: Option<PathBuf>,
+    pub git_path: Option<PathBuf>,
+    pub rclone_path: Option<PathBuf>,
+    pub minisign_path: Option<PathBuf>,
+    pub signing_key: Option<PathBuf>,
+    pub nickname_words: Option<PathBuf>,
+    pub editor: Option<String>,
+    pub pager: Option<String>,
+    pub hash_chunk_size: usize,
+    pub restore_cache_size: u64,
+    // Of restored copies handed to other programs, passed in so tests can keep theirs apart
+    pub temp_dir: PathBuf,
+    // Of new repositories, existing ones keep theirs
+    pub hash_algorithm: HashAlgorithm,
+    pub trash: TrashMode,
+    pub webhooks: Vec<Webhook>,
+    pub desktop_notifications: bool,
+    pub verbose: bool,
+    pub author: String,
+    pub preserve_times: bool,
+    pub dry_run: bool,
+    pub json: bool,
+    // Set by --read-only and serve. Caches and backups are then left alone and temporary files go to temp_dir, so a
+    // repository on a read-only share can be read.
+    pub read_only: bool,
+    pub xdelta3_codec: OnceLock<Codec>,
+    pub image_magick_invocation: OnceLock<Option<Invocation>>,
+    pub preview_stand_in: Option<PreviewStandIn>,
+    pub effects: Effects,
+    pub events: Events,
+    pub webhook_deliveries: WebhookDeliveries,
+}
+
+impl HashEnv for Env {
+    fn hash_chunk_size(&self) -> usize {
+        self.hash_chunk_size
+    }
+
+    fn verbose(&self) -> bool {
+        self.verbose
+    }
 }
 
 impl ImageMagickEnv for Env {
     fn image_magick_path(&self) -> Option<&Path> {
         self.image_magick_path.as_deref()
     }
+
+    fn image_magick_invocation(&self) -> &OnceLock<Option<Invocation>> {
+        &self.image_magick_invocation
+    }
+
+    fn preview_stand_in(&self) -> Option<PreviewStandIn> {
+        self.preview_stand_in
+    }
+}
+
+impl GitEnv for Env {
+    fn git_path(&self) -> Option<&Path> {
+        self.git_path.as_deref()
+    }
+}
+
+impl RcloneEnv for Env {
+    fn rclone_path(&self) -> Option<&Path> {
+        self.rclone_path.as_deref()
+    }
+}
+
+impl MinisignEnv for Env {
+    fn minisign_path(&self) -> Option<&Path> {
+        self.minisign_path.as_deref()
+    }
 }
 
 impl XDelta3Env for Env {
     fn xdelta3_path(&self) -> Option<&Path> {
         self.xdelta3_path.as_deref()
     }
+
+    fn xdelta3_codec(&self) -> &OnceLock<Codec> {
+        &self.xdelta3_codec
+    }
+}
+
+pub fn current_author() -> String {
+    ["BIVER_AUTHOR", "USER", "USERNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_default()
+}
+
+// Like git, an empty PAGER turns paging off. less starts at the end, where the newest versions are.
+pub fn configured_pager(no_pager: bool) -> Option<String> {
+    if no_pager {
+        return None;
+    }
+
+    match std::env::var("PAGER") {
+        Ok(pager) => Some(pager).filter(|pager| !pager.trim().is_empty()),
+        Err(_) => Some("less -R +G".to_string()),
+    }
+}
+
+pub fn configured_editor(editor: Option<String>) -> Option<String> {
+    editor
+        .or_else(|| ["VISUAL", "EDITOR"].iter().find_map(|name| std::env::var(name).ok()))
+        .filter(|editor| !editor.trim().is_empty())
+}
+
+// Program and arguments of a configured editor or pager, split like a shell would, e.g. "code --wait" or
+// "'/Applications/Sublime Text.app/Contents/SharedSupport/bin/subl' -w". A path to an existing program is taken whole,
+// so that Windows paths keep their backslashes and need no quotes. None when the quoting is broken.
+pub fn command_words(command: &str) -> Option<Vec<String>> {
+    if Path::new(command).is_file() {
+        return Some(vec![command.to_string()]);
+    }
+
+    shlex::split(command).filter(|words| !words.is_empty())
 }
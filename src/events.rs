@@ -0,0 +1,241 @@
+use crate::env::Env;
+use crate::repository_data::{Head, ReviewState};
+use crate::version_id::VersionId;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// What an operation changed, for code keeping a view of a repository, such as the daemon's parsed data files, to update
+// it without reading the data file again. Emitted once the change is written, so never in a dry run. There is no library
+// target, so only code of this binary can subscribe.
+#[derive(Debug, Clone)]
+pub enum RepositoryEvent {
+    Committed {
+        versioned_file: PathBuf,
+        version_id: VersionId,
+        branch: String,
+    },
+    Amended {
+        versioned_file: PathBuf,
+        replaced_version_id: VersionId,
+        version_id: VersionId,
+        branch: String,
+    },
+    CheckedOut {
+        versioned_file: PathBuf,
+        head: Head,
+    },
+    Reset {
+        versioned_file: PathBuf,
+        branch: String,
+        version_id: VersionId,
+        erased_version_ids: Vec<VersionId>,
+    },
+    BranchCreated {
+        versioned_file: PathBuf,
+        branch: String,
+        version_id: VersionId,
+    },
+    BranchDeleted {
+        versioned_file: PathBuf,
+        branch: String,
+        erased_version_ids: Vec<VersionId>,
+    },
+    BranchRenamed {
+        versioned_file: PathBuf,
+        old_name: String,
+        new_name: String,
+    },
+    VersionRenamed {
+        versioned_file: PathBuf,
+        version_id: VersionId,
+        nickname: String,
+    },
+    Reworded {
+        versioned_file: PathBuf,
+        version_id: VersionId,
+    },
+    PinChanged {
+        versioned_file: PathBuf,
+        version_id: VersionId,
+        pinned: bool,
+    },
+    NoteAdded {
+        versioned_file: PathBuf,
+        version_id: VersionId,
+    },
+    Reviewed {
+        versioned_file: PathBuf,
+        version_id: VersionId,
+        state: ReviewState,
+    },
+    GarbageCollected {
+        versioned_file: PathBuf,
+        deleted_blob_count: usize,
+    },
+    Pruned {
+        versioned_file: PathBuf,
+        erased_version_ids: Vec<VersionId>,
+    },
+    Pulled {
+        versioned_file: PathBuf,
+        new_version_ids: Vec<VersionId>,
+    },
+    Recovered {
+        versioned_file: PathBuf,
+        version_id: VersionId,
+        branch: String,
+    },
+    Imported {
+        versioned_file: PathBuf,
+        new_version_ids: Vec<VersionId>,
+    },
+}
+
+impl RepositoryEvent {
+    pub fn versioned_file(&self) -> &Path {
+        match self {
+            RepositoryEvent::Committed { versioned_file, .. }
+            | RepositoryEvent::Amended { versioned_file, .. }
+            | RepositoryEvent::CheckedOut { versioned_file, .. }
+            | RepositoryEvent::Reset { versioned_file, .. }
+            | RepositoryEvent::BranchCreated { versioned_file, .. }
+            | RepositoryEvent::BranchDeleted { versioned_file, .. }
+            | RepositoryEvent::BranchRenamed { versioned_file, .. }
+            | RepositoryEvent::VersionRenamed { versioned_file, .. }
+            | RepositoryEvent::Reworded { versioned_file, .. }
+            | RepositoryEvent::PinChanged { versioned_file, .. }
+            | RepositoryEvent::NoteAdded { versioned_file, .. }
+            | RepositoryEvent::Reviewed { versioned_file, .. }
+            | RepositoryEvent::GarbageCollected { versioned_file, .. }
+            | RepositoryEvent::Pruned { versioned_file, .. }
+            | RepositoryEvent::Pulled { versioned_file, .. }
+            | RepositoryEvent::Recovered { versioned_file, .. }
+            | RepositoryEvent::Imported { versioned_file, .. } => versioned_file,
+        }
+    }
+}
+
+impl Display for RepositoryEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let ids = |version_ids: &[VersionId]| match version_ids {
+            [] => "no versions".to_string(),
+            _ => version_ids.iter().map(|id| id.bs58()).collect::<Vec<_>>().join(", "),
+        };
+
+        match self {
+            RepositoryEvent::Committed {
+                versioned_file,
+                version_id,
+                branch,
+            } => write!(f, "{}: committed {} to {}", versioned_file.display(), version_id.bs58(), branch),
+            RepositoryEvent::Amended {
+                versioned_file,
+                replaced_version_id,
+                version_id,
+                branch,
+            } => write!(
+                f,
+                "{}: amended {} as {} on {}",
+                versioned_file.display(),
+                replaced_version_id.bs58(),
+                version_id.bs58(),
+                branch
+            ),
+            RepositoryEvent::CheckedOut { versioned_file, head } => match head {
+                Head::Branch(branch) => write!(f, "{}: checked out branch {}", versioned_file.display(), branch),
+                Head::Version(version_id) => write!(f, "{}: checked out version {}", versioned_file.display(), version_id.bs58()),
+            },
+            RepositoryEvent::Reset {
+                versioned_file,
+                branch,
+                version_id,
+                erased_version_ids,
+            } => write!(
+                f,
+                "{}: reset {} to {}, erasing {}",
+                versioned_file.display(),
+                branch,
+                version_id.bs58(),
+                ids(erased_version_ids)
+            ),
+            RepositoryEvent::BranchCreated {
+                versioned_file,
+                branch,
+                version_id,
+            } => write!(f, "{}: created branch {} at {}", versioned_file.display(), branch, version_id.bs58()),
+            RepositoryEvent::BranchDeleted {
+                versioned_file,
+                branch,
+                erased_version_ids,
+            } => write!(f, "{}: deleted branch {}, erasing {}", versioned_file.display(), branch, ids(erased_version_ids)),
+            RepositoryEvent::BranchRenamed {
+                versioned_file,
+                old_name,
+                new_name,
+            } => write!(f, "{}: renamed branch {} to {}", versioned_file.display(), old_name, new_name),
+            RepositoryEvent::VersionRenamed {
+                versioned_file,
+                version_id,
+                nickname,
+            } => write!(f, "{}: renamed {} to {}", versioned_file.display(), version_id.bs58(), nickname),
+            RepositoryEvent::Reworded { versioned_file, version_id } => write!(f, "{}: reworded {}", versioned_file.display(), version_id.bs58()),
+            RepositoryEvent::PinChanged {
+                versioned_file,
+                version_id,
+                pinned,
+            } => write!(f, "{}: {} {}", versioned_file.display(), if *pinned { "pinned" } else { "unpinned" }, version_id.bs58()),
+            RepositoryEvent::NoteAdded { versioned_file, version_id } => write!(f, "{}: added a note to {}", versioned_file.display(), version_id.bs58()),
+            RepositoryEvent::Reviewed {
+                versioned_file,
+                version_id,
+                state,
+            } => write!(f, "{}: marked {} as {}", versioned_file.display(), version_id.bs58(), state),
+            RepositoryEvent::GarbageCollected {
+                versioned_file,
+                deleted_blob_count,
+            } => write!(f, "{}: collected {} unreferenced blobs", versioned_file.display(), deleted_blob_count),
+            RepositoryEvent::Pruned {
+                versioned_file,
+                erased_version_ids,
+            } => write!(f, "{}: pruned {}", versioned_file.display(), ids(erased_version_ids)),
+            RepositoryEvent::Pulled { versioned_file, new_version_ids } => write!(f, "{}: pulled {}", versioned_file.display(), ids(new_version_ids)),
+            RepositoryEvent::Recovered {
+                versioned_file,
+                version_id,
+                branch,
+            } => write!(f, "{}: recovered {} on {}", versioned_file.display(), version_id.bs58(), branch),
+            RepositoryEvent::Imported { versioned_file, new_version_ids } => write!(f, "{}: imported {}", versioned_file.display(), ids(new_version_ids)),
+        }
+    }
+}
+
+type Subscriber = Box<dyn Fn(&RepositoryEvent) + Send + Sync>;
+
+// Behind a mutex like the effects, so the env can be shared with worker threads
+#[derive(Default)]
+pub struct Events {
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl Events {
+    // Subscribers are called on the thread of the operation, before it returns
+    pub fn subscribe(&self, subscriber: impl Fn(&RepositoryEvent) + Send + Sync + 'static) {
+        self.subscribers.lock().expect("Events lock must not be poisoned").push(Box::new(subscriber));
+    }
+
+    fn emit(&self, event: &RepositoryEvent) {
+        for subscriber in self.subscribers.lock().expect("Events lock must not be poisoned").iter() {
+            subscriber(event);
+        }
+    }
+}
+
+pub fn emit(env: &Env, event: RepositoryEvent) {
+    if env.dry_run {
+        return;
+    }
+
+    env.events.emit(&event);
+}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use std::fs::{File, Metadata};
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+pub fn modified_time(metadata: &Metadata) -> Option<DateTime<Utc>> {
+    metadata.modified().ok().map(DateTime::from)
+}
+
+#[cfg(unix)]
+pub fn unix_mode(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode())
+}
+
+#[cfg(not(unix))]
+pub fn unix_mode(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+pub fn apply(path: &Path, modified_time: Option<DateTime<Utc>>, unix_mode: Option<u32>) -> io::Result<()> {
+    if let Some(modified_time) = modified_time {
+        open_for_attributes(path)?.set_modified(SystemTime::from(modified_time))?;
+    }
+
+    #[cfg(unix)]
+    if let Some(unix_mode) = unix_mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(unix_mode))?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = unix_mode;
+
+    Ok(())
+}
+
+// Opened without write access, which a read-only file does not give. The owner may set the times regardless.
+#[cfg(not(windows))]
+fn open_for_attributes(path: &Path) -> io::Result<File> {
+    File::open(path)
+}
+
+// Windows only needs the right to write attributes, which the read-only attribute does not take away
+#[cfg(windows)]
+fn open_for_attributes(path: &Path) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+    const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+    File::options().access_mode(FILE_WRITE_ATTRIBUTES).open(path)
+}
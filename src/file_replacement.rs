@@ -0,0 +1,80 @@
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use std::{fs, io};
+
+// Antivirus scanners and indexers hold files open for a moment after they change, so a few retries get past them
+const LOCKED_RETRY_COUNT: u32 = 5;
+const LOCKED_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(100);
+
+// Moves the replacement over the destination, which it must be next to, or at least on the same device as. The
+// destination's permissions are kept unless the replacement was given its own, and a read-only destination is replaced
+// like any other.
+pub fn replace(replacement: &Path, destination: &Path, keep_permissions: bool) -> io::Result<()> {
+    let destination_permissions = fs::metadata(destination).ok().map(|metadata| metadata.permissions());
+
+    // Windows refuses to replace read-only files, on other platforms only the directory's permissions matter.
+    // The freshly written replacement is writable, and on Windows permissions are only the read-only flag.
+    let made_writable = match &destination_permissions {
+        Some(permissions) if cfg!(windows) && permissions.readonly() => {
+            fs::set_permissions(destination, fs::metadata(replacement)?.permissions())?;
+            true
+        }
+        _ => false,
+    };
+
+    let result = set_permissions_and_rename(replacement, destination, destination_permissions.clone().filter(|_| keep_permissions));
+
+    // Left as it was when it is not replaced
+    if result.is_err()
+        && made_writable
+        && let Some(permissions) = destination_permissions
+    {
+        let _ = fs::set_permissions(destination, permissions);
+    }
+
+    result
+}
+
+fn set_permissions_and_rename(replacement: &Path, destination: &Path, permissions: Option<fs::Permissions>) -> io::Result<()> {
+    if let Some(permissions) = permissions {
+        fs::set_permissions(replacement, permissions)?;
+    }
+
+    let mut delay = LOCKED_RETRY_INITIAL_DELAY;
+    let mut retry_count = 0;
+
+    loop {
+        match fs::rename(replacement, destination) {
+            Ok(()) => return Ok(()),
+            Err(e) if is_locked(&e) && retry_count < LOCKED_RETRY_COUNT => {
+                tracing::debug!("{} is locked, retrying in {:?}", destination.display(), delay);
+                thread::sleep(delay);
+                delay *= 2;
+                retry_count += 1;
+            }
+            Err(e) if is_locked(&e) => {
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("{} is open in another program. Close it and try again.", destination.display()),
+                ));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Access denied is not among them, since it is also what a lack of permissions gives, which waiting does not fix
+#[cfg(windows)]
+fn is_locked(error: &io::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    matches!(error.raw_os_error(), Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION))
+}
+
+// Other platforms let open files be replaced
+#[cfg(not(windows))]
+fn is_locked(_error: &io::Error) -> bool {
+    false
+}
@@ -1,61 +1,1043 @@
-use crate::repository_data::{RepositoryData, Version};
-use colored::{ColoredString, Colorize};
+use crate::analytics::HistoryStats;
+use crate::biver_result::{BiverError, BiverErrorSeverity};
+use crate::description_editor;
+use crate::diff::ImageDiffResult;
+use crate::doctor::Finding;
+use crate::editing_lock::EditingLock;
+use crate::effects::Effect;
+use crate::env::command_words;
+use crate::logging::LoggedCommand;
+use crate::remote::RemoteLocation;
+use crate::repository_data::{Head, RepositoryData, Review, ReviewState, Version};
+use crate::repository_operations::{
+    DiffReport, InventoryEntry, InventoryRole, PendingVersionSummary, ReconcileOutcome, ReconciledCopy, StatusSummary, VerifyProblem, VerifyReport,
+};
+use crate::signing::SignatureStatus;
+use crate::tools::{DetectedTool, Detection};
+use crate::version_id::VersionId;
+use crate::workspace::FileOutcome;
+use chrono::{DateTime, Local, SecondsFormat, TimeDelta, Utc};
+use colored::Colorize;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::io;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use terminal_size::Height;
 
 const MAX_VERSIONS_TO_PRINT: usize = 20;
 
-pub fn print_repository_data(repo_data: &RepositoryData, has_uncommitted_changes: bool, all: bool) {
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+static STDOUT_COLORS: AtomicBool = AtomicBool::new(false);
+static STDERR_COLORS: AtomicBool = AtomicBool::new(false);
+
+// NO_COLOR (https://no-color.org) only applies in auto mode, asking for colors explicitly wins.
+// Each stream is checked on its own, so errors stay colored in a terminal when the output is piped.
+pub fn init_colors(mode: ColorMode) {
+    let colors = |is_terminal: bool| match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => is_terminal && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+    };
+
+    STDOUT_COLORS.store(colors(io::stdout().is_terminal()), Ordering::Relaxed);
+    STDERR_COLORS.store(colors(io::stderr().is_terminal()), Ordering::Relaxed);
+
+    colored::control::set_override(STDOUT_COLORS.load(Ordering::Relaxed));
+}
+
+pub fn stderr_colors() -> bool {
+    STDERR_COLORS.load(Ordering::Relaxed)
+}
+
+// For text that ends up somewhere other than the terminal, like labels drawn into an image
+pub fn disable_colors() {
+    STDOUT_COLORS.store(false, Ordering::Relaxed);
+    colored::control::set_override(false);
+}
+
+// colored has a single switch, it follows stdout except while writing to stderr
+fn eprintln_colored(line: impl Display) {
+    colored::control::set_override(stderr_colors());
+    eprintln!("{}", line);
+    colored::control::set_override(STDOUT_COLORS.load(Ordering::Relaxed));
+}
+
+pub fn print_error(message: &str) {
+    eprintln_colored(message.red());
+}
+
+#[derive(Clone, Copy)]
+pub struct StatusColumns {
+    pub creation_time: bool,
+    pub humanized_time: bool,
+    pub id: bool,
+    pub nickname: bool,
+    // Head, branches on the version and branches forking from it
+    pub branches: bool,
+    pub pinned: bool,
+    pub attributes: bool,
+    pub review: bool,
+    pub description: bool,
+}
+
+impl StatusColumns {
+    pub const NONE: Self = Self {
+        creation_time: false,
+        humanized_time: false,
+        id: false,
+        nickname: false,
+        branches: false,
+        pinned: false,
+        attributes: false,
+        review: false,
+        description: false,
+    };
+
+    pub const FULL: Self = Self {
+        creation_time: true,
+        humanized_time: true,
+        id: true,
+        nickname: true,
+        branches: true,
+        pinned: true,
+        attributes: true,
+        review: true,
+        description: true,
+    };
+
+    pub const COMPACT: Self = Self {
+        creation_time: false,
+        ..Self::FULL
+    };
+
+    pub const ONELINE: Self = Self {
+        id: true,
+        nickname: true,
+        branches: true,
+        description: true,
+        ..Self::NONE
+    };
+}
+
+// Which versions status shows
+pub enum StatusScope {
+    Head,
+    Branch(String),
+    // Every version, newest first, with the branch each one belongs to
+    AllBranches,
+}
+
+fn status_versions<'a>(repo_data: &'a RepositoryData, scope: &StatusScope) -> Vec<&'a Version> {
+    match scope {
+        StatusScope::Head => repo_data.iter_head_and_ancestors().collect(),
+        StatusScope::Branch(branch) => match repo_data.branches.get(branch) {
+            Some(leaf_id) => repo_data.iter_version_and_ancestors(*leaf_id).collect(),
+            None => Vec::new(),
+        },
+        StatusScope::AllBranches => {
+            let mut versions: Vec<&Version> = repo_data.versions.iter().collect();
+            versions.sort_by_key(|v| Reverse(v.creation_time));
+            versions
+        }
+    }
+}
+
+pub fn print_repository_data(pager: Option<&str>, repo_data: &RepositoryData, summary: &StatusSummary, all: bool, columns: &StatusColumns, scope: &StatusScope) {
     let limit = if all { None } else { Some(MAX_VERSIONS_TO_PRINT) };
 
-    let versions_to_print: Vec<_> = repo_data.iter_head_and_ancestors().collect();
+    let versions_to_print = status_versions(repo_data, scope);
 
-    let prepared = prepared::prepare(repo_data, &versions_to_print, has_uncommitted_changes, limit);
+    // Uncommitted changes are shown on top of head, which is not the newest version shown when looking elsewhere
+    let head_is_newest = versions_to_print.first().is_some_and(|v| v.id == repo_data.head_version().id);
+    let has_uncommitted_changes = summary.uncommitted_size_change.is_some() && head_is_newest;
+
+    let prepared = prepared::prepare(repo_data, &versions_to_print, has_uncommitted_changes, limit, true, columns, scope);
     let prepared = colorization::colorize_prepared(&prepared);
 
+    let head = match &repo_data.head {
+        Head::Branch(branch) => branch.bright_cyan().to_string(),
+        Head::Version(_) => format!("detached at {}", repo_data.head_version().nickname.white()),
+    };
+    let changes = match (summary.uncommitted_changed_bytes, summary.uncommitted_size_change) {
+        (Some(changed_bytes), Some(size_change)) => format!(
+            "uncommitted, {} changed ({:.1}%), size {}",
+            format_byte_count(changed_bytes),
+            percentage(changed_bytes, summary.versioned_file_size),
+            format_signed(size_change)
+        )
+        .yellow()
+        .to_string(),
+        _ => "none".to_string(),
+    };
+
+    let mut lines = vec![format!("{:<16}{}", "Head", head)];
+
+    if let Some(metadata) = repo_data.head.branch().and_then(|b| repo_data.branch_metadata.get(b))
+        && !metadata.description.is_empty()
+    {
+        lines.push(format!("{:<16}{}", "Branch", metadata.description));
+    }
+
+    if let Some(lock) = &summary.editing_lock {
+        let locked = format_editing_lock(lock, summary.editing_lock_is_own);
+        lines.push(format!(
+            "{:<16}{}",
+            "Locked by",
+            if summary.editing_lock_is_own { locked.yellow() } else { locked.red().bold() }
+        ));
+    }
+
+    lines.extend([
+        format!("{:<16}{}", "Versions", summary.version_count),
+        format!("{:<16}{}", "Repository size", format_byte_count(summary.repository_size)),
+        format!("{:<16}{}", "File size", format_byte_count(summary.versioned_file_size)),
+        format!("{:<16}{}", "Changes", changes),
+        String::new(),
+    ]);
+
     if let Some(off_screen_info) = &prepared.off_screen_info {
-        println!("{}", off_screen_info);
+        lines.push(off_screen_info.to_string());
     }
 
     for prepared_version in prepared.versions.iter().rev() {
-        println!("{}", prepared_version);
+        lines.push(prepared_version.to_string());
     }
 
     if let Some(uncommitted_changes) = &prepared.uncommitted_changes {
-        println!("{}", uncommitted_changes);
+        lines.push(uncommitted_changes.to_string());
+    }
+
+    print_paged(pager, &lines);
+}
+
+// Part of the interface for scripts, lines and fields may be added but never changed. Fields are separated by tabs,
+// which are replaced with spaces inside them.
+pub fn print_repository_data_porcelain(repo_data: &RepositoryData, has_uncommitted_changes: bool, all: bool, scope: &StatusScope) {
+    let field = |value: &str| value.replace('\t', " ");
+
+    let head_version = repo_data.head_version();
+    println!(
+        "head\t{}\t{}\t{}",
+        head_version.id.bs58(),
+        field(repo_data.head.branch().unwrap_or("")),
+        if has_uncommitted_changes { "modified" } else { "clean" }
+    );
+
+    let limit = if all { usize::MAX } else { MAX_VERSIONS_TO_PRINT };
+
+    for version in status_versions(repo_data, scope).into_iter().take(limit) {
+        let mut branches: Vec<&str> = repo_data.branches.iter().filter(|(_, id)| **id == version.id).map(|(b, _)| b.as_str()).collect();
+        branches.sort();
+
+        println!(
+            "version\t{}\t{}\t{}\t{}\t{}\t{}",
+            version.id.bs58(),
+            version.parent.map(|p| p.bs58()).unwrap_or_default(),
+            version.creation_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            field(&version.nickname),
+            field(&branches.join(",")),
+            field(version.summary())
+        );
     }
 }
 
+// Output that does not fit the terminal goes through the pager, like git does. Output that is not going to a
+// terminal is printed as is.
+fn print_paged(pager: Option<&str>, lines: &[String]) {
+    let fits_terminal = terminal_size::terminal_size().is_none_or(|(_, Height(height))| lines.len() < height as usize);
+
+    if let Some(pager) = pager
+        && !fits_terminal
+        && io::stdout().is_terminal()
+        && page(pager, lines).is_ok()
+    {
+        return;
+    }
+
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+// The pager may come with arguments, e.g. "less -R". Fails only before the pager has the output, so that the caller
+// does not print it twice
+fn page(pager: &str, lines: &[String]) -> io::Result<()> {
+    let pager_words = command_words(pager).unwrap_or_default();
+    let (program, pager_args) = pager_words.split_first().ok_or(io::ErrorKind::NotFound)?;
+
+    let mut child = Command::new(program).args(pager_args).stdin(Stdio::piped()).logged_spawn()?;
+
+    let mut stdin = child.stdin.take().expect("Pager stdin must be piped");
+    for line in lines {
+        // The pager was quit before reading everything
+        if writeln!(stdin, "{}", line).is_err() {
+            break;
+        }
+    }
+    drop(stdin);
+
+    if let Err(e) = child.wait() {
+        tracing::debug!("Could not wait for the pager: {}", e);
+    }
+
+    Ok(())
+}
+
+pub fn format_editing_lock(lock: &EditingLock, own: bool) -> String {
+    let user = if lock.user.is_empty() { "an unknown user" } else { &lock.user };
+    let holder = if own { "you".to_string() } else { format!("{} on {}", user, lock.machine) };
+    let lock_time = lock.lock_time.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+    format!("{} since {} ({})", holder, lock_time, chrono_humanize::HumanTime::from(lock.lock_time))
+}
+
 pub fn format_versions(repo_data: &RepositoryData, versions: &[&Version]) -> Vec<String> {
-    let prepared = prepared::prepare(repo_data, versions, false, None);
+    let prepared = prepared::prepare(repo_data, versions, false, None, false, &StatusColumns::FULL, &StatusScope::Head);
     prepared.versions.iter().map(|v| v.to_string()).collect()
 }
 
-pub fn print_dependencies(xdelta3_ready: bool, image_magick_ready: bool) {
-    fn optional_dep_status(ready: bool) -> ColoredString {
-        if ready { "ready".green() } else { "not found".yellow() }
+pub fn print_diff(version1: &Version, description1: &str, version2: &Version, description2: &str, report: &DiffReport) {
+    println!("{} {}", "---".red(), description1);
+    println!("{} {}", "+++".green(), description2);
+
+    let length_delta = version2.versioned_file_length as i128 - version1.versioned_file_length as i128;
+    println!(
+        "{:<16}{} -> {} ({})",
+        "Size",
+        format_byte_count(version1.versioned_file_length),
+        format_byte_count(version2.versioned_file_length),
+        format_signed(length_delta).yellow()
+    );
+    println!("{:<16}{:032x} -> {:032x}", "Hash", version1.versioned_file_hash, version2.versioned_file_hash);
+
+    let changed_bytes_percentage = percentage(report.changed_bytes, version2.versioned_file_length);
+    println!("{:<16}{} ({:.1}%)", "Changed bytes", format_byte_count(report.changed_bytes), changed_bytes_percentage);
+
+    let image_source = if report.image_diff_from_previews { " (based on previews)" } else { "" };
+
+    match &report.image_diff {
+        ImageDiffResult::NotAnImage => {}
+        ImageDiffResult::DimensionsDiffer { width1, height1, width2, height2 } => {
+            println!("{:<16}{}x{} -> {}x{}{}", "Dimensions", width1, height1, width2, height2, image_source);
+        }
+        ImageDiffResult::Ok(image_diff) => {
+            let total_pixels = image_diff.width as u64 * image_diff.height as u64;
+            let changed_pixels_percentage = percentage(image_diff.changed_pixels, total_pixels);
+            println!("{:<16}{} ({:.1}%){}", "Changed pixels", image_diff.changed_pixels, changed_pixels_percentage, image_source);
+
+            if let Some(bounding_box) = &image_diff.bounding_box {
+                println!(
+                    "{:<16}({}, {}) - ({}, {}) of {}x{}",
+                    "Changed area", bounding_box.left, bounding_box.top, bounding_box.right, bounding_box.bottom, image_diff.width, image_diff.height
+                );
+            }
+        }
+    }
+}
+
+pub fn print_autosave(action: &str, version: &Version) {
+    let time = Local::now().format("%H:%M:%S").to_string();
+    println!(
+        "{} {} {} ({})",
+        time.bright_black(),
+        action,
+        version.nickname.cyan(),
+        format_byte_count(version.versioned_file_length)
+    );
+}
+
+pub fn print_autosave_error(message: &str) {
+    let time = Local::now().format("%H:%M:%S").to_string();
+    eprintln_colored(format_args!("{} {}", time.bright_black(), message.red()));
+}
+
+pub fn print_hash_progress(hashed_length: u64, total_length: u64) {
+    eprint!("\rHashing... {:.0}%", percentage(hashed_length, total_length));
+
+    if hashed_length == total_length {
+        eprintln!();
     }
+}
+
+fn format_byte_count(byte_count: u64) -> String {
+    format!("{} B", byte_count)
+}
 
+// The two largest units are enough to get a feel for it, e.g. "3d 4h" or "12m 5s"
+fn format_time_span(time_span: TimeDelta) -> String {
+    let units = [
+        (time_span.num_days(), "d"),
+        (time_span.num_hours() % 24, "h"),
+        (time_span.num_minutes() % 60, "m"),
+        (time_span.num_seconds() % 60, "s"),
+    ];
+
+    let largest_unit = units.iter().position(|(value, _)| *value != 0).unwrap_or(units.len() - 1);
+
+    units[largest_unit..]
+        .iter()
+        .take(2)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_signed(value: i128) -> String {
+    if value > 0 { format!("+{} B", value) } else { format!("{} B", value) }
+}
+
+fn percentage(part: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { part as f64 * 100.0 / total as f64 }
+}
+
+pub fn print_pending_version_summary(repo_data: &RepositoryData, summary: &PendingVersionSummary, preview_path: Option<&Path>) {
+    let length_delta = summary.length as i128 - summary.parent_length as i128;
+    let changed_bytes_percentage = percentage(summary.changed_bytes, summary.length);
+
+    println!("{:<16}{}", "Nickname", summary.nickname.white());
+    println!("{:<16}{}", "Branch", repo_data.head.branch().unwrap_or("(detached)").bright_cyan());
+    println!("{:<16}{}", "Parent", summary.parent_nickname.white());
     println!(
-        "{:<14}{:<10}{}",
-        "xdelta3",
-        optional_dep_status(xdelta3_ready),
-        "(Optional) Used for storing version file content as patches, which reduces repository size on disk"
+        "{:<16}{} -> {} ({})",
+        "Size",
+        format_byte_count(summary.parent_length),
+        format_byte_count(summary.length),
+        format_signed(length_delta).yellow()
     );
+    println!("{:<16}{} ({:.1}%)", "Changed bytes", format_byte_count(summary.changed_bytes), changed_bytes_percentage);
+    if let Some(preview_path) = preview_path {
+        println!("{:<16}{}", "Preview", preview_path.display());
+    }
+    println!();
+}
+
+// Below status, so unlike the summary before committing it leaves out what the new version would be called
+pub fn print_pending_changes(repo_data: &RepositoryData, summary: &PendingVersionSummary, preview_path: Option<&Path>) {
+    let length_delta = summary.length as i128 - summary.parent_length as i128;
+    let changed_bytes_percentage = percentage(summary.changed_bytes, summary.length);
+    let since_head = Utc::now() - repo_data.head_version().creation_time;
+
+    println!();
     println!(
-        "{:<14}{:<10}{}",
-        "ImageMagick",
-        optional_dep_status(image_magick_ready),
-        "(Optional) Used for creating version previews for image files"
+        "{:<16}{} -> {} ({})",
+        "Pending size",
+        format_byte_count(summary.parent_length),
+        format_byte_count(summary.length),
+        format_signed(length_delta).yellow()
     );
+    println!("{:<16}{} ({:.1}%)", "Changed bytes", format_byte_count(summary.changed_bytes), changed_bytes_percentage);
+    println!("{:<16}{} ago", "Head committed", format_time_span(since_head).bright_blue());
+    if let Some(preview_path) = preview_path {
+        println!("{:<16}{}", "Preview", preview_path.display());
+    }
+}
+
+pub fn format_description_template(summary: &PendingVersionSummary, branch: &str) -> String {
+    let length_delta = summary.length as i128 - summary.parent_length as i128;
+    let changed_bytes_percentage = percentage(summary.changed_bytes, summary.length);
+
+    let lines = [
+        "Enter the description of the new version. Lines starting with '#' are ignored.".to_string(),
+        "The first line is shown in status, the whole description in show.".to_string(),
+        String::new(),
+        format!("{:<16}{}", "Nickname", summary.nickname),
+        format!("{:<16}{}", "Branch", branch),
+        format!("{:<16}{}", "Parent", summary.parent_nickname),
+        format!(
+            "{:<16}{} -> {} ({})",
+            "Size",
+            format_byte_count(summary.parent_length),
+            format_byte_count(summary.length),
+            format_signed(length_delta)
+        ),
+        format!("{:<16}{} ({:.1}%)", "Changed bytes", format_byte_count(summary.changed_bytes), changed_bytes_percentage),
+    ];
+
+    let comment_lines: Vec<String> = lines
+        .iter()
+        .map(|line| format!("{} {}", description_editor::COMMENT_PREFIX, line).trim_end().to_string())
+        .collect();
+
+    format!("\n{}\n", comment_lines.join("\n"))
+}
+
+pub fn print_signature_report(statuses: &[(&Version, SignatureStatus)]) {
+    for (version, status) in statuses {
+        let status = match status {
+            SignatureStatus::Unsigned => "unsigned".bright_black(),
+            SignatureStatus::Valid { key_id, trusted: true } => format!("signed by {}", key_id).green(),
+            SignatureStatus::Valid { key_id, trusted: false } => format!("valid, unknown key {}", key_id).yellow(),
+            SignatureStatus::Legacy { key_id } => format!("signed by {} over the 128-bit content hash only", key_id).yellow(),
+            SignatureStatus::Invalid { key_id } => format!("INVALID signature by {}", key_id).red(),
+        };
+
+        println!("{} {} {} {}", version.id.bs58().bright_black(), version.nickname.white(), version.author, status);
+    }
 }
 
-pub fn print_branch_list(repo_data: &RepositoryData) {
+pub fn print_verify_report(report: &VerifyReport) {
+    for problem in &report.problems {
+        let problem_text = match problem {
+            VerifyProblem::MissingBlob { blob_file_name } => format!("Blob {} is missing", blob_file_name),
+            VerifyProblem::CorruptedBlob { blob_file_name } => format!("Blob {} is corrupted (checksum mismatch)", blob_file_name),
+            VerifyProblem::ContentMismatch { version_id } => format!("Version {} reconstructs to unexpected content", version_id.bs58()),
+            VerifyProblem::ReconstructionFailed { version_id, error_message } => format!("Version {} could not be reconstructed: {}", version_id.bs58(), error_message),
+        };
+        println!("{}", problem_text.red());
+    }
+
+    println!("Checked {} blobs and {} versions", report.checked_blob_count, report.checked_version_count);
+
+    if report.recorded_checksum_count > 0 {
+        println!("Recorded checksums for {} blobs that had none", report.recorded_checksum_count);
+    }
+    if report.recorded_digest_count > 0 {
+        println!("Recorded full BLAKE3 digests for {} versions that had none", report.recorded_digest_count);
+    }
+}
+
+pub fn print_doctor_findings(findings: &[Finding]) {
+    for finding in findings {
+        let (finding_text, fix) = match finding {
+            Finding::InvalidData(problem) => (problem.to_string(), "Run repair to restore the data file from the freshest valid backup".to_string()),
+            Finding::MissingBlob { blob_file_name, version_count } => (
+                format!("Blob {} used by {} versions is missing", blob_file_name, version_count),
+                "Copy it back from a backup of the repository or pull from a remote that has it".to_string(),
+            ),
+            Finding::UnreachablePatchBase { version_id, base_blob_file_name } => (
+                format!(
+                    "Version {} is a patch against blob {}, which is not the full content of any version",
+                    version_id.bs58(),
+                    base_blob_file_name
+                ),
+                "Run verify to make sure the version still reconstructs".to_string(),
+            ),
+            Finding::MissingVersionRecord(version_id) => (
+                format!("Version {} has no version record", version_id.bs58()),
+                "Run verify, which writes missing version records for repair to rebuild from".to_string(),
+            ),
+            Finding::UnreferencedObjects { count, length } => (
+                format!("{} blob files ({} bytes) are not used by any version", count, length),
+                "Run gc to delete them".to_string(),
+            ),
+            Finding::UnreadablePatches { count } => (
+                format!(
+                    "{} patches were made by xdelta3 with secondary compression or a custom code table, which the bundled codec cannot apply",
+                    count
+                ),
+                "Install xdelta3 or set its path to restore the versions stored in them".to_string(),
+            ),
+        };
+
+        if finding.is_problem() {
+            println!("{}", finding_text.red());
+        } else {
+            println!("{}", finding_text.yellow());
+        }
+        println!("  {}", fix.bright_black());
+    }
+}
+
+pub fn print_dependencies(detected_tools: &[DetectedTool]) {
+    for DetectedTool { tool, detection } in detected_tools {
+        let (status, version, warnings) = match detection {
+            Detection::Missing => ("not found".yellow(), String::new(), &[][..]),
+            Detection::Found { version, warnings } => {
+                let status = if warnings.is_empty() { "ready".green() } else { "limited".yellow() };
+                let version = version.map(|v| v.to_string()).unwrap_or_else(|| "unknown".to_string());
+                (status, version, &warnings[..])
+            }
+        };
+
+        println!("{:<14}{:<10}{:<10}(Optional) {}", tool.name, status, version.bright_black(), tool.purpose);
+
+        for warning in warnings {
+            println!("{:<14}{}", "", warning.yellow());
+        }
+    }
+}
+
+pub fn print_version_list(pager: Option<&str>, repo_data: &RepositoryData, versions: &[&Version]) {
+    let prepared = prepared::prepare(repo_data, versions, false, None, false, &StatusColumns::FULL, &StatusScope::Head);
+    let prepared = colorization::colorize_prepared(&prepared);
+
+    let sizes: Vec<String> = versions.iter().map(|v| format_byte_count(v.versioned_file_length)).collect();
+    let max_size_length = sizes.iter().map(|s| s.len()).max().unwrap_or(0);
+    let max_author_length = versions.iter().map(|v| v.author.len()).max().unwrap_or(0);
+
+    // Like status, the first version in order is printed last, right above the prompt
+    let lines: Vec<String> = versions
+        .iter()
+        .zip(&sizes)
+        .zip(&prepared.versions)
+        .rev()
+        .map(|((version, size), prepared_version)| {
+            let size = format!("{:>max_size_length$}", size);
+
+            if max_author_length > 0 {
+                let author = format!("{:<max_author_length$}", version.author);
+                format!("{} {} {}", size.yellow(), author.bright_black(), prepared_version)
+            } else {
+                format!("{} {}", size.yellow(), prepared_version)
+            }
+        })
+        .collect();
+
+    print_paged(pager, &lines);
+}
+
+// Distance 0 means the previews look the same
+pub fn print_similar_versions(repo_data: &RepositoryData, similar: &[(&Version, u32)]) {
+    let versions: Vec<&Version> = similar.iter().map(|(v, _)| *v).collect();
+    let formatted_versions = format_versions(repo_data, &versions);
+
+    for ((_, distance), formatted_version) in similar.iter().zip(formatted_versions) {
+        println!("{:>2} {}", distance.to_string().yellow(), formatted_version);
+    }
+}
+
+pub fn print_version_details(repo_data: &RepositoryData, version: &Version) {
+    let mut branches: Vec<&str> = repo_data.branches.iter().filter(|(_, id)| **id == version.id).map(|(b, _)| b.as_str()).collect();
+    branches.sort();
+
+    let creation_time = version.creation_time.with_timezone(&Local);
+
+    println!("{:<16}{}", "Version", version.id.bs58().bright_black());
+    println!("{:<16}{}", "Nickname", version.nickname.white());
+    if !version.author.is_empty() {
+        println!("{:<16}{}", "Author", version.author);
+    }
+    println!("{:<16}{}", "Created", creation_time.format("%Y-%m-%d %H:%M:%S").to_string().blue());
+    println!("{:<16}{}", "Size", format_byte_count(version.versioned_file_length).yellow());
+    if let Some(parent) = version.parent.and_then(|p| repo_data.version(p)) {
+        println!("{:<16}{} {}", "Parent", parent.id.bs58().bright_black(), parent.nickname.white());
+    }
+    if !branches.is_empty() {
+        println!("{:<16}{}", "Branches", branches.join(", ").bright_cyan());
+    }
+    if version.id == repo_data.head_version().id {
+        println!("{:<16}{}", "Head", "yes".magenta());
+    }
+    if !version.attributes.is_empty() {
+        println!("{:<16}{}", "Attributes", prepared::format_attributes(version).yellow());
+    }
+    if let Some(review) = version.review() {
+        println!("{:<16}{}", "Review", format_review(review));
+    }
+    if let Some(forked_from) = &version.forked_from {
+        println!(
+            "{:<16}{} {}",
+            "Forked from",
+            forked_from.versioned_file.display(),
+            forked_from.version_id.bs58().bright_black()
+        );
+    }
+
+    if !version.description.is_empty() {
+        println!();
+        for line in version.description.lines() {
+            if line.is_empty() {
+                println!();
+            } else {
+                println!("    {}", line.green());
+            }
+        }
+    }
+
+    if !version.notes.is_empty() {
+        println!();
+        print_notes(version);
+    }
+}
+
+pub fn print_orphaned_versions(repo_data: &RepositoryData) {
+    for orphaned in repo_data.orphaned_versions.iter().rev() {
+        let orphaned_time = orphaned.orphaned_time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string();
+        println!(
+            "{} {} {} {} {}",
+            orphaned_time.blue(),
+            orphaned.version.id.bs58().bright_black(),
+            orphaned.version.nickname.white(),
+            format!("replaced on {}", orphaned.branch).bright_cyan(),
+            orphaned.version.summary().green()
+        );
+    }
+}
+
+pub fn print_notes(version: &Version) {
+    for note in &version.notes {
+        let creation_time = note.creation_time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string();
+
+        if note.author.is_empty() {
+            println!("{}", creation_time.blue());
+        } else {
+            println!("{} {}", creation_time.blue(), note.author.bright_black());
+        }
+
+        for line in note.text.lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+pub fn print_reviews(version: &Version) {
+    for review in &version.reviews {
+        let creation_time = review.creation_time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string();
+        println!("{} {}", creation_time.blue(), format_review(review));
+
+        for line in review.note.lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+fn format_review(review: &Review) -> String {
+    let state = match review.state {
+        ReviewState::Submitted => review.state.to_string().bright_yellow(),
+        ReviewState::Approved => review.state.to_string().bright_green(),
+        ReviewState::Rejected => review.state.to_string().bright_red(),
+    };
+
+    if review.reviewer.is_empty() {
+        state.to_string()
+    } else {
+        format!("{} by {}", state, review.reviewer)
+    }
+}
+
+#[derive(Serialize)]
+pub struct VersionListEntry<'a> {
+    id: String,
+    nickname: &'a str,
+    author: &'a str,
+    creation_time: DateTime<Utc>,
+    description: &'a str,
+    parent: Option<String>,
+    size: u64,
+    // Scripts read it by the name it had when xxh3 was the only algorithm
+    #[serde(rename = "xxh3_128")]
+    hash: String,
+    branches: Vec<&'a str>,
+    head: bool,
+    attributes: &'a BTreeMap<String, String>,
+    review: Option<ReviewState>,
+}
+
+pub fn print_version_list_json(repo_data: &RepositoryData, versions: &[&Version]) -> serde_json::Result<()> {
+    println!("{}", serde_json::to_string_pretty(&version_list_entries(repo_data, versions))?);
+
+    Ok(())
+}
+
+pub fn version_list_entries<'a>(repo_data: &'a RepositoryData, versions: &[&'a Version]) -> Vec<VersionListEntry<'a>> {
+    let head_version_id = repo_data.head_version().id;
+
+    versions
+        .iter()
+        .map(|v| {
+            let mut branches: Vec<&str> = repo_data.branches.iter().filter(|(_, id)| **id == v.id).map(|(b, _)| b.as_str()).collect();
+            branches.sort();
+
+            VersionListEntry {
+                id: v.id.bs58(),
+                nickname: &v.nickname,
+                author: &v.author,
+                creation_time: v.creation_time,
+                description: &v.description,
+                parent: v.parent.map(|p| p.bs58()),
+                size: v.versioned_file_length,
+                hash: format!("{:032x}", v.versioned_file_hash),
+                branches,
+                head: v.id == head_version_id,
+                attributes: &v.attributes,
+                review: v.review().map(|r| r.state),
+            }
+        })
+        .collect()
+}
+
+pub fn print_history_stats(stats: &HistoryStats) {
+    const BAR: &str = "#";
+
+    let first_version_time = stats.first_version_time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string();
+    let last_version_time = stats.last_version_time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string();
+
+    println!("{:<20}{}", "Versions", stats.version_count);
+    println!("{:<20}{}", "Branches", stats.branch_count);
+    println!("{:<20}{}", "First version", first_version_time.blue());
+    println!("{:<20}{}", "Last version", last_version_time.blue());
+    if let Some(average) = stats.average_time_between_versions() {
+        println!("{:<20}{}", "Average interval", format_time_span(average));
+    }
+    println!(
+        "{:<20}{} full, {} patches ({:.0}% patches)",
+        "Storage",
+        stats.full_version_count,
+        stats.patch_version_count,
+        percentage(stats.patch_version_count as u64, stats.version_count as u64)
+    );
+
+    let first_size = stats.sizes.first().map_or(0, |s| s.size);
+    let last_size = stats.sizes.last().map_or(0, |s| s.size);
+    println!(
+        "{:<20}{} -> {} ({})",
+        "Size",
+        format_byte_count(first_size),
+        format_byte_count(last_size),
+        format_signed(last_size as i128 - first_size as i128).yellow()
+    );
+
+    println!();
+    println!("{:<12}{:>10}  Versions", "Week", "Size");
+    for week in &stats.weeks {
+        println!(
+            "{:<12}{:>10}  {} {}",
+            week.week_start.format("%Y-%m-%d").to_string().blue(),
+            format_byte_count(week.size),
+            BAR.repeat(week.version_count).green(),
+            week.version_count
+        );
+    }
+
+    println!();
+    println!("{:<22}  Versions", "Weekday");
+    for weekday in &stats.weekdays {
+        println!(
+            "{:<12}{:>10}  {} {}",
+            weekday.weekday.to_string(),
+            "",
+            BAR.repeat(weekday.version_count).green(),
+            weekday.version_count
+        );
+    }
+}
+
+pub fn print_history_stats_json(stats: &HistoryStats) -> serde_json::Result<()> {
+    println!("{}", serde_json::to_string_pretty(stats)?);
+
+    Ok(())
+}
+
+pub fn print_blob_inventory(entries: &[InventoryEntry]) {
+    const MAX_VERSIONS_PER_BLOB: usize = 3;
+
+    let sizes: Vec<String> = entries.iter().map(|e| format_byte_count(e.size)).collect();
+    let max_size_length = sizes.iter().map(|s| s.len()).max().unwrap_or(0);
+
+    for (entry, size) in entries.iter().zip(&sizes) {
+        let role = match entry.role {
+            InventoryRole::Content => "content",
+            InventoryRole::Patch => "patch",
+            InventoryRole::Preview => "preview",
+            InventoryRole::VersionRecord => "record",
+            InventoryRole::Data => "data",
+            InventoryRole::Backup => "backup",
+            InventoryRole::Conflict => "conflict",
+            InventoryRole::Cache => "cache",
+            InventoryRole::Log => "log",
+            InventoryRole::Temporary => "temp",
+            InventoryRole::Trash => "trash",
+            InventoryRole::Other => "other",
+        };
+
+        let mut nicknames: Vec<&str> = entry.versions.iter().take(MAX_VERSIONS_PER_BLOB).map(|v| v.nickname.as_str()).collect();
+        let more_versions = format!("+{} more", entry.versions.len().saturating_sub(MAX_VERSIONS_PER_BLOB));
+        if entry.versions.len() > MAX_VERSIONS_PER_BLOB {
+            nicknames.push(&more_versions);
+        }
+
+        let orphaned = if entry.orphaned { "orphaned".red() } else { "".normal() };
+
+        let line = format!("{} {:<8} {:<8} {}", format!("{:>max_size_length$}", size).yellow(), role, orphaned, entry.path.display());
+
+        if nicknames.is_empty() {
+            println!("{}", line);
+        } else {
+            println!("{} {}", line, nicknames.join(", ").white());
+        }
+    }
+
+    let total_size: u64 = entries.iter().map(|e| e.size).sum();
+    let orphaned: Vec<_> = entries.iter().filter(|e| e.orphaned).collect();
+    let orphaned_size: u64 = orphaned.iter().map(|e| e.size).sum();
+
+    println!("{} files, {} in total", entries.len(), format_byte_count(total_size));
+    if !orphaned.is_empty() {
+        println!("{} orphaned files, {} reclaimable", orphaned.len(), format_byte_count(orphaned_size));
+    }
+}
+
+pub fn print_branch_list(repo_data: &RepositoryData, verbose: bool) {
     for branch in repo_data.branches.keys() {
-        println!("{}", branch)
+        if repo_data.is_protected(branch) {
+            println!("{} {}", branch, "(protected)".bright_black())
+        } else {
+            println!("{}", branch)
+        }
+
+        if !verbose {
+            continue;
+        }
+
+        let Some(metadata) = repo_data.branch_metadata.get(branch) else {
+            continue;
+        };
+
+        if !metadata.description.is_empty() {
+            println!("    {}", metadata.description);
+        }
+
+        if let Some(creation_time) = metadata.creation_time {
+            let creation_time = creation_time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string();
+            let created = match metadata.creator.as_str() {
+                "" => format!("Created {}", creation_time),
+                creator => format!("Created {} by {}", creation_time, creator),
+            };
+            println!("    {}", created.bright_black());
+        }
     }
 }
 
+pub fn print_remote_list(remotes: &BTreeMap<String, RemoteLocation>) {
+    let max_name_length = remotes.keys().map(|name| name.len()).max().unwrap_or(0);
+
+    for (name, location) in remotes {
+        println!("{:<max_name_length$} {}", name, location.to_string().bright_black());
+    }
+}
+
+pub fn print_reconciled_copies(reconciled_copies: &[ReconciledCopy]) {
+    for reconciled_copy in reconciled_copies {
+        let file_name = reconciled_copy.file_name.bright_black();
+
+        match &reconciled_copy.outcome {
+            ReconcileOutcome::Merged {
+                new_version_count,
+                diverged_branches,
+            } => {
+                println!("{} merged {} versions", file_name, new_version_count);
+
+                for diverged_branch in diverged_branches {
+                    println!(
+                        "{} branch {} diverged, the other one was saved as {}",
+                        file_name, diverged_branch.branch, diverged_branch.remote_branch
+                    );
+                }
+            }
+            ReconcileOutcome::Unreadable => println!("{} {}", file_name, "is not valid repository data".yellow()),
+            ReconcileOutcome::UnrelatedHistories => println!("{} {}", file_name, "holds the history of a different repository".yellow()),
+            ReconcileOutcome::MissingObjects(count) => println!("{} {}", file_name, format!("refers to {} objects that are not synced yet", count).yellow()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorJson<'a> {
+    severity: &'a BiverErrorSeverity,
+    exit_code: u8,
+    message: &'a str,
+}
+
+pub fn print_error_json(biver_error: &BiverError) {
+    let exit_code = match biver_error.severity {
+        BiverErrorSeverity::Warning => 0,
+        BiverErrorSeverity::Error => biver_error.category.exit_code(),
+    };
+
+    let error_json = ErrorJson {
+        severity: &biver_error.severity,
+        exit_code,
+        message: &biver_error.error_message,
+    };
+
+    eprintln!("{}", serde_json::to_string(&error_json).expect("Error JSON must serialize"));
+}
+
+pub fn print_dry_run(original_repo_data: &RepositoryData, repo_data: &RepositoryData, effects: &[Effect]) {
+    println!("{}", "Dry run, nothing was changed".yellow());
+
+    let describe = |version: &Version| format!("{} {}", version.id.bs58(), version.nickname);
+    let describe_id = |id: &VersionId| match repo_data.version(*id).or_else(|| original_repo_data.version(*id)) {
+        Some(version) => describe(version),
+        None => id.bs58(),
+    };
+
+    for version in repo_data.versions.iter().filter(|v| original_repo_data.version(v.id).is_none()) {
+        println!("Would add version {} ({} bytes) {}", describe(version), version.versioned_file_length, version.summary());
+    }
+
+    for version in original_repo_data.versions.iter().filter(|v| repo_data.version(v.id).is_none()) {
+        println!("Would remove version {}", describe(version));
+    }
+
+    let mut branches: Vec<&String> = original_repo_data.branches.keys().chain(repo_data.branches.keys()).collect();
+    branches.sort();
+    branches.dedup();
+
+    for branch in branches {
+        match (original_repo_data.branches.get(branch), repo_data.branches.get(branch)) {
+            (Some(original_leaf_id), Some(leaf_id)) if original_leaf_id != leaf_id => {
+                println!("Would move branch {} from {} to {}", branch, describe_id(original_leaf_id), describe_id(leaf_id))
+            }
+            (None, Some(leaf_id)) => println!("Would create branch {} at {}", branch, describe_id(leaf_id)),
+            (Some(_), None) => println!("Would delete branch {}", branch),
+            _ => {}
+        }
+    }
+
+    if original_repo_data.head.branch() != repo_data.head.branch() {
+        println!("Would move head to {}", repo_data.head.branch().unwrap_or("a detached version"));
+    }
+
+    for effect in effects {
+        match effect {
+            Effect::WriteBlob { blob_file_name, length } => println!("Would write blob {} ({} bytes)", blob_file_name, length),
+            Effect::DeleteBlob { blob_file_name, length } => println!("Would delete blob {} ({} bytes)", blob_file_name, length),
+            Effect::RestoreVersionedFile { version_id } => {
+                let version = repo_data.version(*version_id).expect("A restored version must exist");
+                println!("Would restore the versioned file to {}", describe(version));
+            }
+        }
+    }
+}
+
+pub fn print_workspace_file_header(versioned_file: &Path) {
+    println!("{}", versioned_file.display().to_string().bold());
+}
+
+pub fn print_workspace_file_warning(message: &str) {
+    println!("{}", message.yellow());
+}
+
+pub fn print_workspace_file_error(message: &str) {
+    print_error(message);
+}
+
+pub fn print_workspace_summary(outcomes: &[FileOutcome]) {
+    let count = |predicate: fn(&FileOutcome) -> bool| outcomes.iter().filter(|o| predicate(o)).count();
+
+    println!(
+        "{} files: {} ok, {} warnings, {} failed",
+        outcomes.len(),
+        count(|o| matches!(o, FileOutcome::Ok)),
+        count(|o| matches!(o, FileOutcome::Warning)),
+        count(|o| matches!(o, FileOutcome::Error))
+    );
+}
+
 mod colorization {
     use crate::formatting::prepared::{Prepared, PreparedOffScreen, PreparedUncommitedChanges, PreparedVersion};
+    use crate::repository_data::ReviewState;
     use colored::{ColoredString, Colorize};
 
     pub fn colorize_prepared(prepared: &Prepared<String>) -> Prepared<ColoredString> {
@@ -77,17 +1059,32 @@ mod colorization {
 
     pub fn colorize_version(prepared_version: &PreparedVersion<String>) -> PreparedVersion<ColoredString> {
         PreparedVersion {
-            creation_time: prepared_version.creation_time.blue(),
-            creation_time_humanized: prepared_version.creation_time_humanized.bright_blue(),
-            id: prepared_version.id.bright_black(),
-            nickname: prepared_version.nickname.white(),
+            creation_time: prepared_version.creation_time.clone().map(|c| c.blue()),
+            creation_time_humanized: prepared_version.creation_time_humanized.clone().map(|c| c.bright_blue()),
+            id: prepared_version.id.clone().map(|i| i.bright_black()),
+            nickname: prepared_version.nickname.clone().map(|n| n.white()),
+            lane: prepared_version.lane.clone().map(|l| l.cyan()),
             head_badge: prepared_version.head_badge.clone().map(|h| h.magenta()),
             other_branches_here: prepared_version.other_branches_here.clone().map(|b| b.bright_cyan()),
             forking_branches: prepared_version.forking_branches.clone().map(|f| f.bright_cyan()),
+            pinned_badge: prepared_version.pinned_badge.clone().map(|p| p.bright_red()),
+            attributes: prepared_version.attributes.clone().map(|a| a.yellow()),
+            review_badge: prepared_version.review_badge.clone().map(|r| colorize_review_badge(&r)),
             description: prepared_version.description.clone().map(|d| d.green()),
         }
     }
 
+    // The badge text is all that is left of the state here
+    fn colorize_review_badge(review_badge: &str) -> ColoredString {
+        if review_badge.contains(&ReviewState::Approved.to_string()) {
+            review_badge.bright_green()
+        } else if review_badge.contains(&ReviewState::Rejected.to_string()) {
+            review_badge.bright_red()
+        } else {
+            review_badge.bright_yellow()
+        }
+    }
+
     pub fn colorize_uncommitted_changes(prepared_uncommitted_changes: &PreparedUncommitedChanges<String>) -> PreparedUncommitedChanges<ColoredString> {
         PreparedUncommitedChanges {
             uncommitted_changes_text: prepared_uncommitted_changes.uncommitted_changes_text.yellow(),
@@ -96,13 +1093,18 @@ mod colorization {
 }
 
 mod prepared {
+    use crate::formatting::{StatusColumns, StatusScope};
     use crate::repository_data::{RepositoryData, Version};
+    use crate::repository_operations;
     use crate::version_id::VersionId;
     use chrono_humanize::HumanTime;
+    use std::cmp::Reverse;
     use std::collections::{HashMap, HashSet};
     use std::fmt;
     use std::fmt::{Display, Formatter};
 
+    const CREATION_TIME_LENGTH: usize = "YYYY-MM-DD HH:MM:SS".len();
+
     pub struct Prepared<T> {
         pub off_screen_info: Option<PreparedOffScreen<T>>,
         pub versions: Vec<PreparedVersion<T>>,
@@ -131,51 +1133,43 @@ mod prepared {
     }
 
     pub struct PreparedVersion<T> {
-        pub creation_time: T,
-        pub creation_time_humanized: T,
-        pub id: T,
-        pub nickname: T,
+        pub creation_time: Option<T>,
+        pub creation_time_humanized: Option<T>,
+        pub id: Option<T>,
+        pub nickname: Option<T>,
+        pub lane: Option<T>,
         pub head_badge: Option<T>,
         pub other_branches_here: Option<T>,
         pub forking_branches: Option<T>,
+        pub pinned_badge: Option<T>,
+        pub attributes: Option<T>,
+        pub review_badge: Option<T>,
         pub description: Option<T>,
     }
 
     impl<T: Display> Display for PreparedVersion<T> {
         fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-            fn fmt_clearance(f: &mut Formatter<'_>) -> fmt::Result {
-                write!(f, " ")
-            }
-
-            self.creation_time.fmt(f)?;
-
-            fmt_clearance(f)?;
-            self.creation_time_humanized.fmt(f)?;
-
-            fmt_clearance(f)?;
-            self.id.fmt(f)?;
-
-            fmt_clearance(f)?;
-            self.nickname.fmt(f)?;
-
-            if let Some(head_badge) = &self.head_badge {
-                fmt_clearance(f)?;
-                head_badge.fmt(f)?;
-            }
-
-            if let Some(other_branches_here) = &self.other_branches_here {
-                fmt_clearance(f)?;
-                other_branches_here.fmt(f)?;
-            }
-
-            if let Some(forking_branches) = &self.forking_branches {
-                fmt_clearance(f)?;
-                forking_branches.fmt(f)?;
-            }
+            let fields = [
+                &self.creation_time,
+                &self.creation_time_humanized,
+                &self.id,
+                &self.nickname,
+                &self.lane,
+                &self.head_badge,
+                &self.other_branches_here,
+                &self.forking_branches,
+                &self.pinned_badge,
+                &self.attributes,
+                &self.review_badge,
+                &self.description,
+            ];
+
+            for (index, field) in fields.into_iter().flatten().enumerate() {
+                if index > 0 {
+                    write!(f, " ")?;
+                }
 
-            if let Some(description) = &self.description {
-                fmt_clearance(f)?;
-                description.fmt(f)?;
+                field.fmt(f)?;
             }
 
             Ok(())
@@ -192,10 +1186,55 @@ mod prepared {
         }
     }
 
-    pub fn prepare(repo_data: &RepositoryData, versions_to_prepare: &[&Version], has_uncommitted_changes: bool, limit_from_end: Option<usize>) -> Prepared<String> {
+    pub fn format_attributes(version: &Version) -> String {
+        let attributes: Vec<String> = version.attributes.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+        format!("{{{}}}", attributes.join(" "))
+    }
+
+    // Each version belongs to the first branch leading to it, the head branch first and then the most recently changed
+    fn version_lanes(repo_data: &RepositoryData) -> HashMap<VersionId, &str> {
+        let head_branch = repo_data.head.branch();
+
+        let mut branches: Vec<(&str, &Version)> = repo_data.branches.keys().filter_map(|b| Some((b.as_str(), repo_data.branch_leaf(b)?))).collect();
+        branches.sort_by_key(|(branch, leaf)| (Some(*branch) != head_branch, Reverse(leaf.creation_time), *branch));
+
+        let mut lanes = HashMap::new();
+        for (branch, leaf) in branches {
+            for version in repo_data.iter_version_and_ancestors(leaf.id) {
+                if lanes.contains_key(&version.id) {
+                    break;
+                }
+                lanes.insert(version.id, branch);
+            }
+        }
+
+        lanes
+    }
+
+    pub fn prepare(
+        repo_data: &RepositoryData,
+        versions_to_prepare: &[&Version],
+        has_uncommitted_changes: bool,
+        limit_from_end: Option<usize>,
+        short_ids: bool,
+        columns: &StatusColumns,
+        scope: &StatusScope,
+    ) -> Prepared<String> {
         let mut prepared_versions = Vec::new();
 
-        let head_version_ids: Vec<VersionId> = repo_data.iter_head_and_ancestors().map(|v| v.id).collect();
+        let short_id_length = short_ids.then(|| repository_operations::short_id_length(repo_data));
+
+        // Branches fork from the line of versions leading to head, or to the branch looked at
+        let mainline_leaf_id = match scope {
+            StatusScope::Branch(branch) => repo_data.branches.get(branch).copied().unwrap_or(repo_data.head_version().id),
+            StatusScope::Head | StatusScope::AllBranches => repo_data.head_version().id,
+        };
+        let mainline_version_ids: Vec<VersionId> = repo_data.iter_version_and_ancestors(mainline_leaf_id).map(|v| v.id).collect();
+
+        let lanes = match scope {
+            StatusScope::AllBranches => version_lanes(repo_data),
+            StatusScope::Head | StatusScope::Branch(_) => HashMap::new(),
+        };
 
         let branches_forking_at_version_id: HashMap<VersionId, Vec<String>> = repo_data
             .branches
@@ -204,7 +1243,7 @@ mod prepared {
                 let join_version_id = repo_data
                     .iter_version_and_ancestors(*branch_leaf_id)
                     .map(|v| v.id)
-                    .find(|id| head_version_ids.contains(id))
+                    .find(|id| mainline_version_ids.contains(id))
                     .unwrap();
 
                 (join_version_id, branch.clone())
@@ -223,6 +1262,7 @@ mod prepared {
         let mut version_count = 0;
         let mut off_screen_branches = HashSet::new();
 
+        let mut max_id_length = 0;
         let mut max_nickname_length = 0;
         let mut max_creation_time_humanized_length = 0;
 
@@ -251,7 +1291,7 @@ mod prepared {
                 forking_branches.retain(|b| !branches_on_version.contains(&b.as_str()));
             }
 
-            let forking_branches = if forking_branches.len() > 0 {
+            let forking_branches = if !forking_branches.is_empty() {
                 Some(format!("->[{}]", forking_branches.join(", ")))
             } else {
                 None
@@ -259,6 +1299,12 @@ mod prepared {
 
             let head_is_on_version = version.id == head_version_id;
 
+            // Leaves already show their branches
+            let lane = lanes
+                .get(&version.id)
+                .filter(|lane| !branches_on_version.is_some_and(|b| b.contains(lane)))
+                .map(|lane| format!("<{}>", lane));
+
             let head_badge = if head_is_on_version {
                 if let Some(branch) = head_branch {
                     Some(format!("[HEAD = {}]", branch))
@@ -283,54 +1329,76 @@ mod prepared {
                         }
                     };
 
-                    if branches_on_version.len() == 0{
+                    if branches_on_version.is_empty() {
                         None
-                    }
-                    else {
+                    } else {
                         Some(format!("[{}]", branches_on_version.join(", ")))
                     }
                 }
             };
 
+            let id = match short_id_length {
+                Some(short_id_length) => version.id.bs58().chars().take(short_id_length).collect(),
+                None => version.id.bs58(),
+            };
+
+            max_id_length = max_id_length.max(id.len());
             max_nickname_length = max_nickname_length.max(version.nickname.len());
             max_creation_time_humanized_length = max_creation_time_humanized_length.max(creation_time_humanized.len());
 
             prepared_versions.push(PreparedVersion {
-                creation_time: creation_time_local.format("%Y-%m-%d %H:%M:%S").to_string(),
-                creation_time_humanized: creation_time_humanized.to_string(),
-                id: version.id.bs58(),
-                nickname: version.nickname.clone(),
-                head_badge,
-                other_branches_here,
-                forking_branches,
-                description: if version.description.len() > 0 { Some(version.description.to_string()) } else { None },
+                creation_time: columns.creation_time.then(|| creation_time_local.format("%Y-%m-%d %H:%M:%S").to_string()),
+                creation_time_humanized: columns.humanized_time.then(|| creation_time_humanized.to_string()),
+                id: columns.id.then_some(id),
+                nickname: columns.nickname.then(|| version.nickname.clone()),
+                lane: lane.filter(|_| columns.branches),
+                head_badge: head_badge.filter(|_| columns.branches),
+                other_branches_here: other_branches_here.filter(|_| columns.branches),
+                forking_branches: forking_branches.filter(|_| columns.branches),
+                pinned_badge: (columns.pinned && repo_data.is_pinned(version.id)).then(|| "(pinned)".to_string()),
+                attributes: if columns.attributes && !version.attributes.is_empty() {
+                    Some(format_attributes(version))
+                } else {
+                    None
+                },
+                review_badge: version.review().filter(|_| columns.review).map(|r| format!("({})", r.state)),
+                description: if columns.description && !version.description.is_empty() {
+                    Some(version.summary().to_string())
+                } else {
+                    None
+                },
             });
         }
 
         for version in &mut prepared_versions {
-            version.nickname = format!("{:>max_nickname_length$}", version.nickname);
-            version.creation_time_humanized = format!("{:<max_creation_time_humanized_length$}", version.creation_time_humanized);
+            if let Some(nickname) = &mut version.nickname {
+                *nickname = format!("{:>max_nickname_length$}", nickname);
+            }
+            if let Some(creation_time_humanized) = &mut version.creation_time_humanized {
+                *creation_time_humanized = format!("{:<max_creation_time_humanized_length$}", creation_time_humanized);
+            }
         }
 
         let total_version_count = versions_to_prepare.len();
 
-        let off_screen_version_count = if total_version_count > limit_from_end {
-            total_version_count - limit_from_end
-        } else {
-            0
-        };
+        let off_screen_version_count = total_version_count.saturating_sub(limit_from_end);
 
-        let version_id_position = max_creation_time_humanized_length + 21;
+        // After the visible columns before the ID and their clearances
+        let version_id_position = [(columns.creation_time, CREATION_TIME_LENGTH), (columns.humanized_time, max_creation_time_humanized_length)]
+            .into_iter()
+            .filter(|(visible, _)| *visible)
+            .map(|(_, length)| length + 1)
+            .sum();
 
         let off_screen_info = if off_screen_version_count == 0 {
             None
         } else {
             let more_versions_text = format!("...{} more versions", off_screen_version_count);
 
-            let more_versions_slot_length = 23 + max_nickname_length;
+            let more_versions_slot_length = if columns.id { max_id_length + 1 } else { 0 } + if columns.nickname { max_nickname_length } else { 0 };
             let forking_branches_offset = more_versions_slot_length - more_versions_text.len().min(more_versions_slot_length) + 1;
 
-            let forking_branches = if off_screen_branches.len() == 0 {
+            let forking_branches = if !columns.branches || off_screen_branches.is_empty() {
                 None
             } else {
                 Some(format!("->[{}]", off_screen_branches.into_iter().collect::<Vec<_>>().join(", ")))
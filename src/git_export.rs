@@ -0,0 +1,245 @@
+use crate::biver_result::BiverResult;
+use crate::env::Env;
+use crate::repository_data::{RepositoryData, Version};
+use crate::repository_io;
+use crate::repository_paths::RepositoryPaths;
+use crate::tools::git;
+use crate::version_id::VersionId;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::{fs, io};
+
+const EXPORT_STATE_FILE_NAME: &str = "biver-export.json";
+const EXPORT_MARKS_FILE_NAME: &str = "biver-export-marks";
+const CONTENT_TEMP_FILE_NAME: &str = "git_export.tmp";
+
+// Commits are created on this ref and only reachable from branches once the import is complete
+const EXPORT_REF: &str = "refs/biver-export";
+
+pub enum GitContent {
+    Raw,
+    LfsPointer,
+}
+
+// Kept in the git directory so that repeated exports only add versions created since the previous one
+#[derive(Default, Serialize, Deserialize)]
+struct ExportState {
+    commits: HashMap<String, String>,
+    branches: Vec<String>,
+}
+
+pub fn export(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, git_repo: &Path, git_dir: &Path, content: &GitContent) -> BiverResult<usize> {
+    let state_path = git_dir.join(EXPORT_STATE_FILE_NAME);
+    let mut state: ExportState = match fs::read(&state_path) {
+        Ok(state_contents) => serde_json::from_slice(&state_contents)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => ExportState::default(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let new_versions: Vec<&Version> = parents_first(repo_data).into_iter().filter(|v| !state.commits.contains_key(&v.id.bs58())).collect();
+    let commit_marks: HashMap<VersionId, usize> = new_versions.iter().enumerate().map(|(index, v)| (v.id, 2 * index + 2)).collect();
+
+    let committish = |version_id: VersionId| match commit_marks.get(&version_id) {
+        Some(mark) => format!(":{}", mark),
+        None => state.commits[&version_id.bs58()].clone(),
+    };
+
+    let marks_path = git_dir.join(EXPORT_MARKS_FILE_NAME);
+    let content_temp_path = repo_paths.unique_temp_file_path(env, CONTENT_TEMP_FILE_NAME);
+
+    let mut fast_import = git::spawn_fast_import(env, git_repo, &marks_path)?;
+    let mut stream = BufWriter::new(fast_import.stdin.take().expect("fast-import stdin must be piped"));
+
+    let write_result = (|| -> BiverResult<()> {
+        for version in &new_versions {
+            let commit_mark = commit_marks[&version.id];
+            let blob_mark = commit_mark - 1;
+
+            repository_io::extract_version_content(env, repo_paths, &version.content_blob, &content_temp_path)?;
+            write_content_blob(&mut stream, blob_mark, &content_temp_path, git_dir, content)?;
+
+            write_commit(&mut stream, repo_paths, version, commit_mark, blob_mark, version.parent.map(&committish), content)?;
+        }
+
+        for (branch, version_id) in &repo_data.branches {
+            writeln!(stream, "reset refs/heads/{}\nfrom {}\n", branch, committish(*version_id))?;
+        }
+
+        stream.flush()?;
+        Ok(())
+    })();
+
+    drop(stream);
+    let wait_result = git::wait_for_fast_import(&mut fast_import);
+    let _ = fs::remove_file(&content_temp_path);
+
+    write_result?;
+    wait_result?;
+
+    for line in fs::read_to_string(&marks_path)?.lines() {
+        let Some((mark, sha)) = line.strip_prefix(':').and_then(|l| l.split_once(' ')) else {
+            continue;
+        };
+
+        if let Some(version) = new_versions.iter().find(|v| commit_marks[&v.id].to_string() == mark) {
+            state.commits.insert(version.id.bs58(), sha.to_string());
+        }
+    }
+
+    fs::remove_file(&marks_path)?;
+
+    if !new_versions.is_empty() {
+        git::delete_ref(env, git_repo, EXPORT_REF)?;
+    }
+
+    for deleted_branch in state.branches.iter().filter(|b| !repo_data.branches.contains_key(*b)) {
+        git::delete_ref(env, git_repo, &format!("refs/heads/{}", deleted_branch))?;
+    }
+
+    state.branches = repo_data.branches.keys().cloned().collect();
+    state.branches.sort();
+
+    fs::write(&state_path, serde_json::to_string_pretty(&state)?)?;
+
+    Ok(new_versions.len())
+}
+
+fn parents_first(repo_data: &RepositoryData) -> Vec<&Version> {
+    let mut versions: Vec<&Version> = repo_data.versions.iter().collect();
+    versions.sort_by_key(|v| v.creation_time);
+
+    let mut ordered = Vec::with_capacity(versions.len());
+    let mut visited = HashSet::new();
+
+    for version in versions {
+        let mut unvisited_ancestors = Vec::new();
+        let mut current = Some(version);
+
+        while let Some(v) = current
+            && visited.insert(v.id)
+        {
+            unvisited_ancestors.push(v);
+            current = v.parent.and_then(|parent_id| repo_data.version(parent_id));
+        }
+
+        ordered.extend(unvisited_ancestors.into_iter().rev());
+    }
+
+    ordered
+}
+
+fn write_content_blob(stream: &mut impl Write, blob_mark: usize, content_path: &Path, git_dir: &Path, content: &GitContent) -> BiverResult<()> {
+    writeln!(stream, "blob\nmark :{}", blob_mark)?;
+
+    match content {
+        GitContent::Raw => {
+            writeln!(stream, "data {}", fs::metadata(content_path)?.len())?;
+            io::copy(&mut File::open(content_path)?, stream)?;
+            writeln!(stream)?;
+        }
+        GitContent::LfsPointer => {
+            let pointer = store_lfs_object(content_path, git_dir)?;
+            write_data(stream, &pointer)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Stores the content where git-lfs keeps local objects and returns the pointer file that replaces it in the tree
+fn store_lfs_object(content_path: &Path, git_dir: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(content_path)?, &mut hasher)?;
+    let oid = format!("{:x}", hasher.finalize());
+
+    let object_dir = git_dir.join("lfs").join("objects").join(&oid[0..2]).join(&oid[2..4]);
+    let object_path = object_dir.join(&oid);
+
+    if !fs::exists(&object_path)? {
+        fs::create_dir_all(&object_dir)?;
+        fs::copy(content_path, &object_path)?;
+    }
+
+    let size = fs::metadata(content_path)?.len();
+
+    Ok(format!("version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize {}\n", oid, size))
+}
+
+fn write_commit(
+    stream: &mut impl Write,
+    repo_paths: &RepositoryPaths,
+    version: &Version,
+    commit_mark: usize,
+    blob_mark: usize,
+    parent: Option<String>,
+    content: &GitContent,
+) -> BiverResult<()> {
+    let file_name = repo_paths.versioned_file_name().unwrap_or_else(|| "file".to_string());
+
+    let author: String = version.author.chars().filter(|c| !matches!(c, '<' | '>' | '\n')).collect();
+    let author = if author.trim().is_empty() { "biver" } else { author.trim() };
+    let timestamp = version.creation_time.timestamp();
+
+    let summary = if version.description.is_empty() { &version.nickname } else { &version.description };
+    let message = format!("{}\n\nBiver-Version: {} ({})\n", summary, version.id.bs58(), version.nickname);
+
+    let file_mode = match version.unix_mode {
+        Some(mode) if mode & 0o111 != 0 => "100755",
+        _ => "100644",
+    };
+
+    // A root commit must not continue from whatever the export ref pointed to last
+    if parent.is_none() {
+        writeln!(stream, "reset {}", EXPORT_REF)?;
+    }
+
+    writeln!(stream, "commit {}\nmark :{}", EXPORT_REF, commit_mark)?;
+    writeln!(stream, "author {} <> {} +0000", author, timestamp)?;
+    writeln!(stream, "committer {} <> {} +0000", author, timestamp)?;
+    write_data(stream, &message)?;
+
+    if let Some(parent) = parent {
+        writeln!(stream, "from {}", parent)?;
+    }
+
+    writeln!(stream, "deleteall")?;
+    writeln!(stream, "M {} :{} {}", file_mode, blob_mark, quoted_path(&file_name))?;
+
+    if let GitContent::LfsPointer = content {
+        writeln!(stream, "M 100644 inline .gitattributes")?;
+        write_data(stream, &format!("{} filter=lfs diff=lfs merge=lfs -text\n", gitattributes_pattern(&file_name)))?;
+    }
+
+    writeln!(stream)?;
+
+    Ok(())
+}
+
+fn write_data(stream: &mut impl Write, data: &str) -> io::Result<()> {
+    writeln!(stream, "data {}\n{}", data.len(), data)
+}
+
+fn quoted_path(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+// Glob characters are escaped, and patterns with whitespace are quoted like paths
+fn gitattributes_pattern(file_name: &str) -> String {
+    let mut pattern = String::from("/");
+    for c in file_name.chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+
+    if pattern.contains(char::is_whitespace) || pattern.contains('"') {
+        quoted_path(&pattern)
+    } else {
+        pattern
+    }
+}
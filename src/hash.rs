@@ -1,20 +1,172 @@
+use crate::formatting;
+use memmap2::Mmap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io;
-use std::io::{BufReader, Read};
+use std::io::Read;
+use std::time::Instant;
+use std::{fmt, io};
 use xxhash_rust::xxh3::Xxh3;
 
-pub fn xxh3_128(file: &File) -> io::Result<u128> {
-    let mut reader = BufReader::new(file);
-    let mut hasher = Xxh3::new();
-    let mut buffer = [0; 8192];
+pub trait HashEnv {
+    fn hash_chunk_size(&self) -> usize;
+    fn verbose(&self) -> bool;
+}
+
+// What version contents and blob checksums are hashed with, chosen when a repository is created. xxh3 is what
+// repositories without the field use. BLAKE3 resists deliberately made collisions, which matters once versions are
+// signed. Both give the 128-bit hash versions are told apart by, BLAKE3 the first half of its digest. Versions also keep
+// the full 256-bit BLAKE3 digest, since half of it only resists collisions up to about 2^64 attempts.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Xxh3,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub fn is_xxh3(&self) -> bool {
+        *self == HashAlgorithm::Xxh3
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::new(Xxh3::new())),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgorithm::Xxh3 => write!(f, "xxh3"),
+            HashAlgorithm::Blake3 => write!(f, "BLAKE3"),
+        }
+    }
+}
+
+enum Hasher {
+    Xxh3(Box<Xxh3>),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn update(&mut self, chunk: &[u8]) {
+        match self {
+            Hasher::Xxh3(hasher) => hasher.update(chunk),
+            Hasher::Blake3(hasher) => {
+                hasher.update(chunk);
+            }
+        }
+    }
+
+    fn finish(&self) -> FileHash {
+        match self {
+            Hasher::Xxh3(hasher) => FileHash {
+                hash: hasher.digest128(),
+                digest: None,
+            },
+            Hasher::Blake3(hasher) => {
+                let digest = Digest(*hasher.finalize().as_bytes());
+                FileHash {
+                    hash: digest.first_half(),
+                    digest: Some(digest),
+                }
+            }
+        }
+    }
+}
+
+// A full BLAKE3 hash, written as hex like b3sum prints it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Digest(pub [u8; 32]);
 
-    loop {
-        let bytes_read = reader.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
+impl Digest {
+    pub fn first_half(&self) -> u128 {
+        let (first_half, _) = self.0.split_first_chunk::<16>().expect("BLAKE3 hashes are 32 bytes");
+        u128::from_be_bytes(*first_half)
+    }
+}
+
+impl Display for Digest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", blake3::Hash::from_bytes(self.0).to_hex())
+    }
+}
+
+impl Serialize for Digest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Digest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let hash = blake3::Hash::from_hex(&hex).map_err(serde::de::Error::custom)?;
+        Ok(Digest(*hash.as_bytes()))
+    }
+}
+
+// The 128-bit hash of the repository algorithm, and with BLAKE3 the full digest it is the first half of
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FileHash {
+    pub hash: u128,
+    pub digest: Option<Digest>,
+}
+
+pub fn file_hash(env: &impl HashEnv, algorithm: HashAlgorithm, file: &File) -> io::Result<u128> {
+    Ok(file_hashes(env, algorithm, file)?.hash)
+}
+
+pub fn file_hashes(env: &impl HashEnv, algorithm: HashAlgorithm, file: &File) -> io::Result<FileHash> {
+    let length = file.metadata()?.len();
+    let chunk_size = env.hash_chunk_size();
+    let report_progress = env.verbose() && length > chunk_size as u64;
+
+    let start = Instant::now();
+    let mut hasher = algorithm.hasher();
+    let mut hashed_length = 0;
+
+    // SAFETY: the mapping is only read. A file truncated by another process while mapped can still crash biver,
+    // which is the price for not copying multi-gigabyte files through a buffer. Mapping fails for empty and
+    // special files, in which case the file is read in chunks instead.
+    match unsafe { Mmap::map(file) } {
+        Ok(mmap) => {
+            for chunk in mmap.chunks(chunk_size) {
+                hasher.update(chunk);
+                hashed_length += chunk.len() as u64;
+
+                if report_progress {
+                    formatting::print_hash_progress(hashed_length, length);
+                }
+            }
+        }
+        Err(_) => {
+            let mut reader = file;
+            let mut buffer = vec![0; chunk_size];
+
+            loop {
+                let bytes_read = reader.read(&mut buffer)?;
+                if bytes_read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..bytes_read]);
+                hashed_length += bytes_read as u64;
+
+                if report_progress {
+                    formatting::print_hash_progress(hashed_length, length);
+                }
+            }
         }
-        hasher.update(&buffer[..bytes_read]);
     }
 
-    Ok(hasher.digest128())
+    let elapsed = start.elapsed();
+    let seconds = elapsed.as_secs_f64();
+    let megabytes_per_second = if seconds > 0.0 { hashed_length as f64 / 1_000_000.0 / seconds } else { 0.0 };
+    tracing::debug!("Hashed {} bytes with {} in {:.2?} ({:.1} MB/s)", hashed_length, algorithm, elapsed, megabytes_per_second);
+
+    Ok(hasher.finish())
 }
@@ -0,0 +1,47 @@
+use crate::hash;
+use crate::hash::{HashAlgorithm, HashEnv};
+use crate::repository_data::{RepositoryData, Version};
+use crate::repository_paths::RepositoryPaths;
+use std::fs::File;
+use std::{fs, io};
+
+pub enum BlobStatus {
+    Ok,
+    NoChecksum,
+    Missing,
+    Corrupted,
+}
+
+pub fn blob_checksum(env: &impl HashEnv, repo_paths: &RepositoryPaths, algorithm: HashAlgorithm, blob_file_name: &str) -> io::Result<u128> {
+    let blob_file = File::open(repo_paths.blob_path(blob_file_name))?;
+    hash::file_hash(env, algorithm, &blob_file)
+}
+
+pub fn blob_status(env: &impl HashEnv, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, blob_file_name: &str) -> io::Result<BlobStatus> {
+    if !fs::exists(repo_paths.blob_path(blob_file_name))? {
+        return Ok(BlobStatus::Missing);
+    }
+
+    let Some(expected_checksum) = repo_data.blob_checksums.get(blob_file_name) else {
+        return Ok(BlobStatus::NoChecksum);
+    };
+
+    if blob_checksum(env, repo_paths, repo_data.hash_algorithm, blob_file_name)? == *expected_checksum {
+        Ok(BlobStatus::Ok)
+    } else {
+        Ok(BlobStatus::Corrupted)
+    }
+}
+
+pub fn record_blob_checksums(env: &impl HashEnv, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, version: &Version) -> io::Result<()> {
+    for blob_file_name in version.blob_file_names() {
+        if repo_data.blob_checksums.contains_key(blob_file_name) {
+            continue;
+        }
+
+        let checksum = blob_checksum(env, repo_paths, repo_data.hash_algorithm, blob_file_name)?;
+        repo_data.blob_checksums.insert(blob_file_name.to_string(), checksum);
+    }
+
+    Ok(())
+}
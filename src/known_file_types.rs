@@ -3,3 +3,8 @@ const IMAGE_FILE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "psd"];
 pub fn is_image(file_extension: &str) -> bool {
     IMAGE_FILE_EXTENSIONS.contains(&file_extension.to_lowercase().as_str())
 }
+
+// Previews created by biver are JPEG, but externally regenerated ones may be PNG, WebP and so on
+pub fn preview_mime_type(preview: &[u8]) -> &'static str {
+    image::guess_format(preview).map_or("application/octet-stream", |format| format.to_mime_type())
+}
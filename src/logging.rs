@@ -0,0 +1,201 @@
+use crate::formatting;
+use crate::repository_paths::RepositoryPaths;
+use std::ffi::OsStr;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::process::{Child, Command, ExitStatus, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+use std::{fs, io};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+pub const LOG_FILE_NAME: &str = "biver.log";
+pub const ROTATED_LOG_FILE_NAME: &str = "biver.log.1";
+
+// A log this large is moved aside when a command opens the repository, replacing the one moved aside before, so the
+// two of them stay around twice this size
+const MAX_LOG_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+// External tool invocations are logged under one target regardless of which module runs the tool
+const PROCESS_TARGET: &str = "biver::process";
+
+static LOG_TO_FILE: AtomicBool = AtomicBool::new(false);
+
+// The repository directory is only known once a command opens its repository, events logged before that are not written to the file.
+// A workspace command opens several repositories in turn, each gets the events logged while working on it.
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+pub fn init(verbosity: u8, log_to_file: bool) {
+    let level = match verbosity {
+        0 => LevelFilter::OFF,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    let file_level = if log_to_file { level.max(LevelFilter::DEBUG) } else { LevelFilter::OFF };
+
+    // Only biver's own events, dependencies are too chatty at these levels
+    let terminal_layer = fmt::layer()
+        .with_ansi(formatting::stderr_colors())
+        .with_writer(io::stderr)
+        .with_filter(Targets::new().with_target("biver", level));
+    let file_layer = fmt::layer()
+        .with_ansi(false)
+        .with_writer(|| RepositoryLogWriter)
+        .with_filter(Targets::new().with_target("biver", file_level));
+
+    tracing_subscriber::registry().with(terminal_layer).with(file_layer).init();
+
+    LOG_TO_FILE.store(log_to_file, Ordering::Relaxed);
+}
+
+pub fn attach_repository(repo_paths: &RepositoryPaths) {
+    if !LOG_TO_FILE.load(Ordering::Relaxed) || !repo_paths.repository_dir.is_dir() {
+        return;
+    }
+
+    let log_path = repo_paths.file_path(LOG_FILE_NAME);
+    if fs::metadata(&log_path).is_ok_and(|metadata| metadata.len() >= MAX_LOG_FILE_SIZE)
+        && let Err(e) = fs::rename(&log_path, repo_paths.file_path(ROTATED_LOG_FILE_NAME))
+    {
+        tracing::warn!("Could not rotate the log file: {}", e);
+    }
+
+    let file = match OpenOptions::new().create(true).append(true).open(&log_path) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            tracing::warn!("Could not open the log file: {}", e);
+            None
+        }
+    };
+
+    *lock_log_file() = file;
+}
+
+struct RepositoryLogWriter;
+
+impl Write for RepositoryLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match lock_log_file().as_mut() {
+            Some(file) => file.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match lock_log_file().as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn lock_log_file() -> MutexGuard<'static, Option<File>> {
+    LOG_FILE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Stand-ins for Command::status, output and spawn that log the full command line, exit code and duration
+pub trait LoggedCommand {
+    fn logged_status(&mut self) -> io::Result<ExitStatus>;
+    fn logged_output(&mut self) -> io::Result<Output>;
+    fn logged_spawn(&mut self) -> io::Result<Child>;
+}
+
+impl LoggedCommand for Command {
+    fn logged_status(&mut self) -> io::Result<ExitStatus> {
+        let command_line = command_line(self);
+        tracing::debug!(target: PROCESS_TARGET, "Running {}", command_line);
+
+        let start = Instant::now();
+        let status = self.status();
+        log_exit(&command_line, status.as_ref().copied(), start);
+        status
+    }
+
+    fn logged_output(&mut self) -> io::Result<Output> {
+        let command_line = command_line(self);
+        tracing::debug!(target: PROCESS_TARGET, "Running {}", command_line);
+
+        let start = Instant::now();
+        let output = self.output();
+        log_exit(&command_line, output.as_ref().map(|o| o.status), start);
+
+        if let Ok(output) = &output
+            && !output.stderr.is_empty()
+        {
+            tracing::trace!(target: PROCESS_TARGET, "{} wrote to stderr: {}", command_line, String::from_utf8_lossy(&output.stderr).trim_end());
+        }
+
+        output
+    }
+
+    fn logged_spawn(&mut self) -> io::Result<Child> {
+        let command_line = command_line(self);
+        let child = self.spawn();
+
+        match &child {
+            Ok(child) => tracing::debug!(target: PROCESS_TARGET, "Started {} as process {}", command_line, child.id()),
+            Err(e) => tracing::debug!(target: PROCESS_TARGET, "Could not start {}: {}", command_line, e),
+        }
+
+        child
+    }
+}
+
+// Spawned processes are timed by whoever waits for them, see BackgroundProcess
+pub trait LoggedChild {
+    fn logged_wait(&mut self) -> io::Result<ExitStatus>;
+    fn logged_wait_with_output(self) -> io::Result<Output>;
+}
+
+impl LoggedChild for Child {
+    fn logged_wait(&mut self) -> io::Result<ExitStatus> {
+        let status = self.wait();
+        log_child_exit(self.id(), status.as_ref().copied());
+        status
+    }
+
+    fn logged_wait_with_output(self) -> io::Result<Output> {
+        let id = self.id();
+        let output = self.wait_with_output();
+        log_child_exit(id, output.as_ref().map(|o| o.status));
+        output
+    }
+}
+
+fn log_child_exit(id: u32, status: Result<ExitStatus, &io::Error>) {
+    match status {
+        Ok(status) => tracing::debug!(target: PROCESS_TARGET, "Process {} exited with {}", id, status),
+        Err(e) => tracing::debug!(target: PROCESS_TARGET, "Could not wait for process {}: {}", id, e),
+    }
+}
+
+fn log_exit(command_line: &str, status: Result<ExitStatus, &io::Error>, start: Instant) {
+    match status {
+        Ok(status) => tracing::debug!(target: PROCESS_TARGET, "{} exited with {} after {:?}", command_line, status, start.elapsed()),
+        Err(e) => tracing::debug!(target: PROCESS_TARGET, "Could not run {}: {}", command_line, e),
+    }
+}
+
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(quote_argument)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn quote_argument(argument: &OsStr) -> String {
+    let argument = argument.to_string_lossy();
+    if argument.is_empty() || argument.contains([' ', '"', '\'']) {
+        format!("{:?}", argument)
+    } else {
+        argument.to_string()
+    }
+}
@@ -1,79 +1,328 @@
-use crate::biver_result::{BiverError, BiverErrorSeverity, BiverResult, error, warning};
-use crate::command_line_arguments::{Command, CommandLineArguments, CreateCommand, DeleteCommand, ListCommand, RenameCommand};
+use crate::autosave_guard::SaveAction;
+use crate::bisect::BisectStep;
+use crate::biver_result::{BiverError, BiverErrorCategory, BiverErrorSeverity, BiverResult, categorized_error, error, warning};
+use crate::changelog::ChangelogFormat;
+use crate::clipboard::CopyImageResult;
+use crate::command_line_arguments::{
+    AttrCommand, BisectCommand, BranchCommand, ChangelogFormatArgument, ColorArgument, Command, CommandLineArguments, CreateCommand, DeleteCommand, GitExportContent,
+    HashAlgorithmArgument, IntegrateShellCommand, ListCommand, NicknameCommand, NoteCommand, OnSave, PatchLimitArgument, RenameCommand, ReviewCommand, SeriesOrderArgument,
+    StatusColumnArgument, StatusFormatArgument, TrashArgument, VersionSort,
+};
+use crate::editing_lock::EditingLock;
+use crate::effects::Effects;
 use crate::env::Env;
-use crate::repository_data::RepositoryData;
+use crate::events::Events;
+use crate::formatting::{ColorMode, StatusColumns, StatusScope};
+use crate::git_export::GitContent;
+use crate::hash::HashAlgorithm;
+use crate::notifications::{VersionEvent, WebhookDeliveries};
+use crate::remote::RemoteLocation;
+use crate::repository_data::{PatchLimit, RepositoryData, ReviewState, Version};
 use crate::repository_io::RepositoryDataResult;
 use crate::repository_operations::{
-    AmendResult, CheckOutResult, CommitResult, CreateBranchResult, DeleteBranchResult, InitResult, PreviewResult, RenameBranchResult, ResetResult, RestoreResult, RewordResult,
-    VersionResult,
+    AddNoteResult, AmendResult, BackupResult, BisectMarkResult, BisectResetResult, BisectStartResult, CheckOutResult, CloneResult, CommitResult, CreateBranchResult,
+    CreateRemoteResult, DeleteBranchResult, DeleteRemoteResult, DescribeBranchResult, EditingLockCheck, ForkResult, GcResult, GitExportResult, ImportHistoryResult,
+    ImportSeriesResult, InitResult, ListVersionsResult, LockResult, MoveResult, PendingVersionSummary, PinVersionResult, PreviewResult, ProtectBranchResult, PruneResult,
+    PullResult, PushResult, ReconcileOutcome, ReconcileResult, RecoverResult, RegenerateNicknameResult, RenameBranchResult, RenameVersionResult, RepairPlan,
+    ReplaceVersionedFileResult, ReportResult, ResetResult, RestoreResult, RestoreToTemporaryFileResult, RestoreToWriterResult, ReviewResult, RewordResult, SeriesOrder,
+    SetAttributeResult, SetBranchResult, SimilarResult, UnlockResult, VerifySignaturesResult, VersionAtTimeResult, VersionOrder, VersionQuery, VersionResult,
 };
 use crate::repository_paths::RepositoryPaths;
-use clap::Parser;
+use crate::retention::RetentionPolicy;
+use crate::signing::SignatureStatus;
+use crate::temporary_file::TemporaryFile;
+use crate::tools::Detection;
+use crate::trash::TrashMode;
+use crate::version_id::VersionId;
+use chrono::{Local, TimeDelta};
+use clap::builder::Resettable;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches};
+use clap_complete::Shell;
 use colored::Colorize;
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use std::fs;
 use std::io;
+use std::io::IsTerminal;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-
+use std::sync::OnceLock;
+use std::time::Duration;
+
+mod alias;
+mod analytics;
+mod animated_webp;
+mod autosave_guard;
+mod background_process;
+mod backup;
+mod bisect;
 mod biver_result;
+mod blob_store;
+mod changelog;
+mod clipboard;
 mod command_line_arguments;
+mod config;
+mod daemon;
+mod description_editor;
+mod diff;
+mod doctor;
+mod editing_lock;
+mod effects;
 mod env;
+mod events;
 mod extensions;
+mod file_attributes;
+mod file_replacement;
 mod formatting;
+mod git_export;
 mod hash;
-mod image_magick;
+mod integrity;
 mod known_file_types;
+mod logging;
+mod migrations;
 mod nickname;
+mod notifications;
+mod object_store;
+mod parallel;
+mod perceptual_hash;
+mod reconstruction_cache;
+mod remote;
+mod repair;
+mod report;
 mod repository_data;
 mod repository_io;
 mod repository_operations;
 mod repository_paths;
+mod retention;
+mod rpc;
+mod setup;
+mod shell_integration;
+mod signing;
+mod stat_cache;
+mod temporary_file;
+#[cfg(test)]
+mod test_support;
+mod tools;
+mod transaction;
+mod trash;
 mod version_id;
 mod viewer;
-mod xdelta3;
+mod web_viewer;
+mod workspace;
 
 fn main() -> ExitCode {
-    let arguments = CommandLineArguments::parse();
+    let discovered_versioned_files = discover_versioned_files();
+
+    let config_and_arguments = config::read().and_then(|config| {
+        let arguments = alias::expand(std::env::args_os().collect(), &config.aliases)?;
+        Ok((config, arguments))
+    });
+
+    let (config, arguments) = match config_and_arguments {
+        Ok(config_and_arguments) => config_and_arguments,
+        Err(biver_error) => {
+            eprintln!("{}", biver_error.error_message);
+            return exit_code(&biver_error);
+        }
+    };
+
+    let command_definition = match discovered_versioned_files.as_slice() {
+        [versioned_file] => with_discovered_versioned_file(CommandLineArguments::command(), versioned_file),
+        _ => CommandLineArguments::command(),
+    };
+    let parsed_arguments = command_definition
+        .try_get_matches_from(arguments)
+        .and_then(|matches| CommandLineArguments::from_arg_matches(&matches));
+
+    let arguments = match parsed_arguments {
+        Ok(arguments) => arguments,
+        Err(e) if e.kind() == ErrorKind::MissingRequiredArgument && discovered_versioned_files.len() > 1 && e.to_string().contains("--file") => {
+            eprintln!("Several versioned files found in the current directory, specify one with -f:");
+            for versioned_file in discovered_versioned_files {
+                eprintln!("  {}", versioned_file.display());
+            }
+            return ExitCode::from(2);
+        }
+        Err(e) => e.exit(),
+    };
+
+    formatting::init_colors(match arguments.color {
+        ColorArgument::Auto => ColorMode::Auto,
+        ColorArgument::Always => ColorMode::Always,
+        ColorArgument::Never => ColorMode::Never,
+    });
+    logging::init(arguments.verbose, arguments.log && !arguments.read_only);
 
     let env = Env {
-        xdelta3_path: arguments.xdelta3_path,
-        image_magick_path: arguments.image_magick_path,
+        xdelta3_path: arguments.xdelta3_path.or(config.xdelta3_path),
+        image_magick_path: arguments.image_magick_path.or(config.image_magick_path),
+        git_path: arguments.git_path,
+        rclone_path: arguments.rclone_path,
+        minisign_path: arguments.minisign_path,
+        signing_key: arguments.signing_key,
+        nickname_words: arguments.nickname_words,
+        editor: env::configured_editor(arguments.editor),
+        pager: env::configured_pager(arguments.no_pager),
+        hash_chunk_size: arguments.hash_chunk_size as usize,
+        restore_cache_size: arguments.restore_cache_size,
+        temp_dir: std::env::temp_dir(),
+        hash_algorithm: config.hash_algorithm.unwrap_or(HashAlgorithm::Blake3),
+        trash: arguments
+            .trash
+            .map(|trash| match trash {
+                TrashArgument::Off => TrashMode::Off,
+                TrashArgument::System => TrashMode::System,
+                TrashArgument::Repository => TrashMode::Repository,
+            })
+            .or(config.trash)
+            .unwrap_or(TrashMode::Off),
+        webhooks: config.webhooks,
+        desktop_notifications: config.desktop_notifications,
+        verbose: arguments.verbose > 0,
+        author: env::current_author(),
+        preserve_times: config.preserve_times,
+        dry_run: arguments.dry_run,
+        json: arguments.json,
+        // serve only ever reads, whatever the arguments
+        read_only: arguments.read_only || matches!(arguments.command, Command::Serve { .. }),
+        xdelta3_codec: OnceLock::new(),
+        image_magick_invocation: OnceLock::new(),
+        preview_stand_in: None,
+        effects: Effects::default(),
+        events: Events::default(),
+        webhook_deliveries: WebhookDeliveries::default(),
     };
 
-    match run_command(&env, arguments.command) {
+    // Every change to a repository shows with -v and lands in the log file
+    env.events.subscribe(|event| tracing::info!("{}", event));
+
+    let result = run_command(&env, arguments.command);
+
+    for failure in env.webhook_deliveries.wait() {
+        eprintln!("{}", failure.yellow());
+    }
+
+    match result {
         Ok(()) => ExitCode::SUCCESS,
 
-        Err(BiverError {
-            error_message,
-            severity: BiverErrorSeverity::Warning,
-        }) => {
-            println!("{}", error_message.yellow());
-            ExitCode::SUCCESS
+        Err(biver_error) if arguments.json => {
+            formatting::print_error_json(&biver_error);
+            exit_code(&biver_error)
         }
 
-        Err(BiverError {
-            error_message,
-            severity: BiverErrorSeverity::Error,
-        }) => {
-            eprintln!("{}", error_message.red());
-            ExitCode::FAILURE
+        Err(
+            biver_error @ BiverError {
+                severity: BiverErrorSeverity::Warning,
+                ..
+            },
+        ) => {
+            println!("{}", biver_error.error_message.yellow());
+            exit_code(&biver_error)
         }
+
+        Err(
+            biver_error @ BiverError {
+                severity: BiverErrorSeverity::Error,
+                ..
+            },
+        ) => {
+            formatting::print_error(&biver_error.error_message);
+            exit_code(&biver_error)
+        }
+    }
+}
+
+// Without -f and BIVER_VERSIONED_FILE, the only versioned file found in the current directory is used. Returns what
+// was found, for listing when it is ambiguous.
+fn discover_versioned_files() -> Vec<PathBuf> {
+    if std::env::var_os("BIVER_VERSIONED_FILE").is_some() {
+        return Vec::new();
     }
+
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| workspace::discover_versioned_files(&dir).ok())
+        .unwrap_or_default()
+}
+
+// The discovered file becomes the default of every file argument except the one of init, which would otherwise
+// start a second repository next to the one found
+fn with_discovered_versioned_file(command_definition: clap::Command, versioned_file: &Path) -> clap::Command {
+    command_definition.mut_subcommands(|subcommand| {
+        if subcommand.get_name() == "init" {
+            subcommand
+        } else {
+            with_default_versioned_file(subcommand, versioned_file)
+        }
+    })
+}
+
+fn with_default_versioned_file(command_definition: clap::Command, versioned_file: &Path) -> clap::Command {
+    let command_definition = if command_definition.get_arguments().any(|arg| arg.get_id() == "versioned_file_path") {
+        command_definition.mut_arg("versioned_file_path", |arg| {
+            arg.default_value(versioned_file.as_os_str().to_os_string())
+                .required(false)
+                .required_unless_present(Resettable::Reset)
+        })
+    } else {
+        command_definition
+    };
+
+    command_definition.mut_subcommands(|subcommand| with_default_versioned_file(subcommand, versioned_file))
 }
 
+// clap requires the file argument unless --workspace is given
+const FILE_REQUIRED: &str = "A versioned file must be specified";
+
 fn run_command(env: &Env, command: Command) -> BiverResult<()> {
+    if env.dry_run && !supports_dry_run(&command) {
+        return error("--dry-run is only supported by commit, amend, reset, delete branch, gc and prune");
+    }
+
+    if env.read_only && !supports_read_only(&command) {
+        return error("--read-only only allows commands that read the repository, such as status, list, show, preview, compare, diff, restore and verify");
+    }
+
     match command {
-        Command::Status { versioned_file_path, all } => {
-            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
-            let repo_data = repository_io::read_data(&repo_paths)?;
+        Command::Status {
+            versioned_file_path,
+            workspace,
+            all,
+            branch,
+            all_branches,
+            verify,
+            pending,
+            format,
+            columns,
+            porcelain,
+        } => {
+            let layout = if porcelain {
+                StatusLayout::Porcelain
+            } else {
+                StatusLayout::Columns(status_columns(format, columns))
+            };
 
-            match repo_data {
-                RepositoryDataResult::NotInitialized => println!("Not initialized"),
-                RepositoryDataResult::Initialized(repository_data) => {
-                    let has_uncommitted_changes = repository_operations::has_uncommitted_changes(&repo_paths, &repository_data)?;
-                    formatting::print_repository_data(&repository_data, has_uncommitted_changes, all);
-                }
+            let scope = match branch {
+                Some(branch) => StatusScope::Branch(branch),
+                None if all_branches => StatusScope::AllBranches,
+                None => StatusScope::Head,
+            };
+
+            if workspace {
+                return workspace::run_for_each_file(&workspace::find_current()?, |repo_paths| status(env, repo_paths, all, verify, pending, &layout, &scope));
             }
 
-            success()
+            status(
+                env,
+                RepositoryPaths::from_versioned_file_path(versioned_file_path.expect(FILE_REQUIRED)),
+                all,
+                verify,
+                pending,
+                &layout,
+                &scope,
+            )
         }
 
         Command::Preview { versioned_file_path, target } => {
@@ -81,7 +330,7 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
             let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
             let version = match repository_operations::version(&repo_data, &target) {
-                VersionResult::InvalidTarget => return error("Invalid target"),
+                VersionResult::InvalidTarget => return invalid_target_error(&repo_data, &target),
                 VersionResult::Ok(version) => version,
             };
 
@@ -95,37 +344,265 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
             Ok(())
         }
 
+        Command::Show { versioned_file_path, target } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let version = match target {
+                None => repo_data.head_version(),
+                Some(target) => match repository_operations::version(&repo_data, &target) {
+                    VersionResult::InvalidTarget => return invalid_target_error(&repo_data, &target),
+                    VersionResult::Ok(version) => version,
+                },
+            };
+
+            formatting::print_version_details(&repo_data, version);
+
+            success()
+        }
+
+        Command::Stats { versioned_file_path } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let stats = analytics::history_stats(&repo_data);
+
+            // With --json, including the size of every version over time
+            if env.json {
+                formatting::print_history_stats_json(&stats)?;
+            } else {
+                formatting::print_history_stats(&stats);
+            }
+
+            success()
+        }
+
+        Command::Similar {
+            versioned_file_path,
+            target,
+            max_distance,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            match repository_operations::similar_versions(&repo_paths, &repo_data, &target, max_distance) {
+                SimilarResult::Ok(similar) if similar.is_empty() => warning("No similar versions"),
+                SimilarResult::Ok(similar) => {
+                    formatting::print_similar_versions(&repo_data, &similar);
+                    success()
+                }
+                SimilarResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+                SimilarResult::NoPreviewAvailable => error("No preview available for the target version"),
+            }
+        }
+
         Command::Compare {
             versioned_file_path,
-            target1,
-            target2,
+            targets,
+            other_file,
+            working,
+            blink,
+            blink_interval,
+            output,
         } => {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
             let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            let version_and_preview = |target: Option<&str>| {
-                let version = match target {
-                    None => repo_data.head_version(),
-                    Some(target) => match repository_operations::version(&repo_data, target) {
-                        VersionResult::InvalidTarget => return error(format!("Invalid target {}", target)),
-                        VersionResult::Ok(version) => version,
-                    },
-                };
+            // The descriptions become labels in the image, where terminal colors would show as escape codes
+            if output.is_some() {
+                formatting::disable_colors();
+            }
 
-                match repository_operations::preview(&repo_paths, &version) {
-                    PreviewResult::NoPreviewAvailable => error(format!("No preview available for {}", version.id.bs58())),
-                    PreviewResult::Ok(preview) => Ok((version, preview)),
+            let previews_and_descriptions = match other_file {
+                None if working => {
+                    if targets.len() > 3 {
+                        return error("Up to three versions can be compared with the working file");
+                    }
+
+                    let versions_and_previews = targets
+                        .iter()
+                        .map(|target| comparison_preview(&repo_paths, &repo_data, Some(target)))
+                        .collect::<BiverResult<Vec<_>>>()?;
+
+                    let Some(working_preview) = repository_operations::pending_preview(env, &repo_paths)? else {
+                        return error("No preview can be created for the versioned file");
+                    };
+
+                    let versions: Vec<&Version> = versions_and_previews.iter().map(|(version, _)| *version).collect();
+                    let formatted_versions = formatting::format_versions(&repo_data, &versions);
+
+                    let mut previews_and_descriptions: Vec<_> = versions_and_previews.into_iter().map(|(_, preview)| preview).zip(formatted_versions).collect();
+                    previews_and_descriptions.push((working_preview, "working file".to_string()));
+                    previews_and_descriptions
+                }
+                None => {
+                    let mut versions_and_previews = targets
+                        .iter()
+                        .map(|target| comparison_preview(&repo_paths, &repo_data, Some(target)))
+                        .collect::<BiverResult<Vec<_>>>()?;
+                    if versions_and_previews.len() == 1 {
+                        versions_and_previews.push(comparison_preview(&repo_paths, &repo_data, None)?);
+                    }
+
+                    let versions: Vec<&Version> = versions_and_previews.iter().map(|(version, _)| *version).collect();
+                    let formatted_versions = formatting::format_versions(&repo_data, &versions);
+
+                    versions_and_previews.into_iter().map(|(_, preview)| preview).zip(formatted_versions).collect::<Vec<_>>()
+                }
+                Some(other_file) => {
+                    if targets.len() > 2 {
+                        return error("Only two versions can be compared across versioned files");
+                    }
+
+                    let other_repo_paths = RepositoryPaths::from_versioned_file_path(other_file);
+                    let other_repo_data = repository_io::read_data(&other_repo_paths)?.initialized()?;
+
+                    let (version, preview) = comparison_preview(&repo_paths, &repo_data, Some(&targets[0]))?;
+                    let (other_version, other_preview) = comparison_preview(&other_repo_paths, &other_repo_data, targets.get(1).map(String::as_str))?;
+
+                    // Versions of both files may share nicknames, so the file name tells them apart
+                    let description = |repo_paths: &RepositoryPaths, repo_data: &RepositoryData, version: &Version| {
+                        let formatted_version = formatting::format_versions(repo_data, &[version]).remove(0);
+                        format!("{} {}", repo_paths.versioned_file_name().unwrap_or_default(), formatted_version)
+                    };
+
+                    vec![
+                        (preview, description(&repo_paths, &repo_data, version)),
+                        (other_preview, description(&other_repo_paths, &other_repo_data, other_version)),
+                    ]
                 }
             };
 
-            let (version1, preview_file_path1) = version_and_preview(Some(&target1))?;
-            let (version2, preview_file_path2) = version_and_preview(target2.as_deref())?;
+            let previews: Vec<(&Path, &str)> = previews_and_descriptions
+                .iter()
+                .map(|(preview_file_path, description)| (preview_file_path.as_path(), description.as_str()))
+                .collect();
+
+            let result = match &output {
+                Some(output) => viewer::write_comparison(&previews, output),
+                None => viewer::show_comparison(&previews, blink, Duration::from_millis(blink_interval)),
+            };
+
+            // The working file preview is made for this comparison only
+            if working && let Some((working_preview, _)) = previews_and_descriptions.last() {
+                fs::remove_file(working_preview)?;
+            }
+
+            result?;
+
+            if output.is_some() { success_ok() } else { success() }
+        }
+
+        Command::Timelapse {
+            versioned_file_path,
+            branch,
+            output,
+            frames_per_second,
+            max_size,
+            no_dates,
+        } => {
+            if !output.extension().is_some_and(|e| e.eq_ignore_ascii_case("gif") || e.eq_ignore_ascii_case("webp")) {
+                return error("Timelapses can only be written as GIF or WebP files");
+            }
+
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let leaf_id = match &branch {
+                Some(branch) => match repo_data.branches.get(branch) {
+                    Some(leaf_id) => *leaf_id,
+                    None => return error("Branch does not exist"),
+                },
+                None => repo_data.head_version().id,
+            };
+
+            let mut versions: Vec<&Version> = repo_data.iter_version_and_ancestors(leaf_id).collect();
+            versions.reverse();
+
+            let frames: Vec<(PathBuf, Option<String>)> = versions
+                .iter()
+                .filter_map(|version| match repository_operations::preview(&repo_paths, version) {
+                    PreviewResult::NoPreviewAvailable => None,
+                    PreviewResult::Ok(preview) => {
+                        let label = format!("{}  {}", version.creation_time.with_timezone(&Local).format("%Y-%m-%d %H:%M"), version.nickname);
+                        Some((preview, (!no_dates).then_some(label)))
+                    }
+                })
+                .collect();
+
+            if frames.is_empty() {
+                return error("None of the versions has a preview");
+            }
+
+            let frames: Vec<(&Path, Option<&str>)> = frames.iter().map(|(preview, label)| (preview.as_path(), label.as_deref())).collect();
+            viewer::write_timelapse(&frames, &output, frames_per_second, max_size)?;
+
+            println!("Frames {}", frames.len());
+            if frames.len() < versions.len() {
+                println!("Versions without a preview were left out: {}", versions.len() - frames.len());
+            }
+
+            success_ok()
+        }
+
+        Command::Diff {
+            versioned_file_path,
+            target1,
+            target2,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let version = |target: Option<&str>| match target {
+                None => Ok(repo_data.head_version()),
+                Some(target) => match repository_operations::version(&repo_data, target) {
+                    VersionResult::InvalidTarget => invalid_target_error(&repo_data, target),
+                    VersionResult::Ok(version) => Ok(version),
+                },
+            };
+
+            let version1 = version(Some(&target1))?;
+            let version2 = version(target2.as_deref())?;
+
+            let report = repository_operations::diff(env, &repo_paths, &repo_data, version1, version2)?;
+
+            let formatted_versions = formatting::format_versions(&repo_data, &[version1, version2]);
+
+            formatting::print_diff(version1, &formatted_versions[0], version2, &formatted_versions[1], &report);
+
+            success()
+        }
+
+        Command::Report { versioned_file_path, output } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::report(&repo_paths, &repo_data, &output)?;
+
+            match result {
+                ReportResult::Ok => success_ok(),
+                ReportResult::DanglingBranchesLeftOut(branches) => warning(format!(
+                    "The report leaves out branches whose latest version is missing: {}. Run doctor to find out more.",
+                    branches.join(", ")
+                )),
+            }
+        }
+
+        Command::Changelog {
+            versioned_file_path,
+            format,
+            since,
+            until,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            let formatted_versions = formatting::format_versions(&repo_data, &vec![version1, version2]);
-            let description1 = &formatted_versions[0];
-            let description2 = &formatted_versions[1];
+            let format = match format {
+                ChangelogFormatArgument::Md => ChangelogFormat::Markdown,
+                ChangelogFormatArgument::Csv => ChangelogFormat::Csv,
+            };
 
-            viewer::show_comparison(&preview_file_path1, description1, &preview_file_path2, description2)?;
+            print!("{}", changelog::changelog(&repo_data, format, since, until));
 
             success()
         }
@@ -133,11 +610,18 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
         Command::Init {
             versioned_file_path,
             initial_branch_name: branch_name,
+            hash_algorithm,
             initial_version_description: description,
         } => {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
 
-            let result = repository_operations::init(env, &repo_paths, branch_name.as_deref(), description.as_deref())?;
+            let hash_algorithm = match hash_algorithm {
+                Some(HashAlgorithmArgument::Xxh3) => HashAlgorithm::Xxh3,
+                Some(HashAlgorithmArgument::Blake3) => HashAlgorithm::Blake3,
+                None => env.hash_algorithm,
+            };
+
+            let result = repository_operations::init(env, &repo_paths, branch_name.as_deref(), description.as_deref(), None, hash_algorithm)?;
 
             match result {
                 InitResult::Ok => success_ok(),
@@ -146,28 +630,107 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
             }
         }
 
-        Command::Commit { versioned_file_path, description } => {
+        Command::ImportSeries {
+            pattern,
+            versioned_file_path,
+            order,
+            hash_algorithm,
+        } => {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
-            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            let result = repository_operations::commit_version(env, &repo_paths, &mut repo_data, description.as_deref())?;
+            let order = match order {
+                SeriesOrderArgument::Number => SeriesOrder::Number,
+                SeriesOrderArgument::Modified => SeriesOrder::Modified,
+            };
+
+            let hash_algorithm = match hash_algorithm {
+                Some(HashAlgorithmArgument::Xxh3) => HashAlgorithm::Xxh3,
+                Some(HashAlgorithmArgument::Blake3) => HashAlgorithm::Blake3,
+                None => env.hash_algorithm,
+            };
+
+            let result = repository_operations::import_series(env, &repo_paths, &pattern, order, hash_algorithm)?;
 
             match result {
-                CommitResult::Ok => success_ok(),
-                CommitResult::NothingToCommit => warning("Nothing to commit"),
-                CommitResult::HeadMustBeOnBranch => error("Head must be on a branch"),
+                ImportSeriesResult::Ok { imported_count, skipped_count } => {
+                    println!("Imported {} versions", imported_count);
+                    if skipped_count > 0 {
+                        println!("Copies skipped as identical to the one before: {}", skipped_count);
+                    }
+                    success()
+                }
+                ImportSeriesResult::AlreadyInitialized => warning("Already initialized"),
+                ImportSeriesResult::FileAlreadyExists => error("The file to import into already exists"),
+                ImportSeriesResult::NoMatchingFiles => error("No files match the pattern"),
+            }
+        }
+
+        Command::Commit {
+            versioned_file_path,
+            workspace,
+            new_branch,
+            branch_auto,
+            force,
+            allow_duplicate,
+            stdin,
+            steal,
+            description,
+        } => {
+            if workspace {
+                return workspace::run_for_each_file(&workspace::find_current()?, |repo_paths| {
+                    let stolen_lock = check_editing_lock(env, &repo_paths, steal)?;
+                    let result = commit(env, &repo_paths, new_branch.clone(), branch_auto, force, allow_duplicate, description.as_deref());
+                    take_over_editing_lock(env, &repo_paths, stolen_lock, result)
+                });
+            }
+
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path.expect(FILE_REQUIRED));
+            let stolen_lock = check_editing_lock(env, &repo_paths, steal)?;
+
+            if stdin {
+                if env.dry_run {
+                    return error("--stdin replaces the versioned file, so it cannot be used in a dry run");
+                }
+
+                let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+                match repository_operations::replace_versioned_file(env, &repo_paths, &repo_data, &mut io::stdin().lock())? {
+                    ReplaceVersionedFileResult::Ok => {}
+                    ReplaceVersionedFileResult::BlockedByUncommittedChanges => {
+                        return categorized_error(
+                            BiverErrorCategory::UncommittedChanges,
+                            "The versioned file has uncommitted changes, which standard input would replace. Commit them first.",
+                        );
+                    }
+                }
+
+                // Standard input is used up, so there is nothing to answer questions or run an editor with
+                let result = commit(env, &repo_paths, new_branch, true, force, allow_duplicate, Some(description.as_deref().unwrap_or_default()));
+                return take_over_editing_lock(env, &repo_paths, stolen_lock, result);
             }
+
+            let result = if description.is_none() && new_branch.is_none() && !branch_auto && !force && !allow_duplicate && interactive_commit_available() {
+                interactive_commit(env, &repo_paths)
+            } else {
+                commit(env, &repo_paths, new_branch, branch_auto, force, allow_duplicate, description.as_deref())
+            };
+
+            take_over_editing_lock(env, &repo_paths, stolen_lock, result)
         }
 
         Command::Amend {
             versioned_file_path,
             confirmed,
+            force,
+            recoverable,
+            steal,
             description,
         } => {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let stolen_lock = check_editing_lock(env, &repo_paths, steal)?;
             let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            if !confirmed {
+            // Nothing is lost when the head version stays recoverable
+            if !confirmed && !recoverable && !env.dry_run {
                 println!("Are you sure you want to overwrite the head version? (y/N)");
                 let confirmed = read_yes_no_input()?.unwrap_or(false);
                 if !confirmed {
@@ -175,14 +738,21 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
                 }
             }
 
-            let result = repository_operations::amend_head(env, &repo_paths, &mut repo_data, description.as_deref())?;
+            let result = repository_operations::amend_head(env, &repo_paths, &mut repo_data, description.as_deref(), force, recoverable)?;
 
             match result {
-                AmendResult::Ok => success_ok(),
+                AmendResult::Ok => {
+                    print_visually_identical_warning(&repo_data);
+                    notify_new_head(env, &repo_paths, &repo_data, VersionEvent::Amend);
+                    let result = success_or_dry_run(env, &repo_paths, &repo_data);
+                    take_over_editing_lock(env, &repo_paths, stolen_lock, result)
+                }
                 AmendResult::NoUncommittedChanges => warning("No uncommitted changes"),
                 AmendResult::HeadMustBeBranch => error("Head must be on a branch"),
                 AmendResult::CannotAmendParent => error("Cannot amend head version because it has children"),
                 AmendResult::HeadEqualsParent => error("Amend would result in head version file content being identical to its parent's file content. Use hard reset instead."),
+                AmendResult::BranchProtected => protected_branch_error(&repo_data),
+                AmendResult::HeadPinned => error("Head version is pinned. Unpin it to amend it."),
             }
         }
 
@@ -194,11 +764,11 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
             let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            let result = repository_operations::reword(&repo_paths, &mut repo_data, &target, &description)?;
+            let result = repository_operations::reword(env, &repo_paths, &mut repo_data, &target, &description)?;
 
             match result {
                 RewordResult::Ok => success_ok(),
-                RewordResult::InvalidTarget => error("Invalid target"),
+                RewordResult::InvalidTarget => invalid_target_error(&repo_data, &target),
             }
         }
 
@@ -206,7 +776,7 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
             let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            if !repository_operations::has_uncommitted_changes(&repo_paths, &repo_data)? {
+            if !repository_operations::has_uncommitted_changes(env, &repo_paths, &repo_data, false)? {
                 return warning("No uncommitted changes");
             }
 
@@ -218,7 +788,8 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
                 }
             }
 
-            repository_operations::discard(env, &repo_paths, &repo_data)?;
+            let trashed_path = repository_operations::discard(env, &repo_paths, &repo_data)?;
+            print_trashed_path(trashed_path.as_deref());
 
             success_ok()
         }
@@ -227,12 +798,14 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
             versioned_file_path,
             hard,
             confirmed,
+            force,
+            keep_blobs,
             target,
         } => {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
             let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            if !confirmed {
+            if !confirmed && !env.dry_run {
                 println!("Are you sure you want to reset? (y/N)");
                 let confirmed = read_yes_no_input()?.unwrap_or(false);
                 if !confirmed {
@@ -240,128 +813,1727 @@ fn run_command(env: &Env, command: Command) -> BiverResult<()> {
                 }
             }
 
-            let result = repository_operations::reset(&repo_paths, &mut repo_data, target.as_str())?;
+            let result = repository_operations::reset(env, &repo_paths, &mut repo_data, target.as_str(), force, keep_blobs)?;
 
             match result {
                 ResetResult::Ok => {
                     if hard {
-                        repository_operations::discard(env, &repo_paths, &repo_data)?;
+                        let trashed_path = repository_operations::discard(env, &repo_paths, &repo_data)?;
+                        print_trashed_path(trashed_path.as_deref());
                     }
 
-                    success_ok()
+                    success_or_dry_run(env, &repo_paths, &repo_data)
                 }
                 ResetResult::HeadMustBeBranch => error("Head must be on a branch"),
-                ResetResult::InvalidTarget => error("Invalid target"),
-                ResetResult::CannotLeaveOrphans => error("Reset would leave orphaned versions. Make sure none of the erased versions have children outside of the reset range."),
+                ResetResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+                ResetResult::TargetNotAncestor => error("Target version is not an ancestor of head"),
+                ResetResult::CannotLeaveOrphans => {
+                    error("Reset would leave orphaned versions. Make sure none of the erased versions have children or other branches outside of the reset range.")
+                }
+                ResetResult::BranchProtected => protected_branch_error(&repo_data),
+                ResetResult::VersionPinned(version_id) => pinned_version_error(version_id),
             }
         }
 
-        Command::Checkout { versioned_file_path, target } => {
+        Command::Prune {
+            versioned_file_path,
+            keep_all_days,
+            keep_daily_days,
+            confirmed,
+            force,
+        } => {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
             let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            let result = repository_operations::check_out(env, &repo_paths, &mut repo_data, &target)?;
+            let policy = RetentionPolicy { keep_all_days, keep_daily_days };
+
+            let candidates = repository_operations::prune_candidates(&repo_data, &policy);
+            let candidate_count = candidates.len();
+
+            if candidate_count == 0 {
+                return warning("Nothing to prune");
+            }
+
+            if !force && let Some(branch) = repository_operations::protected_branch_pruned(&repo_data, &candidates) {
+                return error(format!("Branch {} is protected. Use --force to change it anyway.", branch));
+            }
+
+            if !confirmed && !env.dry_run {
+                println!("Are you sure you want to prune {} versions? (y/N)", candidate_count);
+                let confirmed = read_yes_no_input()?.unwrap_or(false);
+                if !confirmed {
+                    return success();
+                }
+            }
+
+            let result = repository_operations::prune(env, &repo_paths, &mut repo_data, &policy, force)?;
 
             match result {
-                CheckOutResult::Ok => success_ok(),
-                CheckOutResult::InvalidTarget => error("Invalid target"),
+                PruneResult::Ok { .. } if env.dry_run => success_or_dry_run(env, &repo_paths, &repo_data),
+                PruneResult::Ok {
+                    pruned_version_count,
+                    deleted_blob_count,
+                } => {
+                    println!("Pruned {} versions, deleted {} blob files", pruned_version_count, deleted_blob_count);
+                    success_ok()
+                }
+                PruneResult::NothingToPrune => warning("Nothing to prune"),
+                PruneResult::BranchProtected(branch) => error(format!("Branch {} is protected. Use --force to change it anyway.", branch)),
             }
         }
 
-        Command::Restore {
+        Command::Gc {
             versioned_file_path,
-            output,
-            target,
+            grace_period_days,
+            confirmed,
+            jobs,
         } => {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
-            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            let result = repository_operations::restore(env, &repo_paths, &repo_data, &target, output.as_deref())?;
+            let expired_count = repository_operations::expire_orphaned_versions(&mut repo_data, TimeDelta::days(grace_period_days as i64));
+
+            let candidate_count = repository_operations::gc_candidates(&repo_paths, &repo_data)?.len();
+
+            // Expired versions may share all their blobs with versions still around
+            if candidate_count == 0 && expired_count > 0 {
+                effects::write_data(env, &repo_paths, &repo_data)?;
+                return success_or_dry_run(env, &repo_paths, &repo_data);
+            }
+
+            if candidate_count == 0 {
+                return warning("Nothing to collect");
+            }
+
+            if !confirmed && !env.dry_run {
+                println!("Are you sure you want to delete {} unreferenced blob files? (y/N)", candidate_count);
+                let confirmed = read_yes_no_input()?.unwrap_or(false);
+                if !confirmed {
+                    return success();
+                }
+            }
+
+            let result = repository_operations::gc(env, &repo_paths, &mut repo_data, jobs_or_default(jobs))?;
 
             match result {
-                RestoreResult::Ok => success_ok(),
-                RestoreResult::BlockedByUncommittedChanges => error("Cannot restore to the versioned file because there are uncommitted changes"),
-                RestoreResult::InvalidTarget => error("Invalid target"),
+                GcResult::Ok { .. } if env.dry_run => success_or_dry_run(env, &repo_paths, &repo_data),
+                GcResult::Ok { deleted_blob_count, freed_bytes } => {
+                    println!("Deleted {} blob files, freed {} bytes", deleted_blob_count, freed_bytes);
+                    success_ok()
+                }
+                GcResult::NothingToCollect => warning("Nothing to collect"),
             }
         }
 
-        Command::Create(CreateCommand::Branch {
-            versioned_file_path,
-            checkout,
-            name,
-        }) => {
+        Command::Verify { versioned_file_path, jobs } => {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
             let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            let result = repository_operations::create_branch(&repo_paths, &mut repo_data, &name, checkout)?;
+            let report = repository_operations::verify(env, &repo_paths, &mut repo_data, jobs_or_default(jobs))?;
 
-            match result {
-                CreateBranchResult::Ok => success_ok(),
-                CreateBranchResult::BranchAlreadyExists => error("Branch already exists"),
-                CreateBranchResult::InvalidBranchName => error("Invalid branch name"),
+            formatting::print_verify_report(&report);
+
+            if report.problems.is_empty() {
+                success_ok()
+            } else {
+                categorized_error(BiverErrorCategory::Corruption, format!("Verification found {} problems", report.problems.len()))
             }
         }
 
-        Command::List(ListCommand::Branches { versioned_file_path }) => {
+        Command::VerifySignatures {
+            versioned_file_path,
+            trusted_keys,
+        } => {
             let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
             let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
 
-            formatting::print_branch_list(&repo_data);
+            let statuses = match repository_operations::verify_signatures(env, &repo_paths, &repo_data, &trusted_keys)? {
+                VerifySignaturesResult::Ok(statuses) => statuses,
+                VerifySignaturesResult::InvalidTrustedKey(path) => return error(format!("{} is not a minisign public key", path.display())),
+                VerifySignaturesResult::MinisignNotFound => {
+                    return categorized_error(BiverErrorCategory::DependencyMissing, "minisign is required to verify signatures but was not found");
+                }
+            };
 
-            success()
+            formatting::print_signature_report(&statuses);
+
+            let invalid_count = statuses.iter().filter(|(_, s)| matches!(s, SignatureStatus::Invalid { .. })).count();
+            let untrusted_count = statuses.iter().filter(|(_, s)| matches!(s, SignatureStatus::Valid { trusted: false, .. })).count();
+            let legacy_count = statuses.iter().filter(|(_, s)| matches!(s, SignatureStatus::Legacy { .. })).count();
+
+            if invalid_count > 0 {
+                categorized_error(BiverErrorCategory::Corruption, format!("{} signatures are invalid", invalid_count))
+            } else if untrusted_count > 0 {
+                error(format!(
+                    "{} versions are signed by keys that are not trusted. Pass the public keys of trusted signers with --trusted-key.",
+                    untrusted_count
+                ))
+            } else if legacy_count > 0 {
+                error(format!("{} signatures only cover the 128-bit content hash and cannot be relied on", legacy_count))
+            } else {
+                success_ok()
+            }
         }
 
-        Command::Rename(rename_command) => match rename_command {
-            RenameCommand::Branch {
-                versioned_file_path,
-                old_name,
-                new_name,
-            } => {
-                let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
-                let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
-
-                let result = repository_operations::rename_branch(&repo_paths, &mut repo_data, &old_name, &new_name)?;
+        Command::Doctor { versioned_file_path } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
 
-                match result {
-                    RenameBranchResult::Ok => success_ok(),
-                    RenameBranchResult::AnotherBranchExistsWithSameName => error("Another branch exists with the same name"),
-                    RenameBranchResult::BranchDoesNotExist => error("Branch does not exist"),
+            let repo_data = match repository_io::read_data(&repo_paths) {
+                Err(e) if matches!(e.kind(), io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof) => {
+                    return categorized_error(
+                        BiverErrorCategory::Corruption,
+                        format!("The data file cannot be read: {}. Run repair to restore it from a backup.", e),
+                    );
                 }
+                result => result?.initialized()?,
+            };
+
+            let findings = doctor::diagnose(env, &repo_paths, &repo_data)?;
+
+            formatting::print_doctor_findings(&findings);
+
+            let problem_count = findings.iter().filter(|f| f.is_problem()).count();
+
+            if problem_count == 0 {
+                success_ok()
+            } else {
+                categorized_error(BiverErrorCategory::Corruption, format!("Doctor found {} problems", problem_count))
             }
-        },
+        }
+
+        Command::Repair { versioned_file_path, confirmed } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+
+            let repo_data = match repository_operations::repair_plan(env, &repo_paths)? {
+                RepairPlan::NothingToRepair => return warning("Repository data is valid, nothing to repair"),
+                RepairPlan::Impossible => {
+                    return categorized_error(
+                        BiverErrorCategory::Corruption,
+                        "No valid backup or version records found, the repository cannot be repaired",
+                    );
+                }
+                RepairPlan::RestoreBackup(backup) => {
+                    println!(
+                        "Repository data is corrupt. The freshest valid backup is {} from {} with {} versions.",
+                        backup.path.display(),
+                        backup.modified.format("%Y-%m-%d %H:%M:%S"),
+                        backup.repo_data.versions.len()
+                    );
+                    if !confirmed {
+                        println!("Restore it? (y/N)");
+                    }
+                    backup.repo_data
+                }
+                RepairPlan::Rebuild(rebuilt) => {
+                    println!(
+                        "Repository data is corrupt and no valid backup was found. {} versions can be rebuilt from version records, {} cannot.",
+                        rebuilt.repo_data.versions.len(),
+                        rebuilt.lost_version_count
+                    );
+                    println!("Branch names will be derived from version nicknames.");
+                    if !confirmed {
+                        println!("Rebuild repository data? (y/N)");
+                    }
+                    rebuilt.repo_data
+                }
+            };
+
+            if !confirmed && !read_yes_no_input()?.unwrap_or(false) {
+                return success();
+            }
+
+            if let Some(corrupt_data_file) = repository_operations::repair(&repo_paths, &repo_data)? {
+                println!("The corrupt data file was kept as {}", corrupt_data_file.display());
+            }
+
+            println!("Run verify to check the repaired repository");
+            success_ok()
+        }
+
+        Command::Move {
+            old_versioned_file_path,
+            new_versioned_file_path,
+        } => {
+            let old_repo_paths = RepositoryPaths::from_versioned_file_path(old_versioned_file_path);
+            let new_repo_paths = RepositoryPaths::from_versioned_file_path(new_versioned_file_path);
+
+            let result = repository_operations::move_versioned_file(&old_repo_paths, &new_repo_paths)?;
+
+            match result {
+                MoveResult::Ok => success_ok(),
+                MoveResult::NotInitialized => categorized_error(BiverErrorCategory::NotInitialized, "Not initialized"),
+                MoveResult::VersionedFileMissing => error("Neither the old nor the new file exists"),
+                MoveResult::DestinationExists => error("The new file already exists"),
+                MoveResult::DestinationRepositoryExists => error("The new file already has a repository"),
+            }
+        }
+
+        Command::Clone {
+            source_versioned_file_path,
+            new_versioned_file_path,
+        } => {
+            let source_repo_paths = RepositoryPaths::from_versioned_file_path(source_versioned_file_path);
+            let new_repo_paths = RepositoryPaths::from_versioned_file_path(new_versioned_file_path);
+
+            let result = repository_operations::clone_versioned_file(env, &source_repo_paths, &new_repo_paths)?;
+
+            match result {
+                CloneResult::Ok => success_ok(),
+                CloneResult::NotInitialized => categorized_error(BiverErrorCategory::NotInitialized, "Not initialized"),
+                CloneResult::VersionedFileMissing => error("The source file does not exist"),
+                CloneResult::DestinationExists => error("The new file already exists"),
+                CloneResult::DestinationRepositoryExists => error("The new file already has a repository"),
+                CloneResult::ValidationFailed(blob_file_names) => error(format!(
+                    "The copy was removed because these blobs are missing or damaged in it: {}. Run verify on the source.",
+                    blob_file_names.join(", ")
+                )),
+            }
+        }
+
+        Command::Push {
+            versioned_file_path,
+            force,
+            remote,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::push(env, &repo_paths, &repo_data, remote.as_deref(), force)?;
+
+            match result {
+                PushResult::Ok { uploaded_object_count } => {
+                    println!("Uploaded {} objects", uploaded_object_count);
+                    success_ok()
+                }
+                PushResult::RemoteDoesNotExist => error(remote_not_found_message(remote.as_deref())),
+                PushResult::UnrelatedHistories => error("The remote holds the history of a different repository"),
+                PushResult::RemoteHasUnknownVersions => error("The remote has versions that are not present locally. Pull first."),
+                PushResult::DivergedBranches(branches) => error(format!("Branches diverged from the remote: {}. Pull first.", branches.join(", "))),
+                PushResult::Conflict => error("The remote was changed by someone else during the push. Pull and push again."),
+                PushResult::RcloneNotReady => categorized_error(BiverErrorCategory::DependencyMissing, "rclone is not available"),
+            }
+        }
+
+        Command::Backup {
+            versioned_file_path,
+            destination,
+            incremental,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::backup(env, &repo_paths, &repo_data, &destination, incremental)?;
+
+            match result {
+                BackupResult::Ok {
+                    copied_object_count,
+                    unchanged_object_count,
+                    removed_object_count,
+                } => {
+                    println!(
+                        "Copied {} objects, {} unchanged, {} removed",
+                        copied_object_count, unchanged_object_count, removed_object_count
+                    );
+                    success_ok()
+                }
+                BackupResult::RcloneNotReady => categorized_error(BiverErrorCategory::DependencyMissing, "rclone is not available"),
+                BackupResult::VerificationFailed(object_names) => error(format!("Copies of these objects differ from the originals: {}", object_names.join(", "))),
+                BackupResult::Conflict => error("The backup destination was changed by another program during the backup. Run it again."),
+            }
+        }
+
+        Command::Pull { versioned_file_path, remote } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::pull(env, &repo_paths, &mut repo_data, remote.as_deref())?;
+
+            match result {
+                PullResult::Ok {
+                    new_version_count,
+                    downloaded_object_count,
+                    diverged_branches,
+                } => {
+                    println!("Received {} versions, downloaded {} objects", new_version_count, downloaded_object_count);
+
+                    for diverged_branch in &diverged_branches {
+                        println!("Branch {} diverged, the remote one was saved as {}", diverged_branch.branch, diverged_branch.remote_branch);
+                    }
+
+                    success_ok()
+                }
+                PullResult::RemoteDoesNotExist => error(remote_not_found_message(remote.as_deref())),
+                PullResult::RemoteIsEmpty => warning("The remote is empty"),
+                PullResult::UnrelatedHistories => error("The remote holds the history of a different repository"),
+                PullResult::RcloneNotReady => categorized_error(BiverErrorCategory::DependencyMissing, "rclone is not available"),
+                PullResult::InvalidObjectName(object_name) => categorized_error(
+                    BiverErrorCategory::Corruption,
+                    format!("The remote names an object {:?} biver does not create, nothing was pulled", object_name),
+                ),
+                PullResult::ChecksumMismatch(object_name) => categorized_error(
+                    BiverErrorCategory::Corruption,
+                    format!("Object {} downloaded from the remote does not match its checksum, nothing was pulled", object_name),
+                ),
+                PullResult::BlockedByUncommittedChanges => categorized_error(BiverErrorCategory::UncommittedChanges, "Cannot pull because there are uncommitted changes"),
+            }
+        }
+
+        Command::Reconcile {
+            versioned_file_path,
+            include_backups,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::reconcile(env, &repo_paths, &mut repo_data, include_backups)?;
+
+            match result {
+                ReconcileResult::Ok(reconciled_copies) => {
+                    formatting::print_reconciled_copies(&reconciled_copies);
+
+                    if reconciled_copies.iter().all(|c| matches!(c.outcome, ReconcileOutcome::Merged { .. })) {
+                        success_ok()
+                    } else {
+                        warning("Some copies could not be merged and were left in place")
+                    }
+                }
+                ReconcileResult::NothingToReconcile => warning("There are no conflicted copies of the repository data"),
+            }
+        }
+
+        Command::ImportHistory {
+            versioned_file_path,
+            other_versioned_file_path,
+            branch,
+            onto,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let other_repo_paths = RepositoryPaths::from_versioned_file_path(other_versioned_file_path);
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::import_history(env, &repo_paths, &mut repo_data, &other_repo_paths, &branch, onto.as_deref())?;
+
+            match result {
+                ImportHistoryResult::Ok { imported_version_count, branches } => {
+                    println!("Imported {} versions", imported_version_count);
+                    println!("Branches {}", branches.join(", "));
+                    success()
+                }
+                ImportHistoryResult::OtherNotInitialized => categorized_error(BiverErrorCategory::NotInitialized, "The file to import from has no repository"),
+                ImportHistoryResult::HashAlgorithmMismatch => error("The repositories use different hash algorithms"),
+                ImportHistoryResult::InvalidBranchName => error("Invalid branch name"),
+                ImportHistoryResult::BranchAlreadyExists(branch) => error(format!("Branch {} already exists", branch)),
+                ImportHistoryResult::InvalidTarget => invalid_target_error(&repo_data, onto.as_deref().unwrap_or_default()),
+                ImportHistoryResult::MissingObjects(count) => error(format!("Blobs missing from both repositories: {}", count)),
+                ImportHistoryResult::ChecksumMismatch(blob_file_name) => categorized_error(
+                    BiverErrorCategory::Corruption,
+                    format!("Blob {} of the other repository does not match its checksum, nothing was imported", blob_file_name),
+                ),
+            }
+        }
+
+        Command::Checkout {
+            versioned_file_path,
+            autostash,
+            target,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::check_out(env, &repo_paths, &mut repo_data, &target, autostash)?;
+
+            let (stashed, restorable_stash) = match result {
+                CheckOutResult::Ok { stashed, restorable_stash } => (stashed, restorable_stash),
+                CheckOutResult::InvalidTarget => return invalid_target_error(&repo_data, &target),
+            };
+
+            if let Some(stashed) = stashed.and_then(|id| repo_data.version(id)) {
+                println!("Uncommitted changes stashed as {}", stashed.nickname);
+            }
+
+            // Anything but an explicit answer, including a closed input, keeps the stash to be offered again
+            if let Some(stash) = restorable_stash.and_then(|id| repo_data.version(id)) {
+                println!("Restore the changes stashed as {}? (y to restore, d to discard, N to keep them for later)", stash.nickname);
+                let input = read_text_input()?.unwrap_or_default();
+                if input.eq_ignore_ascii_case("y") {
+                    repository_operations::settle_autostash(env, &repo_paths, &repo_data, stash.id, true)?;
+                } else if input.eq_ignore_ascii_case("d") {
+                    repository_operations::settle_autostash(env, &repo_paths, &repo_data, stash.id, false)?;
+                }
+            }
+
+            success_ok()
+        }
+
+        Command::Restore {
+            versioned_file_path,
+            output,
+            open,
+            clipboard,
+            stdout,
+            at,
+            branch,
+            target,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            // Standard output carries the content, so notes go to standard error
+            let print_note = |note: String| if stdout { eprintln!("{}", note) } else { println!("{}", note) };
+
+            let target = match (target, at) {
+                (Some(target), _) => target,
+                (None, Some(at)) => match repository_operations::version_at_time(&repo_data, at, branch.as_deref()) {
+                    VersionAtTimeResult::Ok { version, newer_elsewhere } => {
+                        print_note(format!(
+                            "Restoring {}, committed {}",
+                            version.nickname,
+                            version.creation_time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+                        ));
+                        if !newer_elsewhere.is_empty() {
+                            let nicknames: Vec<&str> = newer_elsewhere.iter().map(|v| v.nickname.as_str()).collect();
+                            print_note(format!("Newer by then on other branches: {}", nicknames.join(", ")).yellow().to_string());
+                        }
+                        version.id.bs58()
+                    }
+                    VersionAtTimeResult::BranchDoesNotExist => return error("Branch does not exist"),
+                    VersionAtTimeResult::NoVersionYet => return error("No version had been committed by then"),
+                },
+                (None, None) => unreachable!("clap requires a target or --at"),
+            };
+
+            if stdout {
+                return match repository_operations::restore_to_writer(env, &repo_paths, &repo_data, &target, &mut io::stdout().lock())? {
+                    RestoreToWriterResult::Ok => success(),
+                    RestoreToWriterResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+                };
+            }
+
+            if open || clipboard {
+                let restored_file_path = match repository_operations::restore_to_temporary_file(env, &repo_paths, &repo_data, &target)? {
+                    RestoreToTemporaryFileResult::InvalidTarget => return invalid_target_error(&repo_data, &target),
+                    RestoreToTemporaryFileResult::Ok(restored_file_path) => restored_file_path,
+                };
+
+                if clipboard {
+                    let result = clipboard::copy_image(env, &repo_paths, &restored_file_path);
+                    repository_operations::remove_restored_temporary_file(&restored_file_path)?;
+
+                    return match result? {
+                        CopyImageResult::Ok => success_ok(),
+                        CopyImageResult::NotAnImage => error("The restored version is not an image"),
+                    };
+                }
+
+                open::that_detached(&restored_file_path)?;
+                println!("{}", restored_file_path.display());
+                return success_ok();
+            }
+
+            let result = repository_operations::restore(env, &repo_paths, &repo_data, &target, output.as_deref())?;
+
+            match result {
+                RestoreResult::Ok => success_ok(),
+                RestoreResult::BlockedByUncommittedChanges => categorized_error(
+                    BiverErrorCategory::UncommittedChanges,
+                    "Cannot restore to the versioned file because there are uncommitted changes",
+                ),
+                RestoreResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+            }
+        }
+
+        Command::Fork {
+            versioned_file_path,
+            new_file,
+            description,
+            target,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::fork(env, &repo_paths, &repo_data, &target, &new_file, description.as_deref())?;
+
+            match result {
+                ForkResult::Ok => success_ok(),
+                ForkResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+                ForkResult::FileAlreadyExists => error(format!("{} already exists", new_file.display())),
+            }
+        }
+
+        Command::Lock { versioned_file_path, steal } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            repository_io::read_data(&repo_paths)?.initialized()?;
+
+            match repository_operations::lock(env, &repo_paths, steal)? {
+                LockResult::Ok => success_ok(),
+                LockResult::AlreadyLocked => warning("You already hold the editing lock"),
+                LockResult::LockedByOther(lock) => error(format!(
+                    "Locked for editing by {}. Use --steal to take it over.",
+                    formatting::format_editing_lock(&lock, false)
+                )),
+                LockResult::Stolen(lock) => {
+                    println!("Taken over from {}", formatting::format_editing_lock(&lock, false));
+                    success_ok()
+                }
+            }
+        }
+
+        Command::Unlock { versioned_file_path, steal } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            repository_io::read_data(&repo_paths)?.initialized()?;
+
+            match repository_operations::unlock(env, &repo_paths, steal)? {
+                UnlockResult::Ok => success_ok(),
+                UnlockResult::NotLocked => warning("The file is not locked"),
+                UnlockResult::LockedByOther(lock) => error(format!(
+                    "Locked for editing by {}. Use --steal to release it anyway.",
+                    formatting::format_editing_lock(&lock, false)
+                )),
+            }
+        }
+
+        Command::Protect { versioned_file_path, branch } => protect(versioned_file_path, &branch, true),
+
+        Command::Unprotect { versioned_file_path, branch } => protect(versioned_file_path, &branch, false),
+
+        Command::PatchLimit { versioned_file_path, limit } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let Some(limit) = limit else {
+                match repo_data.patch_limit {
+                    Some(patch_limit) => println!("{}", patch_limit),
+                    None => println!("none"),
+                }
+                return success();
+            };
+
+            let patch_limit = match limit {
+                PatchLimitArgument::Count(count) => Some(PatchLimit::Count(count)),
+                PatchLimitArgument::Auto => Some(PatchLimit::Auto),
+                PatchLimitArgument::None => None,
+            };
+
+            repository_operations::set_patch_limit(&repo_paths, &mut repo_data, patch_limit)?;
+            success_ok()
+        }
+
+        Command::Pin { versioned_file_path, target } => pin(env, versioned_file_path, &target, true),
+
+        Command::Unpin { versioned_file_path, target } => pin(env, versioned_file_path, &target, false),
+
+        Command::Create(CreateCommand::Branch {
+            versioned_file_path,
+            checkout,
+            name,
+            target,
+        })
+        | Command::Branch(BranchCommand::Create {
+            versioned_file_path,
+            checkout,
+            name,
+            target,
+        }) => create_branch(env, versioned_file_path, &name, target.as_deref(), checkout),
+
+        Command::List(ListCommand::Branches { versioned_file_path }) | Command::Branch(BranchCommand::List { versioned_file_path }) => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            formatting::print_branch_list(&repo_data, env.verbose);
+
+            success()
+        }
+
+        Command::Branch(BranchCommand::Set {
+            versioned_file_path,
+            force,
+            name,
+            target,
+        }) => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::set_branch(env, &repo_paths, &mut repo_data, &name, &target, force)?;
+
+            match result {
+                SetBranchResult::Ok => success_or_dry_run(env, &repo_paths, &repo_data),
+                SetBranchResult::BranchDoesNotExist => error("Branch does not exist"),
+                SetBranchResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+                SetBranchResult::CannotLeaveOrphans(count) => error(format!(
+                    "Versions would no longer be on any branch ({}). Create a branch at {} first to keep them.",
+                    count, name
+                )),
+                SetBranchResult::BranchProtected => error(format!("Branch {} is protected. Use --force to change it anyway.", name)),
+            }
+        }
+
+        Command::Branch(BranchCommand::Rename {
+            versioned_file_path,
+            old_name,
+            new_name,
+        }) => rename_branch(env, versioned_file_path, &old_name, &new_name),
+
+        Command::Branch(BranchCommand::Delete {
+            versioned_file_path,
+            confirmed,
+            force,
+            name,
+        }) => delete_branch(env, versioned_file_path, &name, confirmed, force),
+
+        Command::Create(CreateCommand::Remote {
+            versioned_file_path,
+            name,
+            location,
+            endpoint,
+            region,
+        }) => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let location = RemoteLocation::parse(&location, endpoint.as_deref(), region.as_deref())?;
+
+            let result = repository_operations::create_remote(&repo_paths, &name, location)?;
+
+            match result {
+                CreateRemoteResult::Ok => success_ok(),
+                CreateRemoteResult::RemoteAlreadyExists => error("Remote already exists"),
+                CreateRemoteResult::InvalidRemoteName => error("Invalid remote name"),
+            }
+        }
+
+        Command::List(ListCommand::Remotes { versioned_file_path }) => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            repository_io::read_data(&repo_paths)?.initialized()?;
+
+            formatting::print_remote_list(&repository_io::read_remotes(&repo_paths)?);
+
+            success()
+        }
+
+        Command::List(ListCommand::Blobs { versioned_file_path }) => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let entries = repository_operations::blob_inventory(&repo_paths, &repo_data)?;
+
+            formatting::print_blob_inventory(&entries);
+
+            success()
+        }
+
+        Command::List(ListCommand::Versions {
+            versioned_file_path,
+            branch,
+            since,
+            until,
+            author,
+            attribute_filters,
+            sort,
+            limit,
+        }) => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let query = VersionQuery {
+                branch: branch.as_deref(),
+                since,
+                until,
+                author: author.as_deref(),
+                attributes: &attribute_filters,
+                order: match sort {
+                    VersionSort::Date => VersionOrder::NewestFirst,
+                    VersionSort::Size => VersionOrder::LargestFirst,
+                },
+                limit,
+            };
+
+            let versions = match repository_operations::list_versions(&repo_data, &query) {
+                ListVersionsResult::Ok(versions) => versions,
+                ListVersionsResult::BranchDoesNotExist => return error("Branch does not exist"),
+            };
+
+            if env.json {
+                formatting::print_version_list_json(&repo_data, &versions)?;
+            } else {
+                formatting::print_version_list(env.pager.as_deref(), &repo_data, &versions);
+            }
+
+            success()
+        }
+
+        Command::Attr(attr_command) => {
+            let (versioned_file_path, target, key, value) = match attr_command {
+                AttrCommand::Set {
+                    versioned_file_path,
+                    target,
+                    key,
+                    value,
+                } => (versioned_file_path, target, key, Some(value)),
+                AttrCommand::Unset { versioned_file_path, target, key } => (versioned_file_path, target, key, None),
+            };
+
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::set_attribute(&repo_paths, &mut repo_data, &target, &key, value.as_deref())?;
+
+            match result {
+                SetAttributeResult::Ok => success_ok(),
+                SetAttributeResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+                SetAttributeResult::InvalidKey => error("Invalid attribute name"),
+                SetAttributeResult::AttributeNotSet => warning("Attribute is not set"),
+            }
+        }
+
+        Command::Note(note_command) => match note_command {
+            NoteCommand::Add {
+                versioned_file_path,
+                target,
+                text,
+            } => {
+                let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+                let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+                let result = repository_operations::add_note(env, &repo_paths, &mut repo_data, &target, &text)?;
+
+                match result {
+                    AddNoteResult::Ok => success_ok(),
+                    AddNoteResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+                    AddNoteResult::EmptyNote => error("Note is empty"),
+                }
+            }
+
+            NoteCommand::List { versioned_file_path, target } => {
+                let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+                let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+                let version = match target {
+                    None => repo_data.head_version(),
+                    Some(target) => match repository_operations::version(&repo_data, &target) {
+                        VersionResult::InvalidTarget => return invalid_target_error(&repo_data, &target),
+                        VersionResult::Ok(version) => version,
+                    },
+                };
+
+                formatting::print_notes(version);
+
+                success()
+            }
+        },
+
+        Command::Review(review_command) => match review_command {
+            ReviewCommand::Submit {
+                versioned_file_path,
+                target,
+                note,
+                reviewer,
+            } => review(env, versioned_file_path, &target, ReviewState::Submitted, note, reviewer),
+
+            ReviewCommand::Approve {
+                versioned_file_path,
+                target,
+                note,
+                reviewer,
+            } => review(env, versioned_file_path, &target, ReviewState::Approved, note, reviewer),
+
+            ReviewCommand::Reject {
+                versioned_file_path,
+                target,
+                note,
+                reviewer,
+            } => review(env, versioned_file_path, &target, ReviewState::Rejected, note, reviewer),
+
+            ReviewCommand::List { versioned_file_path, target } => {
+                let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+                let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+                let version = match target {
+                    None => repo_data.head_version(),
+                    Some(target) => match repository_operations::version(&repo_data, &target) {
+                        VersionResult::InvalidTarget => return invalid_target_error(&repo_data, &target),
+                        VersionResult::Ok(version) => version,
+                    },
+                };
+
+                formatting::print_reviews(version);
+
+                success()
+            }
+        },
+
+        Command::Nickname(nickname_command) => match nickname_command {
+            NicknameCommand::Regenerate { versioned_file_path, target } => {
+                let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+                let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+                let result = repository_operations::regenerate_nickname(env, &repo_paths, &mut repo_data, &target)?;
+
+                match result {
+                    RegenerateNicknameResult::Ok { old_nickname, new_nickname } => {
+                        println!("{} is now {}", old_nickname, new_nickname);
+                        success_or_dry_run(env, &repo_paths, &repo_data)
+                    }
+                    RegenerateNicknameResult::Unchanged => warning("Nickname is unchanged"),
+                    RegenerateNicknameResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+                    RegenerateNicknameResult::VersionSigned => error("Version is signed and its nickname is part of the signature"),
+                }
+            }
+        },
+
+        Command::Branch(BranchCommand::Describe {
+            versioned_file_path,
+            branch,
+            description,
+        }) => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let result = repository_operations::describe_branch(&repo_paths, &mut repo_data, &branch, &description)?;
+
+            match result {
+                DescribeBranchResult::Ok => success_ok(),
+                DescribeBranchResult::BranchDoesNotExist => error("Branch does not exist"),
+            }
+        }
+
+        Command::Recover {
+            versioned_file_path,
+            list,
+            target,
+            force,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            if list {
+                formatting::print_orphaned_versions(&repo_data);
+                return success();
+            }
+
+            let result = repository_operations::recover(env, &repo_paths, &mut repo_data, target.as_deref(), force)?;
+
+            match result {
+                RecoverResult::Ok { branch, swapped } => {
+                    if swapped {
+                        println!("Recovered as the leaf of {}, the version that replaced it can be recovered in turn", branch);
+                    } else {
+                        println!("Recovered on the new branch {}", branch);
+                    }
+                    success_ok()
+                }
+                RecoverResult::NothingToRecover => warning("Nothing to recover"),
+                RecoverResult::InvalidTarget => categorized_error(BiverErrorCategory::InvalidTarget, "Invalid target"),
+                RecoverResult::ParentMissing => error("Cannot recover because the version it was based on no longer exists"),
+                RecoverResult::BlockedByUncommittedChanges => categorized_error(BiverErrorCategory::UncommittedChanges, "Cannot recover because there are uncommitted changes"),
+                RecoverResult::BranchProtected(branch) => error(format!("Branch {} is protected. Use --force to change it anyway.", branch)),
+            }
+        }
+
+        Command::Bisect(bisect_command) => match bisect_command {
+            BisectCommand::Start { versioned_file_path } => {
+                let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+                let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+                let result = repository_operations::bisect_start(env, &repo_paths, &repo_data)?;
+
+                match result {
+                    BisectStartResult::Ok => success_ok(),
+                    BisectStartResult::AlreadyBisecting => warning("Already bisecting"),
+                    BisectStartResult::BlockedByUncommittedChanges => {
+                        categorized_error(BiverErrorCategory::UncommittedChanges, "Cannot bisect because there are uncommitted changes")
+                    }
+                }
+            }
+
+            BisectCommand::Good { versioned_file_path, target } => bisect_mark(env, versioned_file_path, target.as_deref(), true),
+
+            BisectCommand::Bad { versioned_file_path, target } => bisect_mark(env, versioned_file_path, target.as_deref(), false),
+
+            BisectCommand::Reset { versioned_file_path } => {
+                let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+                let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+                let result = repository_operations::bisect_reset(env, &repo_paths, &mut repo_data)?;
+
+                match result {
+                    BisectResetResult::Ok => success_ok(),
+                    BisectResetResult::NotBisecting => warning("Not bisecting"),
+                    BisectResetResult::BlockedByUncommittedChanges => {
+                        categorized_error(BiverErrorCategory::UncommittedChanges, "Cannot reset because there are uncommitted changes")
+                    }
+                }
+            }
+        },
+
+        Command::Rename(rename_command) => match rename_command {
+            RenameCommand::Branch {
+                versioned_file_path,
+                old_name,
+                new_name,
+            } => rename_branch(env, versioned_file_path, &old_name, &new_name),
+
+            RenameCommand::Version {
+                versioned_file_path,
+                target,
+                new_nickname,
+            } => {
+                let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+                let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+                let result = repository_operations::rename_version(env, &repo_paths, &mut repo_data, &target, &new_nickname)?;
+
+                match result {
+                    RenameVersionResult::Ok => success_or_dry_run(env, &repo_paths, &repo_data),
+                    RenameVersionResult::InvalidTarget => invalid_target_error(&repo_data, &target),
+                    RenameVersionResult::InvalidNickname => error("Nicknames may only contain letters and digits, with single dashes between words"),
+                    RenameVersionResult::NicknameTaken => error("Another version or a branch already has this name"),
+                    RenameVersionResult::VersionSigned => error("Version is signed and its nickname is part of the signature"),
+                }
+            }
+        },
 
         Command::Delete(delete_command) => match delete_command {
             DeleteCommand::Branch {
                 versioned_file_path,
                 confirmed,
+                force,
                 name,
-            } => {
+            } => delete_branch(env, versioned_file_path, &name, confirmed, force),
+
+            DeleteCommand::Remote { versioned_file_path, name } => {
                 let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
-                let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+                repository_io::read_data(&repo_paths)?.initialized()?;
+
+                let result = repository_operations::delete_remote(&repo_paths, &name)?;
+
+                match result {
+                    DeleteRemoteResult::Ok => success_ok(),
+                    DeleteRemoteResult::RemoteDoesNotExist => error("Remote does not exist"),
+                }
+            }
+        },
+
+        Command::Dependencies => {
+            formatting::print_dependencies(&tools::detect_all(env));
+            success()
+        }
+
+        Command::Setup => setup(env),
+
+        Command::Completions { shell } => {
+            setup::write_completions(shell, &mut io::stdout());
+            success()
+        }
+
+        Command::AutosaveGuard {
+            versioned_file_path,
+            on_save,
+            stash_branch,
+            settle_milliseconds,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let save_action = match on_save {
+                OnSave::Commit => SaveAction::Commit,
+                OnSave::Stash => SaveAction::Stash { branch: &stash_branch },
+            };
+
+            autosave_guard::run(env, &repo_paths, save_action, Duration::from_millis(settle_milliseconds))?;
+            success()
+        }
+
+        Command::GitExport {
+            versioned_file_path,
+            git_repo,
+            content,
+        } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            let repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+            let content = match content {
+                GitExportContent::Raw => GitContent::Raw,
+                GitExportContent::LfsPointer => GitContent::LfsPointer,
+            };
 
-                if !confirmed {
-                    println!("Are you sure you want to delete this branch? (y/N)");
-                    let confirmed = read_yes_no_input()?.unwrap_or(false);
-                    if !confirmed {
-                        return success();
-                    }
+            let result = repository_operations::git_export(env, &repo_paths, &repo_data, &git_repo, &content)?;
+
+            match result {
+                GitExportResult::Ok { exported_version_count } => {
+                    println!("Exported {} versions", exported_version_count);
+                    success_ok()
                 }
+                GitExportResult::GitNotReady => categorized_error(BiverErrorCategory::DependencyMissing, "git is not available"),
+                GitExportResult::NotAGitRepository => error("The path is not a git repository"),
+            }
+        }
 
-                let result = repository_operations::delete_branch(&repo_paths, &mut repo_data, &name)?;
+        Command::Daemon { address } => {
+            daemon::run(env, &address)?;
+            success()
+        }
 
-                match result {
-                    DeleteBranchResult::Ok => success_ok(),
-                    DeleteBranchResult::BranchDoesNotExist => error("Branch does not exist"),
-                    DeleteBranchResult::CannotDeleteHead => error("Cannot delete the version currently pointed at by HEAD"),
+        Command::Serve { versioned_file_path, port, bind } => {
+            let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+            repository_io::read_data(&repo_paths)?.initialized()?;
+
+            web_viewer::run(env, &repo_paths, &format!("{}:{}", bind, port))?;
+            success()
+        }
+
+        Command::Rpc { versioned_file_path } => {
+            rpc::run(env, versioned_file_path.as_deref())?;
+            success()
+        }
+
+        Command::IntegrateShell(IntegrateShellCommand::Install) => {
+            let biver_path = std::env::current_exe()?;
+
+            for location in shell_integration::install(&biver_path)? {
+                println!("Installed {}", location);
+            }
+
+            success_ok()
+        }
+
+        Command::IntegrateShell(IntegrateShellCommand::Uninstall) => {
+            let removed = shell_integration::uninstall()?;
+
+            if removed.is_empty() {
+                return warning("Shell integration is not installed");
+            }
+
+            for location in removed {
+                println!("Removed {}", location);
+            }
+
+            success_ok()
+        }
+    }
+}
+
+enum StatusLayout {
+    Columns(StatusColumns),
+    Porcelain,
+}
+
+fn status_columns(format: StatusFormatArgument, columns: Option<Vec<StatusColumnArgument>>) -> StatusColumns {
+    let Some(columns) = columns else {
+        return match format {
+            StatusFormatArgument::Full => StatusColumns::FULL,
+            StatusFormatArgument::Compact => StatusColumns::COMPACT,
+            StatusFormatArgument::Oneline => StatusColumns::ONELINE,
+        };
+    };
+
+    StatusColumns {
+        creation_time: columns.contains(&StatusColumnArgument::Time),
+        humanized_time: columns.contains(&StatusColumnArgument::Humanized),
+        id: columns.contains(&StatusColumnArgument::Id),
+        nickname: columns.contains(&StatusColumnArgument::Nickname),
+        branches: columns.contains(&StatusColumnArgument::Branches),
+        pinned: columns.contains(&StatusColumnArgument::Pinned),
+        attributes: columns.contains(&StatusColumnArgument::Attributes),
+        review: columns.contains(&StatusColumnArgument::Review),
+        description: columns.contains(&StatusColumnArgument::Description),
+    }
+}
+
+fn status(env: &Env, repo_paths: RepositoryPaths, all: bool, verify: bool, pending: bool, layout: &StatusLayout, scope: &StatusScope) -> BiverResult<()> {
+    let repo_data = repository_io::read_data(&repo_paths)?;
+
+    match repo_data {
+        RepositoryDataResult::NotInitialized { detached_versioned_files } => println!("{}", not_initialized_message(&detached_versioned_files)),
+        RepositoryDataResult::Initialized(repository_data) => {
+            if let StatusScope::Branch(branch) = scope
+                && !repository_data.branches.contains_key(branch)
+            {
+                return error("Branch does not exist");
+            }
+
+            let has_uncommitted_changes = repository_operations::has_uncommitted_changes(env, &repo_paths, &repository_data, !verify)?;
+            match layout {
+                StatusLayout::Columns(columns) => {
+                    let summary = repository_operations::status_summary(env, &repo_paths, &repository_data, has_uncommitted_changes)?;
+                    formatting::print_repository_data(env.pager.as_deref(), &repository_data, &summary, all, columns, scope)
+                }
+                StatusLayout::Porcelain => formatting::print_repository_data_porcelain(&repository_data, has_uncommitted_changes, all, scope),
+            }
+
+            // The thumbnail is left for the user to open, gc cleans it up
+            if pending
+                && has_uncommitted_changes
+                && let Some(summary) = repository_operations::pending_version_summary(env, &repo_paths, &repository_data)?
+            {
+                let preview_path = repository_operations::pending_preview(env, &repo_paths)?;
+                formatting::print_pending_changes(&repository_data, &summary, preview_path.as_deref());
+            }
+        }
+    }
+
+    success()
+}
+
+// Before anything is asked or written. Returns the lock of someone else that --steal takes over.
+fn check_editing_lock(env: &Env, repo_paths: &RepositoryPaths, steal: bool) -> BiverResult<Option<EditingLock>> {
+    match repository_operations::check_editing_lock(env, repo_paths, steal)? {
+        EditingLockCheck::Free | EditingLockCheck::Own => Ok(None),
+        EditingLockCheck::Stolen(lock) => Ok(Some(lock)),
+        EditingLockCheck::LockedByOther(lock) => error(format!(
+            "Locked for editing by {}. Ask them to unlock it, or use --steal to go ahead anyway and take the lock over.",
+            formatting::format_editing_lock(&lock, false)
+        )),
+    }
+}
+
+// A stolen lock is only taken over when the change went through, so a failed commit leaves it with its owner
+fn take_over_editing_lock(env: &Env, repo_paths: &RepositoryPaths, stolen_lock: Option<EditingLock>, result: BiverResult<()>) -> BiverResult<()> {
+    result?;
+
+    if let Some(stolen_lock) = stolen_lock {
+        if !repository_operations::take_over_editing_lock(env, repo_paths)? {
+            return warning("The editing lock was taken by someone else in the meantime");
+        }
+        println!("Editing lock taken over from {}", formatting::format_editing_lock(&stolen_lock, false));
+    }
+
+    success()
+}
+
+fn commit(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    new_branch: Option<String>,
+    branch_auto: bool,
+    force: bool,
+    allow_duplicate: bool,
+    description: Option<&str>,
+) -> BiverResult<()> {
+    let mut repo_data = repository_io::read_data(repo_paths)?.initialized()?;
+
+    let new_branch = match new_branch {
+        Some(new_branch) => Some(new_branch),
+        None if repo_data.head.branch().is_some() => None,
+        None => {
+            if !repository_operations::has_uncommitted_changes(env, repo_paths, &repo_data, false)? {
+                return warning("Nothing to commit");
+            }
+
+            let suggested_branch_name = repository_operations::suggested_branch_name(&repo_data);
+
+            if branch_auto {
+                Some(suggested_branch_name)
+            } else {
+                println!(
+                    "Head is not on a branch. Enter a name for a new branch to commit to (default: {}) or 'n' to cancel:",
+                    suggested_branch_name
+                );
+                match read_text_input()?.as_deref() {
+                    None => return success(),
+                    Some("") => Some(suggested_branch_name),
+                    Some(input) if input.eq_ignore_ascii_case("n") => return success(),
+                    Some(input) => Some(input.to_string()),
+                }
+            }
+        }
+    };
+
+    // Checked before the editor opens, so a description is not written for nothing
+    if new_branch.is_none() && !force && repo_data.head.branch().is_some_and(|b| repo_data.is_protected(b)) {
+        return protected_branch_error(&repo_data);
+    }
+
+    let edited_description;
+    let description = match description {
+        Some(description) => Some(description),
+        None if description_editor::available(env) => {
+            let Some(summary) = repository_operations::pending_version_summary(env, repo_paths, &repo_data)? else {
+                return warning("Nothing to commit");
+            };
+
+            let branch = new_branch.as_deref().or(repo_data.head.branch()).unwrap_or_default();
+            let Some(description) = description_editor::edit(env, repo_paths, &formatting::format_description_template(&summary, branch))? else {
+                return warning("The description is empty, nothing was committed");
+            };
+            edited_description = description;
+            Some(edited_description.as_str())
+        }
+        None => None,
+    };
+
+    let result = commit_version(env, repo_paths, &mut repo_data, description, new_branch.as_deref(), force, allow_duplicate)?;
+
+    match result {
+        CommitResult::Ok => {
+            print_visually_identical_warning(&repo_data);
+            success_or_dry_run(env, repo_paths, &repo_data)
+        }
+        CommitResult::NothingToCommit => warning("Nothing to commit"),
+        CommitResult::Duplicate(version_id) => error(format!(
+            "Version {}, the one before head, has the same content, so this would undo the last commit. Use --allow-duplicate to commit anyway.",
+            repo_data.version(version_id).map_or_else(|| version_id.bs58(), |v| v.nickname.clone())
+        )),
+        CommitResult::HeadMustBeOnBranch => error("Head must be on a branch"),
+        CommitResult::BranchAlreadyExists => error("Branch already exists"),
+        CommitResult::InvalidBranchName => error("Invalid branch name"),
+        CommitResult::BranchProtected => protected_branch_error(&repo_data),
+    }
+}
+
+// Shared with rpc, so a commit is announced however it was made
+fn commit_version(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    repo_data: &mut RepositoryData,
+    description: Option<&str>,
+    new_branch: Option<&str>,
+    force: bool,
+    allow_duplicate: bool,
+) -> BiverResult<CommitResult> {
+    let result = repository_operations::commit_version(env, repo_paths, repo_data, description, new_branch, force, allow_duplicate)?;
+
+    if matches!(result, CommitResult::Ok) {
+        notify_new_head(env, repo_paths, repo_data, VersionEvent::Commit);
+    }
+
+    Ok(result)
+}
+
+// Tools already working are left as they are. Nothing is saved when cancelled with Ctrl+C or Ctrl+D.
+fn setup(env: &Env) -> BiverResult<()> {
+    let mut config = config::read()?;
+
+    for setup_tool in &setup::SETUP_TOOLS {
+        let tool = setup_tool.tool();
+
+        if matches!(setup_tool.detect(setup_tool.configured_path(env)), Detection::Found { .. }) {
+            println!("{} {}", tool.name, "is ready".green());
+            continue;
+        }
+
+        println!("{} {}. {}.", tool.name, "was not found".yellow(), tool.purpose);
+
+        if let Some(install_command) = setup_tool.install_command() {
+            let Some(answer) = read_edited_line(&format!("Install it with {}? (y/N) ", install_command.join(" ")))? else {
+                return success();
+            };
+
+            if answer.eq_ignore_ascii_case("y") && setup::run_install_command(&install_command)? && matches!(setup_tool.detect(None), Detection::Found { .. }) {
+                *setup_tool.config_path(&mut config) = None;
+                println!("{} {}", tool.name, "is ready".green());
+                continue;
+            }
+        }
+
+        println!("It can be downloaded from {}", setup_tool.download_url());
+
+        loop {
+            let Some(path) = read_edited_line(&format!("Path to the {} executable (leave empty to skip): ", tool.name))? else {
+                return success();
+            };
+
+            if path.is_empty() {
+                break;
+            }
+
+            let path = PathBuf::from(path);
+            if matches!(setup_tool.detect(Some(&path)), Detection::Found { .. }) {
+                println!("{} {}", tool.name, "is ready".green());
+                *setup_tool.config_path(&mut config) = Some(path);
+                break;
+            }
+
+            println!("{} {} {}", path.display(), "does not work as".yellow(), tool.name);
+        }
+    }
+
+    let config_path = config::write(&config)?;
+    println!("Saved {}", config_path.display());
+
+    match Shell::from_env() {
+        Some(shell) => match setup::completions_path(shell) {
+            Some(completions_path) => {
+                let Some(answer) = read_edited_line(&format!("Install completions for {} to {}? (y/N) ", shell, completions_path.display()))? else {
+                    return success();
+                };
+
+                if answer.eq_ignore_ascii_case("y") {
+                    setup::install_completions(shell, &completions_path)?;
+                    println!("Installed {}", completions_path.display());
                 }
             }
+            None => println!("Completions for {} can be loaded from its profile with the output of: biver completions {}", shell, shell),
         },
+        None => println!("Completions can be loaded from the shell profile with the output of: biver completions <shell>"),
+    }
 
-        Command::Dependencies => {
-            formatting::print_dependencies(xdelta3::ready(env), image_magick::ready(env));
-            success()
+    let Some(answer) = read_edited_line("Add biver actions to the file manager context menu? (y/N) ")? else {
+        return success();
+    };
+
+    if answer.eq_ignore_ascii_case("y") {
+        for location in shell_integration::install(&std::env::current_exe()?)? {
+            println!("Installed {}", location);
+        }
+    }
+
+    success_ok()
+}
+
+fn interactive_commit_available() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+// Shows what is about to be committed, then asks for a new branch to commit to, optionally, and the description,
+// written in the editor when one is configured
+fn interactive_commit(env: &Env, repo_paths: &RepositoryPaths) -> BiverResult<()> {
+    let repo_data = repository_io::read_data(repo_paths)?.initialized()?;
+
+    let Some(summary) = repository_operations::pending_version_summary(env, repo_paths, &repo_data)? else {
+        return warning("Nothing to commit");
+    };
+
+    let preview_file = repository_operations::pending_preview(env, repo_paths)?.map(TemporaryFile::new);
+    formatting::print_pending_version_summary(&repo_data, &summary, preview_file.as_ref().map(TemporaryFile::path));
+
+    match interactive_commit_answers(env, repo_paths, &repo_data, &summary)? {
+        InteractiveCommitAnswers::Commit { description, new_branch } => commit(env, repo_paths, new_branch, false, false, false, Some(&description)),
+        InteractiveCommitAnswers::EmptyDescription => warning("The description is empty, nothing was committed"),
+        InteractiveCommitAnswers::Cancelled => error("Cancelled, nothing was committed"),
+    }
+}
+
+enum InteractiveCommitAnswers {
+    Commit { description: String, new_branch: Option<String> },
+    EmptyDescription,
+    Cancelled,
+}
+
+fn interactive_commit_answers(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, summary: &PendingVersionSummary) -> BiverResult<InteractiveCommitAnswers> {
+    let suggested_branch_name = repository_operations::suggested_branch_name(repo_data);
+    let branch_prompt = match repo_data.head.branch() {
+        Some(branch) if !repo_data.is_protected(branch) => format!("New branch (leave empty to commit to {}): ", branch),
+        Some(branch) => format!("Branch {} is protected. New branch (default: {}): ", branch, suggested_branch_name),
+        None => format!("Head is not on a branch. New branch (default: {}): ", suggested_branch_name),
+    };
+
+    let Some(new_branch) = read_edited_line(&branch_prompt)? else {
+        return Ok(InteractiveCommitAnswers::Cancelled);
+    };
+
+    let new_branch = match new_branch.as_str() {
+        "" if repo_data.head.branch().is_some_and(|b| !repo_data.is_protected(b)) => None,
+        "" => Some(suggested_branch_name),
+        _ => Some(new_branch),
+    };
+
+    let description = if description_editor::available(env) {
+        let branch = new_branch.as_deref().or(repo_data.head.branch()).unwrap_or_default();
+        match description_editor::edit(env, repo_paths, &formatting::format_description_template(summary, branch))? {
+            Some(description) => description,
+            None => return Ok(InteractiveCommitAnswers::EmptyDescription),
+        }
+    } else {
+        loop {
+            match read_edited_line("Description: ")? {
+                None => return Ok(InteractiveCommitAnswers::Cancelled),
+                Some(description) if description.is_empty() => println!("The description cannot be empty"),
+                Some(description) => break description,
+            }
+        }
+    };
+
+    let Some(confirmation) = read_edited_line("Commit? (Y/n) ")? else {
+        return Ok(InteractiveCommitAnswers::Cancelled);
+    };
+
+    if confirmation.eq_ignore_ascii_case("n") {
+        return Ok(InteractiveCommitAnswers::Cancelled);
+    }
+
+    Ok(InteractiveCommitAnswers::Commit { description, new_branch })
+}
+
+fn create_branch(env: &Env, versioned_file_path: PathBuf, name: &str, target: Option<&str>, checkout: bool) -> BiverResult<()> {
+    let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+    let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+    let result = repository_operations::create_branch(env, &repo_paths, &mut repo_data, name, target, checkout)?;
+
+    match result {
+        CreateBranchResult::Ok => success_ok(),
+        CreateBranchResult::BranchAlreadyExists => error("Branch already exists"),
+        CreateBranchResult::InvalidBranchName => error("Invalid branch name"),
+        CreateBranchResult::InvalidTarget => invalid_target_error(&repo_data, target.unwrap_or_default()),
+    }
+}
+
+fn rename_branch(env: &Env, versioned_file_path: PathBuf, old_name: &str, new_name: &str) -> BiverResult<()> {
+    let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+    let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+    let result = repository_operations::rename_branch(env, &repo_paths, &mut repo_data, old_name, new_name)?;
+
+    match result {
+        RenameBranchResult::Ok => success_ok(),
+        RenameBranchResult::AnotherBranchExistsWithSameName => error("Another branch exists with the same name"),
+        RenameBranchResult::BranchDoesNotExist => error("Branch does not exist"),
+    }
+}
+
+fn delete_branch(env: &Env, versioned_file_path: PathBuf, name: &str, confirmed: bool, force: bool) -> BiverResult<()> {
+    let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+    let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+    if !confirmed && !env.dry_run {
+        println!("Are you sure you want to delete this branch? (y/N)");
+        let confirmed = read_yes_no_input()?.unwrap_or(false);
+        if !confirmed {
+            return success();
+        }
+    }
+
+    let result = repository_operations::delete_branch(env, &repo_paths, &mut repo_data, name, force)?;
+
+    match result {
+        DeleteBranchResult::Ok(trashed_path) => {
+            print_trashed_path(trashed_path.as_deref());
+            success_or_dry_run(env, &repo_paths, &repo_data)
+        }
+        DeleteBranchResult::BranchDoesNotExist => error("Branch does not exist"),
+        DeleteBranchResult::CannotDeleteHead => error("Cannot delete the version currently pointed at by HEAD"),
+        DeleteBranchResult::BranchProtected => error(format!("Branch {} is protected. Use --force to delete it anyway.", name)),
+        DeleteBranchResult::VersionPinned(version_id) => pinned_version_error(version_id),
+    }
+}
+
+fn protect(versioned_file_path: PathBuf, branch: &str, protected: bool) -> BiverResult<()> {
+    let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+    let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+    let result = repository_operations::protect_branch(&repo_paths, &mut repo_data, branch, protected)?;
+
+    match result {
+        ProtectBranchResult::Ok => success_ok(),
+        ProtectBranchResult::BranchDoesNotExist => error("Branch does not exist"),
+        ProtectBranchResult::AlreadyInState if protected => warning("Branch is already protected"),
+        ProtectBranchResult::AlreadyInState => warning("Branch is not protected"),
+    }
+}
+
+fn pin(env: &Env, versioned_file_path: PathBuf, target: &str, pinned: bool) -> BiverResult<()> {
+    let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+    let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+    let result = repository_operations::pin_version(env, &repo_paths, &mut repo_data, target, pinned)?;
+
+    match result {
+        PinVersionResult::Ok => success_ok(),
+        PinVersionResult::InvalidTarget => invalid_target_error(&repo_data, target),
+        PinVersionResult::AlreadyInState if pinned => warning("Version is already pinned"),
+        PinVersionResult::AlreadyInState => warning("Version is not pinned"),
+    }
+}
+
+fn comparison_preview<'a>(repo_paths: &RepositoryPaths, repo_data: &'a RepositoryData, target: Option<&str>) -> BiverResult<(&'a Version, PathBuf)> {
+    let version = match target {
+        None => repo_data.head_version(),
+        Some(target) => match repository_operations::version(repo_data, target) {
+            VersionResult::InvalidTarget => return invalid_target_error(repo_data, target),
+            VersionResult::Ok(version) => version,
+        },
+    };
+
+    match repository_operations::preview(repo_paths, version) {
+        PreviewResult::NoPreviewAvailable => error(format!("No preview available for {}", version.id.bs58())),
+        PreviewResult::Ok(preview) => Ok((version, preview)),
+    }
+}
+
+fn bisect_mark(env: &Env, versioned_file_path: PathBuf, target: Option<&str>, good: bool) -> BiverResult<()> {
+    let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+    let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+    let result = repository_operations::bisect_mark(env, &repo_paths, &mut repo_data, target, good)?;
+
+    let step = match result {
+        BisectMarkResult::Ok(step) => step,
+        BisectMarkResult::NotBisecting => return error("Not bisecting. Run bisect start first."),
+        BisectMarkResult::InvalidTarget => return invalid_target_error(&repo_data, target.unwrap_or_default()),
+        BisectMarkResult::BlockedByUncommittedChanges => {
+            return categorized_error(BiverErrorCategory::UncommittedChanges, "Cannot bisect because there are uncommitted changes");
+        }
+        BisectMarkResult::GoodNotAncestorOfBad => return error("The good version must be an ancestor of the bad version"),
+        BisectMarkResult::StaleState => return stale_bisect_error(),
+    };
+
+    let format_version = |version_id| match repo_data.version(version_id) {
+        Some(version) => Ok(formatting::format_versions(&repo_data, &[version]).remove(0)),
+        None => stale_bisect_error(),
+    };
+
+    match step {
+        BisectStep::WaitingForGood => println!("Mark a good version next"),
+        BisectStep::WaitingForBad => println!("Mark a bad version next"),
+        BisectStep::Test {
+            version_id,
+            remaining_versions,
+            remaining_steps,
+        } => {
+            println!("{}", format_version(version_id)?);
+            println!("Checked out for testing, {} versions left (about {} steps)", remaining_versions, remaining_steps);
+        }
+        BisectStep::Found(version_id) => {
+            println!("{}", format_version(version_id)?);
+            println!("This is the first bad version. Run bisect reset to finish.");
         }
     }
+
+    success()
+}
+
+fn stale_bisect_error<T>() -> BiverResult<T> {
+    error("The bisect state is out of date: it cannot be read or a version marked no longer exists. Run bisect reset and start again.")
+}
+
+// An ID prefix or a nickname abbreviation can match several versions, they are listed so that one can be picked by ID
+fn invalid_target_error<T>(repo_data: &RepositoryData, target: &str) -> BiverResult<T> {
+    let mut matches = repository_operations::id_prefix_target_matches(repo_data, target);
+
+    if matches.len() < 2 {
+        matches = repository_operations::nickname_target_matches(repo_data, target);
+    }
+
+    if matches.len() < 2 {
+        return categorized_error(BiverErrorCategory::InvalidTarget, format!("Invalid target {}", target));
+    }
+
+    for line in formatting::format_versions(repo_data, &matches) {
+        println!("{}", line);
+    }
+
+    categorized_error(
+        BiverErrorCategory::InvalidTarget,
+        format!("Target {} matches {} versions. Use a version ID instead.", target, matches.len()),
+    )
+}
+
+fn pinned_version_error(version_id: VersionId) -> BiverResult<()> {
+    error(format!("Version {} is pinned and would be removed. Unpin it first.", version_id.bs58()))
+}
+
+fn protected_branch_error(repo_data: &RepositoryData) -> BiverResult<()> {
+    error(format!(
+        "Branch {} is protected. Use --force to change it anyway.",
+        repo_data.head.branch().unwrap_or_default()
+    ))
+}
+
+fn supports_dry_run(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::Commit { .. }
+            | Command::Amend { .. }
+            | Command::Reset { .. }
+            | Command::Delete(DeleteCommand::Branch { .. })
+            | Command::Branch(BranchCommand::Delete { .. })
+            | Command::Branch(BranchCommand::Set { .. })
+            | Command::Gc { .. }
+            | Command::Prune { .. }
+    )
+}
+
+// Restore writes the versioned file or the output, not the repository. Git export writes the git repository.
+fn supports_read_only(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::Status { .. }
+            | Command::Serve { .. }
+            | Command::Preview { .. }
+            | Command::Show { .. }
+            | Command::Stats { .. }
+            | Command::Compare { .. }
+            | Command::Timelapse { .. }
+            | Command::Similar { .. }
+            | Command::Diff { .. }
+            | Command::Report { .. }
+            | Command::Changelog { .. }
+            | Command::Verify { .. }
+            | Command::VerifySignatures { .. }
+            | Command::Doctor { .. }
+            | Command::Restore { .. }
+            | Command::List(_)
+            | Command::Branch(BranchCommand::List { .. })
+            | Command::Note(NoteCommand::List { .. })
+            | Command::Review(ReviewCommand::List { .. })
+            | Command::GitExport { .. }
+            | Command::Dependencies
+            | Command::Completions { .. }
+    )
+}
+
+fn review(env: &Env, versioned_file_path: PathBuf, target: &str, state: ReviewState, note: Option<String>, reviewer: Option<String>) -> BiverResult<()> {
+    let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path);
+    let mut repo_data = repository_io::read_data(&repo_paths)?.initialized()?;
+
+    let reviewer = reviewer.as_deref().unwrap_or(&env.author);
+    let result = repository_operations::review(env, &repo_paths, &mut repo_data, target, state, reviewer, note.as_deref().unwrap_or_default())?;
+
+    match result {
+        ReviewResult::Ok => success_ok(),
+        ReviewResult::InvalidTarget => invalid_target_error(&repo_data, target),
+        ReviewResult::AlreadyInState => warning(format!("The version is already {}", state)),
+        ReviewResult::NotSubmitted(None) => error("The version has not been submitted for review"),
+        ReviewResult::NotSubmitted(Some(current_state)) => error(format!("The version is {}, submit it again to review it", current_state)),
+    }
+}
+
+fn print_visually_identical_warning(repo_data: &RepositoryData) {
+    if let Some(identical_version) = repository_operations::visually_identical_version(repo_data, repo_data.head_version()) {
+        let message = format!(
+            "The new version looks identical to {} despite different content, e.g. only metadata changed",
+            identical_version.nickname
+        );
+        println!("{}", message.yellow());
+    }
+}
+
+// Nothing was committed in a dry run to tell anyone about
+fn notify_new_head(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, event: VersionEvent) {
+    if env.dry_run {
+        return;
+    }
+
+    for failure in notifications::notify_new_head(env, repo_paths, repo_data, event) {
+        eprintln!("{}", failure.yellow());
+    }
+}
+
+// In system mode, the path is where the file was before the operating system moved it to its trash
+fn print_trashed_path(trashed_path: Option<&Path>) {
+    if let Some(trashed_path) = trashed_path {
+        println!("Previous content kept in the trash as {}", trashed_path.file_name().unwrap_or_default().to_string_lossy());
+    }
+}
+
+// Nothing is written in a dry run, so the data file still holds the state from before the command to compare with
+fn success_or_dry_run(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<()> {
+    if !env.dry_run {
+        return success_ok();
+    }
+
+    let original_repo_data = repository_io::read_data(repo_paths)?.initialized()?;
+    formatting::print_dry_run(&original_repo_data, repo_data, &env.effects.take());
+
+    success()
+}
+
+// Warnings do not fail the command
+fn exit_code(biver_error: &BiverError) -> ExitCode {
+    match biver_error.severity {
+        BiverErrorSeverity::Warning => ExitCode::SUCCESS,
+        BiverErrorSeverity::Error => ExitCode::from(biver_error.category.exit_code()),
+    }
 }
 
 fn success_ok() -> BiverResult<()> {
@@ -373,10 +2545,33 @@ fn success() -> BiverResult<()> {
     Ok(())
 }
 
-fn read_yes_no_input() -> BiverResult<Option<bool>> {
+// None at the end of the input, e.g. when it was closed with Ctrl+D
+fn read_text_input() -> BiverResult<Option<String>> {
     let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    let input = input.trim();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(input.trim().to_string()))
+}
+
+// With cursor movement and the usual editing keys. None when cancelled with Ctrl+C or Ctrl+D.
+fn read_edited_line(prompt: &str) -> BiverResult<Option<String>> {
+    let mut editor = DefaultEditor::new().map_err(io::Error::other)?;
+
+    match editor.readline(prompt) {
+        Ok(line) => Ok(Some(line.trim().to_string())),
+        Err(ReadlineError::Interrupted | ReadlineError::Eof) => Ok(None),
+        Err(e) => Err(io::Error::other(e).into()),
+    }
+}
+
+fn jobs_or_default(jobs: Option<NonZeroUsize>) -> usize {
+    jobs.map(NonZeroUsize::get).unwrap_or_else(parallel::default_jobs)
+}
+
+fn read_yes_no_input() -> BiverResult<Option<bool>> {
+    let input = read_text_input()?.unwrap_or_default();
     if input.eq_ignore_ascii_case("y") {
         return Ok(Some(true));
     }
@@ -395,11 +2590,34 @@ trait RepositoryDataResultExtensions {
 impl RepositoryDataResultExtensions for RepositoryDataResult {
     fn initialized(self) -> BiverResult<RepositoryData> {
         match self {
-            RepositoryDataResult::NotInitialized => Err(BiverError {
-                error_message: "Not initialized".to_string(),
+            RepositoryDataResult::NotInitialized { detached_versioned_files } => Err(BiverError {
+                error_message: not_initialized_message(&detached_versioned_files),
                 severity: BiverErrorSeverity::Error,
+                category: BiverErrorCategory::NotInitialized,
             }),
-            RepositoryDataResult::Initialized(repository_data) => Ok(repository_data),
+            RepositoryDataResult::Initialized(repository_data) => Ok(*repository_data),
         }
     }
 }
+
+fn remote_not_found_message(remote: Option<&str>) -> &'static str {
+    match remote {
+        Some(_) => "Remote does not exist",
+        None => "Specify a remote. It may only be omitted if exactly one remote is configured.",
+    }
+}
+
+fn not_initialized_message(detached_versioned_files: &[PathBuf]) -> String {
+    match detached_versioned_files {
+        [] => "Not initialized".to_string(),
+        [detached_versioned_file] => format!(
+            "Not initialized. A repository for the missing file {} is in the same directory. If the file was renamed, run: biver move \"{}\" <new file>",
+            detached_versioned_file.display(),
+            detached_versioned_file.display()
+        ),
+        _ => format!(
+            "Not initialized. Repositories for missing files are in the same directory: {}. If one of them was renamed, run: biver move <old file> <new file>",
+            detached_versioned_files.iter().map(|f| f.display().to_string()).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
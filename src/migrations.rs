@@ -0,0 +1,82 @@
+use crate::repository_data::RepositoryData;
+use crate::version_id::VersionId;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io;
+
+// Raised with every migration. Data files without the field are format 0.
+pub const CURRENT_FORMAT_VERSION: u32 = 2;
+
+// The migration at index i brings data from format i to format i + 1
+const MIGRATIONS: [fn(&mut RepositoryData); CURRENT_FORMAT_VERSION as usize] = [backfill_branch_creation_times, keep_full_digests];
+
+#[derive(Deserialize)]
+struct FormatVersion {
+    #[serde(default)]
+    format_version: u32,
+}
+
+// A newer format is refused rather than read, since writing it back would drop whatever this version does not know about
+pub fn parse_data(contents: &[u8]) -> io::Result<RepositoryData> {
+    let FormatVersion { format_version } = serde_json::from_slice(contents)?;
+
+    if format_version > CURRENT_FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "The repository has format version {}, this version of biver supports up to {}. Update biver to use the repository.",
+                format_version, CURRENT_FORMAT_VERSION
+            ),
+        ));
+    }
+
+    let mut repo_data = serde_json::from_slice(contents)?;
+    migrate(&mut repo_data);
+    Ok(repo_data)
+}
+
+// Only in memory, the next write persists the result
+pub fn migrate(repo_data: &mut RepositoryData) {
+    for migration in &MIGRATIONS[repo_data.format_version as usize..] {
+        migration(repo_data);
+    }
+
+    repo_data.format_version = CURRENT_FORMAT_VERSION;
+}
+
+// Format 1: branches created before branch metadata was recorded get the creation time of their oldest version that no
+// other branch reaches. Branches without such a version keep no creation time.
+fn backfill_branch_creation_times(repo_data: &mut RepositoryData) {
+    let mut creation_times = Vec::new();
+
+    for (branch, leaf_id) in &repo_data.branches {
+        if repo_data.branch_metadata.get(branch).is_some_and(|m| m.creation_time.is_some()) {
+            continue;
+        }
+
+        let other_branch_version_ids: HashSet<VersionId> = repo_data
+            .branches
+            .iter()
+            .filter(|(other_branch, _)| *other_branch != branch)
+            .flat_map(|(_, other_leaf_id)| repo_data.iter_version_and_ancestors(*other_leaf_id).map(|v| v.id))
+            .collect();
+
+        let oldest_own_version = repo_data
+            .iter_version_and_ancestors(*leaf_id)
+            .take_while(|v| !other_branch_version_ids.contains(&v.id))
+            .last();
+
+        if let Some(version) = oldest_own_version {
+            creation_times.push((branch.clone(), version.creation_time));
+        }
+    }
+
+    for (branch, creation_time) in creation_times {
+        repo_data.branch_metadata.entry(branch).or_default().creation_time = Some(creation_time);
+    }
+}
+
+// Format 2: versions of BLAKE3 repositories keep the full digest. Older versions of biver would drop it when writing, which
+// the format version prevents. It cannot be computed without reconstructing every version, so verify records it for
+// versions that have none.
+fn keep_full_digests(_repo_data: &mut RepositoryData) {}
@@ -1,3 +1,6 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+
 const ADJECTIVES: &[&str] = &[
     "able", "acid", "aged", "airy", "bold", "bony", "boss", "brief", "brisk", "busy", "calm", "cheap", "chief", "civil", "clean", "clear", "close", "cold", "cool", "crisp",
     "curly", "damp", "dark", "dead", "dear", "deep", "dense", "dim", "dizzy", "dry", "dull", "dusty", "early", "east", "easy", "empty", "even", "evil", "fair", "fake", "far",
@@ -37,9 +40,77 @@ const NOUNS: &[&str] = &[
     "wrap", "yard", "year", "yolk", "zone",
 ];
 
-pub fn new_nickname(random_value: u128) -> String {
-    let adjective_index = random_value % (ADJECTIVES.len() as u128);
-    let noun_index = random_value % (NOUNS.len() as u128);
+// Word lists replacing the built-in ones, e.g. project-themed or non-English words.
+// Words are used in file order, so the same lists always give the same nicknames.
+#[derive(Deserialize)]
+pub struct NicknameWords {
+    pub adjectives: Vec<String>,
+    pub nouns: Vec<String>,
+}
+
+impl NicknameWords {
+    pub fn built_in() -> Self {
+        NicknameWords {
+            adjectives: ADJECTIVES.iter().map(|w| w.to_string()).collect(),
+            nouns: NOUNS.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    // Dashes separate the words of a nickname and targets are matched letter by letter, so words must be plain letters.
+    // Duplicates would make two different random values give the same nickname.
+    pub fn validate(&self) -> Result<(), String> {
+        for (list_name, words) in [("adjectives", &self.adjectives), ("nouns", &self.nouns)] {
+            if words.is_empty() {
+                return Err(format!("The {} list is empty", list_name));
+            }
+
+            let mut seen = HashSet::new();
+
+            for word in words {
+                if word.is_empty() || !word.chars().all(char::is_alphabetic) {
+                    return Err(format!("\"{}\" in {} must only consist of letters", word, list_name));
+                }
+
+                if !seen.insert(word.to_lowercase()) {
+                    return Err(format!("\"{}\" appears in {} more than once", word, list_name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// How many neighbouring nouns are tried before a third word is added
+const TWO_WORD_ATTEMPTS: usize = 32;
+
+// Each word comes from its own part of the random value, so every combination is reachable.
+// Nicknames that are taken are skipped, two-word ones first since they are easier to remember and type.
+pub fn new_nickname(words: &NicknameWords, random_value: u128, is_taken: impl Fn(&str) -> bool) -> String {
+    let adjectives = &words.adjectives;
+    let nouns = &words.nouns;
+
+    let adjective_index = (random_value % adjectives.len() as u128) as usize;
+    let noun_index = (random_value / adjectives.len() as u128 % nouns.len() as u128) as usize;
+    let extra_adjective_index = (random_value / adjectives.len() as u128 / nouns.len() as u128 % adjectives.len() as u128) as usize;
+
+    let two_word_nicknames = (0..TWO_WORD_ATTEMPTS.min(nouns.len())).map(|i| format!("{}-{}", adjectives[adjective_index], nouns[(noun_index + i) % nouns.len()]));
+
+    let three_word_nicknames = (0..adjectives.len() * nouns.len()).map(|i| {
+        format!(
+            "{}-{}-{}",
+            adjectives[(extra_adjective_index + i / nouns.len()) % adjectives.len()],
+            adjectives[adjective_index],
+            nouns[(noun_index + i) % nouns.len()]
+        )
+    });
+
+    // Tiny custom lists can run out of combinations, a number keeps the nickname unique then
+    let numbered_nicknames = (2..).map(|n| format!("{}-{}{}", adjectives[adjective_index], nouns[noun_index], n));
 
-    format!("{}-{}", ADJECTIVES[adjective_index as usize], NOUNS[noun_index as usize])
+    two_word_nicknames
+        .chain(three_word_nicknames)
+        .chain(numbered_nicknames)
+        .find(|n| !is_taken(n))
+        .expect("Numbered nicknames never run out")
 }
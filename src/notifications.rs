@@ -0,0 +1,223 @@
+use crate::biver_result::BiverResult;
+use crate::env::Env;
+use crate::formatting;
+use crate::formatting::VersionListEntry;
+use crate::known_file_types;
+use crate::logging::LoggedCommand;
+use crate::repository_data::RepositoryData;
+use crate::repository_paths::RepositoryPaths;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use ureq::Agent;
+use ureq::http::Uri;
+use uuid::Uuid;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+// A [[webhooks]] table of the config file, e.g.
+// url = "https://discord.com/api/webhooks/..."
+// preview = true
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: String,
+    // Sent as multipart/form-data with the JSON in a payload_json field, the way Discord takes attachments
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preview: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum VersionEvent {
+    Commit,
+    Amend,
+}
+
+impl VersionEvent {
+    fn name(self) -> &'static str {
+        match self {
+            VersionEvent::Commit => "commit",
+            VersionEvent::Amend => "amend",
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            VersionEvent::Commit => "committed",
+            VersionEvent::Amend => "amended",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    // The message as Slack (text) and Discord (content) show it, other receivers can read the fields below
+    text: &'a str,
+    content: &'a str,
+    event: &'static str,
+    versioned_file: Option<String>,
+    branch: Option<&'a str>,
+    version: VersionListEntry<'a>,
+}
+
+// Webhooks being posted on threads of their own, so the command carries on meanwhile. Main waits for them before
+// exiting and shows what failed.
+#[derive(Default)]
+pub struct WebhookDeliveries {
+    pending: Mutex<Vec<JoinHandle<Option<String>>>>,
+}
+
+impl WebhookDeliveries {
+    fn start(&self, webhook: Webhook, payload_json: String, preview: Option<Vec<u8>>) {
+        let delivery = thread::spawn(move || {
+            post_webhook(&webhook, &payload_json, preview.as_deref())
+                .err()
+                .map(|e| format!("Webhook to {} failed: {}", redacted_url(&webhook.url), e.error_message))
+        });
+
+        self.pending.lock().expect("Webhook deliveries must not be poisoned").push(delivery);
+    }
+
+    // The failures of the webhooks started so far
+    pub fn wait(&self) -> Vec<String> {
+        let pending = std::mem::take(&mut *self.pending.lock().expect("Webhook deliveries must not be poisoned"));
+        pending
+            .into_iter()
+            .filter_map(|delivery| delivery.join().unwrap_or_else(|_| Some("Webhook thread panicked".to_string())))
+            .collect()
+    }
+}
+
+// Run once the new head version is written. The version exists whatever happens here, so failures are returned to be
+// shown as warnings rather than failing the command. Webhooks report theirs through env.webhook_deliveries.
+pub fn notify_new_head(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, event: VersionEvent) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    if env.webhooks.is_empty() && !env.desktop_notifications {
+        return failures;
+    }
+
+    let head_version = repo_data.head_version();
+    let versioned_file_name = repo_paths.versioned_file_name();
+
+    let mut message = format!(
+        "{} {} {}",
+        if head_version.author.is_empty() { "Someone" } else { &head_version.author },
+        event.verb(),
+        head_version.nickname
+    );
+    if let Some(versioned_file_name) = &versioned_file_name {
+        message.push_str(&format!(" of {}", versioned_file_name));
+    }
+    if let Some(branch) = repo_data.head.branch() {
+        message.push_str(&format!(" on {}", branch));
+    }
+    if !head_version.summary().is_empty() {
+        message.push_str(&format!(": {}", head_version.summary()));
+    }
+
+    if !env.webhooks.is_empty() {
+        let payload = WebhookPayload {
+            text: &message,
+            content: &message,
+            event: event.name(),
+            versioned_file: versioned_file_name,
+            branch: repo_data.head.branch(),
+            version: formatting::version_list_entries(repo_data, &[head_version]).remove(0),
+        };
+
+        match serde_json::to_string(&payload) {
+            Ok(payload_json) => {
+                let preview_path = head_version.preview_blob_file_name.as_deref().map(|p| repo_paths.blob_path(p));
+
+                for webhook in &env.webhooks {
+                    let preview = preview_path.as_deref().filter(|_| webhook.preview).and_then(|p| fs::read(p).ok());
+                    env.webhook_deliveries.start(webhook.clone(), payload_json.clone(), preview);
+                }
+            }
+            Err(e) => failures.push(format!("Webhooks failed: {}", e)),
+        }
+    }
+
+    if env.desktop_notifications && !show_desktop_notification(&message) {
+        failures.push("Desktop notification failed".to_string());
+    }
+
+    failures
+}
+
+// The path and query of webhook URLs are secrets in themselves, as with Slack and Discord, so only the host is shown
+fn redacted_url(url: &str) -> String {
+    match url.parse::<Uri>() {
+        Ok(uri) if uri.host().is_some() => format!("{}://{}/...", uri.scheme_str().unwrap_or("https"), uri.host().unwrap_or_default()),
+        _ => "a webhook URL that cannot be parsed".to_string(),
+    }
+}
+
+fn post_webhook(webhook: &Webhook, payload_json: &str, preview: Option<&[u8]>) -> BiverResult<()> {
+    let config = Agent::config_builder().timeout_global(Some(WEBHOOK_TIMEOUT)).build();
+    let agent = Agent::new_with_config(config);
+
+    match preview {
+        None => {
+            agent.post(&webhook.url).content_type("application/json").send(payload_json)?;
+        }
+        Some(preview) => {
+            let boundary = format!("biver-{}", Uuid::new_v4().simple());
+            let extension = image::guess_format(preview)
+                .ok()
+                .and_then(|format| format.extensions_str().first().copied())
+                .unwrap_or("jpg");
+
+            let mut body = Vec::new();
+            body.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"payload_json\"\r\n", boundary).as_bytes());
+            body.extend_from_slice(format!("Content-Type: application/json\r\n\r\n{}\r\n", payload_json).as_bytes());
+            body.extend_from_slice(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"files[0]\"; filename=\"preview.{}\"\r\n",
+                    boundary, extension
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(format!("Content-Type: {}\r\n\r\n", known_file_types::preview_mime_type(preview)).as_bytes());
+            body.extend_from_slice(preview);
+            body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+            agent
+                .post(&webhook.url)
+                .content_type(format!("multipart/form-data; boundary={}", boundary))
+                .send(&body[..])?;
+        }
+    }
+
+    Ok(())
+}
+
+fn show_desktop_notification(message: &str) -> bool {
+    let mut command = if cfg!(target_os = "windows") {
+        // A balloon tip from the notification area, which needs nothing beyond Windows PowerShell
+        let script = format!(
+            "Add-Type -AssemblyName System.Windows.Forms; $n = New-Object System.Windows.Forms.NotifyIcon; $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; $n.ShowBalloonTip(5000, 'biver', '{}', 'Info'); Start-Sleep -Seconds 5; $n.Dispose()",
+            message.replace('\'', "''")
+        );
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command", &script]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let script = format!("display notification \"{}\" with title \"biver\"", message.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut command = Command::new("osascript");
+        command.args(["-e", &script]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["biver", message]);
+        command
+    };
+
+    // Not waited for, the balloon tip stays up for seconds
+    command.stdout(Stdio::null()).stderr(Stdio::null()).logged_spawn().is_ok()
+}
@@ -0,0 +1,78 @@
+use crate::blob_store;
+use crate::repository_paths::RepositoryPaths;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+const SHARD_NAME_LENGTH: usize = 2;
+
+// Names are read from data files, which may come from a remote. One that biver would not give an object is percent
+// encoded, so it cannot point outside the objects directory, and is then simply not found.
+pub fn object_path(objects_dir: &Path, blob_file_name: &str) -> PathBuf {
+    let blob_file_name = if blob_store::is_valid_object_name(blob_file_name) {
+        Cow::Borrowed(blob_file_name)
+    } else {
+        Cow::Owned(utf8_percent_encode(blob_file_name, NON_ALPHANUMERIC).to_string())
+    };
+
+    let split_at = SHARD_NAME_LENGTH.min(blob_file_name.len());
+    let (shard_name, object_name) = blob_file_name.split_at(split_at);
+    objects_dir.join(shard_name).join(object_name)
+}
+
+// Only blobs were ever stored next to data.json, and only names biver gives them can be looked up there
+pub fn legacy_object_path(repository_dir: &Path, blob_file_name: &str) -> Option<PathBuf> {
+    (blob_store::is_valid_object_name(blob_file_name) && blob_store::is_blob_file_name(blob_file_name)).then(|| repository_dir.join(blob_file_name))
+}
+
+pub fn prepare_object_path(object_path: &Path) -> io::Result<()> {
+    if let Some(shard_dir) = object_path.parent() {
+        fs::create_dir_all(shard_dir)?;
+    }
+
+    Ok(())
+}
+
+pub fn list_object_names(repo_paths: &RepositoryPaths) -> io::Result<Vec<String>> {
+    let mut object_names = Vec::new();
+
+    if !fs::exists(&repo_paths.objects_dir)? {
+        return Ok(object_names);
+    }
+
+    for shard_entry in fs::read_dir(&repo_paths.objects_dir)? {
+        let shard_entry = shard_entry?;
+        if !shard_entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let shard_name = shard_entry.file_name().to_string_lossy().to_string();
+
+        for object_entry in fs::read_dir(shard_entry.path())? {
+            let object_name = object_entry?.file_name().to_string_lossy().to_string();
+            object_names.push(shard_name.clone() + &object_name);
+        }
+    }
+
+    object_names.sort();
+
+    Ok(object_names)
+}
+
+pub fn migrate_legacy_layout(repo_paths: &RepositoryPaths) -> io::Result<()> {
+    for entry in fs::read_dir(&repo_paths.repository_dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        if !entry.file_type()?.is_file() || !blob_store::is_blob_file_name(&file_name) {
+            continue;
+        }
+
+        let object_path = object_path(&repo_paths.objects_dir, &file_name);
+        prepare_object_path(&object_path)?;
+        fs::rename(entry.path(), object_path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use crate::diff;
+use image::imageops::FilterType;
+use std::path::Path;
+
+// Previews this many bits apart or fewer look the same at a glance
+pub const DEFAULT_MAX_DISTANCE: u32 = 6;
+
+// dHash of a preview: the image shrunk to 9x8 grey pixels, one bit per pair of horizontal neighbours telling whether
+// the right one is brighter. Recompression, scaling and metadata changes leave it as it is, unlike the bytes.
+pub fn dhash(image_path: &Path) -> Option<u64> {
+    let image = diff::decode_image(image_path)?;
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    Some(hash)
+}
+
+pub fn distance(hash1: u64, hash2: u64) -> u32 {
+    (hash1 ^ hash2).count_ones()
+}
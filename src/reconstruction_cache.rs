@@ -0,0 +1,108 @@
+use crate::effects;
+use crate::env::Env;
+use crate::hash;
+use crate::hash::HashAlgorithm;
+use crate::repository_paths::RepositoryPaths;
+use std::cmp::Reverse;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use std::{fs, io};
+
+pub const RECONSTRUCTION_CACHE_DIR_NAME: &str = "reconstruction_cache";
+
+// Versions reconstructed from patches, so going back and forth between versions skips xdelta3.
+// An entry is named after the patch blob it was reconstructed from, which is named after the content hash, so it
+// cannot go stale while the patch blob exists. Its content is still checked against the version hash before use. Entries of deleted patch blobs are dropped along with the least
+// recently used ones, whose modification times are updated on every use.
+
+pub fn restore(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    patch_blob_file_name: &str,
+    hash_algorithm: HashAlgorithm,
+    expected_hash: u128,
+    destination_path: &Path,
+) -> io::Result<bool> {
+    let entry_path = entry_path(repo_paths, patch_blob_file_name);
+
+    let entry_hash = match File::open(&entry_path) {
+        Ok(entry) => hash::file_hash(env, hash_algorithm, &entry)?,
+        Err(_) => return Ok(false),
+    };
+
+    // An interrupted copy leaves a short entry behind, and the disk or another program may have changed it since
+    if entry_hash != expected_hash {
+        if effects::update_caches(env) {
+            fs::remove_file(&entry_path)?;
+        }
+        tracing::debug!("Dropped the reconstruction cache entry of {}, its content does not match", patch_blob_file_name);
+        return Ok(false);
+    }
+
+    fs::copy(&entry_path, destination_path)?;
+    if effects::update_caches(env) {
+        File::options().write(true).open(&entry_path)?.set_modified(SystemTime::now())?;
+    }
+    tracing::debug!("Restored {} from the reconstruction cache", patch_blob_file_name);
+
+    Ok(true)
+}
+
+pub fn store(env: &Env, repo_paths: &RepositoryPaths, patch_blob_file_name: &str, reconstructed_path: &Path, max_size: u64) -> io::Result<()> {
+    if !effects::update_caches(env) || fs::metadata(reconstructed_path)?.len() > max_size {
+        return Ok(());
+    }
+
+    fs::create_dir_all(cache_dir(repo_paths))?;
+    fs::copy(reconstructed_path, entry_path(repo_paths, patch_blob_file_name))?;
+
+    evict(repo_paths, max_size)
+}
+
+// Cached files with their sizes, most recently used first
+pub fn entries(repo_paths: &RepositoryPaths) -> io::Result<Vec<(PathBuf, u64)>> {
+    let cache_dir = cache_dir(repo_paths);
+    if !fs::exists(&cache_dir)? {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for dir_entry in fs::read_dir(&cache_dir)? {
+        let dir_entry = dir_entry?;
+        let metadata = dir_entry.metadata()?;
+        entries.push((dir_entry.path(), metadata.len(), metadata.modified()?));
+    }
+
+    entries.sort_by_key(|(_, _, modified)| Reverse(*modified));
+
+    Ok(entries.into_iter().map(|(path, size, _)| (path, size)).collect())
+}
+
+fn evict(repo_paths: &RepositoryPaths, max_size: u64) -> io::Result<()> {
+    let mut total_size = 0;
+
+    for (entry_path, size) in entries(repo_paths)? {
+        let patch_blob_exists = match entry_path.file_name().and_then(|n| n.to_str()) {
+            Some(patch_blob_file_name) => fs::exists(repo_paths.blob_path(patch_blob_file_name))?,
+            None => false,
+        };
+
+        if patch_blob_exists && total_size + size <= max_size {
+            total_size += size;
+        } else {
+            fs::remove_file(&entry_path)?;
+            tracing::trace!("Evicted {}", entry_path.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn cache_dir(repo_paths: &RepositoryPaths) -> PathBuf {
+    repo_paths.file_path(RECONSTRUCTION_CACHE_DIR_NAME)
+}
+
+fn entry_path(repo_paths: &RepositoryPaths, patch_blob_file_name: &str) -> PathBuf {
+    cache_dir(repo_paths).join(patch_blob_file_name)
+}
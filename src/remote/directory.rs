@@ -0,0 +1,95 @@
+use crate::biver_result::BiverResult;
+use crate::remote::{DataWriteResult, RemoteBackend, RemoteData};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use xxhash_rust::xxh3::xxh3_128;
+
+// A remote on a mounted or synced drive
+pub struct DirectoryBackend {
+    data_file: PathBuf,
+    objects_dir: PathBuf,
+}
+
+impl DirectoryBackend {
+    pub fn new(path: &Path) -> Self {
+        DirectoryBackend {
+            data_file: path.join("data.json"),
+            objects_dir: path.join("objects"),
+        }
+    }
+}
+
+impl RemoteBackend for DirectoryBackend {
+    fn object_names(&self) -> BiverResult<HashSet<String>> {
+        let mut object_names = HashSet::new();
+
+        if !fs::exists(&self.objects_dir)? {
+            return Ok(object_names);
+        }
+
+        for entry in fs::read_dir(&self.objects_dir)? {
+            object_names.insert(entry?.file_name().to_string_lossy().to_string());
+        }
+
+        Ok(object_names)
+    }
+
+    fn upload_object(&self, object_name: &str, source: &Path) -> BiverResult<()> {
+        fs::create_dir_all(&self.objects_dir)?;
+        copy_atomically(source, &self.objects_dir.join(object_name))?;
+        Ok(())
+    }
+
+    fn download_object(&self, object_name: &str, destination: &Path) -> BiverResult<()> {
+        fs::copy(self.objects_dir.join(object_name), destination)?;
+        Ok(())
+    }
+
+    fn delete_object(&self, object_name: &str) -> BiverResult<()> {
+        match fs::remove_file(self.objects_dir.join(object_name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn read_data(&self) -> BiverResult<Option<RemoteData>> {
+        match fs::read(&self.data_file) {
+            Ok(contents) => Ok(Some(RemoteData {
+                token: content_token(&contents),
+                contents,
+            })),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Without locking there is a short window between the check and the rename, which is acceptable for a shared drive
+    fn write_data(&self, contents: &[u8], expected_token: Option<&str>) -> BiverResult<DataWriteResult> {
+        let current_token = self.read_data()?.map(|data| data.token);
+
+        if current_token.as_deref() != expected_token {
+            return Ok(DataWriteResult::Conflict);
+        }
+
+        if let Some(parent) = self.data_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let temp_path = self.data_file.with_extension("json.tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, &self.data_file)?;
+
+        Ok(DataWriteResult::Ok)
+    }
+}
+
+fn copy_atomically(source: &Path, destination: &Path) -> io::Result<()> {
+    let temp_path = destination.with_extension("tmp");
+    fs::copy(source, &temp_path)?;
+    fs::rename(&temp_path, destination)
+}
+
+fn content_token(contents: &[u8]) -> String {
+    format!("{:032x}", xxh3_128(contents))
+}
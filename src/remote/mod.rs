@@ -0,0 +1,106 @@
+mod directory;
+mod rclone;
+mod s3;
+
+use crate::biver_result::BiverResult;
+use crate::tools::rclone::RcloneEnv;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::{env, io};
+
+const DEFAULT_S3_REGION: &str = "us-east-1";
+const RCLONE_PREFIX: &str = "rclone:";
+
+// A remote holds a copy of data.json and a flat `objects` directory with blobs and version records
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RemoteLocation {
+    Directory {
+        path: PathBuf,
+    },
+    S3 {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        prefix: String,
+    },
+    Rclone {
+        // An rclone path such as gdrive:backups/drawing
+        path: String,
+    },
+}
+
+impl RemoteLocation {
+    pub fn parse(location: &str, s3_endpoint: Option<&str>, s3_region: Option<&str>) -> io::Result<Self> {
+        if let Some(path) = location.strip_prefix(RCLONE_PREFIX) {
+            return Ok(RemoteLocation::Rclone { path: path.to_string() });
+        }
+
+        let Some(bucket_and_prefix) = location.strip_prefix("s3://") else {
+            return Ok(RemoteLocation::Directory {
+                path: std::path::absolute(location)?,
+            });
+        };
+
+        let (bucket, prefix) = bucket_and_prefix.split_once('/').unwrap_or((bucket_and_prefix, ""));
+
+        let region = match s3_region {
+            Some(region) => region.to_string(),
+            None => env::var("AWS_REGION").unwrap_or_else(|_| DEFAULT_S3_REGION.to_string()),
+        };
+
+        let endpoint = match s3_endpoint {
+            Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+            None => format!("https://s3.{}.amazonaws.com", region),
+        };
+
+        Ok(RemoteLocation::S3 {
+            endpoint,
+            region,
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+}
+
+impl Display for RemoteLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteLocation::Directory { path } => write!(f, "{}", path.display()),
+            RemoteLocation::S3 { endpoint, bucket, prefix, .. } => write!(f, "s3://{}/{} ({})", bucket, prefix, endpoint),
+            RemoteLocation::Rclone { path } => write!(f, "{}{}", RCLONE_PREFIX, path),
+        }
+    }
+}
+
+pub struct RemoteData {
+    pub contents: Vec<u8>,
+    // Identifies this state of the remote data file for optimistic concurrency checks
+    pub token: String,
+}
+
+pub enum DataWriteResult {
+    Ok,
+    Conflict,
+}
+
+pub trait RemoteBackend {
+    fn object_names(&self) -> BiverResult<HashSet<String>>;
+    fn upload_object(&self, object_name: &str, source: &Path) -> BiverResult<()>;
+    fn download_object(&self, object_name: &str, destination: &Path) -> BiverResult<()>;
+    fn delete_object(&self, object_name: &str) -> BiverResult<()>;
+    fn read_data(&self) -> BiverResult<Option<RemoteData>>;
+
+    // Only writes if the remote data file is still in the state identified by the token, or still missing if there is no token
+    fn write_data(&self, contents: &[u8], expected_token: Option<&str>) -> BiverResult<DataWriteResult>;
+}
+
+pub fn backend(env: &impl RcloneEnv, location: &RemoteLocation) -> BiverResult<Box<dyn RemoteBackend>> {
+    match location {
+        RemoteLocation::Directory { path } => Ok(Box::new(directory::DirectoryBackend::new(path))),
+        RemoteLocation::S3 { endpoint, region, bucket, prefix } => Ok(Box::new(s3::S3Backend::new(endpoint, region, bucket, prefix)?)),
+        RemoteLocation::Rclone { path } => Ok(Box::new(rclone::RcloneBackend::new(env, path))),
+    }
+}
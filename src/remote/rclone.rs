@@ -0,0 +1,70 @@
+use crate::biver_result::BiverResult;
+use crate::remote::{DataWriteResult, RemoteBackend, RemoteData};
+use crate::tools::rclone;
+use crate::tools::rclone::RcloneEnv;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::xxh3_128;
+
+// A remote on any storage rclone is configured for, such as Google Drive, Dropbox or OneDrive
+pub struct RcloneBackend {
+    rclone_path: Option<PathBuf>,
+    data_file: String,
+    objects_dir: String,
+}
+
+impl RcloneBackend {
+    pub fn new(env: &impl RcloneEnv, path: &str) -> Self {
+        RcloneBackend {
+            rclone_path: env.rclone_path().map(Path::to_path_buf),
+            data_file: rclone::join(path, "data.json"),
+            objects_dir: rclone::join(path, "objects"),
+        }
+    }
+}
+
+impl RcloneEnv for RcloneBackend {
+    fn rclone_path(&self) -> Option<&Path> {
+        self.rclone_path.as_deref()
+    }
+}
+
+impl RemoteBackend for RcloneBackend {
+    fn object_names(&self) -> BiverResult<HashSet<String>> {
+        Ok(rclone::list_file_names(self, &self.objects_dir)?)
+    }
+
+    fn upload_object(&self, object_name: &str, source: &Path) -> BiverResult<()> {
+        Ok(rclone::copy_to(self, source, &rclone::join(&self.objects_dir, object_name))?)
+    }
+
+    fn download_object(&self, object_name: &str, destination: &Path) -> BiverResult<()> {
+        Ok(rclone::copy_from(self, &rclone::join(&self.objects_dir, object_name), destination)?)
+    }
+
+    fn delete_object(&self, object_name: &str) -> BiverResult<()> {
+        Ok(rclone::delete_file(self, &rclone::join(&self.objects_dir, object_name))?)
+    }
+
+    fn read_data(&self) -> BiverResult<Option<RemoteData>> {
+        let contents = rclone::read_file(self, &self.data_file)?;
+
+        Ok(contents.map(|contents| RemoteData {
+            token: format!("{:032x}", xxh3_128(&contents)),
+            contents,
+        }))
+    }
+
+    // Most rclone backends have no conditional writes, so like with a shared drive there is a short window between the check and the write
+    fn write_data(&self, contents: &[u8], expected_token: Option<&str>) -> BiverResult<DataWriteResult> {
+        let current_token = self.read_data()?.map(|data| data.token);
+
+        if current_token.as_deref() != expected_token {
+            return Ok(DataWriteResult::Conflict);
+        }
+
+        rclone::write_file(self, &self.data_file, contents)?;
+
+        Ok(DataWriteResult::Ok)
+    }
+}
@@ -0,0 +1,296 @@
+use crate::biver_result::{BiverResult, error};
+use crate::remote::{DataWriteResult, RemoteBackend, RemoteData};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use ureq::http::{Request, Response, StatusCode};
+use ureq::{Agent, AsSendBody, Body};
+
+// Characters that SigV4 leaves unencoded
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+// Blobs are large and already verified by checksums, so payloads are not hashed for signing
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl Credentials {
+    fn from_env() -> BiverResult<Self> {
+        let (Ok(access_key_id), Ok(secret_access_key)) = (env::var("AWS_ACCESS_KEY_ID"), env::var("AWS_SECRET_ACCESS_KEY")) else {
+            return error("AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set to use an S3 remote");
+        };
+
+        Ok(Credentials {
+            access_key_id,
+            secret_access_key,
+            session_token: env::var("AWS_SESSION_TOKEN").ok().filter(|t| !t.is_empty()),
+        })
+    }
+}
+
+// Uses path-style requests, which AWS as well as MinIO and most other S3-compatible servers accept
+pub struct S3Backend {
+    agent: Agent,
+    origin: String,
+    host: String,
+    base_path: String,
+    region: String,
+    bucket: String,
+    key_prefix: String,
+    credentials: Credentials,
+}
+
+impl S3Backend {
+    pub fn new(endpoint: &str, region: &str, bucket: &str, prefix: &str) -> BiverResult<Self> {
+        let Some((scheme, rest)) = endpoint.split_once("://") else {
+            return error("The S3 endpoint must start with http:// or https://");
+        };
+
+        let (authority, base_path) = match rest.split_once('/') {
+            Some((authority, path)) => (authority, format!("/{}", path.trim_end_matches('/'))),
+            None => (rest, String::new()),
+        };
+
+        let default_port = if scheme == "https" { ":443" } else { ":80" };
+        let host = authority.strip_suffix(default_port).unwrap_or(authority);
+
+        let config = Agent::config_builder().http_status_as_error(false).build();
+
+        Ok(S3Backend {
+            agent: Agent::new_with_config(config),
+            origin: format!("{}://{}", scheme, host),
+            host: host.to_string(),
+            base_path,
+            region: region.to_string(),
+            bucket: bucket.to_string(),
+            key_prefix: if prefix.is_empty() { String::new() } else { format!("{}/", prefix) },
+            credentials: Credentials::from_env()?,
+        })
+    }
+
+    fn object_key(&self, object_name: &str) -> String {
+        format!("{}objects/{}", self.key_prefix, object_name)
+    }
+
+    fn data_key(&self) -> String {
+        format!("{}data.json", self.key_prefix)
+    }
+
+    fn send(&self, method: &str, key: Option<&str>, query: &[(&str, &str)], extra_headers: &[(&str, &str)], body: impl AsSendBody) -> BiverResult<Response<Body>> {
+        let mut path = format!("{}/{}", self.base_path, encode(&self.bucket));
+        if let Some(key) = key {
+            let encoded_segments: Vec<String> = key.split('/').map(encode).collect();
+            path = format!("{}/{}", path, encoded_segments.join("/"));
+        }
+
+        let mut query_pairs: Vec<(String, String)> = query.iter().map(|(name, value)| (encode(name), encode(value))).collect();
+        query_pairs.sort();
+        let canonical_query = query_pairs.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("&");
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let mut signed_headers = vec![
+            ("host", self.host.clone()),
+            ("x-amz-content-sha256", UNSIGNED_PAYLOAD.to_string()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(session_token) = &self.credentials.session_token {
+            signed_headers.push(("x-amz-security-token", session_token.clone()));
+        }
+        signed_headers.sort();
+
+        let canonical_headers: String = signed_headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+        let signed_header_names = signed_headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method, path, canonical_query, canonical_headers, signed_header_names, UNSIGNED_PAYLOAD
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{:x}", amz_date, scope, Sha256::digest(canonical_request.as_bytes()));
+
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac_sha256(format!("AWS4{}", self.credentials.secret_access_key).as_bytes(), date.as_bytes()),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+        let signature: String = hmac_sha256(&signing_key, string_to_sign.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.credentials.access_key_id, scope, signed_header_names, signature
+        );
+
+        let url = if canonical_query.is_empty() {
+            format!("{}{}", self.origin, path)
+        } else {
+            format!("{}{}?{}", self.origin, path, canonical_query)
+        };
+
+        let mut request = Request::builder().method(method).uri(url).header("authorization", authorization);
+        for (name, value) in signed_headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        for (name, value) in extra_headers {
+            request = request.header(*name, *value);
+        }
+
+        let Ok(request) = request.body(body) else {
+            return error(format!("Invalid S3 request for {}", key.unwrap_or(&self.bucket)));
+        };
+
+        Ok(self.agent.run(request)?)
+    }
+}
+
+impl RemoteBackend for S3Backend {
+    fn object_names(&self) -> BiverResult<HashSet<String>> {
+        let objects_prefix = self.object_key("");
+        let mut object_names = HashSet::new();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", objects_prefix.as_str())];
+            if let Some(continuation_token) = &continuation_token {
+                query.push(("continuation-token", continuation_token));
+            }
+
+            let mut response = self.send("GET", None, &query, &[], ())?;
+            let listing = read_body(&mut response)?;
+            check_status(&response, "list objects", &listing)?;
+            let listing = String::from_utf8_lossy(&listing);
+
+            for key in xml_elements(&listing, "Key") {
+                if let Some(object_name) = key.strip_prefix(&objects_prefix) {
+                    object_names.insert(object_name.to_string());
+                }
+            }
+
+            continuation_token = xml_elements(&listing, "NextContinuationToken").into_iter().next();
+
+            if xml_elements(&listing, "IsTruncated").first().map(String::as_str) != Some("true") || continuation_token.is_none() {
+                return Ok(object_names);
+            }
+        }
+    }
+
+    fn upload_object(&self, object_name: &str, source: &Path) -> BiverResult<()> {
+        let mut response = self.send("PUT", Some(&self.object_key(object_name)), &[], &[], File::open(source)?)?;
+        let body = read_body(&mut response)?;
+        check_status(&response, &format!("upload {}", object_name), &body)
+    }
+
+    fn download_object(&self, object_name: &str, destination: &Path) -> BiverResult<()> {
+        let mut response = self.send("GET", Some(&self.object_key(object_name)), &[], &[], ())?;
+
+        if response.status() != StatusCode::OK {
+            let body = read_body(&mut response)?;
+            return check_status(&response, &format!("download {}", object_name), &body);
+        }
+
+        io::copy(&mut response.body_mut().as_reader(), &mut File::create(destination)?)?;
+
+        Ok(())
+    }
+
+    fn delete_object(&self, object_name: &str) -> BiverResult<()> {
+        let mut response = self.send("DELETE", Some(&self.object_key(object_name)), &[], &[], ())?;
+        let body = read_body(&mut response)?;
+        check_status(&response, &format!("delete {}", object_name), &body)
+    }
+
+    fn read_data(&self) -> BiverResult<Option<RemoteData>> {
+        let mut response = self.send("GET", Some(&self.data_key()), &[], &[], ())?;
+        let contents = read_body(&mut response)?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        check_status(&response, "read data", &contents)?;
+
+        let Some(token) = response.headers().get("etag").and_then(|etag| etag.to_str().ok()) else {
+            return error("The S3 server did not return an ETag for the data file");
+        };
+
+        Ok(Some(RemoteData {
+            token: token.to_string(),
+            contents,
+        }))
+    }
+
+    fn write_data(&self, contents: &[u8], expected_token: Option<&str>) -> BiverResult<DataWriteResult> {
+        let condition = match expected_token {
+            Some(token) => ("if-match", token),
+            None => ("if-none-match", "*"),
+        };
+
+        let mut response = self.send("PUT", Some(&self.data_key()), &[], &[condition], contents)?;
+        let body = read_body(&mut response)?;
+
+        // 409 is returned when another conditional write to the same key is in progress
+        if matches!(response.status(), StatusCode::PRECONDITION_FAILED | StatusCode::CONFLICT) {
+            return Ok(DataWriteResult::Conflict);
+        }
+
+        check_status(&response, "write data", &body)?;
+
+        Ok(DataWriteResult::Ok)
+    }
+}
+
+fn read_body(response: &mut Response<Body>) -> BiverResult<Vec<u8>> {
+    Ok(response.body_mut().with_config().limit(u64::MAX).read_to_vec()?)
+}
+
+fn check_status(response: &Response<Body>, operation: &str, body: &[u8]) -> BiverResult<()> {
+    if response.status().is_success() {
+        return Ok(());
+    }
+
+    let message = String::from_utf8_lossy(body);
+    let code = xml_elements(&message, "Code").into_iter().next().unwrap_or_default();
+
+    error(format!("S3 {} failed: {} {}", operation, response.status(), code))
+}
+
+// S3 responses are simple enough that the few needed values can be picked out without an XML parser
+fn xml_elements(xml: &str, name: &str) -> Vec<String> {
+    let open_tag = format!("<{}>", name);
+    let close_tag = format!("</{}>", name);
+
+    xml.split(&open_tag)
+        .skip(1)
+        .filter_map(|rest| rest.split_once(&close_tag).map(|(value, _)| value))
+        .map(|value| {
+            value
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&")
+        })
+        .collect()
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, UNRESERVED).to_string()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
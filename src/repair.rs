@@ -0,0 +1,168 @@
+use crate::hash::{HashAlgorithm, HashEnv};
+use crate::repository_data::{ContentBlob, Head, RepositoryData, Version};
+use crate::repository_paths::RepositoryPaths;
+use crate::version_id::VersionId;
+use crate::{blob_store, hash, migrations, object_store, repository_io};
+use chrono::{DateTime, Local};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+pub struct DataBackup {
+    pub path: PathBuf,
+    pub modified: DateTime<Local>,
+    pub repo_data: RepositoryData,
+}
+
+pub struct RebuiltData {
+    pub repo_data: RepositoryData,
+    pub lost_version_count: usize,
+}
+
+pub fn read_valid_data(data_file_path: &Path) -> Option<RepositoryData> {
+    let data_file_contents = fs::read(data_file_path).ok()?;
+    let repo_data = migrations::parse_data(&data_file_contents).ok()?;
+
+    if !repo_data.valid() {
+        return None;
+    }
+
+    Some(repo_data)
+}
+
+// Backups rotate from data_backup1 towards data_backup5, so the first valid one is the freshest
+pub fn freshest_valid_backup(repo_paths: &RepositoryPaths) -> io::Result<Option<DataBackup>> {
+    for backup_path in repository_io::data_backup_paths(repo_paths) {
+        if !fs::exists(&backup_path)? {
+            continue;
+        }
+
+        if let Some(repo_data) = read_valid_data(&backup_path) {
+            let modified = fs::metadata(&backup_path)?.modified()?.into();
+            return Ok(Some(DataBackup {
+                path: backup_path,
+                modified,
+                repo_data,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+pub fn rebuild_from_version_records(env: &impl HashEnv, repo_paths: &RepositoryPaths) -> io::Result<Option<RebuiltData>> {
+    let mut records = Vec::new();
+
+    for object_name in object_store::list_object_names(repo_paths)? {
+        if !blob_store::is_version_record_file_name(&object_name) {
+            continue;
+        }
+
+        let record_contents = fs::read(repo_paths.blob_path(&object_name))?;
+        if let Ok(version) = serde_json::from_slice::<Version>(&record_contents) {
+            records.push(version);
+        }
+    }
+
+    let record_count = records.len();
+
+    let mut versions = Vec::new();
+    for mut version in records {
+        if let Some(preview_blob_file_name) = &version.preview_blob_file_name
+            && !fs::exists(repo_paths.blob_path(preview_blob_file_name))?
+        {
+            version.preview_blob_file_name = None;
+        }
+
+        let mut all_blobs_exist = true;
+        for blob_file_name in version.blob_file_names() {
+            all_blobs_exist &= fs::exists(repo_paths.blob_path(blob_file_name))?;
+        }
+
+        if all_blobs_exist {
+            versions.push(version);
+        }
+    }
+
+    let Some(root_id) = versions.iter().filter(|v| v.is_root()).min_by_key(|v| v.creation_time).map(|v| v.id) else {
+        return Ok(None);
+    };
+
+    let mut connected_ids = HashSet::from([root_id]);
+    loop {
+        let newly_connected: Vec<VersionId> = versions
+            .iter()
+            .filter(|v| !connected_ids.contains(&v.id) && v.parent.is_some_and(|p| connected_ids.contains(&p)))
+            .map(|v| v.id)
+            .collect();
+
+        if newly_connected.is_empty() {
+            break;
+        }
+
+        connected_ids.extend(newly_connected);
+    }
+
+    versions.retain(|v| connected_ids.contains(&v.id));
+
+    let parent_ids: HashSet<VersionId> = versions.iter().filter_map(|v| v.parent).collect();
+    let mut leaves: Vec<&Version> = versions.iter().filter(|v| !parent_ids.contains(&v.id)).collect();
+    leaves.sort_by_key(|v| Reverse(v.creation_time));
+
+    let mut branches = HashMap::new();
+    let mut head_branch = None;
+    for leaf in leaves {
+        let branch = (1..)
+            .map(|n| if n == 1 { leaf.nickname.clone() } else { format!("{}-{}", leaf.nickname, n) })
+            .find(|name| !branches.contains_key(name))
+            .expect("There should always be a free branch name");
+
+        // Leaves are sorted newest first, so the head ends up on the most recent work
+        head_branch.get_or_insert_with(|| branch.clone());
+        branches.insert(branch, leaf.id);
+    }
+
+    let hash_algorithm = detect_hash_algorithm(env, repo_paths, &versions)?;
+
+    // Rebuilt data has no branch metadata, the migrations fill in what they can
+    let mut repo_data = RepositoryData {
+        format_version: 0,
+        head: Head::Branch(head_branch.expect("The root version guarantees at least one leaf")),
+        branches,
+        versions,
+        blob_checksums: HashMap::new(),
+        versioned_file_name: repo_paths.versioned_file_name(),
+        protected_branches: BTreeSet::new(),
+        orphaned_versions: Vec::new(),
+        pinned_versions: BTreeSet::new(),
+        patch_limit: None,
+        hash_algorithm,
+        branch_metadata: BTreeMap::new(),
+    };
+    migrations::migrate(&mut repo_data);
+
+    Ok(Some(RebuiltData {
+        lost_version_count: record_count - repo_data.versions.len(),
+        repo_data,
+    }))
+}
+
+// Version records do not say which algorithm their hashes are from, but a full blob is the content its version hashes to
+fn detect_hash_algorithm(env: &impl HashEnv, repo_paths: &RepositoryPaths, versions: &[Version]) -> io::Result<HashAlgorithm> {
+    let Some((version, full_blob_file_name)) = versions.iter().find_map(|v| match &v.content_blob {
+        ContentBlob::Full { full_blob_file_name } => Some((v, full_blob_file_name)),
+        ContentBlob::Patch { .. } => None,
+    }) else {
+        return Ok(HashAlgorithm::default());
+    };
+
+    let full_blob = File::open(repo_paths.blob_path(full_blob_file_name))?;
+
+    if hash::file_hash(env, HashAlgorithm::Blake3, &full_blob)? == version.versioned_file_hash {
+        Ok(HashAlgorithm::Blake3)
+    } else {
+        Ok(HashAlgorithm::Xxh3)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::known_file_types;
+use crate::repository_data::{Head, RepositoryData, Version};
+use crate::repository_paths::RepositoryPaths;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::cmp::Reverse;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; background: #fafafa; }
+h1 { margin-bottom: 0; }
+.subtitle { color: #777; margin-top: 0.2em; }
+table { border-collapse: collapse; width: 100%; }
+td, th { padding: 0.6em; border-bottom: 1px solid #ddd; text-align: left; vertical-align: top; }
+img { max-width: 240px; max-height: 240px; box-shadow: 0 1px 4px rgba(0, 0, 0, 0.3); }
+.id { color: #999; font-family: monospace; }
+.nickname { font-weight: bold; }
+.branch { display: inline-block; background: #d6f0f5; color: #0b6577; border-radius: 3px; padding: 0 0.4em; margin-right: 0.3em; }
+.head { background: #f3dcf3; color: #7a1f7a; }
+.description { white-space: pre-wrap; }
+";
+
+pub fn html_report(repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> io::Result<String> {
+    let title = repo_paths.versioned_file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+    let mut versions: Vec<_> = repo_data.versions.iter().collect();
+    versions.sort_by_key(|v| Reverse(v.creation_time));
+
+    let mut branches: Vec<_> = repo_data.branches.iter().collect();
+    branches.sort_by_key(|(name, _)| name.as_str());
+
+    let mut html = String::new();
+
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        escape(&title),
+        STYLE
+    );
+    let _ = writeln!(html, "<h1>{}</h1>", escape(&title));
+    let _ = writeln!(
+        html,
+        "<p class=\"subtitle\">{} versions, {} branches, generated {}</p>",
+        versions.len(),
+        branches.len(),
+        chrono::Local::now().format("%Y-%m-%d %H:%M")
+    );
+
+    let _ = writeln!(html, "<h2>Branches</h2>\n<table>\n<tr><th>Branch</th><th>Latest version</th><th>Versions</th></tr>");
+    // A branch whose leaf is missing from damaged data is left out, report() names it
+    for (branch, leaf_id) in &branches {
+        let Some(leaf) = repo_data.version(**leaf_id) else {
+            continue;
+        };
+        let _ = writeln!(
+            html,
+            "<tr><td><span class=\"branch\">{}</span></td><td><span class=\"nickname\">{}</span> <span class=\"id\">{}</span></td><td>{}</td></tr>",
+            escape(branch),
+            escape(&leaf.nickname),
+            leaf.id.bs58(),
+            repo_data.iter_version_and_ancestors(**leaf_id).count()
+        );
+    }
+    let _ = writeln!(html, "</table>");
+
+    let _ = writeln!(html, "<h2>Timeline</h2>\n<table>\n<tr><th>Preview</th><th>Version</th><th>Description</th></tr>");
+    for version in versions {
+        let thumbnail = thumbnail_html(repo_paths, version)?;
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td class=\"description\">{}</td></tr>",
+            thumbnail,
+            version_html(repo_data, version),
+            escape(&version.description)
+        );
+    }
+    let _ = writeln!(html, "</table>\n</body>\n</html>");
+
+    Ok(html)
+}
+
+fn version_html(repo_data: &RepositoryData, version: &Version) -> String {
+    let mut html = String::new();
+
+    let creation_time = version.creation_time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S");
+    let _ = write!(
+        html,
+        "<span class=\"nickname\">{}</span><br><span class=\"id\">{}</span><br>{}<br>",
+        escape(&version.nickname),
+        version.id.bs58(),
+        creation_time
+    );
+
+    // Not head_version(), which panics when the head branch is one of those left out
+    let head_version_id = match &repo_data.head {
+        Head::Branch(branch) => repo_data.branches.get(branch).copied(),
+        Head::Version(version_id) => Some(*version_id),
+    };
+    if head_version_id == Some(version.id) {
+        let _ = write!(html, "<span class=\"branch head\">HEAD</span>");
+    }
+
+    let mut branches_here: Vec<_> = repo_data.branches.iter().filter(|(_, id)| **id == version.id).map(|(b, _)| b.as_str()).collect();
+    branches_here.sort();
+    for branch in branches_here {
+        let _ = write!(html, "<span class=\"branch\">{}</span>", escape(branch));
+    }
+
+    if let Some(parent) = version.parent.and_then(|id| repo_data.version(id)) {
+        let _ = write!(html, "<br>after <span class=\"nickname\">{}</span>", escape(&parent.nickname));
+    }
+
+    html
+}
+
+fn thumbnail_html(repo_paths: &RepositoryPaths, version: &Version) -> io::Result<String> {
+    let Some(preview_blob_file_name) = &version.preview_blob_file_name else {
+        return Ok(String::new());
+    };
+
+    let preview_blob_file_path = repo_paths.blob_path(preview_blob_file_name);
+    if !preview_blob_file_path.exists() {
+        return Ok(String::new());
+    }
+
+    let preview = fs::read(preview_blob_file_path)?;
+
+    Ok(format!(
+        "<img src=\"data:{};base64,{}\">",
+        known_file_types::preview_mime_type(&preview),
+        STANDARD.encode(&preview)
+    ))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
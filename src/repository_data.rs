@@ -1,20 +1,121 @@
+use crate::hash::{Digest, HashAlgorithm};
 use crate::version_id::VersionId;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RepositoryData {
+    // See migrations
+    #[serde(default)]
+    pub format_version: u32,
     pub head: Head,
     pub branches: HashMap<String, VersionId>,
     pub versions: Vec<Version>,
+    #[serde(default)]
+    pub blob_checksums: HashMap<String, u128>,
+    #[serde(default)]
+    pub versioned_file_name: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub protected_branches: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub orphaned_versions: Vec<OrphanedVersion>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub pinned_versions: BTreeSet<VersionId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch_limit: Option<PatchLimit>,
+    // Of versioned_file_hash and the blob checksums, which keep their names whatever the algorithm
+    #[serde(default, skip_serializing_if = "HashAlgorithm::is_xxh3")]
+    pub hash_algorithm: HashAlgorithm,
+    // Branches created before this was recorded have none
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub branch_metadata: BTreeMap<String, BranchMetadata>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BranchMetadata {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creation_time: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub creator: String,
+}
+
+pub enum DataProblem {
+    RootCount(usize),
+    MissingParent { version_id: VersionId, parent_id: VersionId },
+    MissingHeadBranch(String),
+    MissingHeadVersion(VersionId),
+    MissingBranchLeaf { branch: String, version_id: VersionId },
+    VersionOutsideBranches(VersionId),
+}
+
+impl Display for DataProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataProblem::RootCount(0) => write!(f, "No version is the root of the history"),
+            DataProblem::RootCount(count) => write!(f, "{} versions are roots of the history, there must be exactly one", count),
+            DataProblem::MissingParent { version_id, parent_id } => write!(f, "Version {} has parent {}, which does not exist", version_id.bs58(), parent_id.bs58()),
+            DataProblem::MissingHeadBranch(branch) => write!(f, "Head is on branch {}, which does not exist", branch),
+            DataProblem::MissingHeadVersion(version_id) => write!(f, "Head is on version {}, which does not exist", version_id.bs58()),
+            DataProblem::MissingBranchLeaf { branch, version_id } => write!(f, "Branch {} points at version {}, which does not exist", branch, version_id.bs58()),
+            DataProblem::VersionOutsideBranches(version_id) => write!(f, "Version {} is not on any branch", version_id.bs58()),
+        }
+    }
+}
+
+// When a version is stored in full although a patch would do. Without a limit, patches are stored as long as they stay small.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchLimit {
+    // Versions in a row stored as patches
+    Count(u32),
+    // The patches since the last version stored in full add up to its size
+    Auto,
+}
+
+impl Display for PatchLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchLimit::Count(count) => write!(f, "{}", count),
+            PatchLimit::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+// A head version replaced by a recoverable amend. It is no longer part of the history, but its blobs are kept until gc expires it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrphanedVersion {
+    pub version: Version,
+    pub branch: String,
+    pub replaced_by: VersionId,
+    pub orphaned_time: DateTime<Utc>,
 }
 
 impl RepositoryData {
+    pub fn is_protected(&self, branch: &str) -> bool {
+        self.protected_branches.contains(branch)
+    }
+
+    pub fn is_pinned(&self, version_id: VersionId) -> bool {
+        self.pinned_versions.contains(&version_id)
+    }
+
+    pub fn nickname_taken(&self, nickname: &str) -> bool {
+        self.versions_and_orphans().any(|v| v.nickname.eq_ignore_ascii_case(nickname))
+    }
+
     pub fn version(&self, id: VersionId) -> Option<&Version> {
         self.versions.iter().find(|v| v.id == id)
     }
 
+    pub fn version_mut(&mut self, id: VersionId) -> Option<&mut Version> {
+        self.versions.iter_mut().find(|v| v.id == id)
+    }
+
     pub fn head_version(&self) -> &Version {
         let head_version = match &self.head {
             Head::Branch(branch) => {
@@ -28,38 +129,64 @@ impl RepositoryData {
     }
 
     pub fn valid(&self) -> bool {
-        let there_is_exactly_one_root = self.versions.iter().filter(|v| v.parent.is_none()).count() == 1;
+        self.problems().is_empty()
+    }
+
+    // Broken invariants, which write_data refuses to save and doctor reports
+    pub fn problems(&self) -> Vec<DataProblem> {
+        let mut problems = Vec::new();
+
+        let root_count = self.versions.iter().filter(|v| v.parent.is_none()).count();
+        if root_count != 1 {
+            problems.push(DataProblem::RootCount(root_count));
+        }
 
-        let all_parent_references_are_valid = self.versions.iter().all(|v| {
-            if let Some(parent) = &v.parent {
-                self.versions.iter().any(|v2| v2.id == *parent)
-            } else {
-                true
+        for version in &self.versions {
+            if let Some(parent_id) = version.parent
+                && self.version(parent_id).is_none()
+            {
+                problems.push(DataProblem::MissingParent {
+                    version_id: version.id,
+                    parent_id,
+                });
             }
-        });
+        }
 
-        let head_reference_is_valid = match &self.head {
-            Head::Branch(branch) => self.branches.contains_key(branch),
-            Head::Version(version_id) => self.versions.iter().any(|v| v.id == *version_id),
-        };
+        match &self.head {
+            Head::Branch(branch) if !self.branches.contains_key(branch) => problems.push(DataProblem::MissingHeadBranch(branch.clone())),
+            Head::Version(version_id) if self.version(*version_id).is_none() => problems.push(DataProblem::MissingHeadVersion(*version_id)),
+            _ => {}
+        }
 
-        let all_branches_reference_valid_versions = self.branches.values().all(|branch_version_id| self.versions.iter().any(|v| v.id == *branch_version_id));
+        let mut branches: Vec<_> = self.branches.iter().collect();
+        branches.sort_by_key(|(name, _)| name.as_str());
 
-        let all_versions_belong_to_branches = {
-            let mut versions_belonging_to_branches = HashSet::new();
+        for (branch, leaf_id) in &branches {
+            if self.version(**leaf_id).is_none() {
+                problems.push(DataProblem::MissingBranchLeaf {
+                    branch: branch.to_string(),
+                    version_id: **leaf_id,
+                });
+            }
+        }
 
-            for branch_leaf_id in self.branches.values() {
-                for v in self.iter_version_and_ancestors(*branch_leaf_id) {
-                    if !versions_belonging_to_branches.insert(v.id) {
-                        break;
-                    }
+        let mut versions_belonging_to_branches = HashSet::new();
+
+        for (_, leaf_id) in &branches {
+            for v in self.iter_version_and_ancestors(**leaf_id) {
+                if !versions_belonging_to_branches.insert(v.id) {
+                    break;
                 }
             }
+        }
 
-            versions_belonging_to_branches.len() == self.versions.len()
-        };
+        for version in &self.versions {
+            if !versions_belonging_to_branches.contains(&version.id) {
+                problems.push(DataProblem::VersionOutsideBranches(version.id));
+            }
+        }
 
-        there_is_exactly_one_root && all_parent_references_are_valid && head_reference_is_valid && all_branches_reference_valid_versions && all_versions_belong_to_branches
+        problems
     }
 
     pub fn iter_version_and_ancestors(&'_ self, version_id: VersionId) -> impl Iterator<Item = &'_ Version> {
@@ -81,6 +208,41 @@ impl RepositoryData {
     pub fn branch_leaf(&self, branch: &str) -> Option<&Version> {
         self.branches.get(branch).and_then(|version_id| self.version(*version_id))
     }
+
+    pub fn referenced_blob_file_names(&self) -> HashSet<&str> {
+        self.versions_and_orphans().flat_map(|v| v.blob_file_names()).collect()
+    }
+
+    // Orphaned versions keep their blobs and version records referenced
+    pub fn versions_and_orphans(&self) -> impl Iterator<Item = &Version> {
+        self.versions.iter().chain(self.orphaned_versions.iter().map(|o| &o.version))
+    }
+
+    pub fn remove_versions_and_reparent(&mut self, removed_ids: &HashSet<VersionId>) -> Vec<Version> {
+        let new_parents: HashMap<VersionId, Option<VersionId>> = self
+            .versions
+            .iter()
+            .filter(|v| !removed_ids.contains(&v.id))
+            .filter_map(|v| {
+                let parent_id = v.parent?;
+                if !removed_ids.contains(&parent_id) {
+                    return None;
+                }
+                let new_parent_id = self.iter_version_and_ancestors(parent_id).map(|a| a.id).find(|id| !removed_ids.contains(id));
+                Some((v.id, new_parent_id))
+            })
+            .collect();
+
+        for version in self.versions.iter_mut() {
+            if let Some(new_parent_id) = new_parents.get(&version.id) {
+                version.parent = *new_parent_id;
+            }
+        }
+
+        let (removed, kept) = std::mem::take(&mut self.versions).into_iter().partition(|v| removed_ids.contains(&v.id));
+        self.versions = kept;
+        removed
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -88,21 +250,202 @@ pub struct Version {
     pub id: VersionId,
     pub creation_time: DateTime<Utc>,
     pub nickname: String,
+    // Set when the nickname was chosen with rename version rather than generated
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub custom_nickname: bool,
+    #[serde(default)]
+    pub author: String,
     pub versioned_file_length: u64,
-    pub versioned_file_xxh3_128: u128,
+    // From the algorithm of the repository, the name in the data file is from when xxh3 was the only one
+    #[serde(rename = "versioned_file_xxh3_128")]
+    pub versioned_file_hash: u128,
+    // With BLAKE3, the full digest versioned_file_hash is the first half of. Versions committed before it was kept get it
+    // from verify.
+    #[serde(default, rename = "versioned_file_blake3", skip_serializing_if = "Option::is_none")]
+    pub versioned_file_digest: Option<Digest>,
     pub description: String,
     pub parent: Option<VersionId>,
     pub content_blob: ContentBlob,
     pub preview_blob_file_name: Option<String>,
+    // dHash of the preview, for finding versions that look the same
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perceptual_hash: Option<u64>,
+    #[serde(default)]
+    pub modified_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub unix_mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<Note>,
+    // Oldest first, the last one is the current state
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reviews: Vec<Review>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<VersionSignature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<ForkOrigin>,
+}
+
+// The repository and version the initial version of a forked repository was taken from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkOrigin {
+    #[serde(with = "lossless_path")]
+    pub versioned_file: PathBuf,
+    pub version_id: VersionId,
+}
+
+// A path that is not valid Unicode, which Linux and Windows both allow, is kept as its bytes or UTF-16 code units so
+// that the origin still names the file. Read on the other platform, it gets replacement characters.
+mod lossless_path {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::path::{Path, PathBuf};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum StoredPath {
+        Unicode(String),
+        Unix { unix: Vec<u8> },
+        Windows { windows: Vec<u16> },
+    }
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        stored_path(path).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        Ok(match StoredPath::deserialize(deserializer)? {
+            StoredPath::Unicode(path) => PathBuf::from(path),
+            StoredPath::Unix { unix } => unix_path(unix),
+            StoredPath::Windows { windows } => windows_path(windows),
+        })
+    }
+
+    fn stored_path(path: &Path) -> StoredPath {
+        if let Some(path) = path.to_str() {
+            return StoredPath::Unicode(path.to_string());
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            StoredPath::Unix {
+                unix: path.as_os_str().as_bytes().to_vec(),
+            }
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::ffi::OsStrExt;
+            StoredPath::Windows {
+                windows: path.as_os_str().encode_wide().collect(),
+            }
+        }
+
+        #[cfg(not(any(unix, windows)))]
+        StoredPath::Unicode(path.to_string_lossy().into_owned())
+    }
+
+    #[cfg(unix)]
+    fn unix_path(bytes: Vec<u8>) -> PathBuf {
+        use std::os::unix::ffi::OsStringExt;
+        PathBuf::from(std::ffi::OsString::from_vec(bytes))
+    }
+
+    #[cfg(not(unix))]
+    fn unix_path(bytes: Vec<u8>) -> PathBuf {
+        PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    #[cfg(windows)]
+    fn windows_path(units: Vec<u16>) -> PathBuf {
+        use std::os::windows::ffi::OsStringExt;
+        PathBuf::from(std::ffi::OsString::from_wide(&units))
+    }
+
+    #[cfg(not(windows))]
+    fn windows_path(units: Vec<u16>) -> PathBuf {
+        PathBuf::from(String::from_utf16_lossy(&units))
+    }
+}
+
+// A minisign signature over what identifies a version and its content, with the public key it can be checked against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionSignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+// Review feedback on a version. Notes are only ever added, unlike the description they keep their history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub creation_time: DateTime<Utc>,
+    #[serde(default)]
+    pub author: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Review {
+    pub state: ReviewState,
+    pub creation_time: DateTime<Utc>,
+    #[serde(default)]
+    pub reviewer: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewState {
+    Submitted,
+    Approved,
+    Rejected,
+}
+
+impl Display for ReviewState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReviewState::Submitted => write!(f, "submitted"),
+            ReviewState::Approved => write!(f, "approved"),
+            ReviewState::Rejected => write!(f, "rejected"),
+        }
+    }
 }
 
 impl Version {
     pub fn is_root(&self) -> bool {
         self.parent.is_none()
     }
+
+    pub fn review(&self) -> Option<&Review> {
+        self.reviews.last()
+    }
+
+    // The first line of the description, which is all that fits in a version list
+    pub fn summary(&self) -> &str {
+        self.description.lines().next().unwrap_or_default()
+    }
+
+    pub fn blob_file_names(&self) -> Vec<&str> {
+        let mut blob_file_names = match &self.content_blob {
+            ContentBlob::Full { full_blob_file_name } => vec![full_blob_file_name.as_str()],
+            ContentBlob::Patch {
+                base_blob_file_name,
+                patch_blob_file_name,
+                ..
+            } => vec![base_blob_file_name.as_str(), patch_blob_file_name.as_str()],
+        };
+
+        if let Some(preview_blob_file_name) = &self.preview_blob_file_name {
+            blob_file_names.push(preview_blob_file_name);
+        }
+
+        blob_file_names
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Head {
     Branch(String),
     Version(VersionId),
@@ -117,7 +460,14 @@ impl Head {
     }
 }
 
+// Uncommitted changes stashed by checkout --autostash, offered back when head returns to where they were made
 #[derive(Debug, Serialize, Deserialize)]
+pub struct AutostashEntry {
+    pub head: Head,
+    pub version_id: VersionId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ContentBlob {
     Full {
         full_blob_file_name: String,
@@ -1,37 +1,63 @@
+use crate::backup::BackupManifest;
+use crate::bisect::BisectState;
+use crate::editing_lock::EditingLock;
 use crate::env::Env;
-use crate::repository_data::{ContentBlob, RepositoryData};
+use crate::nickname::NicknameWords;
+use crate::remote::RemoteLocation;
+use crate::repository_data::{AutostashEntry, ContentBlob, RepositoryData, Version};
 use crate::repository_paths::RepositoryPaths;
-use crate::{image_magick, xdelta3};
-use std::path::Path;
-use std::time::{Duration, SystemTime};
+use crate::tools::xdelta3;
+use crate::{blob_store, logging, migrations, object_store};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use std::{fs, io};
 
+// Remotes are kept out of data.json because that file is what gets pushed and pulled
+pub const REMOTES_FILE_NAME: &str = "remotes.json";
+pub const BISECT_FILE_NAME: &str = "bisect.json";
+pub const AUTOSTASH_FILE_NAME: &str = "autostash.json";
+pub const EDITING_LOCK_FILE_NAME: &str = "editing_lock.json";
+pub const NICKNAME_WORDS_FILE_NAME: &str = "nickname_words.json";
+pub const BACKUP_MANIFEST_FILE_NAME: &str = "backup_manifest.json";
+const STAGED_DATA_FILE_NAME: &str = "data.tmp";
+
 pub enum RepositoryDataResult {
-    Initialized(RepositoryData),
-    NotInitialized,
+    Initialized(Box<RepositoryData>),
+    NotInitialized { detached_versioned_files: Vec<PathBuf> },
 }
 
 pub fn read_data(repository_paths: &RepositoryPaths) -> io::Result<RepositoryDataResult> {
+    logging::attach_repository(repository_paths);
+
     if !repository_paths.data_file.exists() {
-        return Ok(RepositoryDataResult::NotInitialized);
+        return Ok(RepositoryDataResult::NotInitialized {
+            detached_versioned_files: detached_versioned_files(repository_paths)?,
+        });
     }
 
     let data_file_contents = fs::read(&repository_paths.data_file)?;
-    let repository_data = serde_json::from_slice(&data_file_contents)?;
+    tracing::trace!("Read {} ({} bytes)", repository_paths.data_file.display(), data_file_contents.len());
+    let repository_data = migrations::parse_data(&data_file_contents)?;
 
-    Ok(RepositoryDataResult::Initialized(repository_data))
+    Ok(RepositoryDataResult::Initialized(Box::new(repository_data)))
 }
 
 pub fn write_data(paths: &RepositoryPaths, data: &RepositoryData) -> io::Result<()> {
-    if !data.valid() {
-        panic!("Repository data is not valid: {:#?}", data);
+    // Refusing to write keeps the last good data file in place, whatever operation produced the broken data
+    if let Some(problem) = data.problems().first() {
+        return Err(io::Error::other(format!(
+            "Repository data is not valid, nothing was written: {}. Run doctor to check the repository.",
+            problem
+        )));
     }
 
-    let backup1 = paths.file_path("data_backup1.json");
-    let backup2 = paths.file_path("data_backup2.json");
-    let backup3 = paths.file_path("data_backup3.json");
-    let backup4 = paths.file_path("data_backup4.json");
-    let backup5 = paths.file_path("data_backup5.json");
+    // Only commands that write the repository move blobs out of the legacy layout, reading one leaves it as it is
+    object_store::migrate_legacy_layout(paths)?;
+
+    let [backup1, backup2, backup3, backup4, backup5] = data_backup_paths(paths);
 
     rotate_backup(&backup4, &backup5, Duration::from_hours(24))?;
     rotate_backup(&backup3, &backup4, Duration::from_hours(5))?;
@@ -39,41 +65,180 @@ pub fn write_data(paths: &RepositoryPaths, data: &RepositoryData) -> io::Result<
     rotate_backup(&backup1, &backup2, Duration::from_mins(5))?;
     rotate_backup(&paths.data_file, &backup1, Duration::from_secs(10))?;
 
+    // Renamed into place, so an interrupted write cannot leave a truncated data file behind
     let data_file_content = serde_json::to_string_pretty(data)?;
-    fs::write(&paths.data_file, data_file_content)?;
+    let staged_data_file = paths.file_path(STAGED_DATA_FILE_NAME);
+    fs::write(&staged_data_file, &data_file_content)?;
+    fs::rename(&staged_data_file, &paths.data_file)?;
+    tracing::trace!("Wrote {} ({} bytes)", paths.data_file.display(), data_file_content.len());
 
     Ok(())
 }
 
-pub fn store_version_content(env: &Env, repo_paths: &RepositoryPaths, content_blob: &ContentBlob, content_to_store_path: &Path) -> io::Result<()> {
-    match content_blob {
-        ContentBlob::Full { full_blob_file_name } => {
-            let full_blob_file_path = repo_paths.file_path(&full_blob_file_name);
-            fs::copy(content_to_store_path, full_blob_file_path)?;
+// Repositories next to the given file whose own versioned file no longer exists, most likely because it was renamed
+fn detached_versioned_files(repository_paths: &RepositoryPaths) -> io::Result<Vec<PathBuf>> {
+    let mut detached_versioned_files = Vec::new();
+
+    let Some(parent_dir) = repository_paths.versioned_file.parent() else {
+        return Ok(detached_versioned_files);
+    };
+
+    let parent_dir = if parent_dir.as_os_str().is_empty() { Path::new(".") } else { parent_dir };
+
+    if !fs::exists(parent_dir)? {
+        return Ok(detached_versioned_files);
+    }
+
+    for entry in fs::read_dir(parent_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
         }
 
-        ContentBlob::Patch {
-            base_blob_file_name,
-            patch_blob_file_name,
-            ..
-        } => {
-            let patch_blob_file_path = repo_paths.file_path(&patch_blob_file_name);
-            let base_blob_file_path = repo_paths.file_path(&base_blob_file_name);
-            xdelta3::create_patch(env, &base_blob_file_path, content_to_store_path, &patch_blob_file_path)?;
+        let Some(sibling_paths) = RepositoryPaths::from_repository_dir(&repository_paths.versioned_file.with_file_name(entry.file_name())) else {
+            continue;
+        };
+
+        if fs::exists(&sibling_paths.data_file)? && !fs::exists(&sibling_paths.versioned_file)? {
+            detached_versioned_files.push(sibling_paths.versioned_file);
         }
     }
 
-    Ok(())
+    detached_versioned_files.sort();
+
+    Ok(detached_versioned_files)
+}
+
+pub fn read_remotes(paths: &RepositoryPaths) -> io::Result<BTreeMap<String, RemoteLocation>> {
+    match fs::read(paths.file_path(REMOTES_FILE_NAME)) {
+        Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_remotes(paths: &RepositoryPaths, remotes: &BTreeMap<String, RemoteLocation>) -> io::Result<()> {
+    fs::write(paths.file_path(REMOTES_FILE_NAME), serde_json::to_string_pretty(remotes)?)
+}
+
+pub fn read_backup_manifest(paths: &RepositoryPaths) -> io::Result<BackupManifest> {
+    match fs::read(paths.file_path(BACKUP_MANIFEST_FILE_NAME)) {
+        Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BackupManifest::default()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_backup_manifest(paths: &RepositoryPaths, manifest: &BackupManifest) -> io::Result<()> {
+    fs::write(paths.file_path(BACKUP_MANIFEST_FILE_NAME), serde_json::to_string_pretty(manifest)?)
+}
+
+pub fn read_bisect_state(paths: &RepositoryPaths) -> io::Result<Option<BisectState>> {
+    match fs::read(paths.file_path(BISECT_FILE_NAME)) {
+        Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_bisect_state(paths: &RepositoryPaths, state: &BisectState) -> io::Result<()> {
+    fs::write(paths.file_path(BISECT_FILE_NAME), serde_json::to_string_pretty(state)?)
+}
+
+pub fn delete_bisect_state(paths: &RepositoryPaths) -> io::Result<()> {
+    fs::remove_file(paths.file_path(BISECT_FILE_NAME))
+}
+
+pub fn read_editing_lock(paths: &RepositoryPaths) -> io::Result<Option<EditingLock>> {
+    match fs::read(paths.file_path(EDITING_LOCK_FILE_NAME)) {
+        Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Created only when there is none, so of two users locking at the same time only one gets it. False when a lock exists.
+pub fn create_editing_lock(paths: &RepositoryPaths, lock: &EditingLock) -> io::Result<bool> {
+    let mut file = match OpenOptions::new().write(true).create_new(true).open(paths.file_path(EDITING_LOCK_FILE_NAME)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    if let Err(e) = file.write_all(serde_json::to_string_pretty(lock)?.as_bytes()) {
+        let _ = fs::remove_file(paths.file_path(EDITING_LOCK_FILE_NAME));
+        return Err(e);
+    }
+
+    Ok(true)
 }
 
-pub fn store_version_content_patch(env: &Env, patch_blob_file_path: &Path, base_blob_file_path: &Path, content_to_store_path: &Path) -> io::Result<()> {
-    xdelta3::create_patch(env, &base_blob_file_path, content_to_store_path, &patch_blob_file_path)?;
+pub fn delete_editing_lock(paths: &RepositoryPaths) -> io::Result<()> {
+    fs::remove_file(paths.file_path(EDITING_LOCK_FILE_NAME))
+}
+
+pub fn read_autostash_entries(paths: &RepositoryPaths) -> io::Result<Vec<AutostashEntry>> {
+    match fs::read(paths.file_path(AUTOSTASH_FILE_NAME)) {
+        Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_autostash_entries(paths: &RepositoryPaths, entries: &[AutostashEntry]) -> io::Result<()> {
+    fs::write(paths.file_path(AUTOSTASH_FILE_NAME), serde_json::to_string_pretty(entries)?)
+}
+
+pub fn read_nickname_words(path: &Path) -> io::Result<Option<NicknameWords>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn data_backup_paths(paths: &RepositoryPaths) -> [PathBuf; 5] {
+    [1, 2, 3, 4, 5].map(|n| paths.file_path(&format!("data_backup{}.json", n)))
+}
+
+// File sync services keep both sides of a conflicting edit, naming the other copy like `data (Bob's conflicted copy 2025-01-02).json` (Dropbox),
+// `data.sync-conflict-20250102-120000-ABCDEFG.json` (Syncthing), `data (1).json` (Google Drive) or `data-LAPTOP.json` (OneDrive)
+pub fn conflicted_data_paths(paths: &RepositoryPaths) -> io::Result<Vec<PathBuf>> {
+    let mut conflicted_data_paths = Vec::new();
+
+    for dir_entry in fs::read_dir(&paths.repository_dir)? {
+        let dir_entry = dir_entry?;
+        if dir_entry.file_type()?.is_file() && is_conflicted_data_file_name(&dir_entry.file_name().to_string_lossy()) {
+            conflicted_data_paths.push(dir_entry.path());
+        }
+    }
+
+    conflicted_data_paths.sort();
+    Ok(conflicted_data_paths)
+}
+
+pub fn is_conflicted_data_file_name(file_name: &str) -> bool {
+    let Some(suffix) = file_name.strip_suffix(".json").and_then(|stem| stem.strip_prefix("data")) else {
+        return false;
+    };
+
+    suffix.starts_with(" (") || suffix.starts_with('-') || suffix.starts_with(".sync-conflict")
+}
+
+pub fn write_version_record(paths: &RepositoryPaths, version: &Version) -> io::Result<()> {
+    let version_record_path = paths.blob_path(&blob_store::version_record_file_name(version.id));
+    object_store::prepare_object_path(&version_record_path)?;
+
+    let version_record_content = serde_json::to_string_pretty(version)?;
+    fs::write(&version_record_path, version_record_content)?;
+    tracing::trace!("Wrote {}", version_record_path.display());
 
     Ok(())
 }
 
 pub fn store_version_content_full(full_blob_file_path: &Path, content_to_store_path: &Path) -> io::Result<()> {
-    fs::copy(content_to_store_path, full_blob_file_path)?;
+    object_store::prepare_object_path(full_blob_file_path)?;
+    copy_logged(content_to_store_path, full_blob_file_path)?;
 
     Ok(())
 }
@@ -81,8 +246,8 @@ pub fn store_version_content_full(full_blob_file_path: &Path, content_to_store_p
 pub fn extract_version_content(env: &Env, repo_paths: &RepositoryPaths, content_blob: &ContentBlob, destination_path: &Path) -> io::Result<()> {
     match content_blob {
         ContentBlob::Full { full_blob_file_name } => {
-            let full_blob_file_path = repo_paths.file_path(&full_blob_file_name);
-            fs::copy(&full_blob_file_path, destination_path)?;
+            let full_blob_file_path = repo_paths.blob_path(full_blob_file_name);
+            copy_logged(&full_blob_file_path, destination_path)?;
         }
 
         ContentBlob::Patch {
@@ -90,8 +255,8 @@ pub fn extract_version_content(env: &Env, repo_paths: &RepositoryPaths, content_
             patch_blob_file_name,
             ..
         } => {
-            let patch_blob_file_path = repo_paths.file_path(&patch_blob_file_name);
-            let base_blob_file_path = repo_paths.file_path(&base_blob_file_name);
+            let patch_blob_file_path = repo_paths.blob_path(patch_blob_file_name);
+            let base_blob_file_path = repo_paths.blob_path(base_blob_file_name);
             xdelta3::apply_patch(env, &base_blob_file_path, &patch_blob_file_path, destination_path)?;
         }
     }
@@ -99,8 +264,52 @@ pub fn extract_version_content(env: &Env, repo_paths: &RepositoryPaths, content_
     Ok(())
 }
 
-pub fn store_version_preview(env: &Env, preview_blob_file_path: &Path, content_to_store_path: &Path) -> io::Result<()> {
-    image_magick::create_preview(env, content_to_store_path, preview_blob_file_path)?;
+// The same content as extract_version_content, without a file in between
+pub fn write_version_content(env: &Env, repo_paths: &RepositoryPaths, content_blob: &ContentBlob, output: &mut impl Write) -> io::Result<()> {
+    match content_blob {
+        ContentBlob::Full { full_blob_file_name } => {
+            io::copy(&mut File::open(repo_paths.blob_path(full_blob_file_name))?, output)?;
+        }
+
+        ContentBlob::Patch {
+            base_blob_file_name,
+            patch_blob_file_name,
+            ..
+        } => {
+            let patch_blob_file_path = repo_paths.blob_path(patch_blob_file_name);
+            let base_blob_file_path = repo_paths.blob_path(base_blob_file_name);
+            xdelta3::apply_patch_to_writer(env, &base_blob_file_path, &patch_blob_file_path, output)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn delete_blobs<'a>(repo_paths: &RepositoryPaths, blob_file_names: impl IntoIterator<Item = &'a str>) -> io::Result<usize> {
+    let mut deleted_count = 0;
+
+    for blob_file_name in blob_file_names {
+        let blob_file_path = repo_paths.blob_path(blob_file_name);
+        if fs::exists(&blob_file_path)? {
+            fs::remove_file(&blob_file_path)?;
+            tracing::trace!("Deleted {}", blob_file_path.display());
+            deleted_count += 1;
+        }
+    }
+
+    Ok(deleted_count)
+}
+
+fn copy_logged(source: &Path, destination: &Path) -> io::Result<()> {
+    let start = Instant::now();
+    let copied_length = fs::copy(source, destination)?;
+    tracing::debug!(
+        "Copied {} to {} ({} bytes) in {:?}",
+        source.display(),
+        destination.display(),
+        copied_length,
+        start.elapsed()
+    );
 
     Ok(())
 }
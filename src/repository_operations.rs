@@ -1,18 +1,53 @@
-use crate::biver_result::BiverResult;
+use crate::background_process::BackgroundProcess;
+use crate::backup::{BackupRecord, ObjectStat};
+use crate::bisect::{BisectState, BisectStep};
+use crate::biver_result::{BiverErrorCategory, BiverResult, categorized_error, error};
+use crate::blob_store::ObjectKind;
+use crate::diff::ImageDiffResult;
+use crate::editing_lock::EditingLock;
 use crate::env::Env;
+use crate::events::RepositoryEvent;
 use crate::extensions::CountIsAtLeast;
-use crate::repository_data::{ContentBlob, Head, RepositoryData, Version};
+use crate::git_export::GitContent;
+use crate::hash::{Digest, FileHash, HashAlgorithm};
+use crate::integrity::BlobStatus;
+use crate::nickname::NicknameWords;
+use crate::parallel;
+use crate::reconstruction_cache;
+use crate::remote::{DataWriteResult, RemoteLocation};
+use crate::repair::{DataBackup, RebuiltData};
+use crate::repository_data::{
+    AutostashEntry, BranchMetadata, ContentBlob, DataProblem, ForkOrigin, Head, Note, OrphanedVersion, PatchLimit, RepositoryData, Review, ReviewState, Version,
+};
+use crate::repository_io::RepositoryDataResult;
 use crate::repository_paths::RepositoryPaths;
+use crate::retention::RetentionPolicy;
+use crate::signing::SignatureStatus;
+use crate::temporary_file::TemporaryFile;
+use crate::tools::{git, image_magick, minisign, rclone, xdelta3};
+use crate::transaction::Transaction;
+use crate::trash::TrashMode;
 use crate::version_id::VersionId;
-use crate::{hash, image_magick, known_file_types, nickname, repository_io, xdelta3};
-use chrono::Utc;
-use std::collections::{HashMap, HashSet};
+use crate::{
+    bisect, blob_store, diff, effects, events, file_attributes, file_replacement, git_export, hash, integrity, known_file_types, logging, migrations, nickname, object_store,
+    perceptual_hash, remote, repair, report, repository_io, retention, signing, stat_cache, trash,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
+use std::io::{Read, Write};
+use std::mem;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::SystemTime;
 use std::{fs, io};
+use uuid::Uuid;
 
 const DEFAULT_BRANCH: &str = "main";
+const AUTOSTASH_BRANCH: &str = "autostash";
+const CORRUPT_DATA_FILE_NAME: &str = "data_corrupt.json";
+const PENDING_PREVIEW_FILE_NAME: &str = "pending_preview.jpg";
 
 pub enum InitResult {
     Ok,
@@ -20,7 +55,14 @@ pub enum InitResult {
     InvalidBranchName,
 }
 
-pub fn init(env: &Env, repo_paths: &RepositoryPaths, branch: Option<&str>, description: Option<&str>) -> BiverResult<InitResult> {
+pub fn init(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    branch: Option<&str>,
+    description: Option<&str>,
+    forked_from: Option<ForkOrigin>,
+    hash_algorithm: HashAlgorithm,
+) -> BiverResult<InitResult> {
     if fs::exists(&repo_paths.data_file)? {
         return Ok(InitResult::AlreadyInitialized);
     }
@@ -29,102 +71,518 @@ pub fn init(env: &Env, repo_paths: &RepositoryPaths, branch: Option<&str>, descr
         fs::create_dir(&repo_paths.repository_dir)?;
     }
 
-    let versioned_file = File::open(&repo_paths.versioned_file)?;
-    let versioned_file_xxh3_128 = hash::xxh3_128(&versioned_file)?;
-    let versioned_file_length = fs::metadata(&repo_paths.versioned_file)?.len();
-
-    let new_version_id = VersionId::new();
-
     let branch = branch.unwrap_or(DEFAULT_BRANCH);
 
     if !valid_branch_name(branch) {
         return Ok(InitResult::InvalidBranchName);
     }
 
-    let content_blob_file_name = content_blob_file_name(new_version_id);
-    let content_blob_file_path = repo_paths.file_path(&content_blob_file_name);
+    let pending_blobs = start_pending_blobs(env, repo_paths, None, None)?;
+
+    let file_hash = stat_cache::versioned_file_hash(env, repo_paths, hash_algorithm, false)?;
+    let versioned_file_hash = file_hash.hash;
+    let versioned_file_metadata = fs::metadata(&repo_paths.versioned_file)?;
+    let versioned_file_length = versioned_file_metadata.len();
+
+    let new_version_id = VersionId::new();
 
-    let preview_blob_file_name = preview_blob_file_name(env, repo_paths, new_version_id);
-    let preview_blob_file_path = preview_blob_file_name.as_ref().map(|n| repo_paths.file_path(n));
+    let (content_blob, preview_blob_file_name, mut transaction) = store_new_version_blobs(env, repo_paths, None, file_hash, versioned_file_length, None, pending_blobs)?;
 
-    let new_version = Version {
+    let mut new_version = Version {
         id: new_version_id,
         creation_time: Utc::now(),
-        nickname: nickname::new_nickname(versioned_file_xxh3_128),
+        nickname: nickname::new_nickname(&nickname_words(env, repo_paths)?, versioned_file_hash, |_| false),
+        custom_nickname: false,
+        author: env.author.clone(),
         versioned_file_length,
-        versioned_file_xxh3_128,
+        versioned_file_hash,
+        versioned_file_digest: file_hash.digest,
         description: description.unwrap_or_default().to_string(),
         parent: None,
-        content_blob: ContentBlob::Full {
-            full_blob_file_name: content_blob_file_name,
-        },
+        content_blob,
+        perceptual_hash: preview_perceptual_hash(repo_paths, preview_blob_file_name.as_deref()),
         preview_blob_file_name,
+        modified_time: file_attributes::modified_time(&versioned_file_metadata),
+        unix_mode: file_attributes::unix_mode(&versioned_file_metadata),
+        attributes: BTreeMap::new(),
+        notes: Vec::new(),
+        reviews: Vec::new(),
+        signature: None,
+        forked_from,
     };
 
-    let repo_data = RepositoryData {
+    new_version.signature = signing::sign(env, repo_paths, &new_version)?;
+
+    let mut repo_data = RepositoryData {
+        format_version: migrations::CURRENT_FORMAT_VERSION,
         head: Head::Branch(branch.to_string()),
         branches: HashMap::from([(branch.to_string(), new_version_id)]),
-        versions: vec![new_version],
+        versions: Vec::new(),
+        blob_checksums: HashMap::new(),
+        versioned_file_name: repo_paths.versioned_file_name(),
+        protected_branches: BTreeSet::new(),
+        orphaned_versions: Vec::new(),
+        pinned_versions: BTreeSet::new(),
+        patch_limit: None,
+        hash_algorithm,
+        branch_metadata: BTreeMap::from([(branch.to_string(), new_branch_metadata(env))]),
     };
 
-    if let Some(preview_blob_file_path) = preview_blob_file_path {
-        repository_io::store_version_preview(env, &preview_blob_file_path, &repo_paths.versioned_file)?;
-    }
-    repository_io::store_version_content_full(&content_blob_file_path, &repo_paths.versioned_file)?;
+    integrity::record_blob_checksums(env, repo_paths, &mut repo_data, &new_version)?;
+    transaction.create(repo_paths.blob_path(&blob_store::version_record_file_name(new_version.id)), || {
+        repository_io::write_version_record(repo_paths, &new_version)
+    })?;
+    repo_data.versions.push(new_version);
+
     repository_io::write_data(repo_paths, &repo_data)?;
+    transaction.commit()?;
 
     Ok(InitResult::Ok)
 }
 
+pub enum ForkResult {
+    Ok,
+    InvalidTarget,
+    FileAlreadyExists,
+}
+
+// The new file starts its own repository, with the target version as its initial version
+pub fn fork(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, target: &str, new_file: &Path, description: Option<&str>) -> BiverResult<ForkResult> {
+    let target_version = match resolve_target(repo_data, target) {
+        TargetResult::Invalid => return Ok(ForkResult::InvalidTarget),
+        TargetResult::Branch(branch) => repo_data.version(repo_data.branches[branch]).expect("Branch resolved from target must exist"),
+        TargetResult::Version(version) => version,
+    };
+
+    let new_repo_paths = RepositoryPaths::from_versioned_file_path(new_file.to_path_buf());
+
+    if fs::exists(new_file)? || fs::exists(&new_repo_paths.data_file)? {
+        return Ok(ForkResult::FileAlreadyExists);
+    }
+
+    restore_version_file(env, repo_paths, repo_data, target_version, new_file)?;
+
+    // The source is recorded absolute, the forked repository may be used from anywhere
+    let forked_from = ForkOrigin {
+        versioned_file: fs::canonicalize(&repo_paths.versioned_file)?,
+        version_id: target_version.id,
+    };
+
+    let default_description = format!("Forked from {} {}", repo_paths.versioned_file_name().unwrap_or_default(), target_version.nickname);
+    let description = description.unwrap_or(&default_description);
+
+    match init(env, &new_repo_paths, None, Some(description), Some(forked_from), repo_data.hash_algorithm)? {
+        InitResult::Ok => Ok(ForkResult::Ok),
+        InitResult::AlreadyInitialized => Ok(ForkResult::FileAlreadyExists),
+        InitResult::InvalidBranchName => unreachable!("The default branch name is valid"),
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum SeriesOrder {
+    // drawing_v2 before drawing_v10
+    Number,
+    Modified,
+}
+
+pub enum ImportSeriesResult {
+    Ok { imported_count: usize, skipped_count: usize },
+    AlreadyInitialized,
+    FileAlreadyExists,
+    NoMatchingFiles,
+}
+
+// Each matching copy becomes a version, oldest first, described by its file name. Copies identical to the one before
+// are skipped. The copies themselves are left as they are, the newest ends up as the versioned file.
+pub fn import_series(env: &Env, repo_paths: &RepositoryPaths, pattern: &str, order: SeriesOrder, hash_algorithm: HashAlgorithm) -> BiverResult<ImportSeriesResult> {
+    if fs::exists(&repo_paths.data_file)? {
+        return Ok(ImportSeriesResult::AlreadyInitialized);
+    }
+
+    if fs::exists(&repo_paths.versioned_file)? {
+        return Ok(ImportSeriesResult::FileAlreadyExists);
+    }
+
+    let pattern_path = Path::new(pattern);
+    let dir = match pattern_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name_pattern: Vec<char> = pattern_path.file_name().unwrap_or_default().to_string_lossy().chars().collect();
+
+    let mut series = Vec::new();
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let file_name = dir_entry.file_name().to_string_lossy().to_string();
+
+        if dir_entry.file_type()?.is_file() && wildcard_matches(&file_name_pattern, &file_name.chars().collect::<Vec<_>>()) {
+            series.push((dir_entry.path(), dir_entry.metadata()?.modified()?));
+        }
+    }
+
+    if series.is_empty() {
+        return Ok(ImportSeriesResult::NoMatchingFiles);
+    }
+
+    match order {
+        SeriesOrder::Number => series.sort_by_cached_key(|(path, _)| natural_sort_key(&path.file_name().unwrap_or_default().to_string_lossy())),
+        SeriesOrder::Modified => series.sort_by_key(|(_, modified_time)| *modified_time),
+    }
+
+    // Nothing existed before, so a failure part way leaves nothing behind either, and the import can simply be run again
+    let result = import_sorted_series(env, repo_paths, &series, hash_algorithm);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&repo_paths.repository_dir);
+        let _ = fs::remove_file(&repo_paths.versioned_file);
+    }
+
+    result
+}
+
+// Each version gets the modification time of its file as creation time, which is when that state was made
+fn import_sorted_series(env: &Env, repo_paths: &RepositoryPaths, series: &[(PathBuf, SystemTime)], hash_algorithm: HashAlgorithm) -> BiverResult<ImportSeriesResult> {
+    let description = |path: &Path| path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+    let (first_path, _) = &series[0];
+    fs::copy(first_path, &repo_paths.versioned_file)?;
+    match init(env, repo_paths, None, Some(&description(first_path)), None, hash_algorithm)? {
+        InitResult::Ok => {}
+        InitResult::AlreadyInitialized => return Ok(ImportSeriesResult::AlreadyInitialized),
+        InitResult::InvalidBranchName => unreachable!("The default branch name is valid"),
+    }
+
+    let RepositoryDataResult::Initialized(mut repo_data) = repository_io::read_data(repo_paths)? else {
+        unreachable!("The repository was just initialized");
+    };
+    let mut skipped_count = 0;
+    let mut creation_times = vec![(repo_data.head_version().id, series[0].1)];
+
+    for (path, modified_time) in &series[1..] {
+        fs::copy(path, &repo_paths.versioned_file)?;
+
+        // A copy that went back to an earlier state is still part of the history, so duplicates are allowed
+        match commit_version(env, repo_paths, &mut repo_data, Some(&description(path)), None, false, true)? {
+            CommitResult::Ok => creation_times.push((repo_data.head_version().id, *modified_time)),
+            CommitResult::NothingToCommit => skipped_count += 1,
+            _ => unreachable!("A new repository has one unprotected branch"),
+        }
+    }
+
+    for (version_id, modified_time) in creation_times {
+        if let Some(version) = repo_data.version_mut(version_id) {
+            version.creation_time = DateTime::<Utc>::from(modified_time);
+        }
+    }
+    effects::write_data(env, repo_paths, &repo_data)?;
+
+    events::emit(
+        env,
+        RepositoryEvent::Imported {
+            versioned_file: repo_paths.versioned_file.clone(),
+            new_version_ids: repo_data.versions.iter().map(|v| v.id).collect(),
+        },
+    );
+
+    Ok(ImportSeriesResult::Ok {
+        imported_count: series.len() - skipped_count,
+        skipped_count,
+    })
+}
+
+// * matches any run of characters, ? any single one. On a mismatch only the last * is given one more character, which
+// is enough since it can take over whatever an earlier one would, so matching never takes exponential time.
+fn wildcard_matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Of the last *, and where in the name what it matches ends
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, star_end)) => {
+                    backtrack = Some((star, star_end + 1));
+                    p = star + 1;
+                    n = star_end + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum NaturalChunk {
+    Number(u128),
+    Text(String),
+}
+
+fn natural_sort_key(name: &str) -> Vec<NaturalChunk> {
+    let mut chunks = Vec::new();
+    let mut rest = name;
+
+    while let Some(c) = rest.chars().next() {
+        let is_digit = c.is_ascii_digit();
+        let end = rest.find(|c: char| c.is_ascii_digit() != is_digit).unwrap_or(rest.len());
+        let (chunk, remaining) = rest.split_at(end);
+
+        chunks.push(if is_digit {
+            NaturalChunk::Number(chunk.parse().unwrap_or(u128::MAX))
+        } else {
+            NaturalChunk::Text(chunk.to_string())
+        });
+        rest = remaining;
+    }
+
+    chunks
+}
+
 pub enum CommitResult {
     Ok,
     NothingToCommit,
+    // The version before head has the same content, so the commit would undo the last one
+    Duplicate(VersionId),
     HeadMustBeOnBranch,
+    BranchAlreadyExists,
+    InvalidBranchName,
+    BranchProtected,
 }
 
-pub fn commit_version(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, description: Option<&str>) -> BiverResult<CommitResult> {
-    let versioned_file = File::open(&repo_paths.versioned_file)?;
-    let versioned_file_xxh3_128 = hash::xxh3_128(&versioned_file)?;
-    let versioned_file_length = fs::metadata(&repo_paths.versioned_file)?.len();
+pub fn commit_version(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    repo_data: &mut RepositoryData,
+    description: Option<&str>,
+    new_branch: Option<&str>,
+    force: bool,
+    allow_duplicate: bool,
+) -> BiverResult<CommitResult> {
+    if new_branch.is_none()
+        && let Some(head_branch) = repo_data.head.branch()
+        && repo_data.is_protected(head_branch)
+        && !force
+    {
+        return Ok(CommitResult::BranchProtected);
+    }
+
+    let pending_blobs = start_pending_blobs(env, repo_paths, Some(repo_data), Some(repo_data.head_version().id))?;
+
+    let file_hash = stat_cache::versioned_file_hash(env, repo_paths, repo_data.hash_algorithm, false)?;
+    let versioned_file_hash = file_hash.hash;
 
     let parent = repo_data.head_version();
 
-    if versioned_file_xxh3_128 == parent.versioned_file_xxh3_128 {
+    if versioned_file_hash == parent.versioned_file_hash {
         return Ok(CommitResult::NothingToCommit);
     }
 
-    let Some(branch) = repo_data.head.branch() else {
-        return Ok(CommitResult::HeadMustBeOnBranch);
+    // Identical content is stored once whichever versions have it, so only what is likely a mistake is refused
+    if !allow_duplicate
+        && let Some(grandparent) = parent.parent.and_then(|grandparent_id| repo_data.version(grandparent_id))
+        && blob_store::has_content(grandparent, file_hash, fs::metadata(&repo_paths.versioned_file)?.len())
+    {
+        return Ok(CommitResult::Duplicate(grandparent.id));
+    }
+
+    let branch = match (new_branch, repo_data.head.branch()) {
+        (Some(new_branch), _) => {
+            if repo_data.branches.contains_key(new_branch) {
+                return Ok(CommitResult::BranchAlreadyExists);
+            }
+
+            if !valid_branch_name(new_branch) {
+                return Ok(CommitResult::InvalidBranchName);
+            }
+
+            new_branch.to_string()
+        }
+        (None, Some(head_branch)) => head_branch.to_string(),
+        (None, None) => return Ok(CommitResult::HeadMustBeOnBranch),
     };
 
-    let new_version_id = VersionId::new();
+    let (new_version_id, transaction) = add_child_version(env, repo_paths, repo_data, parent.id, description, file_hash, pending_blobs)?;
+
+    if new_branch.is_some() {
+        repo_data.branch_metadata.insert(branch.clone(), new_branch_metadata(env));
+    }
+    repo_data.branches.insert(branch.clone(), new_version_id);
+    repo_data.head = Head::Branch(branch.clone());
+
+    effects::write_data(env, repo_paths, repo_data)?;
+    transaction.commit()?;
+
+    events::emit(
+        env,
+        RepositoryEvent::Committed {
+            versioned_file: repo_paths.versioned_file.clone(),
+            version_id: new_version_id,
+            branch,
+        },
+    );
+
+    Ok(CommitResult::Ok)
+}
+
+pub struct PendingVersionSummary {
+    pub nickname: String,
+    pub parent_nickname: String,
+    pub length: u64,
+    pub parent_length: u64,
+    pub changed_bytes: u64,
+}
+
+// What committing the versioned file now would produce, shown while the description is being written
+pub fn pending_version_summary(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<Option<PendingVersionSummary>> {
+    let versioned_file_hash = stat_cache::versioned_file_hash(env, repo_paths, repo_data.hash_algorithm, false)?.hash;
+    let parent = repo_data.head_version();
+
+    if versioned_file_hash == parent.versioned_file_hash {
+        return Ok(None);
+    }
+
+    let parent_content = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "summary.tmp"));
+    extract_version_content(env, repo_paths, repo_data, parent, parent_content.path())?;
+    let changed_bytes = diff::changed_bytes(parent_content.path(), &repo_paths.versioned_file)?;
+
+    Ok(Some(PendingVersionSummary {
+        nickname: nickname::new_nickname(&nickname_words(env, repo_paths)?, versioned_file_hash, |n| repo_data.nickname_taken(n)),
+        parent_nickname: parent.nickname.clone(),
+        length: fs::metadata(&repo_paths.versioned_file)?.len(),
+        parent_length: parent.versioned_file_length,
+        changed_bytes,
+    }))
+}
+
+// A thumbnail of the versioned file as it would be committed, to look at while describing it. None when previews
+// are not made for the file. Each call gets its own file, which the caller deletes.
+pub fn pending_preview(env: &Env, repo_paths: &RepositoryPaths) -> BiverResult<Option<PathBuf>> {
+    if !can_create_preview(env, repo_paths) {
+        return Ok(None);
+    }
+
+    let preview_path = repo_paths.unique_temp_file_path(env, PENDING_PREVIEW_FILE_NAME);
+    image_magick::spawn_create_preview(env, &repo_paths.versioned_file, &preview_path)?.wait(image_magick::wait_for_preview)?;
+
+    Ok(Some(preview_path))
+}
+
+pub enum StashResult {
+    Ok(VersionId),
+    NothingToStash,
+    InvalidBranchName,
+}
+
+// Stores the versioned file as a new version on the stash branch without moving head.
+// Successive stashes form a chain on that branch, starting from the head version.
+pub fn stash_version(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, stash_branch: &str) -> BiverResult<StashResult> {
+    if !valid_branch_name(stash_branch) {
+        return Ok(StashResult::InvalidBranchName);
+    }
+
+    let parent_id = match repo_data.branches.get(stash_branch) {
+        Some(stash_leaf_id) => *stash_leaf_id,
+        None => repo_data.head_version().id,
+    };
+
+    let pending_blobs = start_pending_blobs(env, repo_paths, Some(repo_data), Some(parent_id))?;
+
+    let file_hash = stat_cache::versioned_file_hash(env, repo_paths, repo_data.hash_algorithm, false)?;
+    let versioned_file_hash = file_hash.hash;
+
+    let parent = repo_data.version(parent_id).expect("Stash parent must exist");
+
+    if versioned_file_hash == parent.versioned_file_hash {
+        return Ok(StashResult::NothingToStash);
+    }
 
-    let content_blob = store_version_content(env, repo_paths, repo_data, new_version_id, Some(parent.id))?;
+    let (new_version_id, transaction) = add_child_version(env, repo_paths, repo_data, parent_id, None, file_hash, pending_blobs)?;
+
+    if !repo_data.branches.contains_key(stash_branch) {
+        repo_data.branch_metadata.insert(stash_branch.to_string(), new_branch_metadata(env));
+    }
+    repo_data.branches.insert(stash_branch.to_string(), new_version_id);
+
+    repository_io::write_data(repo_paths, repo_data)?;
+    transaction.commit()?;
+
+    Ok(StashResult::Ok(new_version_id))
+}
+
+// The transaction is committed by the caller once the data file referencing the new version is written
+fn add_child_version(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    repo_data: &mut RepositoryData,
+    parent_id: VersionId,
+    description: Option<&str>,
+    file_hash: FileHash,
+    pending_blobs: PendingBlobs,
+) -> BiverResult<(VersionId, Transaction)> {
+    let versioned_file_hash = file_hash.hash;
+    let new_version_id = VersionId::new();
+    let versioned_file_metadata = fs::metadata(&repo_paths.versioned_file)?;
+    let versioned_file_length = versioned_file_metadata.len();
 
-    let preview_blob_file_name = preview_blob_file_name(env, repo_paths, new_version_id);
-    let preview_blob_file_path = preview_blob_file_name.as_ref().map(|n| repo_paths.file_path(n));
+    let (content_blob, preview_blob_file_name, mut transaction) =
+        store_new_version_blobs(env, repo_paths, Some(repo_data), file_hash, versioned_file_length, Some(parent_id), pending_blobs)?;
 
-    let new_version = Version {
+    let mut new_version = Version {
         id: new_version_id,
         creation_time: Utc::now(),
-        nickname: nickname::new_nickname(versioned_file_xxh3_128),
+        nickname: nickname::new_nickname(&nickname_words(env, repo_paths)?, versioned_file_hash, |n| repo_data.nickname_taken(n)),
+        custom_nickname: false,
+        author: env.author.clone(),
         versioned_file_length,
-        versioned_file_xxh3_128,
+        versioned_file_hash,
+        versioned_file_digest: file_hash.digest,
         description: description.unwrap_or_default().to_string(),
-        parent: Some(parent.id),
+        parent: Some(parent_id),
         content_blob,
+        perceptual_hash: preview_perceptual_hash(repo_paths, preview_blob_file_name.as_deref()),
         preview_blob_file_name,
+        modified_time: file_attributes::modified_time(&versioned_file_metadata),
+        unix_mode: file_attributes::unix_mode(&versioned_file_metadata),
+        attributes: BTreeMap::new(),
+        notes: Vec::new(),
+        reviews: Vec::new(),
+        signature: None,
+        forked_from: None,
     };
 
+    new_version.signature = signing::sign(env, repo_paths, &new_version)?;
+
+    // Checksums are read from the stored blobs, which a dry run does not write
+    if !env.dry_run {
+        integrity::record_blob_checksums(env, repo_paths, repo_data, &new_version)?;
+    }
+    transaction.create(repo_paths.blob_path(&blob_store::version_record_file_name(new_version_id)), || {
+        effects::write_version_record(env, repo_paths, &new_version)
+    })?;
     repo_data.versions.push(new_version);
-    repo_data.branches.insert(branch.to_string(), new_version_id);
 
-    if let Some(preview_blob_file_path) = preview_blob_file_path {
-        repository_io::store_version_preview(env, &preview_blob_file_path, &repo_paths.versioned_file)?;
+    Ok((new_version_id, transaction))
+}
+
+pub fn suggested_branch_name(repo_data: &RepositoryData) -> String {
+    let base_name = repo_data.head_version().nickname.clone();
+
+    if !repo_data.branches.contains_key(&base_name) {
+        return base_name;
     }
-    repository_io::write_data(repo_paths, repo_data)?;
 
-    Ok(CommitResult::Ok)
+    (2..)
+        .map(|n| format!("{}-{}", base_name, n))
+        .find(|name| !repo_data.branches.contains_key(name))
+        .expect("There should always be a free branch name")
 }
 
 pub enum AmendResult {
@@ -133,17 +591,34 @@ pub enum AmendResult {
     HeadMustBeBranch,
     CannotAmendParent,
     HeadEqualsParent,
+    BranchProtected,
+    HeadPinned,
 }
 
-pub fn amend_head(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, description: Option<&str>) -> BiverResult<AmendResult> {
-    let versioned_file = File::open(&repo_paths.versioned_file)?;
-    let versioned_file_xxh3_128 = hash::xxh3_128(&versioned_file)?;
-    let versioned_file_length = fs::metadata(&repo_paths.versioned_file)?.len();
+// A recoverable amend keeps the replaced head as an orphaned version, which recover can bring back until gc expires it
+pub fn amend_head(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, description: Option<&str>, force: bool, recoverable: bool) -> BiverResult<AmendResult> {
+    if let Some(head_branch) = repo_data.head.branch()
+        && repo_data.is_protected(head_branch)
+        && !force
+    {
+        return Ok(AmendResult::BranchProtected);
+    }
+
+    if repo_data.is_pinned(repo_data.head_version().id) {
+        return Ok(AmendResult::HeadPinned);
+    }
+
+    let pending_blobs = start_pending_blobs(env, repo_paths, Some(repo_data), repo_data.head_version().parent)?;
+
+    let file_hash = stat_cache::versioned_file_hash(env, repo_paths, repo_data.hash_algorithm, false)?;
+    let versioned_file_hash = file_hash.hash;
+    let versioned_file_metadata = fs::metadata(&repo_paths.versioned_file)?;
+    let versioned_file_length = versioned_file_metadata.len();
 
     let head = repo_data.head_version();
     let head_id = head.id;
 
-    if versioned_file_xxh3_128 == head.versioned_file_xxh3_128 {
+    if versioned_file_hash == head.versioned_file_hash {
         return Ok(AmendResult::NoUncommittedChanges);
     }
 
@@ -156,41 +631,77 @@ pub fn amend_head(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut Repos
     }
 
     if let Some(parent_id) = head.parent
-        && repo_data.version(parent_id).unwrap().versioned_file_xxh3_128 == versioned_file_xxh3_128
+        && repo_data.version(parent_id).unwrap().versioned_file_hash == versioned_file_hash
     {
         return Ok(AmendResult::HeadEqualsParent);
     }
 
     let new_version_id = VersionId::new();
 
-    let content_blob = store_version_content(env, repo_paths, repo_data, new_version_id, head.parent)?;
-
-    let preview_blob_file_name = preview_blob_file_name(env, repo_paths, new_version_id);
-    let preview_blob_file_path = preview_blob_file_name.as_ref().map(|n| repo_paths.file_path(n));
+    let (content_blob, preview_blob_file_name, mut transaction) =
+        store_new_version_blobs(env, repo_paths, Some(repo_data), file_hash, versioned_file_length, head.parent, pending_blobs)?;
 
-    let new_head = Version {
+    let mut new_head = Version {
         id: new_version_id,
         creation_time: Utc::now(),
-        nickname: nickname::new_nickname(versioned_file_xxh3_128),
+        nickname: nickname::new_nickname(&nickname_words(env, repo_paths)?, versioned_file_hash, |n| repo_data.nickname_taken(n)),
+        custom_nickname: false,
+        author: env.author.clone(),
         versioned_file_length,
-        versioned_file_xxh3_128,
+        versioned_file_hash,
+        versioned_file_digest: file_hash.digest,
         description: description.unwrap_or(&head.description).to_string(),
         parent: head.parent,
         content_blob,
+        perceptual_hash: preview_perceptual_hash(repo_paths, preview_blob_file_name.as_deref()),
         preview_blob_file_name,
+        modified_time: file_attributes::modified_time(&versioned_file_metadata),
+        unix_mode: file_attributes::unix_mode(&versioned_file_metadata),
+        attributes: head.attributes.clone(),
+        notes: head.notes.clone(),
+        // Given for the replaced content
+        reviews: Vec::new(),
+        signature: None,
+        forked_from: head.forked_from.clone(),
     };
 
-    repo_data.branches.insert(head_branch.to_string(), new_version_id);
-    repo_data.versions.retain(|v| v.id != head_id);
-    repo_data.versions.push(new_head);
+    new_head.signature = signing::sign(env, repo_paths, &new_head)?;
+
+    let head_branch = head_branch.to_string();
+    repo_data.branches.insert(head_branch.clone(), new_version_id);
 
-    let new_head = repo_data.head_version();
+    let replaced_head_index = repo_data.versions.iter().position(|v| v.id == head_id).expect("Head version must exist");
+    let replaced_head = repo_data.versions.remove(replaced_head_index);
 
-    if let Some(preview_blob_file_path) = preview_blob_file_path {
-        repository_io::store_version_preview(env, &preview_blob_file_path, &repo_paths.versioned_file)?;
+    if recoverable {
+        repo_data.orphaned_versions.push(OrphanedVersion {
+            version: replaced_head,
+            branch: head_branch.clone(),
+            replaced_by: new_version_id,
+            orphaned_time: Utc::now(),
+        });
     }
-    repository_io::store_version_content(env, repo_paths, &new_head.content_blob, &repo_paths.versioned_file)?;
-    repository_io::write_data(repo_paths, &repo_data)?;
+
+    if !env.dry_run {
+        integrity::record_blob_checksums(env, repo_paths, repo_data, &new_head)?;
+    }
+    transaction.create(repo_paths.blob_path(&blob_store::version_record_file_name(new_version_id)), || {
+        effects::write_version_record(env, repo_paths, &new_head)
+    })?;
+    repo_data.versions.push(new_head);
+
+    effects::write_data(env, repo_paths, repo_data)?;
+    transaction.commit()?;
+
+    events::emit(
+        env,
+        RepositoryEvent::Amended {
+            versioned_file: repo_paths.versioned_file.clone(),
+            replaced_version_id: head_id,
+            version_id: new_version_id,
+            branch: head_branch,
+        },
+    );
 
     Ok(AmendResult::Ok)
 }
@@ -200,227 +711,2824 @@ pub enum RewordResult {
     InvalidTarget,
 }
 
-pub fn reword(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str, description: &str) -> BiverResult<RewordResult> {
-    let Some(target_version) = resolve_target_strict_mut(repo_data, target) else {
+pub fn reword(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str, description: &str) -> BiverResult<RewordResult> {
+    let Some(target_version_id) = resolve_version_target(repo_data, target).map(|v| v.id) else {
         return Ok(RewordResult::InvalidTarget);
     };
 
+    let target_version = repo_data.version_mut(target_version_id).expect("Version resolved from target must exist");
+
     target_version.description = description.to_string();
+    repository_io::write_version_record(repo_paths, target_version)?;
 
     repository_io::write_data(repo_paths, repo_data)?;
 
+    events::emit(
+        env,
+        RepositoryEvent::Reworded {
+            versioned_file: repo_paths.versioned_file.clone(),
+            version_id: target_version_id,
+        },
+    );
+
     Ok(RewordResult::Ok)
 }
 
-pub fn has_uncommitted_changes(repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<bool> {
-    let versioned_file_metadata = fs::metadata(&repo_paths.versioned_file)?;
-    let head_version = repo_data.head_version();
+pub enum RegenerateNicknameResult {
+    Ok { old_nickname: String, new_nickname: String },
+    Unchanged,
+    InvalidTarget,
+    VersionSigned,
+}
 
-    if versioned_file_metadata.len() != head_version.versioned_file_length {
-        return Ok(true);
+// The nickname is part of what a signature covers, so signed versions keep theirs
+pub fn regenerate_nickname(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str) -> BiverResult<RegenerateNicknameResult> {
+    let version = match version(repo_data, target) {
+        VersionResult::InvalidTarget => return Ok(RegenerateNicknameResult::InvalidTarget),
+        VersionResult::Ok(version) => version,
+    };
+
+    if version.signature.is_some() {
+        return Ok(RegenerateNicknameResult::VersionSigned);
     }
 
-    let versioned_file = File::open(&repo_paths.versioned_file)?;
+    let version_id = version.id;
+    let new_nickname = nickname::new_nickname(&nickname_words(env, repo_paths)?, version.versioned_file_hash, |n| {
+        repo_data.versions_and_orphans().any(|v| v.id != version_id && v.nickname.eq_ignore_ascii_case(n))
+    });
 
-    let current_xxh3_128 = hash::xxh3_128(&versioned_file)?;
+    let version = repo_data.version_mut(version_id).expect("Version resolved from target must exist");
 
-    Ok(head_version.versioned_file_xxh3_128 != current_xxh3_128)
-}
+    if version.nickname == new_nickname {
+        return Ok(RegenerateNicknameResult::Unchanged);
+    }
 
-pub fn discard(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<()> {
-    let head_version = repo_data.head_version();
-    repository_io::extract_version_content(env, repo_paths, &head_version.content_blob, &repo_paths.versioned_file)?;
-    Ok(())
+    let old_nickname = mem::replace(&mut version.nickname, new_nickname.clone());
+    version.custom_nickname = false;
+    effects::write_version_record(env, repo_paths, version)?;
+
+    effects::write_data(env, repo_paths, repo_data)?;
+
+    events::emit(
+        env,
+        RepositoryEvent::VersionRenamed {
+            versioned_file: repo_paths.versioned_file.clone(),
+            version_id,
+            nickname: new_nickname.clone(),
+        },
+    );
+
+    Ok(RegenerateNicknameResult::Ok { old_nickname, new_nickname })
 }
 
-pub enum ResetResult {
+pub enum RenameVersionResult {
     Ok,
-    HeadMustBeBranch,
     InvalidTarget,
-    CannotLeaveOrphans,
+    InvalidNickname,
+    NicknameTaken,
+    VersionSigned,
 }
 
-pub fn reset(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str) -> BiverResult<ResetResult> {
-    let Some(branch) = repo_data.head.branch() else {
-        return Ok(ResetResult::HeadMustBeBranch);
+pub fn rename_version(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str, new_nickname: &str) -> BiverResult<RenameVersionResult> {
+    let version = match version(repo_data, target) {
+        VersionResult::InvalidTarget => return Ok(RenameVersionResult::InvalidTarget),
+        VersionResult::Ok(version) => version,
     };
 
-    let Some(target_version) = resolve_version_id_target(repo_data, target) else {
-        return Ok(ResetResult::InvalidTarget);
-    };
-    let target_version_id = target_version.id;
+    if version.signature.is_some() {
+        return Ok(RenameVersionResult::VersionSigned);
+    }
 
-    let erased_versions: Vec<_> = repo_data.iter_head_and_ancestors().take_while(|v| v.id != target_version.id).collect();
+    if !valid_nickname(new_nickname) {
+        return Ok(RenameVersionResult::InvalidNickname);
+    }
 
-    let erased_versions_have_root = erased_versions.iter().any(|v| v.is_root());
-    if erased_versions_have_root {
-        return Ok(ResetResult::InvalidTarget);
+    // Branch names are resolved before nicknames, so a nickname equal to one could never be targeted
+    let version_id = version.id;
+    let taken = repo_data.branches.contains_key(new_nickname)
+        || repo_data
+            .versions_and_orphans()
+            .any(|v| v.id != version_id && v.nickname.eq_ignore_ascii_case(new_nickname));
+
+    if taken {
+        return Ok(RenameVersionResult::NicknameTaken);
     }
 
-    let head_has_children = repo_data.iter_children(repo_data.head_version().id).count_is_at_least(1);
-    if head_has_children {
-        return Ok(ResetResult::CannotLeaveOrphans);
+    let version = repo_data.version_mut(version_id).expect("Version resolved from target must exist");
+    version.nickname = new_nickname.to_string();
+    version.custom_nickname = true;
+    effects::write_version_record(env, repo_paths, version)?;
+
+    effects::write_data(env, repo_paths, repo_data)?;
+
+    events::emit(
+        env,
+        RepositoryEvent::VersionRenamed {
+            versioned_file: repo_paths.versioned_file.clone(),
+            version_id,
+            nickname: new_nickname.to_string(),
+        },
+    );
+
+    Ok(RenameVersionResult::Ok)
+}
+
+// Words of letters and digits joined by single dashes. Anything that reads as a version ID or a head offset is left to those.
+fn valid_nickname(nickname: &str) -> bool {
+    nickname.split('-').all(|word| !word.is_empty() && word.chars().all(char::is_alphanumeric)) && VersionId::from_bs58(nickname).is_none()
+}
+
+pub enum SetAttributeResult {
+    Ok,
+    InvalidTarget,
+    InvalidKey,
+    AttributeNotSet,
+}
+
+// Attributes are not part of the version content, so they can be changed on any version, like its description.
+// A value of None removes the attribute.
+pub fn set_attribute(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str, key: &str, value: Option<&str>) -> BiverResult<SetAttributeResult> {
+    let target_version_id = match version(repo_data, target) {
+        VersionResult::InvalidTarget => return Ok(SetAttributeResult::InvalidTarget),
+        VersionResult::Ok(version) => version.id,
+    };
+
+    if !valid_attribute_key(key) {
+        return Ok(SetAttributeResult::InvalidKey);
     }
 
-    let erased_versions_have_multi_parents = erased_versions.iter().any(|v| repo_data.iter_children(v.id).count_is_at_least(2));
-    if erased_versions_have_multi_parents {
-        return Ok(ResetResult::CannotLeaveOrphans);
+    let target_version = repo_data.version_mut(target_version_id).expect("Resolved version must exist");
+
+    match value {
+        Some(value) => {
+            target_version.attributes.insert(key.to_string(), value.to_string());
+        }
+        None => {
+            if target_version.attributes.remove(key).is_none() {
+                return Ok(SetAttributeResult::AttributeNotSet);
+            }
+        }
     }
 
-    let erased_version_ids: Vec<_> = erased_versions.iter().map(|v| v.id).collect();
+    repository_io::write_version_record(repo_paths, target_version)?;
 
-    repo_data.versions.retain(|v| !erased_version_ids.contains(&v.id));
-    repo_data.branches.insert(branch.to_string(), target_version_id);
+    repository_io::write_data(repo_paths, repo_data)?;
 
-    repository_io::write_data(repo_paths, &repo_data)?;
+    Ok(SetAttributeResult::Ok)
+}
+
+// Keys are shown as key=value badges and matched by --where key=value, so they cannot contain '=' or whitespace
+fn valid_attribute_key(key: &str) -> bool {
+    !key.is_empty() && !key.contains(|c: char| c == '=' || c.is_whitespace())
+}
+
+pub enum AddNoteResult {
+    Ok,
+    InvalidTarget,
+    EmptyNote,
+}
+
+pub fn add_note(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str, text: &str) -> BiverResult<AddNoteResult> {
+    let target_version_id = match version(repo_data, target) {
+        VersionResult::InvalidTarget => return Ok(AddNoteResult::InvalidTarget),
+        VersionResult::Ok(version) => version.id,
+    };
+
+    if text.trim().is_empty() {
+        return Ok(AddNoteResult::EmptyNote);
+    }
+
+    let target_version = repo_data.version_mut(target_version_id).expect("Resolved version must exist");
+
+    target_version.notes.push(Note {
+        creation_time: Utc::now(),
+        author: env.author.clone(),
+        text: text.to_string(),
+    });
+
+    repository_io::write_version_record(repo_paths, target_version)?;
+
+    repository_io::write_data(repo_paths, repo_data)?;
+
+    events::emit(
+        env,
+        RepositoryEvent::NoteAdded {
+            versioned_file: repo_paths.versioned_file.clone(),
+            version_id: target_version_id,
+        },
+    );
+
+    Ok(AddNoteResult::Ok)
+}
+
+pub enum ReviewResult {
+    Ok,
+    InvalidTarget,
+    AlreadyInState,
+    // Approving and rejecting is for submitted versions, a rejected one is submitted again once it is fixed
+    NotSubmitted(Option<ReviewState>),
+}
+
+pub fn review(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str, state: ReviewState, reviewer: &str, note: &str) -> BiverResult<ReviewResult> {
+    let target_version_id = match version(repo_data, target) {
+        VersionResult::InvalidTarget => return Ok(ReviewResult::InvalidTarget),
+        VersionResult::Ok(version) => version.id,
+    };
+
+    let target_version = repo_data.version_mut(target_version_id).expect("Resolved version must exist");
+    let current_state = target_version.review().map(|r| r.state);
+
+    if current_state == Some(state) {
+        return Ok(ReviewResult::AlreadyInState);
+    }
+
+    if state != ReviewState::Submitted && current_state != Some(ReviewState::Submitted) {
+        return Ok(ReviewResult::NotSubmitted(current_state));
+    }
+
+    target_version.reviews.push(Review {
+        state,
+        creation_time: Utc::now(),
+        reviewer: reviewer.to_string(),
+        note: note.trim().to_string(),
+    });
+
+    repository_io::write_version_record(repo_paths, target_version)?;
+
+    repository_io::write_data(repo_paths, repo_data)?;
+
+    events::emit(
+        env,
+        RepositoryEvent::Reviewed {
+            versioned_file: repo_paths.versioned_file.clone(),
+            version_id: target_version_id,
+            state,
+        },
+    );
+
+    Ok(ReviewResult::Ok)
+}
+
+pub enum VerifySignaturesResult<'a> {
+    Ok(Vec<(&'a Version, SignatureStatus)>),
+    InvalidTrustedKey(PathBuf),
+    MinisignNotFound,
+}
+
+pub fn verify_signatures<'a>(env: &Env, repo_paths: &RepositoryPaths, repo_data: &'a RepositoryData, trusted_keys: &[PathBuf]) -> BiverResult<VerifySignaturesResult<'a>> {
+    let mut trusted_key_ids = Vec::new();
+    for trusted_key in trusted_keys {
+        match signing::read_public_key(trusted_key)?.and_then(|public_key| signing::key_id(&public_key)) {
+            Some(key_id) => trusted_key_ids.push(key_id),
+            None => return Ok(VerifySignaturesResult::InvalidTrustedKey(trusted_key.clone())),
+        }
+    }
+
+    if repo_data.versions.iter().any(|v| v.signature.is_some()) && !minisign::ready(env) {
+        return Ok(VerifySignaturesResult::MinisignNotFound);
+    }
+
+    let mut versions: Vec<&Version> = repo_data.versions.iter().collect();
+    versions.sort_by_key(|v| v.creation_time);
+
+    let mut statuses = Vec::new();
+    for version in versions {
+        statuses.push((version, signing::verify(env, repo_paths, version, &trusted_key_ids)?));
+    }
+
+    Ok(VerifySignaturesResult::Ok(statuses))
+}
+
+pub fn has_uncommitted_changes(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, trust_stat_cache: bool) -> BiverResult<bool> {
+    let versioned_file_metadata = fs::metadata(&repo_paths.versioned_file)?;
+    let head_version = repo_data.head_version();
+
+    if versioned_file_metadata.len() != head_version.versioned_file_length {
+        return Ok(true);
+    }
+
+    let current_hash = stat_cache::versioned_file_hash(env, repo_paths, repo_data.hash_algorithm, trust_stat_cache)?.hash;
+
+    Ok(head_version.versioned_file_hash != current_hash)
+}
+
+pub struct StatusSummary {
+    pub version_count: usize,
+    pub repository_size: u64,
+    pub versioned_file_size: u64,
+    // Both None without uncommitted changes
+    pub uncommitted_size_change: Option<i128>,
+    pub uncommitted_changed_bytes: Option<u64>,
+    pub editing_lock: Option<EditingLock>,
+    pub editing_lock_is_own: bool,
+}
+
+pub fn status_summary(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, has_uncommitted_changes: bool) -> BiverResult<StatusSummary> {
+    let versioned_file_size = fs::metadata(&repo_paths.versioned_file)?.len();
+    let editing_lock = repository_io::read_editing_lock(repo_paths)?;
+
+    let uncommitted_changed_bytes = if has_uncommitted_changes {
+        let head_content = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "status.tmp"));
+        extract_version_content(env, repo_paths, repo_data, repo_data.head_version(), head_content.path())?;
+        Some(diff::changed_bytes(head_content.path(), &repo_paths.versioned_file)?)
+    } else {
+        None
+    };
+
+    Ok(StatusSummary {
+        editing_lock_is_own: editing_lock.as_ref().is_some_and(|lock| lock.is_own(env)),
+        editing_lock,
+        version_count: repo_data.versions.len(),
+        repository_size: blob_inventory(repo_paths, repo_data)?.iter().map(|e| e.size).sum(),
+        versioned_file_size,
+        uncommitted_size_change: has_uncommitted_changes.then(|| versioned_file_size as i128 - repo_data.head_version().versioned_file_length as i128),
+        uncommitted_changed_bytes,
+    })
+}
+
+// Returns where the overwritten versioned file was kept, if the trash is on and the file had anything head does not
+pub fn discard(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<Option<PathBuf>> {
+    let head_version = repo_data.head_version();
+    let mut trashed_path = None;
+
+    if effects::restore_versioned_file(env, head_version.id) {
+        if !matches!(env.trash, TrashMode::Off) && fs::exists(&repo_paths.versioned_file)? && has_uncommitted_changes(env, repo_paths, repo_data, true)? {
+            trashed_path = trash::keep_versioned_file(env, repo_paths)?;
+        }
+
+        restore_version_file(env, repo_paths, repo_data, head_version, &repo_paths.versioned_file)?;
+    }
+
+    Ok(trashed_path)
+}
+
+pub enum ResetResult {
+    Ok,
+    HeadMustBeBranch,
+    InvalidTarget,
+    TargetNotAncestor,
+    CannotLeaveOrphans,
+    BranchProtected,
+    VersionPinned(VersionId),
+}
+
+// Blobs only the erased versions use are deleted along with them unless keep_blobs is set.
+// Full blobs that patches of the remaining versions are based on stay.
+pub fn reset(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str, force: bool, keep_blobs: bool) -> BiverResult<ResetResult> {
+    let Some(branch) = repo_data.head.branch() else {
+        return Ok(ResetResult::HeadMustBeBranch);
+    };
+
+    if repo_data.is_protected(branch) && !force {
+        return Ok(ResetResult::BranchProtected);
+    }
+
+    let Some(target_version) = resolve_version_target(repo_data, target) else {
+        return Ok(ResetResult::InvalidTarget);
+    };
+    let target_version_id = target_version.id;
+
+    let erased_versions: Vec<_> = repo_data.iter_head_and_ancestors().take_while(|v| v.id != target_version.id).collect();
+
+    let erased_versions_have_root = erased_versions.iter().any(|v| v.is_root());
+    if erased_versions_have_root {
+        return Ok(ResetResult::TargetNotAncestor);
+    }
+
+    let head_has_children = repo_data.iter_children(repo_data.head_version().id).count_is_at_least(1);
+    if head_has_children {
+        return Ok(ResetResult::CannotLeaveOrphans);
+    }
+
+    let erased_versions_have_multi_parents = erased_versions.iter().any(|v| repo_data.iter_children(v.id).count_is_at_least(2));
+    if erased_versions_have_multi_parents {
+        return Ok(ResetResult::CannotLeaveOrphans);
+    }
+
+    // Other branches ending on the erased versions would be left pointing at nothing
+    let erased_versions_have_other_branches = erased_versions.iter().any(|v| repo_data.branches.iter().any(|(b, leaf)| b != branch && *leaf == v.id));
+    if erased_versions_have_other_branches {
+        return Ok(ResetResult::CannotLeaveOrphans);
+    }
+
+    if let Some(pinned_version) = erased_versions.iter().find(|v| repo_data.is_pinned(v.id)) {
+        return Ok(ResetResult::VersionPinned(pinned_version.id));
+    }
+
+    let erased_version_ids: HashSet<_> = erased_versions.iter().map(|v| v.id).collect();
+
+    let branch = branch.to_string();
+    let erased_versions = repo_data.remove_versions_and_reparent(&erased_version_ids);
+    repo_data.branches.insert(branch.clone(), target_version_id);
+
+    let unreferenced_blob_file_names: Vec<_> = if keep_blobs {
+        Vec::new()
+    } else {
+        let referenced_blob_file_names = repo_data.referenced_blob_file_names();
+        erased_versions
+            .iter()
+            .flat_map(|v| v.blob_file_names())
+            .filter(|n| !referenced_blob_file_names.contains(n))
+            .map(|n| n.to_string())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect()
+    };
+
+    for blob_file_name in &unreferenced_blob_file_names {
+        repo_data.blob_checksums.remove(blob_file_name);
+    }
+
+    effects::write_data(env, repo_paths, repo_data)?;
+
+    if !keep_blobs {
+        delete_erased_objects(env, repo_paths, &unreferenced_blob_file_names)?;
+
+        let erased_version_record_file_names: Vec<_> = erased_versions.iter().map(|v| blob_store::version_record_file_name(v.id)).collect();
+        delete_erased_objects(env, repo_paths, &erased_version_record_file_names)?;
+    }
+
+    events::emit(
+        env,
+        RepositoryEvent::Reset {
+            versioned_file: repo_paths.versioned_file.clone(),
+            branch,
+            version_id: target_version_id,
+            erased_version_ids: erased_versions.iter().map(|v| v.id).collect(),
+        },
+    );
+
+    Ok(ResetResult::Ok)
+}
+
+pub enum PruneResult {
+    Ok { pruned_version_count: usize, deleted_blob_count: usize },
+    NothingToPrune,
+    // Versions of this branch would be pruned
+    BranchProtected(String),
+}
+
+pub fn prune_candidates(repo_data: &RepositoryData, policy: &RetentionPolicy) -> Vec<VersionId> {
+    retention::versions_to_prune(repo_data, policy, Utc::now())
+}
+
+// Pruning rewrites the history of every branch whose leaf descends from a pruned version
+pub fn protected_branch_pruned<'a>(repo_data: &'a RepositoryData, pruned_ids: &[VersionId]) -> Option<&'a str> {
+    repo_data
+        .protected_branches
+        .iter()
+        .find(|branch| {
+            repo_data
+                .branches
+                .get(*branch)
+                .is_some_and(|leaf_id| repo_data.iter_version_and_ancestors(*leaf_id).any(|v| pruned_ids.contains(&v.id)))
+        })
+        .map(String::as_str)
+}
+
+pub fn prune(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, policy: &RetentionPolicy, force: bool) -> BiverResult<PruneResult> {
+    let candidates = prune_candidates(repo_data, policy);
+
+    if candidates.is_empty() {
+        return Ok(PruneResult::NothingToPrune);
+    }
+
+    if !force && let Some(branch) = protected_branch_pruned(repo_data, &candidates) {
+        return Ok(PruneResult::BranchProtected(branch.to_string()));
+    }
+
+    let pruned_ids: HashSet<_> = candidates.into_iter().collect();
+
+    let pruned_versions = repo_data.remove_versions_and_reparent(&pruned_ids);
+
+    let referenced_blob_file_names = repo_data.referenced_blob_file_names();
+    let unreferenced_blob_file_names: Vec<_> = pruned_versions
+        .iter()
+        .flat_map(|v| v.blob_file_names())
+        .filter(|n| !referenced_blob_file_names.contains(n))
+        .map(|n| n.to_string())
+        .collect();
+
+    for blob_file_name in &unreferenced_blob_file_names {
+        repo_data.blob_checksums.remove(blob_file_name);
+    }
+
+    effects::write_data(env, repo_paths, repo_data)?;
+
+    let deleted_blob_count = delete_erased_objects(env, repo_paths, &unreferenced_blob_file_names)?;
+
+    let pruned_version_record_file_names: Vec<_> = pruned_versions.iter().map(|v| blob_store::version_record_file_name(v.id)).collect();
+    delete_erased_objects(env, repo_paths, &pruned_version_record_file_names)?;
+
+    events::emit(
+        env,
+        RepositoryEvent::Pruned {
+            versioned_file: repo_paths.versioned_file.clone(),
+            erased_version_ids: pruned_versions.iter().map(|v| v.id).collect(),
+        },
+    );
+
+    Ok(PruneResult::Ok {
+        pruned_version_count: pruned_versions.len(),
+        deleted_blob_count,
+    })
+}
+
+pub enum GcResult {
+    Ok { deleted_blob_count: usize, freed_bytes: u64 },
+    NothingToCollect,
+}
+
+// Returns how many orphaned versions were older than the grace period and dropped, leaving their blobs to gc
+pub fn expire_orphaned_versions(repo_data: &mut RepositoryData, grace_period: TimeDelta) -> usize {
+    let expiry_time = Utc::now() - grace_period;
+    let orphaned_version_count = repo_data.orphaned_versions.len();
+
+    // A pinned head swapped out by recover stays recoverable for good
+    let pinned_versions = &repo_data.pinned_versions;
+    repo_data
+        .orphaned_versions
+        .retain(|o| o.orphaned_time > expiry_time || pinned_versions.contains(&o.version.id));
+
+    orphaned_version_count - repo_data.orphaned_versions.len()
+}
+
+pub enum RecoverResult {
+    Ok { branch: String, swapped: bool },
+    NothingToRecover,
+    InvalidTarget,
+    ParentMissing,
+    BlockedByUncommittedChanges,
+    // The recovered version would take the place of the leaf of this branch
+    BranchProtected(String),
+}
+
+// When the version that replaced the orphaned one is still the leaf of its branch, the two swap places, which undoes the amend.
+// Otherwise the orphaned version is put on a new branch.
+pub fn recover(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: Option<&str>, force: bool) -> BiverResult<RecoverResult> {
+    let orphaned_index = match target {
+        None if repo_data.orphaned_versions.is_empty() => return Ok(RecoverResult::NothingToRecover),
+        None => repo_data.orphaned_versions.len() - 1,
+        Some(target) => match repo_data
+            .orphaned_versions
+            .iter()
+            .position(|o| o.version.nickname == target || o.version.id.bs58() == target)
+        {
+            Some(orphaned_index) => orphaned_index,
+            None => return Ok(RecoverResult::InvalidTarget),
+        },
+    };
+
+    let orphaned = &repo_data.orphaned_versions[orphaned_index];
+
+    // The replacement may have been reset or pruned away since, even with its branch still pointing at it
+    let replacement_index = repo_data
+        .versions
+        .iter()
+        .position(|v| v.id == orphaned.replaced_by)
+        .filter(|_| repo_data.branches.get(&orphaned.branch) == Some(&orphaned.replaced_by) && repo_data.iter_children(orphaned.replaced_by).next().is_none());
+    let swappable = replacement_index.is_some();
+
+    // A root version can only take the place of the root that replaced it, there cannot be two
+    let parent_exists = match orphaned.version.parent {
+        Some(parent_id) => repo_data.version(parent_id).is_some(),
+        None => swappable,
+    };
+
+    if !parent_exists {
+        return Ok(RecoverResult::ParentMissing);
+    }
+
+    if swappable && repo_data.is_protected(&orphaned.branch) && !force {
+        return Ok(RecoverResult::BranchProtected(orphaned.branch.clone()));
+    }
+
+    let head_on_branch = swappable && repo_data.head.branch() == Some(orphaned.branch.as_str());
+
+    if head_on_branch && has_uncommitted_changes(env, repo_paths, repo_data, false)? {
+        return Ok(RecoverResult::BlockedByUncommittedChanges);
+    }
+
+    let orphaned = repo_data.orphaned_versions.remove(orphaned_index);
+    let recovered_id = orphaned.version.id;
+
+    let branch = if let Some(replacement_index) = replacement_index {
+        let replacement = repo_data.versions.remove(replacement_index);
+
+        repo_data.orphaned_versions.push(OrphanedVersion {
+            version: replacement,
+            branch: orphaned.branch.clone(),
+            replaced_by: recovered_id,
+            orphaned_time: Utc::now(),
+        });
+
+        orphaned.branch
+    } else {
+        let base_name = format!("recovered-{}", orphaned.version.nickname);
+        let mut branch = base_name.clone();
+        let mut suffix = 2;
+        while repo_data.branches.contains_key(&branch) {
+            branch = format!("{}-{}", base_name, suffix);
+            suffix += 1;
+        }
+        branch
+    };
+
+    if !repo_data.branches.contains_key(&branch) {
+        repo_data.branch_metadata.insert(branch.clone(), new_branch_metadata(env));
+    }
+    repo_data.branches.insert(branch.clone(), recovered_id);
+    repo_data.versions.push(orphaned.version);
+
+    effects::write_data(env, repo_paths, repo_data)?;
+
+    if head_on_branch {
+        restore_version_file(env, repo_paths, repo_data, repo_data.head_version(), &repo_paths.versioned_file)?;
+    }
+
+    events::emit(
+        env,
+        RepositoryEvent::Recovered {
+            versioned_file: repo_paths.versioned_file.clone(),
+            version_id: recovered_id,
+            branch: branch.clone(),
+        },
+    );
+
+    Ok(RecoverResult::Ok { branch, swapped: swappable })
+}
+
+// Called once data.json is written, since writing it may rotate the data from before the operation into a backup
+fn delete_erased_objects(env: &Env, repo_paths: &RepositoryPaths, object_names: &[String]) -> io::Result<usize> {
+    let backup_object_names = blob_store::backup_referenced_object_names(repo_paths)?;
+    effects::delete_blobs(env, repo_paths, object_names.iter().map(|n| n.as_str()).filter(|n| !backup_object_names.contains(*n)))
+}
+
+pub fn gc_candidates(repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<Vec<String>> {
+    Ok(blob_store::unreferenced_blob_file_names(repo_paths, repo_data)?)
+}
+
+pub fn gc(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, jobs: usize) -> BiverResult<GcResult> {
+    let unreferenced_blob_file_names = gc_candidates(repo_paths, repo_data)?;
+
+    if unreferenced_blob_file_names.is_empty() {
+        return Ok(GcResult::NothingToCollect);
+    }
+
+    let mut freed_bytes = 0;
+    for length in parallel::map(jobs, &unreferenced_blob_file_names, |n| fs::metadata(repo_paths.blob_path(n)).map(|m| m.len())) {
+        freed_bytes += length?;
+    }
+
+    let mut deleted_blob_count = 0;
+    for deleted_count in parallel::map(jobs, &unreferenced_blob_file_names, |n| effects::delete_blobs(env, repo_paths, [n.as_str()])) {
+        deleted_blob_count += deleted_count?;
+    }
+
+    let referenced_blob_file_names: HashSet<String> = repo_data.referenced_blob_file_names().into_iter().map(|n| n.to_string()).collect();
+    repo_data.blob_checksums.retain(|n, _| referenced_blob_file_names.contains(n));
+    effects::write_data(env, repo_paths, repo_data)?;
+
+    events::emit(
+        env,
+        RepositoryEvent::GarbageCollected {
+            versioned_file: repo_paths.versioned_file.clone(),
+            deleted_blob_count,
+        },
+    );
+
+    Ok(GcResult::Ok { deleted_blob_count, freed_bytes })
+}
+
+pub enum VerifyProblem {
+    MissingBlob { blob_file_name: String },
+    CorruptedBlob { blob_file_name: String },
+    ContentMismatch { version_id: VersionId },
+    ReconstructionFailed { version_id: VersionId, error_message: String },
+}
+
+pub struct VerifyReport {
+    pub checked_blob_count: usize,
+    pub checked_version_count: usize,
+    pub recorded_checksum_count: usize,
+    pub recorded_digest_count: usize,
+    pub problems: Vec<VerifyProblem>,
+}
+
+// Blobs are hashed and versions reconstructed on several threads, each version into a temp file of its own
+pub fn verify(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, jobs: usize) -> BiverResult<VerifyReport> {
+    let mut problems = Vec::new();
+    let mut blobs_without_checksum = Vec::new();
+    let mut damaged_blob_file_names = HashSet::new();
+
+    let mut blob_file_names: Vec<_> = repo_data.referenced_blob_file_names().into_iter().collect();
+    blob_file_names.sort();
+
+    let blob_statuses = parallel::map(jobs, &blob_file_names, |n| integrity::blob_status(env, repo_paths, repo_data, n));
+
+    for (blob_file_name, blob_status) in blob_file_names.iter().zip(blob_statuses) {
+        match blob_status? {
+            BlobStatus::Ok => {}
+            BlobStatus::NoChecksum => blobs_without_checksum.push(blob_file_name.to_string()),
+            BlobStatus::Missing => {
+                damaged_blob_file_names.insert(blob_file_name.to_string());
+                problems.push(VerifyProblem::MissingBlob {
+                    blob_file_name: blob_file_name.to_string(),
+                });
+            }
+            BlobStatus::Corrupted => {
+                damaged_blob_file_names.insert(blob_file_name.to_string());
+                problems.push(VerifyProblem::CorruptedBlob {
+                    blob_file_name: blob_file_name.to_string(),
+                });
+            }
+        }
+    }
+
+    let checked_blob_count = blob_file_names.len();
+    let mut unreliable_blob_file_names = HashSet::new();
+
+    let reconstructable_versions: Vec<&Version> = repo_data
+        .versions
+        .iter()
+        .filter(|v| !v.blob_file_names().iter().any(|n| damaged_blob_file_names.contains(*n)))
+        .collect();
+
+    let version_checks = parallel::map(jobs, &reconstructable_versions, |version| {
+        verify_version(env, repo_paths, repo_data.hash_algorithm, version)
+    });
+
+    let mut missing_digests = Vec::new();
+
+    for (version, version_check) in reconstructable_versions.iter().zip(version_checks) {
+        let (version_problem, missing_digest) = version_check?;
+
+        if let Some(version_problem) = version_problem {
+            unreliable_blob_file_names.extend(version.blob_file_names().into_iter().map(|n| n.to_string()));
+            problems.push(version_problem);
+        }
+        if let Some(missing_digest) = missing_digest {
+            missing_digests.push((version.id, missing_digest));
+        }
+    }
+
+    // A repository opened read-only is only checked, what would be recorded is left for a later verify
+    if env.read_only {
+        missing_digests.clear();
+        blobs_without_checksum.clear();
+    }
+
+    for (version_id, digest) in &missing_digests {
+        if let Some(version) = repo_data.version_mut(*version_id) {
+            version.versioned_file_digest = Some(*digest);
+        }
+    }
+
+    let checked_version_count = repo_data.versions.len();
+
+    blobs_without_checksum.retain(|n| !unreliable_blob_file_names.contains(n));
+
+    let checksums = parallel::map(jobs, &blobs_without_checksum, |n| integrity::blob_checksum(env, repo_paths, repo_data.hash_algorithm, n));
+
+    for (blob_file_name, checksum) in blobs_without_checksum.iter().zip(checksums) {
+        repo_data.blob_checksums.insert(blob_file_name.clone(), checksum?);
+    }
+
+    if !blobs_without_checksum.is_empty() || !missing_digests.is_empty() {
+        effects::write_data(env, repo_paths, repo_data)?;
+    }
+
+    Ok(VerifyReport {
+        checked_blob_count,
+        checked_version_count,
+        recorded_checksum_count: blobs_without_checksum.len(),
+        recorded_digest_count: missing_digests.len(),
+        problems,
+    })
+}
+
+// Along with the problem found, the full digest of a version that was committed before digests were kept
+fn verify_version(env: &Env, repo_paths: &RepositoryPaths, hash_algorithm: HashAlgorithm, version: &Version) -> io::Result<(Option<VerifyProblem>, Option<Digest>)> {
+    let reconstructed_file = TemporaryFile::new(repo_paths.unique_temp_file_path(env, &format!("verify-{}.tmp", version.id.bs58())));
+    let mut missing_digest = None;
+
+    let problem = match repository_io::extract_version_content(env, repo_paths, &version.content_blob, reconstructed_file.path()) {
+        Err(error) => Some(VerifyProblem::ReconstructionFailed {
+            version_id: version.id,
+            error_message: error.to_string(),
+        }),
+        Ok(()) => {
+            let file_hash = hash::file_hashes(env, hash_algorithm, &File::open(reconstructed_file.path())?)?;
+            let digest_matches = version.versioned_file_digest.is_none() || version.versioned_file_digest == file_hash.digest;
+
+            if file_hash.hash != version.versioned_file_hash || !digest_matches {
+                Some(VerifyProblem::ContentMismatch { version_id: version.id })
+            } else {
+                missing_digest = file_hash.digest.filter(|_| version.versioned_file_digest.is_none());
+                None
+            }
+        }
+    };
+
+    if !env.read_only
+        && !matches!(problem, Some(VerifyProblem::ReconstructionFailed { .. }))
+        && !fs::exists(repo_paths.blob_path(&blob_store::version_record_file_name(version.id)))?
+    {
+        repository_io::write_version_record(repo_paths, version)?;
+    }
+
+    Ok((problem, missing_digest))
+}
+
+pub enum RepairPlan {
+    NothingToRepair,
+    RestoreBackup(DataBackup),
+    Rebuild(RebuiltData),
+    Impossible,
+}
+
+pub fn repair_plan(env: &Env, repo_paths: &RepositoryPaths) -> BiverResult<RepairPlan> {
+    if !fs::exists(&repo_paths.repository_dir)? {
+        return categorized_error(BiverErrorCategory::NotInitialized, "Not initialized");
+    }
+
+    if repair::read_valid_data(&repo_paths.data_file).is_some() {
+        return Ok(RepairPlan::NothingToRepair);
+    }
+
+    if let Some(backup) = repair::freshest_valid_backup(repo_paths)? {
+        return Ok(RepairPlan::RestoreBackup(backup));
+    }
+
+    match repair::rebuild_from_version_records(env, repo_paths)? {
+        Some(rebuilt) => Ok(RepairPlan::Rebuild(rebuilt)),
+        None => Ok(RepairPlan::Impossible),
+    }
+}
+
+pub fn repair(repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<Option<PathBuf>> {
+    let corrupt_data_file = if fs::exists(&repo_paths.data_file)? {
+        let corrupt_data_file = repo_paths.file_path(CORRUPT_DATA_FILE_NAME);
+        fs::rename(&repo_paths.data_file, &corrupt_data_file)?;
+        Some(corrupt_data_file)
+    } else {
+        None
+    };
+
+    repository_io::write_data(repo_paths, repo_data)?;
+
+    Ok(corrupt_data_file)
+}
+
+pub enum MoveResult {
+    Ok,
+    NotInitialized,
+    VersionedFileMissing,
+    DestinationExists,
+    DestinationRepositoryExists,
+}
+
+pub fn move_versioned_file(old_repo_paths: &RepositoryPaths, new_repo_paths: &RepositoryPaths) -> BiverResult<MoveResult> {
+    let RepositoryDataResult::Initialized(mut repo_data) = repository_io::read_data(old_repo_paths)? else {
+        return Ok(MoveResult::NotInitialized);
+    };
+
+    if fs::exists(&new_repo_paths.repository_dir)? {
+        return Ok(MoveResult::DestinationRepositoryExists);
+    }
+
+    // The file may already have been renamed by hand, in which case only the repository follows it
+    let versioned_file_renamed = if fs::exists(&old_repo_paths.versioned_file)? {
+        if fs::exists(&new_repo_paths.versioned_file)? {
+            return Ok(MoveResult::DestinationExists);
+        }
+
+        fs::rename(&old_repo_paths.versioned_file, &new_repo_paths.versioned_file)?;
+        true
+    } else if !fs::exists(&new_repo_paths.versioned_file)? {
+        return Ok(MoveResult::VersionedFileMissing);
+    } else {
+        false
+    };
+
+    // The file goes back to where it was, so that it is not separated from its repository
+    if let Err(e) = fs::rename(&old_repo_paths.repository_dir, &new_repo_paths.repository_dir) {
+        if versioned_file_renamed {
+            let _ = fs::rename(&new_repo_paths.versioned_file, &old_repo_paths.versioned_file);
+        }
+        return Err(e.into());
+    }
+
+    repo_data.versioned_file_name = new_repo_paths.versioned_file_name();
+    repository_io::write_data(new_repo_paths, &repo_data)?;
+
+    Ok(MoveResult::Ok)
+}
+
+pub enum CloneResult {
+    Ok,
+    NotInitialized,
+    VersionedFileMissing,
+    DestinationExists,
+    DestinationRepositoryExists,
+    // Blobs missing or damaged in the copy, which is then removed
+    ValidationFailed(Vec<String>),
+}
+
+// The copy keeps every ID, so it can push to and pull from the same remotes as the original. Caches, the log and
+// temporary files stay behind.
+pub fn clone_versioned_file(env: &Env, source_repo_paths: &RepositoryPaths, destination_repo_paths: &RepositoryPaths) -> BiverResult<CloneResult> {
+    let RepositoryDataResult::Initialized(mut repo_data) = repository_io::read_data(source_repo_paths)? else {
+        return Ok(CloneResult::NotInitialized);
+    };
+
+    if !fs::exists(&source_repo_paths.versioned_file)? {
+        return Ok(CloneResult::VersionedFileMissing);
+    }
+
+    if fs::exists(&destination_repo_paths.versioned_file)? {
+        return Ok(CloneResult::DestinationExists);
+    }
+
+    if fs::exists(&destination_repo_paths.repository_dir)? {
+        return Ok(CloneResult::DestinationRepositoryExists);
+    }
+
+    // The copy is made next to the destination under a temporary name and only renamed into place once it checked out,
+    // so an interrupted clone leaves nothing that looks like a repository behind
+    let staging_file_name = format!(".{}-{}.tmp", Uuid::new_v4().simple(), destination_repo_paths.versioned_file_name().unwrap_or_default());
+    let staging_repo_paths = RepositoryPaths::from_versioned_file_path(destination_repo_paths.versioned_file.with_file_name(staging_file_name));
+
+    repo_data.versioned_file_name = destination_repo_paths.versioned_file_name();
+
+    let result = stage_clone(env, source_repo_paths, &staging_repo_paths, &repo_data).and_then(|failed_blob_file_names| {
+        if !failed_blob_file_names.is_empty() {
+            return Ok(CloneResult::ValidationFailed(failed_blob_file_names));
+        }
+
+        fs::rename(&staging_repo_paths.repository_dir, &destination_repo_paths.repository_dir)?;
+        if let Err(e) = fs::rename(&staging_repo_paths.versioned_file, &destination_repo_paths.versioned_file) {
+            let _ = fs::remove_dir_all(&destination_repo_paths.repository_dir);
+            return Err(e.into());
+        }
+
+        Ok(CloneResult::Ok)
+    });
+
+    if !matches!(result, Ok(CloneResult::Ok)) {
+        let _ = fs::remove_dir_all(&staging_repo_paths.repository_dir);
+        let _ = fs::remove_file(&staging_repo_paths.versioned_file);
+    }
+
+    result
+}
+
+// Returns the blobs of the copy that are missing or damaged. Blobs without a recorded checksum are checked by
+// reconstructing the versions that use them.
+fn stage_clone(env: &Env, source_repo_paths: &RepositoryPaths, staging_repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<Vec<String>> {
+    copy_repository_dir(&source_repo_paths.repository_dir, &staging_repo_paths.repository_dir)?;
+    fs::copy(&source_repo_paths.versioned_file, &staging_repo_paths.versioned_file)?;
+
+    let mut failed_blob_file_names = BTreeSet::new();
+    let mut unchecked_blob_file_names = HashSet::new();
+
+    for blob_file_name in repo_data.referenced_blob_file_names() {
+        match integrity::blob_status(env, staging_repo_paths, repo_data, blob_file_name)? {
+            BlobStatus::Ok => {}
+            BlobStatus::NoChecksum => {
+                unchecked_blob_file_names.insert(blob_file_name);
+            }
+            BlobStatus::Missing | BlobStatus::Corrupted => {
+                failed_blob_file_names.insert(blob_file_name.to_string());
+            }
+        }
+    }
+
+    for version in repo_data.versions_and_orphans() {
+        let blob_file_names = version.blob_file_names();
+        let needs_check = blob_file_names.iter().any(|n| unchecked_blob_file_names.contains(n));
+        if !needs_check || blob_file_names.iter().any(|n| failed_blob_file_names.contains(*n)) {
+            continue;
+        }
+
+        let (problem, _) = verify_version(env, staging_repo_paths, repo_data.hash_algorithm, version)?;
+        if problem.is_some() {
+            failed_blob_file_names.extend(blob_file_names.into_iter().filter(|n| unchecked_blob_file_names.contains(n)).map(|n| n.to_string()));
+        }
+    }
+
+    repository_io::write_data(staging_repo_paths, repo_data)?;
+
+    Ok(failed_blob_file_names.into_iter().collect())
+}
+
+fn copy_repository_dir(source: &Path, destination: &Path) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+
+    for dir_entry in fs::read_dir(source)? {
+        let dir_entry = dir_entry?;
+        let file_name = dir_entry.file_name().to_string_lossy().to_string();
+        let destination_path = destination.join(&file_name);
+
+        let left_behind = file_name == stat_cache::STAT_CACHE_FILE_NAME
+            || file_name == logging::LOG_FILE_NAME
+            || file_name == logging::ROTATED_LOG_FILE_NAME
+            || file_name == reconstruction_cache::RECONSTRUCTION_CACHE_DIR_NAME
+            || file_name.ends_with(PENDING_PREVIEW_FILE_NAME)
+            || file_name == repository_io::EDITING_LOCK_FILE_NAME
+            || file_name.ends_with(".tmp");
+        if left_behind {
+            continue;
+        }
+
+        if dir_entry.file_type()?.is_dir() {
+            copy_repository_dir(&dir_entry.path(), &destination_path)?;
+        } else {
+            fs::copy(dir_entry.path(), &destination_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub enum CheckOutResult {
+    Ok {
+        stashed: Option<VersionId>,
+        // Changes stashed when head last left the checked out branch or version
+        restorable_stash: Option<VersionId>,
+    },
+    InvalidTarget,
+}
+
+// Without autostash, uncommitted changes stay in the versioned file and are carried over to the new head.
+// With it, they are stashed on the autostash branch first, and the file is set to the new head like without changes.
+pub fn check_out(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str, autostash: bool) -> BiverResult<CheckOutResult> {
+    let has_uncommitted_changes = has_uncommitted_changes(env, repo_paths, repo_data, false)?;
+
+    let new_head = match resolve_target(repo_data, target) {
+        TargetResult::Invalid => return Ok(CheckOutResult::InvalidTarget),
+        TargetResult::Branch(branch) => Head::Branch(branch.to_string()),
+        TargetResult::Version(version) => Head::Version(version.id),
+    };
+
+    let mut autostash_entries = repository_io::read_autostash_entries(repo_paths)?;
+
+    let stashed = if autostash && has_uncommitted_changes {
+        match stash_version(env, repo_paths, repo_data, AUTOSTASH_BRANCH)? {
+            StashResult::Ok(version_id) => {
+                autostash_entries.push(AutostashEntry {
+                    head: repo_data.head.clone(),
+                    version_id,
+                });
+                repository_io::write_autostash_entries(repo_paths, &autostash_entries)?;
+                Some(version_id)
+            }
+            StashResult::NothingToStash => None,
+            StashResult::InvalidBranchName => unreachable!("The autostash branch name is valid"),
+        }
+    } else {
+        None
+    };
+
+    let previous_head = mem::replace(&mut repo_data.head, new_head);
+
+    let versioned_file_restored = !has_uncommitted_changes || stashed.is_some();
+
+    if let Err(e) = write_checked_out_head(env, repo_paths, repo_data, versioned_file_restored) {
+        repo_data.head = previous_head;
+        return Err(e);
+    }
+
+    events::emit(
+        env,
+        RepositoryEvent::CheckedOut {
+            versioned_file: repo_paths.versioned_file.clone(),
+            head: repo_data.head.clone(),
+        },
+    );
+
+    // Changes carried over are not overwritten by offering a stash
+    let restorable_stash = if versioned_file_restored {
+        autostash_entries
+            .iter()
+            .rev()
+            .find(|entry| entry.head == repo_data.head && repo_data.version(entry.version_id).is_some())
+            .map(|entry| entry.version_id)
+    } else {
+        None
+    };
+
+    Ok(CheckOutResult::Ok { stashed, restorable_stash })
+}
+
+// Head only moves once the versioned file has the content of the version checked out. Nothing new is stored, so the
+// data file is written after the transaction is committed rather than before.
+fn write_checked_out_head(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, restore_versioned_file: bool) -> BiverResult<()> {
+    let mut transaction = Transaction::default();
+
+    if restore_versioned_file {
+        stage_version_file(env, repo_paths, &mut transaction, repo_data, repo_data.head_version(), &repo_paths.versioned_file)?;
+    }
+
+    transaction.commit()?;
+    effects::write_data(env, repo_paths, repo_data)?;
+
+    Ok(())
+}
+
+// Restores the stashed changes into the versioned file, or discards them when not restoring so they are no longer
+// offered. Either way the stash stays on the autostash branch.
+pub fn settle_autostash(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, stash_id: VersionId, restore: bool) -> BiverResult<()> {
+    if restore {
+        let stash = repo_data.version(stash_id).expect("Stash must exist");
+        restore_version_file(env, repo_paths, repo_data, stash, &repo_paths.versioned_file)?;
+    }
+
+    let mut autostash_entries = repository_io::read_autostash_entries(repo_paths)?;
+    autostash_entries.retain(|entry| entry.version_id != stash_id);
+    repository_io::write_autostash_entries(repo_paths, &autostash_entries)?;
+
+    Ok(())
+}
+
+pub enum BisectStartResult {
+    Ok,
+    AlreadyBisecting,
+    BlockedByUncommittedChanges,
+}
+
+pub fn bisect_start(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<BisectStartResult> {
+    if repository_io::read_bisect_state(repo_paths)?.is_some() {
+        return Ok(BisectStartResult::AlreadyBisecting);
+    }
+
+    if has_uncommitted_changes(env, repo_paths, repo_data, false)? {
+        return Ok(BisectStartResult::BlockedByUncommittedChanges);
+    }
+
+    let state = BisectState {
+        original_head: repo_data.head.clone(),
+        good: None,
+        bad: None,
+    };
+
+    repository_io::write_bisect_state(repo_paths, &state)?;
+
+    Ok(BisectStartResult::Ok)
+}
+
+pub enum BisectMarkResult {
+    Ok(BisectStep),
+    NotBisecting,
+    InvalidTarget,
+    BlockedByUncommittedChanges,
+    GoodNotAncestorOfBad,
+    // A version marked earlier no longer exists, or the state cannot be read
+    StaleState,
+}
+
+// Marks the target (default: head) and checks out the next version to test, unless the first bad version has been found
+pub fn bisect_mark(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: Option<&str>, good: bool) -> BiverResult<BisectMarkResult> {
+    let mut state = match repository_io::read_bisect_state(repo_paths) {
+        Ok(Some(state)) if !bisect::is_stale(repo_data, &state) => state,
+        Ok(Some(_)) => return Ok(BisectMarkResult::StaleState),
+        Ok(None) => return Ok(BisectMarkResult::NotBisecting),
+        Err(e) if is_unparsable(&e) => return Ok(BisectMarkResult::StaleState),
+        Err(e) => return Err(e.into()),
+    };
+
+    if has_uncommitted_changes(env, repo_paths, repo_data, false)? {
+        return Ok(BisectMarkResult::BlockedByUncommittedChanges);
+    }
+
+    let version_id = match target {
+        None => repo_data.head_version().id,
+        Some(target) => match version(repo_data, target) {
+            VersionResult::InvalidTarget => return Ok(BisectMarkResult::InvalidTarget),
+            VersionResult::Ok(version) => version.id,
+        },
+    };
+
+    if good {
+        state.good = Some(version_id);
+    } else {
+        state.bad = Some(version_id);
+    }
+
+    let Some(step) = bisect::next_step(repo_data, &state) else {
+        return Ok(BisectMarkResult::GoodNotAncestorOfBad);
+    };
+
+    if let BisectStep::Test { version_id, .. } = step {
+        repo_data.head = Head::Version(version_id);
+        repository_io::write_data(repo_paths, repo_data)?;
+        restore_version_file(env, repo_paths, repo_data, repo_data.head_version(), &repo_paths.versioned_file)?;
+    }
+
+    repository_io::write_bisect_state(repo_paths, &state)?;
+
+    Ok(BisectMarkResult::Ok(step))
+}
+
+pub enum BisectResetResult {
+    Ok,
+    NotBisecting,
+    BlockedByUncommittedChanges,
+}
+
+pub fn bisect_reset(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData) -> BiverResult<BisectResetResult> {
+    let state = match repository_io::read_bisect_state(repo_paths) {
+        Ok(Some(state)) => state,
+        Ok(None) => return Ok(BisectResetResult::NotBisecting),
+        // Nothing can be taken from a state that cannot be read, it is only removed and head stays where it is
+        Err(e) if is_unparsable(&e) => {
+            repository_io::delete_bisect_state(repo_paths)?;
+            return Ok(BisectResetResult::Ok);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if has_uncommitted_changes(env, repo_paths, repo_data, false)? {
+        return Ok(BisectResetResult::BlockedByUncommittedChanges);
+    }
+
+    // The original branch may have been deleted while bisecting, head then stays where it is
+    let original_head_exists = match &state.original_head {
+        Head::Branch(branch) => repo_data.branches.contains_key(branch),
+        Head::Version(version_id) => repo_data.version(*version_id).is_some(),
+    };
+
+    if original_head_exists {
+        repo_data.head = state.original_head;
+        repository_io::write_data(repo_paths, repo_data)?;
+        restore_version_file(env, repo_paths, repo_data, repo_data.head_version(), &repo_paths.versioned_file)?;
+    }
+
+    repository_io::delete_bisect_state(repo_paths)?;
+
+    Ok(BisectResetResult::Ok)
+}
+
+fn is_unparsable(error: &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof)
+}
+
+pub enum LockResult {
+    Ok,
+    AlreadyLocked,
+    LockedByOther(EditingLock),
+    // Taken over from someone else with steal
+    Stolen(EditingLock),
+}
+
+pub fn lock(env: &Env, repo_paths: &RepositoryPaths, steal: bool) -> BiverResult<LockResult> {
+    loop {
+        let result = match repository_io::read_editing_lock(repo_paths)? {
+            None => LockResult::Ok,
+            Some(lock) if lock.is_own(env) => return Ok(LockResult::AlreadyLocked),
+            Some(lock) if steal => {
+                remove_editing_lock(repo_paths)?;
+                LockResult::Stolen(lock)
+            }
+            Some(lock) => return Ok(LockResult::LockedByOther(lock)),
+        };
+
+        if repository_io::create_editing_lock(repo_paths, &EditingLock::new(env))? {
+            return Ok(result);
+        }
+
+        // Someone else locked it in between, so it is looked at again
+    }
+}
+
+fn remove_editing_lock(repo_paths: &RepositoryPaths) -> io::Result<()> {
+    match repository_io::delete_editing_lock(repo_paths) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+pub enum UnlockResult {
+    Ok,
+    NotLocked,
+    LockedByOther(EditingLock),
+}
+
+// With steal, a lock someone else forgot to release is removed
+pub fn unlock(env: &Env, repo_paths: &RepositoryPaths, steal: bool) -> BiverResult<UnlockResult> {
+    match repository_io::read_editing_lock(repo_paths)? {
+        None => return Ok(UnlockResult::NotLocked),
+        Some(lock) if !lock.is_own(env) && !steal => return Ok(UnlockResult::LockedByOther(lock)),
+        Some(_) => {}
+    }
+
+    repository_io::delete_editing_lock(repo_paths)?;
+
+    Ok(UnlockResult::Ok)
+}
+
+pub enum EditingLockCheck {
+    Free,
+    Own,
+    LockedByOther(EditingLock),
+    Stolen(EditingLock),
+}
+
+// Before committing. Nothing is written, a lock to steal is only taken over with take_over_editing_lock once the commit went through.
+pub fn check_editing_lock(env: &Env, repo_paths: &RepositoryPaths, steal: bool) -> BiverResult<EditingLockCheck> {
+    match repository_io::read_editing_lock(repo_paths)? {
+        None => Ok(EditingLockCheck::Free),
+        Some(lock) if lock.is_own(env) => Ok(EditingLockCheck::Own),
+        Some(lock) if steal => Ok(EditingLockCheck::Stolen(lock)),
+        Some(lock) => Ok(EditingLockCheck::LockedByOther(lock)),
+    }
+}
+
+// False when someone else locked it after the check
+pub fn take_over_editing_lock(env: &Env, repo_paths: &RepositoryPaths) -> BiverResult<bool> {
+    if env.dry_run {
+        return Ok(true);
+    }
+
+    match repository_io::read_editing_lock(repo_paths)? {
+        Some(lock) if lock.is_own(env) => return Ok(true),
+        Some(_) => remove_editing_lock(repo_paths)?,
+        None => {}
+    }
+
+    Ok(repository_io::create_editing_lock(repo_paths, &EditingLock::new(env))?)
+}
+
+pub enum ReplaceVersionedFileResult {
+    Ok,
+    BlockedByUncommittedChanges,
+}
+
+// For content that comes from another program rather than being saved to the versioned file, e.g. through a pipe.
+// Uncommitted changes would be lost, so they have to be committed first.
+pub fn replace_versioned_file(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, content: &mut impl Read) -> BiverResult<ReplaceVersionedFileResult> {
+    if has_uncommitted_changes(env, repo_paths, repo_data, false)? {
+        return Ok(ReplaceVersionedFileResult::BlockedByUncommittedChanges);
+    }
+
+    // Written to the repository directory first, so an interrupted stream leaves the versioned file as it was
+    let staged_file = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "replacement.tmp"));
+    io::copy(content, &mut File::create(staged_file.path())?)?;
+    file_replacement::replace(staged_file.path(), &repo_paths.versioned_file, true)?;
+
+    Ok(ReplaceVersionedFileResult::Ok)
+}
+
+pub enum RestoreResult {
+    Ok,
+    BlockedByUncommittedChanges,
+    InvalidTarget,
+}
+
+pub fn restore(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, target: &str, output: Option<&Path>) -> BiverResult<RestoreResult> {
+    let output = output.unwrap_or_else(|| &repo_paths.versioned_file);
+
+    // Only the versioned file can lose uncommitted changes, restoring elsewhere is fine
+    if output == repo_paths.versioned_file && has_uncommitted_changes(env, repo_paths, repo_data, false)? {
+        return Ok(RestoreResult::BlockedByUncommittedChanges);
+    }
+
+    let target_version = match resolve_target(repo_data, target) {
+        TargetResult::Invalid => return Ok(RestoreResult::InvalidTarget),
+        TargetResult::Branch(branch) => repo_data.version(repo_data.branches[branch]).expect("Branch resolved from target must exist"),
+        TargetResult::Version(version) => version,
+    };
+
+    restore_version_file(env, repo_paths, repo_data, target_version, output)?;
+
+    Ok(RestoreResult::Ok)
+}
+
+pub enum RestoreToTemporaryFileResult {
+    Ok(PathBuf),
+    InvalidTarget,
+}
+
+// The versioned file is left alone, so uncommitted changes do not get in the way.
+// The file name keeps the extension of the versioned file for other programs to recognize it.
+pub fn restore_to_temporary_file(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, target: &str) -> BiverResult<RestoreToTemporaryFileResult> {
+    let target_version = match resolve_target(repo_data, target) {
+        TargetResult::Invalid => return Ok(RestoreToTemporaryFileResult::InvalidTarget),
+        TargetResult::Branch(branch) => repo_data.version(repo_data.branches[branch]).expect("Branch resolved from target must exist"),
+        TargetResult::Version(version) => version,
+    };
+
+    let versioned_file = &repo_paths.versioned_file;
+    let mut file_name = versioned_file.file_stem().unwrap_or_default().to_os_string();
+    file_name.push("-");
+    file_name.push(&target_version.nickname);
+
+    if let Some(extension) = versioned_file.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+
+    // The file keeps a readable name for the application it is opened in, so the directory makes the path unique
+    let output_dir = env.temp_dir.join(format!("biver-{}", Uuid::new_v4().simple()));
+    fs::create_dir(&output_dir)?;
+    let output = output_dir.join(file_name);
+
+    if let Err(e) = restore_version_file(env, repo_paths, repo_data, target_version, &output) {
+        let _ = fs::remove_dir_all(&output_dir);
+        return Err(e);
+    }
+
+    Ok(RestoreToTemporaryFileResult::Ok(output))
+}
+
+pub enum RestoreToWriterResult {
+    Ok,
+    InvalidTarget,
+}
+
+// Like restore_to_temporary_file, without a file in between. Problems found with the blobs stop it before anything
+// is written, those found while reconstructing the version may come after part of it was.
+pub fn restore_to_writer(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, target: &str, output: &mut impl Write) -> BiverResult<RestoreToWriterResult> {
+    let target_version = match resolve_target(repo_data, target) {
+        TargetResult::Invalid => return Ok(RestoreToWriterResult::InvalidTarget),
+        TargetResult::Branch(branch) => repo_data.version(repo_data.branches[branch]).expect("Branch resolved from target must exist"),
+        TargetResult::Version(version) => version,
+    };
+
+    check_content_blobs(env, repo_paths, repo_data, target_version)?;
+    repository_io::write_version_content(env, repo_paths, &target_version.content_blob, output)?;
+    output.flush()?;
+
+    Ok(RestoreToWriterResult::Ok)
+}
+
+// Along with the directory restore_to_temporary_file made for it
+pub fn remove_restored_temporary_file(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) => fs::remove_dir_all(dir),
+        None => fs::remove_file(path),
+    }
+}
+
+pub enum VersionResult<'a> {
+    Ok(&'a Version),
+    InvalidTarget,
+}
+
+pub fn version<'a>(repo_data: &'a RepositoryData, target: &str) -> VersionResult<'a> {
+    let version = match resolve_target(repo_data, target) {
+        TargetResult::Invalid => return VersionResult::InvalidTarget,
+        TargetResult::Version(version) => version,
+        TargetResult::Branch(branch) => repo_data.branch_leaf(branch).expect("Branch resolved from target must exist"),
+    };
+
+    VersionResult::Ok(version)
+}
+
+pub enum InventoryRole {
+    Content,
+    Patch,
+    Preview,
+    VersionRecord,
+    Data,
+    Backup,
+    Conflict,
+    Cache,
+    Log,
+    Temporary,
+    Trash,
+    Other,
+}
+
+pub struct InventoryEntry<'a> {
+    pub path: PathBuf,
+    pub role: InventoryRole,
+    pub size: u64,
+    pub versions: Vec<&'a Version>,
+    pub orphaned: bool,
+}
+
+pub fn blob_inventory<'a>(repo_paths: &RepositoryPaths, repo_data: &'a RepositoryData) -> BiverResult<Vec<InventoryEntry<'a>>> {
+    let mut entries = Vec::new();
+
+    let backup_paths = repository_io::data_backup_paths(repo_paths);
+
+    for dir_entry in fs::read_dir(&repo_paths.repository_dir)? {
+        let dir_entry = dir_entry?;
+        if !dir_entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let path = dir_entry.path();
+        let file_name = dir_entry.file_name().to_string_lossy().to_string();
+
+        let role = if path == repo_paths.data_file
+            || file_name == repository_io::REMOTES_FILE_NAME
+            || file_name == repository_io::BISECT_FILE_NAME
+            || file_name == repository_io::AUTOSTASH_FILE_NAME
+            || file_name == repository_io::EDITING_LOCK_FILE_NAME
+            || file_name == repository_io::NICKNAME_WORDS_FILE_NAME
+            || file_name == repository_io::BACKUP_MANIFEST_FILE_NAME
+        {
+            InventoryRole::Data
+        } else if backup_paths.contains(&path) || file_name == CORRUPT_DATA_FILE_NAME {
+            InventoryRole::Backup
+        } else if repository_io::is_conflicted_data_file_name(&file_name) {
+            InventoryRole::Conflict
+        } else if file_name == stat_cache::STAT_CACHE_FILE_NAME {
+            InventoryRole::Cache
+        } else if file_name == logging::LOG_FILE_NAME || file_name == logging::ROTATED_LOG_FILE_NAME {
+            InventoryRole::Log
+        } else if file_name.ends_with(".tmp") || file_name.ends_with(PENDING_PREVIEW_FILE_NAME) {
+            InventoryRole::Temporary
+        } else {
+            InventoryRole::Other
+        };
+
+        entries.push(InventoryEntry {
+            path: PathBuf::from(file_name),
+            orphaned: matches!(role, InventoryRole::Temporary),
+            role,
+            size: dir_entry.metadata()?.len(),
+            versions: Vec::new(),
+        });
+    }
+
+    for (cache_entry_path, size) in reconstruction_cache::entries(repo_paths)? {
+        entries.push(InventoryEntry {
+            path: cache_entry_path.strip_prefix(&repo_paths.repository_dir).unwrap_or(&cache_entry_path).to_path_buf(),
+            role: InventoryRole::Cache,
+            size,
+            versions: Vec::new(),
+            orphaned: false,
+        });
+    }
+
+    for (trashed_path, size) in trash::entries(repo_paths)? {
+        entries.push(InventoryEntry {
+            path: trashed_path.strip_prefix(&repo_paths.repository_dir).unwrap_or(&trashed_path).to_path_buf(),
+            role: InventoryRole::Trash,
+            size,
+            versions: Vec::new(),
+            orphaned: false,
+        });
+    }
+
+    let mut versions_by_blob: HashMap<&str, Vec<&Version>> = HashMap::new();
+    for version in &repo_data.versions {
+        for blob_file_name in version.blob_file_names() {
+            versions_by_blob.entry(blob_file_name).or_default().push(version);
+        }
+    }
+
+    let versions_by_record: HashMap<String, &Version> = repo_data.versions.iter().map(|v| (blob_store::version_record_file_name(v.id), v)).collect();
+
+    for object_name in object_store::list_object_names(repo_paths)? {
+        let object_path = repo_paths.blob_path(&object_name);
+
+        let role = match blob_store::object_kind(&object_name) {
+            Some(ObjectKind::Full) => InventoryRole::Content,
+            Some(ObjectKind::Patch) => InventoryRole::Patch,
+            Some(ObjectKind::Preview) => InventoryRole::Preview,
+            Some(ObjectKind::VersionRecord) => InventoryRole::VersionRecord,
+            None => InventoryRole::Other,
+        };
+
+        let versions = match role {
+            InventoryRole::VersionRecord => versions_by_record.get(&object_name).into_iter().copied().collect(),
+            _ => versions_by_blob.get(object_name.as_str()).cloned().unwrap_or_default(),
+        };
+
+        entries.push(InventoryEntry {
+            path: object_path.strip_prefix(&repo_paths.repository_dir).unwrap_or(&object_path).to_path_buf(),
+            orphaned: versions.is_empty(),
+            role,
+            size: fs::metadata(&object_path)?.len(),
+            versions,
+        });
+    }
+
+    entries.sort_by_key(|e| Reverse(e.size));
+
+    Ok(entries)
+}
+
+pub enum VersionOrder {
+    NewestFirst,
+    LargestFirst,
+}
+
+pub struct VersionQuery<'a> {
+    pub branch: Option<&'a str>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub author: Option<&'a str>,
+    pub attributes: &'a [(String, String)],
+    pub order: VersionOrder,
+    pub limit: Option<usize>,
+}
+
+pub enum ListVersionsResult<'a> {
+    Ok(Vec<&'a Version>),
+    BranchDoesNotExist,
+}
+
+pub fn list_versions<'a>(repo_data: &'a RepositoryData, query: &VersionQuery) -> ListVersionsResult<'a> {
+    let mut versions: Vec<&Version> = match query.branch {
+        None => repo_data.versions.iter().collect(),
+        Some(branch) => match repo_data.branches.get(branch) {
+            None => return ListVersionsResult::BranchDoesNotExist,
+            Some(branch_leaf_id) => repo_data.iter_version_and_ancestors(*branch_leaf_id).collect(),
+        },
+    };
+
+    versions.retain(|v| query.since.is_none_or(|since| v.creation_time >= since));
+    versions.retain(|v| query.until.is_none_or(|until| v.creation_time < until));
+    versions.retain(|v| query.author.is_none_or(|author| v.author.eq_ignore_ascii_case(author)));
+    versions.retain(|v| query.attributes.iter().all(|(key, value)| v.attributes.get(key) == Some(value)));
+
+    match query.order {
+        VersionOrder::NewestFirst => versions.sort_by_key(|v| Reverse(v.creation_time)),
+        VersionOrder::LargestFirst => versions.sort_by_key(|v| (Reverse(v.versioned_file_length), Reverse(v.creation_time))),
+    }
+
+    if let Some(limit) = query.limit {
+        versions.truncate(limit);
+    }
+
+    ListVersionsResult::Ok(versions)
+}
+
+pub enum PreviewResult {
+    Ok(PathBuf),
+    NoPreviewAvailable,
+}
+
+// A dry run does not write the preview blob, so it has none
+fn preview_perceptual_hash(repo_paths: &RepositoryPaths, preview_blob_file_name: Option<&str>) -> Option<u64> {
+    perceptual_hash::dhash(&repo_paths.blob_path(preview_blob_file_name?))
+}
+
+// Another version that looks the same as the given one while its content differs, such as when only metadata changed
+pub fn visually_identical_version<'a>(repo_data: &'a RepositoryData, version: &Version) -> Option<&'a Version> {
+    let perceptual_hash = version.perceptual_hash?;
+
+    repo_data
+        .versions
+        .iter()
+        .filter(|v| v.versioned_file_hash != version.versioned_file_hash)
+        .find(|v| v.perceptual_hash == Some(perceptual_hash))
+}
+
+pub enum SimilarResult<'a> {
+    // With their distances, closest first
+    Ok(Vec<(&'a Version, u32)>),
+    InvalidTarget,
+    NoPreviewAvailable,
+}
+
+// Versions committed before perceptual hashes were stored get theirs from their previews
+pub fn similar_versions<'a>(repo_paths: &RepositoryPaths, repo_data: &'a RepositoryData, target: &str, max_distance: u32) -> SimilarResult<'a> {
+    let perceptual_hash_of = |version: &Version| {
+        version
+            .perceptual_hash
+            .or_else(|| preview_perceptual_hash(repo_paths, version.preview_blob_file_name.as_deref()))
+    };
+
+    let Some(target_version) = resolve_version_target(repo_data, target) else {
+        return SimilarResult::InvalidTarget;
+    };
+
+    let Some(target_hash) = perceptual_hash_of(target_version) else {
+        return SimilarResult::NoPreviewAvailable;
+    };
+
+    let mut similar: Vec<(&Version, u32)> = repo_data
+        .versions
+        .iter()
+        .filter(|v| v.id != target_version.id)
+        .filter_map(|v| Some((v, perceptual_hash::distance(target_hash, perceptual_hash_of(v)?))))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .collect();
+
+    similar.sort_by_key(|(v, distance)| (*distance, Reverse(v.creation_time)));
+
+    SimilarResult::Ok(similar)
+}
+
+pub fn preview(repo_paths: &RepositoryPaths, version: &Version) -> PreviewResult {
+    match version.preview_blob_file_name.as_ref() {
+        None => PreviewResult::NoPreviewAvailable,
+        Some(preview_file_name) => PreviewResult::Ok(repo_paths.blob_path(preview_file_name)),
+    }
+}
+
+pub struct DiffReport {
+    pub changed_bytes: u64,
+    pub image_diff: ImageDiffResult,
+    pub image_diff_from_previews: bool,
+}
+
+pub fn diff(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, version1: &Version, version2: &Version) -> BiverResult<DiffReport> {
+    let content1 = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "diff1.tmp"));
+    let content2 = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "diff2.tmp"));
+
+    extract_version_content(env, repo_paths, repo_data, version1, content1.path())?;
+    extract_version_content(env, repo_paths, repo_data, version2, content2.path())?;
+
+    let changed_bytes = if version1.versioned_file_hash == version2.versioned_file_hash {
+        0
+    } else {
+        diff::changed_bytes(content1.path(), content2.path())?
+    };
+
+    let mut image_diff = diff::image_diff(content1.path(), content2.path());
+    let mut image_diff_from_previews = false;
+
+    if let ImageDiffResult::NotAnImage = image_diff
+        && let (PreviewResult::Ok(preview1), PreviewResult::Ok(preview2)) = (preview(repo_paths, version1), preview(repo_paths, version2))
+    {
+        image_diff = diff::image_diff(&preview1, &preview2);
+        image_diff_from_previews = true;
+    }
+
+    Ok(DiffReport {
+        changed_bytes,
+        image_diff,
+        image_diff_from_previews,
+    })
+}
+
+pub enum ReportResult {
+    Ok,
+    // Written without these branches, whose leaf versions are missing
+    DanglingBranchesLeftOut(Vec<String>),
+}
+
+pub fn report(repo_paths: &RepositoryPaths, repo_data: &RepositoryData, output: &Path) -> BiverResult<ReportResult> {
+    let html = report::html_report(repo_paths, repo_data)?;
+    fs::write(output, html)?;
+
+    let dangling_branches: Vec<String> = repo_data
+        .branches
+        .iter()
+        .filter(|(_, leaf_id)| repo_data.version(**leaf_id).is_none())
+        .map(|(b, _)| b.clone())
+        .collect();
+    if !dangling_branches.is_empty() {
+        return Ok(ReportResult::DanglingBranchesLeftOut(dangling_branches));
+    }
+
+    Ok(ReportResult::Ok)
+}
+
+pub enum GitExportResult {
+    Ok { exported_version_count: usize },
+    GitNotReady,
+    NotAGitRepository,
+}
+
+pub fn git_export(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, git_repo: &Path, content: &GitContent) -> BiverResult<GitExportResult> {
+    if !git::ready(env) {
+        return Ok(GitExportResult::GitNotReady);
+    }
+
+    let create_repository = !fs::exists(git_repo)? || fs::read_dir(git_repo)?.next().is_none();
+
+    if create_repository {
+        git::init_bare(env, git_repo)?;
+    }
+
+    let Some(git_dir) = git::absolute_git_dir(env, git_repo)? else {
+        return Ok(GitExportResult::NotAGitRepository);
+    };
+
+    let exported_version_count = git_export::export(env, repo_paths, repo_data, git_repo, &git_dir, content)?;
+
+    // A bare mirror has no working tree to disturb, so its default branch follows the biver head
+    if git::is_bare(env, git_repo)?
+        && let Some(head_branch) = repo_data.head.branch()
+    {
+        git::set_head(env, git_repo, &format!("refs/heads/{}", head_branch))?;
+    }
+
+    Ok(GitExportResult::Ok { exported_version_count })
+}
+
+pub enum CreateRemoteResult {
+    Ok,
+    RemoteAlreadyExists,
+    InvalidRemoteName,
+}
+
+pub fn create_remote(repo_paths: &RepositoryPaths, name: &str, location: RemoteLocation) -> BiverResult<CreateRemoteResult> {
+    let mut remotes = repository_io::read_remotes(repo_paths)?;
+
+    if remotes.contains_key(name) {
+        return Ok(CreateRemoteResult::RemoteAlreadyExists);
+    }
+
+    // Remote names become part of branch names when pulled branches diverge
+    if !valid_branch_name(name) {
+        return Ok(CreateRemoteResult::InvalidRemoteName);
+    }
+
+    remotes.insert(name.to_string(), location);
+    repository_io::write_remotes(repo_paths, &remotes)?;
+
+    Ok(CreateRemoteResult::Ok)
+}
+
+pub enum DeleteRemoteResult {
+    Ok,
+    RemoteDoesNotExist,
+}
+
+pub fn delete_remote(repo_paths: &RepositoryPaths, name: &str) -> BiverResult<DeleteRemoteResult> {
+    let mut remotes = repository_io::read_remotes(repo_paths)?;
+
+    if remotes.remove(name).is_none() {
+        return Ok(DeleteRemoteResult::RemoteDoesNotExist);
+    }
+
+    repository_io::write_remotes(repo_paths, &remotes)?;
+
+    Ok(DeleteRemoteResult::Ok)
+}
+
+pub enum PushResult {
+    Ok { uploaded_object_count: usize },
+    RemoteDoesNotExist,
+    UnrelatedHistories,
+    RemoteHasUnknownVersions,
+    DivergedBranches(Vec<String>),
+    Conflict,
+    RcloneNotReady,
+}
+
+pub fn push(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, remote_name: Option<&str>, force: bool) -> BiverResult<PushResult> {
+    let remotes = repository_io::read_remotes(repo_paths)?;
+    let Some((_, location)) = resolve_remote(&remotes, remote_name) else {
+        return Ok(PushResult::RemoteDoesNotExist);
+    };
+
+    if matches!(location, RemoteLocation::Rclone { .. }) && !rclone::ready(env) {
+        return Ok(PushResult::RcloneNotReady);
+    }
+
+    let backend = remote::backend(env, location)?;
+    let remote_data = backend.read_data()?;
+
+    if let Some(remote_data) = &remote_data
+        && !force
+    {
+        let remote_repo_data = migrations::parse_data(&remote_data.contents)?;
+
+        if root_version_id(&remote_repo_data) != root_version_id(repo_data) {
+            return Ok(PushResult::UnrelatedHistories);
+        }
+
+        if remote_repo_data.versions.iter().any(|v| repo_data.version(v.id).is_none()) {
+            return Ok(PushResult::RemoteHasUnknownVersions);
+        }
+
+        let mut diverged_branches: Vec<String> = remote_repo_data
+            .branches
+            .iter()
+            .filter(|(branch, remote_leaf_id)| match repo_data.branches.get(*branch) {
+                None => true,
+                Some(local_leaf_id) => !is_ancestor_or_same(repo_data, **remote_leaf_id, *local_leaf_id),
+            })
+            .map(|(branch, _)| branch.clone())
+            .collect();
+
+        if !diverged_branches.is_empty() {
+            diverged_branches.sort();
+            return Ok(PushResult::DivergedBranches(diverged_branches));
+        }
+    }
+
+    let remote_object_names = backend.object_names()?;
+    let mut uploaded_object_count = 0;
+
+    for object_name in local_object_names(repo_data) {
+        let object_path = repo_paths.blob_path(&object_name);
+        if remote_object_names.contains(&object_name) || !fs::exists(&object_path)? {
+            continue;
+        }
+
+        backend.upload_object(&object_name, &object_path)?;
+        uploaded_object_count += 1;
+    }
+
+    let contents = serde_json::to_vec_pretty(repo_data)?;
+
+    match backend.write_data(&contents, remote_data.as_ref().map(|d| d.token.as_str()))? {
+        DataWriteResult::Ok => Ok(PushResult::Ok { uploaded_object_count }),
+        DataWriteResult::Conflict => Ok(PushResult::Conflict),
+    }
+}
+
+pub struct DivergedBranch {
+    pub branch: String,
+    pub remote_branch: String,
+}
+
+pub enum PullResult {
+    Ok {
+        new_version_count: usize,
+        downloaded_object_count: usize,
+        diverged_branches: Vec<DivergedBranch>,
+    },
+    RemoteDoesNotExist,
+    RemoteIsEmpty,
+    UnrelatedHistories,
+    RcloneNotReady,
+    InvalidObjectName(String),
+    ChecksumMismatch(String),
+    BlockedByUncommittedChanges,
+}
+
+pub fn pull(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, remote_name: Option<&str>) -> BiverResult<PullResult> {
+    let remotes = repository_io::read_remotes(repo_paths)?;
+    let Some((remote_name, location)) = resolve_remote(&remotes, remote_name) else {
+        return Ok(PullResult::RemoteDoesNotExist);
+    };
+
+    if matches!(location, RemoteLocation::Rclone { .. }) && !rclone::ready(env) {
+        return Ok(PullResult::RcloneNotReady);
+    }
+
+    let backend = remote::backend(env, location)?;
+
+    let Some(remote_data) = backend.read_data()? else {
+        return Ok(PullResult::RemoteIsEmpty);
+    };
+
+    let mut remote_repo_data = migrations::parse_data(&remote_data.contents)?;
+
+    if root_version_id(&remote_repo_data) != root_version_id(repo_data) {
+        return Ok(PullResult::UnrelatedHistories);
+    }
+
+    // The head branch may be fast-forwarded, which would leave the changes on top of a version they were not made from
+    if has_uncommitted_changes(env, repo_paths, repo_data, false)? {
+        return Ok(PullResult::BlockedByUncommittedChanges);
+    }
+
+    let previous_head_version_id = repo_data.head_version().id;
+
+    let new_versions: Vec<Version> = std::mem::take(&mut remote_repo_data.versions)
+        .into_iter()
+        .filter(|v| repo_data.version(v.id).is_none())
+        .collect();
+
+    let mut missing_object_names = Vec::new();
+    for version in &new_versions {
+        let record_file_name = blob_store::version_record_file_name(version.id);
+        let object_names = version.blob_file_names().into_iter().chain([record_file_name.as_str()]);
+
+        for object_name in object_names {
+            // The remote may be corrupted or written by someone else, its names must not lead outside the repository
+            if !blob_store::is_valid_object_name(object_name) {
+                return Ok(PullResult::InvalidObjectName(object_name.to_string()));
+            }
+
+            if !fs::exists(repo_paths.blob_path(object_name))? && !missing_object_names.iter().any(|n| n == object_name) {
+                missing_object_names.push(object_name.to_string());
+            }
+        }
+    }
+
+    // Objects are all downloaded next to data.json and checked first, so neither an interrupted download nor a corrupted
+    // object leaves anything behind among the others
+    let mut downloads = Vec::new();
+    for object_name in &missing_object_names {
+        let download = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "download.tmp"));
+        backend.download_object(object_name, download.path())?;
+
+        // Checked before the object joins the others, where a corrupted one would be found only on restore
+        let mut checksum = None;
+        if let Some(remote_checksum) = remote_repo_data.blob_checksums.get(object_name) {
+            if hash::file_hash(env, remote_repo_data.hash_algorithm, &File::open(download.path())?)? != *remote_checksum {
+                return Ok(PullResult::ChecksumMismatch(object_name.clone()));
+            }
+
+            checksum = Some(if remote_repo_data.hash_algorithm == repo_data.hash_algorithm {
+                *remote_checksum
+            } else {
+                hash::file_hash(env, repo_data.hash_algorithm, &File::open(download.path())?)?
+            });
+        }
+
+        downloads.push((object_name, download, checksum));
+    }
+
+    for (object_name, download, checksum) in downloads {
+        let object_path = repo_paths.blob_path(object_name);
+        object_store::prepare_object_path(&object_path)?;
+        fs::rename(download.path(), &object_path)?;
+
+        if let Some(checksum) = checksum {
+            repo_data.blob_checksums.entry(object_name.clone()).or_insert(checksum);
+        }
+    }
+
+    let new_version_ids: Vec<_> = new_versions.iter().map(|v| v.id).collect();
+    repo_data.versions.extend(new_versions);
+
+    let diverged_branches = merge_branches(repo_data, remote_repo_data.branches, &remote_repo_data.branch_metadata, remote_name);
+
+    repository_io::write_data(repo_paths, repo_data)?;
+
+    let head_version = repo_data.head_version();
+    if head_version.id != previous_head_version_id {
+        restore_version_file(env, repo_paths, repo_data, head_version, &repo_paths.versioned_file)?;
+    }
+
+    let new_version_count = new_version_ids.len();
+    events::emit(
+        env,
+        RepositoryEvent::Pulled {
+            versioned_file: repo_paths.versioned_file.clone(),
+            new_version_ids,
+        },
+    );
+
+    Ok(PullResult::Ok {
+        new_version_count,
+        downloaded_object_count: missing_object_names.len(),
+        diverged_branches,
+    })
+}
+
+// Branches missing locally are added and ones that are behind are fast-forwarded.
+// A diverged branch is kept under a name prefixed with the source the other copy came from.
+fn merge_branches(
+    repo_data: &mut RepositoryData,
+    other_branches: HashMap<String, VersionId>,
+    other_branch_metadata: &BTreeMap<String, BranchMetadata>,
+    source_name: &str,
+) -> Vec<DivergedBranch> {
+    let mut other_branches: Vec<(String, VersionId)> = other_branches.into_iter().collect();
+    other_branches.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut diverged_branches = Vec::new();
+
+    for (branch, other_leaf_id) in other_branches {
+        let new_leaf_id = match repo_data.branches.get(&branch) {
+            None => Some(other_leaf_id),
+            Some(local_leaf_id) if is_ancestor_or_same(repo_data, other_leaf_id, *local_leaf_id) => None,
+            Some(local_leaf_id) if is_ancestor_or_same(repo_data, *local_leaf_id, other_leaf_id) => Some(other_leaf_id),
+            Some(_) => {
+                let remote_branch = remote_branch_name(repo_data, source_name, &branch, other_leaf_id);
+                if let Some(metadata) = other_branch_metadata.get(&branch) {
+                    repo_data.branch_metadata.insert(remote_branch.clone(), metadata.clone());
+                }
+                repo_data.branches.insert(remote_branch.clone(), other_leaf_id);
+                diverged_branches.push(DivergedBranch {
+                    branch: branch.clone(),
+                    remote_branch,
+                });
+                None
+            }
+        };
+
+        if let Some(new_leaf_id) = new_leaf_id {
+            if let Some(metadata) = other_branch_metadata.get(&branch) {
+                repo_data.branch_metadata.entry(branch.clone()).or_insert_with(|| metadata.clone());
+            }
+            repo_data.branches.insert(branch, new_leaf_id);
+        }
+    }
+
+    diverged_branches
+}
+
+pub enum BackupResult {
+    Ok {
+        copied_object_count: usize,
+        unchanged_object_count: usize,
+        removed_object_count: usize,
+    },
+    RcloneNotReady,
+    // Objects whose copies read back differently, data.json is then left as it was at the destination
+    VerificationFailed(Vec<String>),
+    Conflict,
+}
+
+// Copies data.json and the objects to a remote or to any location a remote could have, overwriting what is there.
+// Each copied object is read back and hashed. Incrementally, objects unchanged since the last backup to the same
+// destination are skipped, otherwise everything is copied again. Once data.json is written, objects it no longer
+// references, such as those of pruned versions, are removed from the destination.
+pub fn backup(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, destination: &str, incremental: bool) -> BiverResult<BackupResult> {
+    let remotes = repository_io::read_remotes(repo_paths)?;
+    let parsed_location;
+    let location = match remotes.get(destination) {
+        Some(location) => location,
+        None => {
+            parsed_location = RemoteLocation::parse(destination, None, None)?;
+            &parsed_location
+        }
+    };
+
+    if matches!(location, RemoteLocation::Rclone { .. }) && !rclone::ready(env) {
+        return Ok(BackupResult::RcloneNotReady);
+    }
+
+    let backend = remote::backend(env, location)?;
+
+    let mut manifest = repository_io::read_backup_manifest(repo_paths)?;
+    let destination_key = location.to_string();
+    let previous_record = manifest.destinations.remove(&destination_key).filter(|_| incremental).unwrap_or_default();
+
+    let mut record = BackupRecord::default();
+    let mut copied_object_count = 0;
+    let mut unchanged_object_count = 0;
+    let mut failed_object_names = Vec::new();
+
+    let verify_path = repo_paths.unique_temp_file_path(env, "backup-verify.tmp");
+
+    let object_names = local_object_names(repo_data);
+
+    for object_name in &object_names {
+        let object_path = repo_paths.blob_path(object_name);
+        let Ok(metadata) = fs::metadata(&object_path) else {
+            continue;
+        };
+        let stat = ObjectStat::of(&metadata)?;
+
+        if previous_record.objects.get(object_name) == Some(&stat) {
+            record.objects.insert(object_name.clone(), stat);
+            unchanged_object_count += 1;
+            continue;
+        }
+
+        backend.upload_object(object_name, &object_path)?;
+
+        backend.download_object(object_name, &verify_path)?;
+        let local_hash = hash::file_hash(env, repo_data.hash_algorithm, &File::open(&object_path)?)?;
+        let copy_hash = hash::file_hash(env, repo_data.hash_algorithm, &File::open(&verify_path)?)?;
+        fs::remove_file(&verify_path)?;
+
+        if local_hash != copy_hash {
+            failed_object_names.push(object_name.clone());
+            continue;
+        }
+
+        record.objects.insert(object_name.clone(), stat);
+        copied_object_count += 1;
+    }
+
+    if !failed_object_names.is_empty() {
+        return Ok(BackupResult::VerificationFailed(failed_object_names));
+    }
+
+    let contents = serde_json::to_vec_pretty(repo_data)?;
+    let token = backend.read_data()?.map(|d| d.token);
+    if let DataWriteResult::Conflict = backend.write_data(&contents, token.as_deref())? {
+        return Ok(BackupResult::Conflict);
+    }
+
+    let unreferenced_object_names: Vec<String> = backend.object_names()?.into_iter().filter(|n| object_names.binary_search(n).is_err()).collect();
+    for object_name in &unreferenced_object_names {
+        backend.delete_object(object_name)?;
+    }
+
+    record.last_backup_time = Some(Utc::now());
+    manifest.destinations.insert(destination_key, record);
+    repository_io::write_backup_manifest(repo_paths, &manifest)?;
+
+    Ok(BackupResult::Ok {
+        copied_object_count,
+        unchanged_object_count,
+        removed_object_count: unreferenced_object_names.len(),
+    })
+}
+
+// Without a name, the only configured remote is used
+fn resolve_remote<'a>(remotes: &'a BTreeMap<String, RemoteLocation>, remote_name: Option<&str>) -> Option<(&'a str, &'a RemoteLocation)> {
+    match remote_name {
+        Some(remote_name) => remotes.get_key_value(remote_name).map(|(name, location)| (name.as_str(), location)),
+        None if remotes.len() == 1 => remotes.iter().next().map(|(name, location)| (name.as_str(), location)),
+        None => None,
+    }
+}
+
+fn root_version_id(repo_data: &RepositoryData) -> Option<VersionId> {
+    repo_data.versions.iter().find(|v| v.is_root()).map(|v| v.id)
+}
+
+fn is_ancestor_or_same(repo_data: &RepositoryData, ancestor_id: VersionId, version_id: VersionId) -> bool {
+    repo_data.iter_version_and_ancestors(version_id).any(|v| v.id == ancestor_id)
+}
+
+fn local_object_names(repo_data: &RepositoryData) -> Vec<String> {
+    let mut object_names: Vec<String> = repo_data.referenced_blob_file_names().into_iter().map(String::from).collect();
+    object_names.extend(repo_data.versions.iter().map(|v| blob_store::version_record_file_name(v.id)));
+    object_names.sort();
+    object_names
+}
+
+// A diverged remote branch is kept under `<remote>-<branch>`. An existing branch of that name is only moved forward,
+// never away from versions that would otherwise be left without a branch.
+fn remote_branch_name(repo_data: &RepositoryData, remote_name: &str, branch: &str, remote_leaf_id: VersionId) -> String {
+    let base_name = format!("{}-{}", remote_name, branch);
+
+    (1..)
+        .map(|n| if n == 1 { base_name.clone() } else { format!("{}-{}", base_name, n) })
+        .find(|name| match repo_data.branches.get(name) {
+            None => true,
+            Some(leaf_id) => is_ancestor_or_same(repo_data, *leaf_id, remote_leaf_id),
+        })
+        .expect("An unused branch name must exist")
+}
+
+pub enum ReconcileOutcome {
+    Merged {
+        new_version_count: usize,
+        diverged_branches: Vec<DivergedBranch>,
+    },
+    Unreadable,
+    UnrelatedHistories,
+    MissingObjects(usize),
+}
+
+pub struct ReconciledCopy {
+    pub file_name: String,
+    pub outcome: ReconcileOutcome,
+}
+
+pub enum ReconcileResult {
+    Ok(Vec<ReconciledCopy>),
+    NothingToReconcile,
+}
+
+pub fn reconcile(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, include_backups: bool) -> BiverResult<ReconcileResult> {
+    let mut copies: Vec<(PathBuf, bool)> = repository_io::conflicted_data_paths(repo_paths)?.into_iter().map(|path| (path, false)).collect();
+
+    if include_backups {
+        for backup_path in repository_io::data_backup_paths(repo_paths) {
+            if fs::exists(&backup_path)? {
+                copies.push((backup_path, true));
+            }
+        }
+    }
+
+    if copies.is_empty() {
+        return Ok(ReconcileResult::NothingToReconcile);
+    }
+
+    let has_uncommitted_changes = has_uncommitted_changes(env, repo_paths, repo_data, false)?;
+    let previous_head_version_id = repo_data.head_version().id;
+
+    let mut reconciled_copies = Vec::new();
+    let mut merged_copy_paths = Vec::new();
+
+    for (path, is_backup) in copies {
+        let outcome = merge_data_copy(repo_paths, repo_data, &path, is_backup)?;
+
+        if let ReconcileOutcome::Merged { new_version_count, .. } = outcome {
+            // Backups are part of the repository and stay, and the ones that add nothing are not worth reporting
+            if !is_backup {
+                merged_copy_paths.push(path.clone());
+            } else if new_version_count == 0 {
+                continue;
+            }
+        }
+
+        reconciled_copies.push(ReconciledCopy {
+            file_name: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+            outcome,
+        });
+    }
+
+    repository_io::write_data(repo_paths, repo_data)?;
+
+    for merged_copy_path in merged_copy_paths {
+        fs::remove_file(merged_copy_path)?;
+    }
+
+    let head_version = repo_data.head_version();
+    if head_version.id != previous_head_version_id && !has_uncommitted_changes {
+        restore_version_file(env, repo_paths, repo_data, head_version, &repo_paths.versioned_file)?;
+    }
+
+    Ok(ReconcileResult::Ok(reconciled_copies))
+}
+
+fn merge_data_copy(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, path: &Path, is_backup: bool) -> BiverResult<ReconcileOutcome> {
+    let Some(mut other_repo_data) = repair::read_valid_data(path) else {
+        return Ok(ReconcileOutcome::Unreadable);
+    };
+
+    if root_version_id(&other_repo_data) != root_version_id(repo_data) {
+        return Ok(ReconcileOutcome::UnrelatedHistories);
+    }
+
+    let new_versions: Vec<Version> = std::mem::take(&mut other_repo_data.versions)
+        .into_iter()
+        .filter(|v| repo_data.version(v.id).is_none())
+        .collect();
+
+    // Blobs may still be on their way from the other machine, in which case the copy is left for a later run
+    let mut missing_blob_file_names = HashSet::new();
+    for version in &new_versions {
+        for blob_file_name in version.blob_file_names() {
+            if !fs::exists(repo_paths.blob_path(blob_file_name))? {
+                missing_blob_file_names.insert(blob_file_name);
+            }
+        }
+    }
+
+    if !missing_blob_file_names.is_empty() {
+        return Ok(ReconcileOutcome::MissingObjects(missing_blob_file_names.len()));
+    }
+
+    for version in &new_versions {
+        if !fs::exists(repo_paths.blob_path(&blob_store::version_record_file_name(version.id)))? {
+            repository_io::write_version_record(repo_paths, version)?;
+        }
+
+        for blob_file_name in version.blob_file_names() {
+            if let Some(checksum) = other_repo_data.blob_checksums.get(blob_file_name) {
+                repo_data.blob_checksums.entry(blob_file_name.to_string()).or_insert(*checksum);
+            }
+        }
+    }
+
+    let new_version_ids: HashSet<VersionId> = new_versions.iter().map(|v| v.id).collect();
+    let new_version_count = new_versions.len();
+    repo_data.versions.extend(new_versions);
+
+    // A backup also remembers branches deleted since, which only come back when they lead to recovered versions
+    let (branches, source_name) = if is_backup {
+        let branches = other_repo_data.branches.into_iter().filter(|(_, leaf_id)| new_version_ids.contains(leaf_id)).collect();
+        (branches, "backup")
+    } else {
+        (other_repo_data.branches, "conflict")
+    };
+
+    let diverged_branches = merge_branches(repo_data, branches, &other_repo_data.branch_metadata, source_name);
+
+    Ok(ReconcileOutcome::Merged {
+        new_version_count,
+        diverged_branches,
+    })
+}
+
+pub enum ImportHistoryResult {
+    Ok { imported_version_count: usize, branches: Vec<String> },
+    OtherNotInitialized,
+    HashAlgorithmMismatch,
+    InvalidBranchName,
+    BranchAlreadyExists(String),
+    InvalidTarget,
+    MissingObjects(usize),
+    // Of the other repository, which is corrupted
+    ChecksumMismatch(String),
+}
+
+// Versions of another repository join this history, its root as a child of the onto version. Without onto, a fork is
+// grafted where it was forked from and anything else onto the root version. Versions both repositories have, like the
+// one a fork started from, are not imported twice. The other head becomes the named branch, the other branches get it
+// as a prefix.
+pub fn import_history(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    repo_data: &mut RepositoryData,
+    other_repo_paths: &RepositoryPaths,
+    branch: &str,
+    onto: Option<&str>,
+) -> BiverResult<ImportHistoryResult> {
+    let RepositoryDataResult::Initialized(mut other_repo_data) = repository_io::read_data(other_repo_paths)? else {
+        return Ok(ImportHistoryResult::OtherNotInitialized);
+    };
+
+    // Blob names and checksums are hashes, which only match under the same algorithm
+    if other_repo_data.hash_algorithm != repo_data.hash_algorithm {
+        return Ok(ImportHistoryResult::HashAlgorithmMismatch);
+    }
+
+    let mut new_branches = vec![(branch.to_string(), other_repo_data.head_version().id, other_repo_data.head.branch())];
+    let mut other_branches: Vec<(&String, &VersionId)> = other_repo_data.branches.iter().collect();
+    other_branches.sort();
+    for (other_branch, leaf_id) in other_branches {
+        if other_repo_data.head.branch() != Some(other_branch) {
+            new_branches.push((format!("{}-{}", branch, other_branch), *leaf_id, Some(other_branch)));
+        }
+    }
+
+    for (new_branch, _, _) in &new_branches {
+        if !valid_branch_name(new_branch) {
+            return Ok(ImportHistoryResult::InvalidBranchName);
+        }
+
+        if repo_data.branches.contains_key(new_branch) {
+            return Ok(ImportHistoryResult::BranchAlreadyExists(new_branch.clone()));
+        }
+    }
+
+    let same_content = |version: &Version, other_version: &Version| {
+        version.versioned_file_hash == other_version.versioned_file_hash && version.versioned_file_length == other_version.versioned_file_length
+    };
+
+    let fork_origin = other_repo_data
+        .versions
+        .iter()
+        .find(|v| v.is_root())
+        .and_then(|root| Some((root, repo_data.version(root.forked_from.as_ref()?.version_id)?)))
+        .filter(|(root, origin)| same_content(origin, root))
+        .map(|(root, origin)| (root.id, origin.id));
+
+    let onto_id = match onto {
+        Some(onto) => match resolve_target(repo_data, onto) {
+            TargetResult::Invalid => return Ok(ImportHistoryResult::InvalidTarget),
+            TargetResult::Branch(onto_branch) => repo_data.branches[onto_branch],
+            TargetResult::Version(version) => version.id,
+        },
+        None => match fork_origin {
+            Some((_, origin_id)) => origin_id,
+            None => root_version_id(repo_data).expect("A repository always has a root version"),
+        },
+    };
+
+    // Parents first, so every parent is mapped before its children
+    let depths: HashMap<VersionId, usize> = other_repo_data
+        .versions
+        .iter()
+        .map(|v| (v.id, other_repo_data.iter_version_and_ancestors(v.id).count()))
+        .collect();
+    let mut other_versions = std::mem::take(&mut other_repo_data.versions);
+    other_versions.sort_by_key(|v| depths[&v.id]);
+
+    let words = nickname_words(env, repo_paths)?;
+    let mut version_id_map = HashMap::new();
+    let mut new_versions: Vec<Version> = Vec::new();
+
+    for mut new_version in other_versions {
+        let other_version_id = new_version.id;
+        let existing_version = repo_data.version(other_version_id);
+        let parent_id = new_version.parent.map(|parent_id| version_id_map[&parent_id]);
+
+        // The same version only when it also follows the same parent here, otherwise its ID is taken
+        if existing_version.is_some_and(|v| same_content(v, &new_version) && v.parent == parent_id) {
+            version_id_map.insert(other_version_id, other_version_id);
+            continue;
+        }
+
+        if onto.is_none()
+            && let Some((root_id, origin_id)) = fork_origin
+            && other_version_id == root_id
+        {
+            version_id_map.insert(root_id, origin_id);
+            continue;
+        }
+
+        new_version.parent = Some(parent_id.unwrap_or(onto_id));
+
+        // A new ID or nickname would not match the signature any more
+        if existing_version.is_some() {
+            new_version.id = VersionId::new();
+            new_version.signature = None;
+        }
+
+        if repo_data.nickname_taken(&new_version.nickname) || new_versions.iter().any(|v| v.nickname.eq_ignore_ascii_case(&new_version.nickname)) {
+            new_version.nickname = nickname::new_nickname(&words, new_version.versioned_file_hash, |n| {
+                repo_data.nickname_taken(n) || new_versions.iter().any(|v| v.nickname.eq_ignore_ascii_case(n))
+            });
+            new_version.custom_nickname = false;
+            new_version.signature = None;
+        }
+
+        version_id_map.insert(other_version_id, new_version.id);
+        new_versions.push(new_version);
+    }
+
+    let mut missing_object_count = 0;
+    for blob_file_name in new_versions.iter().flat_map(|v| v.blob_file_names()).collect::<BTreeSet<_>>() {
+        if !fs::exists(repo_paths.blob_path(blob_file_name))? && !fs::exists(other_repo_paths.blob_path(blob_file_name))? {
+            missing_object_count += 1;
+        }
+    }
+
+    if missing_object_count > 0 {
+        return Ok(ImportHistoryResult::MissingObjects(missing_object_count));
+    }
+
+    // Copied next to data.json and checked first, so neither an interrupted copy nor a corrupted blob leaves anything
+    // behind among the others
+    let mut copies = Vec::new();
+    for blob_file_name in new_versions.iter().flat_map(|v| v.blob_file_names()).collect::<BTreeSet<_>>() {
+        if fs::exists(repo_paths.blob_path(blob_file_name))? {
+            continue;
+        }
+
+        let copy = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "import.tmp"));
+        fs::copy(other_repo_paths.blob_path(blob_file_name), copy.path())?;
+
+        if let Some(checksum) = other_repo_data.blob_checksums.get(blob_file_name)
+            && hash::file_hash(env, repo_data.hash_algorithm, &File::open(copy.path())?)? != *checksum
+        {
+            return Ok(ImportHistoryResult::ChecksumMismatch(blob_file_name.to_string()));
+        }
+
+        copies.push((blob_file_name, copy));
+    }
+
+    for (blob_file_name, copy) in copies {
+        let blob_path = repo_paths.blob_path(blob_file_name);
+        object_store::prepare_object_path(&blob_path)?;
+        fs::rename(copy.path(), &blob_path)?;
+    }
+
+    for version in &new_versions {
+        for blob_file_name in version.blob_file_names() {
+            if let Some(checksum) = other_repo_data.blob_checksums.get(blob_file_name) {
+                repo_data.blob_checksums.entry(blob_file_name.to_string()).or_insert(*checksum);
+            }
+        }
+
+        repository_io::write_version_record(repo_paths, version)?;
+    }
+
+    let new_version_ids: Vec<_> = new_versions.iter().map(|v| v.id).collect();
+    repo_data.versions.extend(new_versions);
+
+    let mut branches = Vec::new();
+    for (new_branch, other_leaf_id, other_branch) in new_branches {
+        let metadata = other_branch
+            .and_then(|b| other_repo_data.branch_metadata.get(b))
+            .cloned()
+            .unwrap_or_else(|| new_branch_metadata(env));
+        repo_data.branch_metadata.insert(new_branch.clone(), metadata);
+        repo_data.branches.insert(new_branch.clone(), version_id_map[&other_leaf_id]);
+        branches.push(new_branch);
+    }
+
+    repository_io::write_data(repo_paths, repo_data)?;
+
+    let imported_version_count = new_version_ids.len();
+    events::emit(
+        env,
+        RepositoryEvent::Imported {
+            versioned_file: repo_paths.versioned_file.clone(),
+            new_version_ids,
+        },
+    );
+
+    Ok(ImportHistoryResult::Ok { imported_version_count, branches })
+}
+
+pub enum CreateBranchResult {
+    Ok,
+    BranchAlreadyExists,
+    InvalidBranchName,
+    InvalidTarget,
+}
+
+// Without a target, the branch starts at the head version
+pub fn create_branch(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, name: &str, target: Option<&str>, checkout: bool) -> BiverResult<CreateBranchResult> {
+    if repo_data.branches.contains_key(name) {
+        return Ok(CreateBranchResult::BranchAlreadyExists);
+    }
+
+    if !valid_branch_name(name) {
+        return Ok(CreateBranchResult::InvalidBranchName);
+    }
+
+    let head_version_id = repo_data.head_version().id;
+
+    let target_version_id = match target {
+        None => head_version_id,
+        Some(target) => match resolve_version_target(repo_data, target) {
+            None => return Ok(CreateBranchResult::InvalidTarget),
+            Some(target_version) => target_version.id,
+        },
+    };
+
+    repo_data.branches.insert(name.to_string(), target_version_id);
+    repo_data.branch_metadata.insert(name.to_string(), new_branch_metadata(env));
+
+    let branch_created = RepositoryEvent::BranchCreated {
+        versioned_file: repo_paths.versioned_file.clone(),
+        branch: name.to_string(),
+        version_id: target_version_id,
+    };
+
+    // Another version has to be checked out like any other, which also writes the data
+    if checkout && target_version_id != head_version_id {
+        check_out(env, repo_paths, repo_data, name, false)?;
+        events::emit(env, branch_created);
+        return Ok(CreateBranchResult::Ok);
+    }
+
+    if checkout {
+        repo_data.head = Head::Branch(name.to_string());
+    }
+
+    repository_io::write_data(repo_paths, repo_data)?;
+
+    events::emit(env, branch_created);
 
-    Ok(ResetResult::Ok)
+    Ok(CreateBranchResult::Ok)
 }
 
-pub enum CheckOutResult {
+pub enum SetBranchResult {
     Ok,
+    BranchDoesNotExist,
     InvalidTarget,
+    // Versions only the branch leads to would no longer be on any branch
+    CannotLeaveOrphans(usize),
+    BranchProtected,
 }
 
-pub fn check_out(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str) -> BiverResult<CheckOutResult> {
-    let has_uncommitted_changes = has_uncommitted_changes(repo_paths, repo_data)?;
-
-    let new_head = match resolve_target(repo_data, target) {
-        TargetResult::Invalid => return Ok(CheckOutResult::InvalidTarget),
-        TargetResult::Branch(branch) => Head::Branch(branch.to_string()),
-        TargetResult::Version(version) => Head::Version(version.id),
+// Nothing is erased, the branch only points elsewhere. When head is on the branch, the versioned file is left as it is,
+// like with a reset that is not hard.
+pub fn set_branch(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, name: &str, target: &str, force: bool) -> BiverResult<SetBranchResult> {
+    let Some(previous_leaf_id) = repo_data.branches.get(name).copied() else {
+        return Ok(SetBranchResult::BranchDoesNotExist);
     };
 
-    repo_data.head = new_head;
-    let new_head_version = repo_data.head_version();
+    if repo_data.is_protected(name) && !force {
+        return Ok(SetBranchResult::BranchProtected);
+    }
 
-    repository_io::write_data(repo_paths, repo_data)?;
+    let Some(target_version) = resolve_version_target(repo_data, target) else {
+        return Ok(SetBranchResult::InvalidTarget);
+    };
+    let target_version_id = target_version.id;
+
+    repo_data.branches.insert(name.to_string(), target_version_id);
 
-    if !has_uncommitted_changes {
-        repository_io::extract_version_content(env, repo_paths, &new_head_version.content_blob, &repo_paths.versioned_file)?;
+    let orphan_count = repo_data.problems().iter().filter(|p| matches!(p, DataProblem::VersionOutsideBranches(_))).count();
+    if orphan_count > 0 {
+        repo_data.branches.insert(name.to_string(), previous_leaf_id);
+        return Ok(SetBranchResult::CannotLeaveOrphans(orphan_count));
     }
 
-    Ok(CheckOutResult::Ok)
+    effects::write_data(env, repo_paths, repo_data)?;
+
+    Ok(SetBranchResult::Ok)
 }
 
-pub enum RestoreResult {
+pub enum RenameBranchResult {
     Ok,
-    BlockedByUncommittedChanges,
-    InvalidTarget,
+    AnotherBranchExistsWithSameName,
+    BranchDoesNotExist,
 }
 
-pub fn restore(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, target: &str, output: Option<&Path>) -> BiverResult<RestoreResult> {
-    let has_uncommitted_changes = has_uncommitted_changes(repo_paths, repo_data)?;
+pub fn rename_branch(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, old_name: &str, new_name: &str) -> BiverResult<RenameBranchResult> {
+    if old_name == new_name {
+        return Ok(RenameBranchResult::Ok);
+    }
 
-    if has_uncommitted_changes {
-        return Ok(RestoreResult::BlockedByUncommittedChanges);
+    if repo_data.branches.contains_key(new_name) {
+        return Ok(RenameBranchResult::AnotherBranchExistsWithSameName);
     }
 
-    let target_version = match resolve_target(repo_data, target) {
-        TargetResult::Invalid => return Ok(RestoreResult::InvalidTarget),
-        TargetResult::Branch(branch) => repo_data.version(repo_data.branches[branch]).expect("Branch resolved from target must exist"),
-        TargetResult::Version(version) => version,
+    let Some(branch_version_id) = repo_data.branches.remove(old_name) else {
+        return Ok(RenameBranchResult::BranchDoesNotExist);
     };
 
-    let output = output.unwrap_or_else(|| &repo_paths.versioned_file);
+    repo_data.branches.insert(new_name.to_string(), branch_version_id);
 
-    repository_io::extract_version_content(env, repo_paths, &target_version.content_blob, output)?;
+    if repo_data.protected_branches.remove(old_name) {
+        repo_data.protected_branches.insert(new_name.to_string());
+    }
 
-    Ok(RestoreResult::Ok)
-}
+    if let Some(metadata) = repo_data.branch_metadata.remove(old_name) {
+        repo_data.branch_metadata.insert(new_name.to_string(), metadata);
+    }
 
-pub enum VersionResult<'a> {
-    Ok(&'a Version),
-    InvalidTarget,
-}
+    repository_io::write_data(repo_paths, repo_data)?;
 
-pub fn version<'a>(repo_data: &'a RepositoryData, target: &str) -> VersionResult<'a> {
-    let version = match resolve_target(repo_data, target) {
-        TargetResult::Invalid => return VersionResult::InvalidTarget,
-        TargetResult::Version(version) => version,
-        TargetResult::Branch(branch) => repo_data.branch_leaf(branch).expect("Branch resolved from target must exist"),
-    };
+    events::emit(
+        env,
+        RepositoryEvent::BranchRenamed {
+            versioned_file: repo_paths.versioned_file.clone(),
+            old_name: old_name.to_string(),
+            new_name: new_name.to_string(),
+        },
+    );
 
-    VersionResult::Ok(version)
+    Ok(RenameBranchResult::Ok)
 }
 
-pub enum PreviewResult {
-    Ok(PathBuf),
-    NoPreviewAvailable,
+fn new_branch_metadata(env: &Env) -> BranchMetadata {
+    BranchMetadata {
+        description: String::new(),
+        creation_time: Some(Utc::now()),
+        creator: env.author.clone(),
+    }
 }
 
-pub fn preview(repo_paths: &RepositoryPaths, version: &Version) -> PreviewResult {
-    match version.preview_blob_file_name.as_ref() {
-        None => PreviewResult::NoPreviewAvailable,
-        Some(preview_file_name) => PreviewResult::Ok(repo_paths.file_path(preview_file_name)),
+pub enum DescribeBranchResult {
+    Ok,
+    BranchDoesNotExist,
+}
+
+// An empty description removes it
+pub fn describe_branch(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, name: &str, description: &str) -> BiverResult<DescribeBranchResult> {
+    if !repo_data.branches.contains_key(name) {
+        return Ok(DescribeBranchResult::BranchDoesNotExist);
     }
+
+    repo_data.branch_metadata.entry(name.to_string()).or_default().description = description.trim().to_string();
+
+    repository_io::write_data(repo_paths, repo_data)?;
+
+    Ok(DescribeBranchResult::Ok)
 }
 
-pub enum CreateBranchResult {
+pub enum ProtectBranchResult {
     Ok,
-    BranchAlreadyExists,
-    InvalidBranchName,
+    BranchDoesNotExist,
+    AlreadyInState,
 }
 
-pub fn create_branch(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, name: &str, checkout: bool) -> BiverResult<CreateBranchResult> {
-    if repo_data.branches.contains_key(name) {
-        return Ok(CreateBranchResult::BranchAlreadyExists);
+pub fn protect_branch(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, name: &str, protected: bool) -> BiverResult<ProtectBranchResult> {
+    if !repo_data.branches.contains_key(name) {
+        return Ok(ProtectBranchResult::BranchDoesNotExist);
     }
 
-    if !valid_branch_name(name) {
-        return Ok(CreateBranchResult::InvalidBranchName);
-    }
+    let changed = if protected {
+        repo_data.protected_branches.insert(name.to_string())
+    } else {
+        repo_data.protected_branches.remove(name)
+    };
 
-    let head_version_id = repo_data.head_version().id;
+    if !changed {
+        return Ok(ProtectBranchResult::AlreadyInState);
+    }
 
-    repo_data.branches.insert(name.to_string(), head_version_id);
+    repository_io::write_data(repo_paths, repo_data)?;
 
-    if checkout {
-        repo_data.head = Head::Branch(name.to_string());
-    }
+    Ok(ProtectBranchResult::Ok)
+}
 
+pub fn set_patch_limit(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, patch_limit: Option<PatchLimit>) -> BiverResult<()> {
+    repo_data.patch_limit = patch_limit;
     repository_io::write_data(repo_paths, repo_data)?;
 
-    Ok(CreateBranchResult::Ok)
+    Ok(())
 }
 
-pub enum RenameBranchResult {
+pub enum PinVersionResult {
     Ok,
-    AnotherBranchExistsWithSameName,
-    BranchDoesNotExist,
+    InvalidTarget,
+    AlreadyInState,
 }
 
-pub fn rename_branch(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, old_name: &str, new_name: &str) -> BiverResult<RenameBranchResult> {
-    if old_name == new_name {
-        return Ok(RenameBranchResult::Ok);
-    }
-
-    if repo_data.branches.contains_key(new_name) {
-        return Ok(RenameBranchResult::AnotherBranchExistsWithSameName);
-    }
+// Pinned versions survive prune, reset, amend and branch deletion. A branch target pins its leaf.
+pub fn pin_version(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, target: &str, pinned: bool) -> BiverResult<PinVersionResult> {
+    let version_id = match version(repo_data, target) {
+        VersionResult::Ok(version) => version.id,
+        VersionResult::InvalidTarget => return Ok(PinVersionResult::InvalidTarget),
+    };
 
-    let Some(branch_version_id) = repo_data.branches.remove(old_name) else {
-        return Ok(RenameBranchResult::BranchDoesNotExist);
+    let changed = if pinned {
+        repo_data.pinned_versions.insert(version_id)
+    } else {
+        repo_data.pinned_versions.remove(&version_id)
     };
 
-    repo_data.branches.insert(new_name.to_string(), branch_version_id);
+    if !changed {
+        return Ok(PinVersionResult::AlreadyInState);
+    }
 
     repository_io::write_data(repo_paths, repo_data)?;
 
-    Ok(RenameBranchResult::Ok)
+    events::emit(
+        env,
+        RepositoryEvent::PinChanged {
+            versioned_file: repo_paths.versioned_file.clone(),
+            version_id,
+            pinned,
+        },
+    );
+
+    Ok(PinVersionResult::Ok)
 }
 
 pub enum DeleteBranchResult {
-    Ok,
+    // With where the content of the branch leaf was kept, if the trash is on
+    Ok(Option<PathBuf>),
     BranchDoesNotExist,
     CannotDeleteHead,
+    BranchProtected,
+    VersionPinned(VersionId),
 }
 
-pub fn delete_branch(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, name: &String) -> BiverResult<DeleteBranchResult> {
+pub fn delete_branch(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, name: &str, force: bool) -> BiverResult<DeleteBranchResult> {
     if !repo_data.branches.contains_key(name) {
         return Ok(DeleteBranchResult::BranchDoesNotExist);
     }
 
+    if repo_data.is_protected(name) && !force {
+        return Ok(DeleteBranchResult::BranchProtected);
+    }
+
     let branch_leaf_version_id = repo_data.branches[name];
 
     let versions_on_other_branches = {
@@ -440,7 +3548,7 @@ pub fn delete_branch(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryDat
         .iter_version_and_ancestors(branch_leaf_version_id)
         .map(|v| v.id)
         .take_while(|id| !versions_on_other_branches.contains(id))
-        .collect::<Vec<_>>();
+        .collect::<HashSet<_>>();
 
     let head_version = repo_data.head_version();
 
@@ -448,12 +3556,171 @@ pub fn delete_branch(repo_paths: &RepositoryPaths, repo_data: &mut RepositoryDat
         return Ok(DeleteBranchResult::CannotDeleteHead);
     }
 
+    if let Some(pinned_version_id) = repo_data.pinned_versions.iter().find(|id| erased_version_ids.contains(id)) {
+        return Ok(DeleteBranchResult::VersionPinned(*pinned_version_id));
+    }
+
+    // Only the leaf is kept, the versions before it were steps towards it. A leaf other branches lead through stays anyway.
+    let trashed_path = match repo_data.version(branch_leaf_version_id).filter(|v| erased_version_ids.contains(&v.id)) {
+        Some(leaf_version) => trash::keep(env, repo_paths, |trashed_path| {
+            repository_io::extract_version_content(env, repo_paths, &leaf_version.content_blob, trashed_path)
+        })?,
+        None => None,
+    };
+
     repo_data.branches.remove(name);
-    repo_data.versions.retain(|v| !erased_version_ids.contains(&v.id));
+    repo_data.protected_branches.remove(name);
+    repo_data.branch_metadata.remove(name);
+    let erased_versions = repo_data.remove_versions_and_reparent(&erased_version_ids);
+
+    store_patches_without_base_as_full(env, repo_paths, repo_data)?;
+
+    let referenced_blob_file_names = repo_data.referenced_blob_file_names();
+    let unreferenced_blob_file_names: Vec<_> = erased_versions
+        .iter()
+        .flat_map(|v| v.blob_file_names())
+        .filter(|n| !referenced_blob_file_names.contains(n))
+        .map(|n| n.to_string())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    for blob_file_name in &unreferenced_blob_file_names {
+        repo_data.blob_checksums.remove(blob_file_name);
+    }
 
-    repository_io::write_data(repo_paths, repo_data)?;
+    effects::write_data(env, repo_paths, repo_data)?;
+
+    delete_erased_objects(env, repo_paths, &unreferenced_blob_file_names)?;
+
+    let erased_version_record_file_names: Vec<_> = erased_versions.iter().map(|v| blob_store::version_record_file_name(v.id)).collect();
+    delete_erased_objects(env, repo_paths, &erased_version_record_file_names)?;
+
+    events::emit(
+        env,
+        RepositoryEvent::BranchDeleted {
+            versioned_file: repo_paths.versioned_file.clone(),
+            branch: name.to_string(),
+            erased_version_ids: erased_versions.iter().map(|v| v.id).collect(),
+        },
+    );
+
+    Ok(DeleteBranchResult::Ok(trashed_path))
+}
+
+// Identical content shares blobs across branches, so a remaining version can be a patch against the full blob
+// of a removed one. Such versions are stored as full blobs, which leaves the old base free to be deleted.
+fn store_patches_without_base_as_full(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData) -> BiverResult<()> {
+    let full_blob_file_names: HashSet<&str> = repo_data
+        .versions_and_orphans()
+        .filter_map(|v| match &v.content_blob {
+            ContentBlob::Full { full_blob_file_name } => Some(full_blob_file_name.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let dependent_versions: Vec<&Version> = repo_data
+        .versions_and_orphans()
+        .filter(|v| matches!(&v.content_blob, ContentBlob::Patch { base_blob_file_name, .. } if !full_blob_file_names.contains(base_blob_file_name.as_str())))
+        .collect();
+
+    let content = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "content.tmp"));
+    let mut new_content_blobs = HashMap::new();
+    let mut new_blob_checksums = HashMap::new();
+
+    for version in dependent_versions {
+        let full_blob_file_name = blob_store::full_blob_file_name(version.versioned_file_hash);
+
+        if !new_blob_checksums.contains_key(&full_blob_file_name) && !fs::exists(repo_paths.blob_path(&full_blob_file_name))? {
+            extract_version_content(env, repo_paths, repo_data, version, content.path())?;
+            effects::store_full_blob(env, repo_paths, content.path(), &full_blob_file_name)?;
+
+            // Checksums are read from the stored blobs, which a dry run does not write
+            let checksum = if env.dry_run {
+                None
+            } else {
+                Some(integrity::blob_checksum(env, repo_paths, repo_data.hash_algorithm, &full_blob_file_name)?)
+            };
+            new_blob_checksums.insert(full_blob_file_name.clone(), checksum);
+        }
+
+        new_content_blobs.insert(version.id, ContentBlob::Full { full_blob_file_name });
+    }
+
+    for (blob_file_name, checksum) in new_blob_checksums {
+        if let Some(checksum) = checksum {
+            repo_data.blob_checksums.insert(blob_file_name, checksum);
+        }
+    }
+
+    let versions = repo_data.versions.iter_mut().chain(repo_data.orphaned_versions.iter_mut().map(|o| &mut o.version));
+    for version in versions {
+        if let Some(content_blob) = new_content_blobs.remove(&version.id) {
+            version.content_blob = content_blob;
+            effects::write_version_record(env, repo_paths, version)?;
+        }
+    }
+
+    Ok(())
+}
+
+// A word list in the repository directory takes precedence over the one given on the command line
+fn nickname_words(env: &Env, repo_paths: &RepositoryPaths) -> BiverResult<NicknameWords> {
+    let repository_words_path = repo_paths.file_path(repository_io::NICKNAME_WORDS_FILE_NAME);
+
+    let (words, path) = match (repository_io::read_nickname_words(&repository_words_path)?, &env.nickname_words) {
+        (Some(words), _) => (words, repository_words_path),
+        (None, Some(path)) => match repository_io::read_nickname_words(path)? {
+            Some(words) => (words, path.clone()),
+            None => return error(format!("Nickname word list {} does not exist", path.display())),
+        },
+        (None, None) => return Ok(NicknameWords::built_in()),
+    };
+
+    if let Err(message) = words.validate() {
+        return error(format!("Nickname words in {} are not usable: {}", path.display(), message));
+    }
+
+    Ok(words)
+}
+
+pub const MIN_ID_PREFIX_LENGTH: usize = 4;
+
+pub enum VersionAtTimeResult<'v> {
+    Ok {
+        version: &'v Version,
+        // Created after the version and by the time, but on other branches, so they may have been what was open then
+        newer_elsewhere: Vec<&'v Version>,
+    },
+    BranchDoesNotExist,
+    NoVersionYet,
+}
+
+// The newest version created by the time among the branch leaf and its ancestors. Where a branch pointed in the past
+// is not recorded, so a branch that was reset or set since is followed as it is now. Without a branch, head is followed.
+pub fn version_at_time<'v>(repo_data: &'v RepositoryData, time: DateTime<Utc>, branch: Option<&str>) -> VersionAtTimeResult<'v> {
+    let leaf_id = match branch {
+        Some(branch) => match repo_data.branches.get(branch) {
+            Some(leaf_id) => *leaf_id,
+            None => return VersionAtTimeResult::BranchDoesNotExist,
+        },
+        None => repo_data.head_version().id,
+    };
+
+    let lineage: Vec<&Version> = repo_data.iter_version_and_ancestors(leaf_id).collect();
+
+    let Some(version) = lineage.iter().filter(|v| v.creation_time < time).max_by_key(|v| v.creation_time) else {
+        return VersionAtTimeResult::NoVersionYet;
+    };
 
-    Ok(DeleteBranchResult::Ok)
+    let mut newer_elsewhere: Vec<&Version> = repo_data
+        .versions
+        .iter()
+        .filter(|v| v.creation_time > version.creation_time && v.creation_time < time && !lineage.iter().any(|l| l.id == v.id))
+        .collect();
+    newer_elsewhere.sort_by_key(|v| v.creation_time);
+
+    VersionAtTimeResult::Ok { version, newer_elsewhere }
 }
 
 enum TargetResult<'b, 'v> {
@@ -487,7 +3754,7 @@ fn resolve_target<'b, 'v>(repo_data: &'v RepositoryData, target: &'b str) -> Tar
         return TargetResult::Version(repo_data.head_version());
     }
 
-    if target.chars().nth(0) == Some('~')
+    if target.starts_with('~')
         && let Ok(offset) = usize::from_str(&target[1..])
     {
         let target_version = repo_data.iter_head_and_ancestors().nth(offset);
@@ -497,51 +3764,64 @@ fn resolve_target<'b, 'v>(repo_data: &'v RepositoryData, target: &'b str) -> Tar
         };
     }
 
-    // As version nickname
-    let mut versions: Vec<_> = repo_data.versions.iter().collect();
-    versions.sort_by(|a, b| b.creation_time.cmp(&a.creation_time));
-
-    let version = versions.iter().find(|v| nickname_matches(&v.nickname, target));
-
-    if let Some(version) = version {
+    // As unique version ID prefix
+    if let [version] = id_prefix_target_matches(repo_data, target)[..] {
         return TargetResult::Version(version);
     }
 
-    TargetResult::Invalid
+    // As version nickname, which must not be ambiguous
+    match nickname_target_matches(repo_data, target)[..] {
+        [version] => TargetResult::Version(version),
+        _ => TargetResult::Invalid,
+    }
 }
 
-fn resolve_target_strict_mut<'v>(repo_data: &'v mut RepositoryData, target: &str) -> Option<&'v mut Version> {
-    if target.is_empty() {
-        return None;
+// Shorter prefixes are too likely to be meant as nickname abbreviations. IDs are case-sensitive.
+pub fn id_prefix_target_matches<'v>(repo_data: &'v RepositoryData, target: &str) -> Vec<&'v Version> {
+    if target.len() < MIN_ID_PREFIX_LENGTH {
+        return Vec::new();
     }
 
-    let target_as_version_id = VersionId::from_bs58(target);
+    repo_data.versions.iter().filter(|v| v.id.bs58().starts_with(target)).collect()
+}
 
-    if let Some(target_as_version_id) = target_as_version_id {
-        let version = repo_data.versions.iter_mut().find(|v| v.id == target_as_version_id);
-        if let Some(version) = version {
-            return Some(version);
-        }
-    }
+// The same length for every version keeps the status columns aligned. Only neighbours in sorted order can share
+// the longest prefix.
+pub fn short_id_length(repo_data: &RepositoryData) -> usize {
+    let mut ids: Vec<String> = repo_data.versions.iter().map(|v| v.id.bs58()).collect();
+    ids.sort();
 
-    None
+    ids.windows(2)
+        .map(|pair| pair[0].chars().zip(pair[1].chars()).take_while(|(a, b)| a == b).count() + 1)
+        .fold(MIN_ID_PREFIX_LENGTH, usize::max)
 }
 
-fn resolve_version_id_target<'v>(repo_data: &'v RepositoryData, target: &str) -> Option<&'v Version> {
-    if target.is_empty() {
-        return None;
-    }
+// An exact nickname takes precedence over abbreviations of other nicknames, and one chosen with rename version
+// over a generated one. Newest first.
+pub fn nickname_target_matches<'v>(repo_data: &'v RepositoryData, target: &str) -> Vec<&'v Version> {
+    let mut versions: Vec<_> = repo_data.versions.iter().collect();
+    versions.sort_by_key(|v| Reverse(v.creation_time));
 
-    let target_as_version_id = VersionId::from_bs58(target);
+    let exact_matches: Vec<_> = versions.iter().copied().filter(|v| v.nickname.eq_ignore_ascii_case(target)).collect();
 
-    if let Some(target_as_version_id) = target_as_version_id {
-        let version = repo_data.versions.iter().find(|v| v.id == target_as_version_id);
-        if let Some(version) = version {
-            return Some(version);
-        }
+    if exact_matches.iter().any(|v| v.custom_nickname) {
+        return exact_matches.into_iter().filter(|v| v.custom_nickname).collect();
     }
 
-    None
+    if !exact_matches.is_empty() {
+        return exact_matches;
+    }
+
+    versions.into_iter().filter(|v| nickname_matches(&v.nickname, target)).collect()
+}
+
+// For operations on a version rather than on a branch, a branch name stands for its leaf
+fn resolve_version_target<'v>(repo_data: &'v RepositoryData, target: &str) -> Option<&'v Version> {
+    match resolve_target(repo_data, target) {
+        TargetResult::Version(version) => Some(version),
+        TargetResult::Branch(branch) => repo_data.branch_leaf(branch),
+        TargetResult::Invalid => None,
+    }
 }
 
 fn nickname_matches(nickname: &str, input: &str) -> bool {
@@ -562,7 +3842,7 @@ fn nickname_matches(nickname: &str, input: &str) -> bool {
             }
         }
 
-        zip_length == input.len()
+        zip_length == input.chars().count()
     }
 
     if nickname_without_dash_matches(nickname, input) {
@@ -570,34 +3850,105 @@ fn nickname_matches(nickname: &str, input: &str) -> bool {
     }
 
     fn nickname_initials_match(nickname: &str, input: &str) -> bool {
-        if input.len() != 2 {
+        let mut input_chars = input.chars();
+        let (Some(input_initials_first), Some(input_initials_second), None) = (input_chars.next(), input_chars.next(), input_chars.next()) else {
             return false;
-        }
+        };
 
-        let input_initials_first = input.chars().nth(0).unwrap();
-        let input_initials_second = input.chars().nth(1).unwrap();
+        // Words of custom lists may be non-English, so initials are taken by character rather than by byte
+        let mut nickname_words = nickname.split('-');
+        let nickname_initials_first = nickname_words.next().and_then(|w| w.chars().next());
+        let nickname_initials_second = nickname_words.next().and_then(|w| w.chars().next());
 
-        let index_of_dash = nickname.find('-').unwrap();
-        let nickname_initials_first = nickname.chars().nth(0).unwrap();
-        let nickname_initials_second = nickname.chars().nth(index_of_dash + 1).unwrap();
+        let initial_matches = |input_initial: char, nickname_initial: Option<char>| nickname_initial.is_some_and(|n| n.eq_ignore_ascii_case(&input_initial));
 
-        input_initials_first.eq_ignore_ascii_case(&nickname_initials_first) && input_initials_second.eq_ignore_ascii_case(&nickname_initials_second)
+        initial_matches(input_initials_first, nickname_initials_first) && initial_matches(input_initials_second, nickname_initials_second)
     }
 
     nickname_initials_match(nickname, input)
 }
 
-fn content_blob_file_name(version_id: VersionId) -> String {
-    version_id.to_file_name() + "_content"
+struct PendingBlobs {
+    preview: Option<BackgroundProcess>,
+    patches: Vec<PendingPatch>,
+}
+
+struct PendingPatch {
+    process: BackgroundProcess,
+    base_blob_file_name: String,
 }
 
-fn preview_blob_file_name(env: &Env, repo_paths: &RepositoryPaths, version_id: VersionId) -> Option<String> {
-    if can_create_preview(env, repo_paths) {
-        let file_name = version_id.to_file_name() + "_preview";
-        Some(file_name)
+// Preview and patch generation only depend on the versioned file, so they start before hashing
+// and run alongside it. Whatever turns out to be unnecessary is killed when dropped.
+fn start_pending_blobs(env: &Env, repo_paths: &RepositoryPaths, repo_data: Option<&RepositoryData>, parent_id: Option<VersionId>) -> BiverResult<PendingBlobs> {
+    let preview = if can_create_preview(env, repo_paths) {
+        let process = BackgroundProcess::start(
+            repo_paths.unique_temp_file_path(env, "preview.tmp"),
+            |output| image_magick::spawn_create_preview(env, &repo_paths.versioned_file, output),
+            image_magick::wait_for_preview,
+        )?;
+        Some(process)
     } else {
         None
+    };
+
+    let mut patches = Vec::new();
+    if let (Some(repo_data), Some(parent_id)) = (repo_data, parent_id)
+        && !patch_limit_reached(repo_data, parent_id)
+        && xdelta3::ready(env)
+    {
+        for (index, base_blob_file_name) in patch_base_blob_file_names(repo_data, parent_id).into_iter().enumerate() {
+            let base_blob_file_path = repo_paths.blob_path(base_blob_file_name);
+            let process = BackgroundProcess::start(
+                repo_paths.unique_temp_file_path(env, &format!("patch{}.tmp", index)),
+                |output| xdelta3::spawn_create_patch(env, &base_blob_file_path, &repo_paths.versioned_file, output),
+                xdelta3::wait_for_patch,
+            )?;
+            patches.push(PendingPatch {
+                process,
+                base_blob_file_name: base_blob_file_name.to_string(),
+            });
+        }
+    }
+
+    Ok(PendingBlobs { preview, patches })
+}
+
+// The returned transaction holds the blobs written, for the caller to add the version record to and commit
+fn store_new_version_blobs(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    repo_data: Option<&RepositoryData>,
+    file_hash: FileHash,
+    versioned_file_length: u64,
+    parent_id: Option<VersionId>,
+    pending_blobs: PendingBlobs,
+) -> BiverResult<(ContentBlob, Option<String>, Transaction)> {
+    let versioned_file_hash = file_hash.hash;
+    let PendingBlobs { preview, patches } = pending_blobs;
+    let mut transaction = Transaction::default();
+
+    if let Some(identical_version) = repo_data.and_then(|d| blob_store::find_identical_content(d, file_hash, versioned_file_length)) {
+        return Ok((identical_version.content_blob.clone(), identical_version.preview_blob_file_name.clone(), transaction));
     }
+
+    let content_blob = match (repo_data, parent_id) {
+        (Some(repo_data), Some(parent_id)) if !patches.is_empty() => store_version_content(env, repo_paths, &mut transaction, repo_data, versioned_file_hash, parent_id, patches)?,
+        _ => store_version_content_full(env, repo_paths, &mut transaction, versioned_file_hash)?,
+    };
+
+    let preview_blob_file_name = match preview {
+        Some(preview) => {
+            let preview_blob_file_name = blob_store::preview_blob_file_name(versioned_file_hash);
+            transaction.create(repo_paths.blob_path(&preview_blob_file_name), || {
+                effects::keep_blob(env, repo_paths, preview, &preview_blob_file_name)
+            })?;
+            Some(preview_blob_file_name)
+        }
+        None => None,
+    };
+
+    Ok((content_blob, preview_blob_file_name, transaction))
 }
 
 fn can_create_preview(env: &Env, repo_paths: &RepositoryPaths) -> bool {
@@ -612,15 +3963,46 @@ fn can_create_preview(env: &Env, repo_paths: &RepositoryPaths) -> bool {
     known_file_types::is_image(versioned_file_extension)
 }
 
-fn base_blob_file_name(repo_date: &RepositoryData, version_parent_id: VersionId) -> &str {
-    repo_date
-        .iter_version_and_ancestors(version_parent_id)
-        .filter_map(|v| match &v.content_blob {
-            ContentBlob::Full { full_blob_file_name } => Some(full_blob_file_name),
-            _ => None,
-        })
-        .next()
-        .expect("There should be a full blob in every branch")
+// Patches are counted back from the parent to the nearest version stored in full
+fn patch_limit_reached(repo_data: &RepositoryData, version_parent_id: VersionId) -> bool {
+    let patch_ratios = repo_data.iter_version_and_ancestors(version_parent_id).map_while(|v| match &v.content_blob {
+        ContentBlob::Patch { ratio, .. } => Some(*ratio),
+        ContentBlob::Full { .. } => None,
+    });
+
+    match repo_data.patch_limit {
+        None => false,
+        Some(PatchLimit::Count(count)) => patch_ratios.count() >= count as usize,
+        Some(PatchLimit::Auto) => patch_ratios.sum::<f64>() >= 1.0,
+    }
+}
+
+// Patches are only made against full blobs. The one the parent is based on comes first, then those of older ancestors
+// and of the latest versions elsewhere, since content that was reverted and redone can be closer to one of them.
+const MAX_PATCH_BASE_COUNT: usize = 3;
+
+fn patch_base_blob_file_names(repo_data: &RepositoryData, version_parent_id: VersionId) -> Vec<&str> {
+    let mut latest_versions: Vec<&Version> = repo_data.versions.iter().collect();
+    latest_versions.sort_by_key(|v| Reverse(v.creation_time));
+
+    let mut base_blob_file_names = Vec::new();
+
+    for version in repo_data.iter_version_and_ancestors(version_parent_id).chain(latest_versions) {
+        let base_blob_file_name = match &version.content_blob {
+            ContentBlob::Full { full_blob_file_name } => full_blob_file_name.as_str(),
+            ContentBlob::Patch { base_blob_file_name, .. } => base_blob_file_name.as_str(),
+        };
+
+        if !base_blob_file_names.contains(&base_blob_file_name) {
+            base_blob_file_names.push(base_blob_file_name);
+        }
+
+        if base_blob_file_names.len() == MAX_PATCH_BASE_COUNT {
+            break;
+        }
+    }
+
+    base_blob_file_names
 }
 
 fn patch_ratio(patch_blob_path: &Path, base_blob_path: &Path) -> io::Result<f64> {
@@ -661,42 +4043,132 @@ fn should_create_patch(real_ratio: f64, best_expected_ratio: Option<f64>) -> boo
     real_ratio - best_expected_ratio < 0.5
 }
 
-fn store_version_content(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, version_id: VersionId, parent_id: Option<VersionId>) -> BiverResult<ContentBlob> {
-    let content_blob_file_name = content_blob_file_name(version_id);
-    let content_blob_file_path = repo_paths.file_path(&content_blob_file_name);
+fn extract_version_content(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, version: &Version, destination_path: &Path) -> BiverResult<()> {
+    check_content_blobs(env, repo_paths, repo_data, version)?;
 
-    if !xdelta3::ready(env) {
-        return Ok(ContentBlob::Full {
-            full_blob_file_name: content_blob_file_name,
-        });
+    if let ContentBlob::Patch { patch_blob_file_name, .. } = &version.content_blob
+        && env.restore_cache_size > 0
+        && !env.dry_run
+    {
+        if !reconstruction_cache::restore(
+            env,
+            repo_paths,
+            patch_blob_file_name,
+            repo_data.hash_algorithm,
+            version.versioned_file_hash,
+            destination_path,
+        )? {
+            repository_io::extract_version_content(env, repo_paths, &version.content_blob, destination_path)?;
+            reconstruction_cache::store(env, repo_paths, patch_blob_file_name, destination_path, env.restore_cache_size)?;
+        }
+
+        return Ok(());
     }
 
-    let Some(parent_id) = parent_id else {
-        return Ok(ContentBlob::Full {
-            full_blob_file_name: content_blob_file_name,
-        });
-    };
+    repository_io::extract_version_content(env, repo_paths, &version.content_blob, destination_path)?;
+
+    Ok(())
+}
+
+fn check_content_blobs(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, version: &Version) -> BiverResult<()> {
+    for blob_file_name in version.blob_file_names() {
+        if version.preview_blob_file_name.as_deref() == Some(blob_file_name) {
+            continue;
+        }
+
+        match integrity::blob_status(env, repo_paths, repo_data, blob_file_name)? {
+            BlobStatus::Ok | BlobStatus::NoChecksum => {}
+            BlobStatus::Missing => return categorized_error(BiverErrorCategory::Corruption, format!("Blob {} is missing. Run verify for details.", blob_file_name)),
+            BlobStatus::Corrupted => return categorized_error(BiverErrorCategory::Corruption, format!("Blob {} is corrupted. Run verify for details.", blob_file_name)),
+        }
+    }
+
+    Ok(())
+}
+
+// The destination is only replaced once the version is fully reconstructed
+fn restore_version_file(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, version: &Version, destination_path: &Path) -> BiverResult<()> {
+    let mut transaction = Transaction::default();
+    stage_version_file(env, repo_paths, &mut transaction, repo_data, version, destination_path)?;
+    transaction.commit()?;
+
+    Ok(())
+}
 
-    let base_blob_file_name = base_blob_file_name(repo_data, parent_id);
-    let base_blob_file_path = repo_paths.file_path(&base_blob_file_name);
+fn stage_version_file(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    transaction: &mut Transaction,
+    repo_data: &RepositoryData,
+    version: &Version,
+    destination_path: &Path,
+) -> BiverResult<()> {
+    // A mode recorded with the version wins over the one of the file it replaces
+    let version_has_mode = env.preserve_times && version.unix_mode.is_some();
+    let staged_path = transaction.stage(destination_path, !version_has_mode);
+    extract_version_content(env, repo_paths, repo_data, version, &staged_path)?;
+
+    if env.preserve_times {
+        file_attributes::apply(&staged_path, version.modified_time, version.unix_mode)?;
+    }
+
+    Ok(())
+}
+
+fn store_version_content_full(env: &Env, repo_paths: &RepositoryPaths, transaction: &mut Transaction, versioned_file_hash: u128) -> BiverResult<ContentBlob> {
+    let full_blob_file_name = blob_store::full_blob_file_name(versioned_file_hash);
+
+    if !fs::exists(repo_paths.blob_path(&full_blob_file_name))? {
+        transaction.create(repo_paths.blob_path(&full_blob_file_name), || {
+            effects::store_full_blob(env, repo_paths, &repo_paths.versioned_file, &full_blob_file_name)
+        })?;
+    }
+
+    Ok(ContentBlob::Full { full_blob_file_name })
+}
+
+// The smallest of the patches against the different bases is kept, the others are discarded
+fn store_version_content(
+    env: &Env,
+    repo_paths: &RepositoryPaths,
+    transaction: &mut Transaction,
+    repo_data: &RepositoryData,
+    versioned_file_hash: u128,
+    parent_id: VersionId,
+    pending_patches: Vec<PendingPatch>,
+) -> BiverResult<ContentBlob> {
+    let mut smallest_patch: Option<(PendingPatch, u64)> = None;
+
+    for mut pending_patch in pending_patches {
+        let patch_length = fs::metadata(pending_patch.process.wait()?)?.len();
+
+        if smallest_patch.as_ref().is_none_or(|(_, smallest_length)| patch_length < *smallest_length) {
+            smallest_patch = Some((pending_patch, patch_length));
+        }
+    }
 
-    repository_io::store_version_content_patch(env, &content_blob_file_path, &base_blob_file_path, &repo_paths.versioned_file)?;
+    let (mut pending_patch, _) = smallest_patch.expect("There must be at least one pending patch");
+    tracing::debug!("Patching against {}", pending_patch.base_blob_file_name);
 
-    let patch_ratio = patch_ratio(&content_blob_file_path, &base_blob_file_path)?;
+    let base_blob_file_path = repo_paths.blob_path(&pending_patch.base_blob_file_name);
+
+    let patch_ratio = patch_ratio(pending_patch.process.wait()?, &base_blob_file_path)?;
     let best_expected_patch_ratio = best_expected_patch_ratio(repo_data, parent_id);
     let should_create_patch = should_create_patch(patch_ratio, best_expected_patch_ratio);
 
     let content_blob = if should_create_patch {
+        let patch_blob_file_name = blob_store::patch_blob_file_name(versioned_file_hash);
+        transaction.create(repo_paths.blob_path(&patch_blob_file_name), || {
+            effects::keep_blob(env, repo_paths, pending_patch.process, &patch_blob_file_name)
+        })?;
+
         ContentBlob::Patch {
-            base_blob_file_name: base_blob_file_name.to_string(),
-            patch_blob_file_name: content_blob_file_name,
+            base_blob_file_name: pending_patch.base_blob_file_name,
+            patch_blob_file_name,
             ratio: patch_ratio,
         }
     } else {
-        repository_io::store_version_content_full(&content_blob_file_path, &repo_paths.versioned_file)?;
-        ContentBlob::Full {
-            full_blob_file_name: content_blob_file_name,
-        }
+        store_version_content_full(env, repo_paths, transaction, versioned_file_hash)?
     };
 
     Ok(content_blob)
@@ -705,3 +4177,6 @@ fn store_version_content(env: &Env, repo_paths: &RepositoryPaths, repo_data: &Re
 fn valid_branch_name(branch_name: &str) -> bool {
     branch_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,365 @@
+use super::*;
+use crate::test_support::{DagStep, TestRepository, content, dag_steps, edit, read_data};
+use proptest::prelude::*;
+use std::ffi::OsString;
+
+fn assert_restores(repo: &TestRepository, versions: &[(VersionId, Vec<u8>)]) {
+    let output = repo.dir().join("restored.bin");
+
+    for (version_id, content) in versions {
+        let result = restore(&repo.env, &repo.repo_paths, &repo.data, &version_id.bs58(), Some(&output)).expect("Restore must succeed");
+        assert!(matches!(result, RestoreResult::Ok), "Restore must succeed");
+        assert_eq!(&fs::read(&output).expect("Restored file must be read"), content);
+    }
+}
+
+fn assert_no_problems(repo: &TestRepository) {
+    assert_eq!(repo.data.problems().len(), 0, "Data left in memory must have no problems");
+    assert_eq!(repo.data_on_disk().problems().len(), 0, "Data written must have no problems");
+}
+
+fn version_graph(repo_data: &RepositoryData) -> Vec<(VersionId, Option<VersionId>, u128, Vec<&str>)> {
+    repo_data.versions.iter().map(|v| (v.id, v.parent, v.versioned_file_hash, v.blob_file_names())).collect()
+}
+
+fn is_patch(version: &Version) -> bool {
+    matches!(version.content_blob, ContentBlob::Patch { .. })
+}
+
+#[test]
+fn reset_refuses_to_erase_the_leaf_of_another_branch() {
+    let mut repo = TestRepository::new(b"root");
+    let versions = repo.build(&[DagStep::Commit(b"first".to_vec()), DagStep::Commit(b"second".to_vec())]);
+    let (root_id, _) = &versions[0];
+    let (first_id, _) = &versions[1];
+
+    let result = create_branch(&repo.env, &repo.repo_paths, &mut repo.data, "other", Some(&first_id.bs58()), true).expect("Branch creation must succeed");
+    assert!(matches!(result, CreateBranchResult::Ok), "Branch creation must succeed");
+
+    let result = reset(&repo.env, &repo.repo_paths, &mut repo.data, &root_id.bs58(), false, false).expect("Reset must not fail");
+
+    assert!(matches!(result, ResetResult::CannotLeaveOrphans), "Reset must be refused");
+    assert_eq!(repo.data.branches["other"], *first_id);
+    assert_restores(&repo, &versions);
+    assert_no_problems(&repo);
+}
+
+#[test]
+fn prune_refuses_to_rewrite_a_protected_branch_without_force() {
+    let mut repo = TestRepository::new(b"root");
+    let versions = repo.build(&[DagStep::Commit(b"first".to_vec()), DagStep::Commit(b"second".to_vec()), DagStep::Commit(b"third".to_vec())]);
+    let (first_id, second_id) = (versions[1].0, versions[2].0);
+
+    // Two versions of the same old week, one of which the policy prunes
+    for version in repo.data.versions.iter_mut().filter(|v| v.id == first_id || v.id == second_id) {
+        version.creation_time = Utc::now() - TimeDelta::days(100);
+    }
+    let policy = RetentionPolicy {
+        keep_all_days: 7,
+        keep_daily_days: 30,
+    };
+
+    let branch = repo.data.head.branch().expect("Head must be a branch").to_string();
+    protect_branch(&repo.repo_paths, &mut repo.data, &branch, true).expect("Protecting must succeed");
+
+    let result = prune(&repo.env, &repo.repo_paths, &mut repo.data, &policy, false).expect("Prune must not fail");
+    assert!(matches!(result, PruneResult::BranchProtected(b) if b == branch), "Prune must be refused");
+    assert_eq!(repo.data.versions.len(), 4);
+
+    let result = prune(&repo.env, &repo.repo_paths, &mut repo.data, &policy, true).expect("Prune must not fail");
+    assert!(matches!(result, PruneResult::Ok { pruned_version_count: 1, .. }), "Prune must succeed with force");
+    assert_no_problems(&repo);
+}
+
+// Forked from a file whose name is not plain ASCII, or on Linux not even valid Unicode, the origin names that file exactly
+#[test]
+fn fork_records_the_origin_path_as_it_is() {
+    let repo = TestRepository::new(b"root");
+
+    let mut names = vec![OsString::from("Ünïcödé ☃ 🎨 合成.psd"), OsString::from("e\u{301}-combining.psd")];
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        names.push(OsString::from_vec(b"line\nbreak caf\xe9.psd".to_vec()));
+    }
+
+    for (index, name) in names.iter().enumerate() {
+        let origin_file = repo.dir().join(name);
+        let result = fork(&repo.env, &repo.repo_paths, &repo.data, &repo.data.head_version().id.bs58(), &origin_file, None).expect("Fork must succeed");
+        assert!(matches!(result, ForkResult::Ok), "Fork must succeed");
+
+        let origin_paths = RepositoryPaths::from_versioned_file_path(origin_file.clone());
+        let origin_data = read_data(&origin_paths);
+        let forked_file = repo.dir().join(format!("forked-{}.psd", index));
+        let result = fork(&repo.env, &origin_paths, &origin_data, &origin_data.head_version().id.bs58(), &forked_file, None).expect("Fork must succeed");
+        assert!(matches!(result, ForkResult::Ok), "Fork must succeed");
+
+        let forked_data = read_data(&RepositoryPaths::from_versioned_file_path(forked_file));
+        let origin = forked_data.head_version().forked_from.as_ref().expect("Fork must record its origin");
+        assert_eq!(origin.versioned_file, fs::canonicalize(&origin_file).expect("Origin must exist"));
+        assert_eq!(origin.version_id, origin_data.head_version().id);
+    }
+}
+
+#[test]
+fn wildcard_matches_like_file_name_patterns() {
+    let matches = |pattern: &str, name: &str| wildcard_matches(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>());
+
+    assert!(matches("shot-*.png", "shot-001.png"));
+    assert!(matches("shot-???.png", "shot-001.png"));
+    assert!(matches("*", ""));
+    assert!(matches("*a*b", "xaxxb"));
+    assert!(matches("a*b*c", "abbbc"));
+    assert!(!matches("shot-*.png", "shot-001.psd"));
+    assert!(!matches("shot-??.png", "shot-001.png"));
+    assert!(!matches("*a*b", "xaxxbx"));
+    assert!(!matches("", "a"));
+
+    // Exponential when each * tries every split
+    let name = "a".repeat(64);
+    assert!(!matches(&format!("{}b", "*a".repeat(16)), &name));
+}
+
+#[test]
+fn import_series_dates_versions_by_file_time() {
+    let repo = TestRepository::new(b"unused");
+    let series_dir = repo.dir().join("series");
+    fs::create_dir(&series_dir).expect("Series directory must be created");
+
+    let times: Vec<DateTime<Utc>> = ["2024-03-01T09:00:00Z", "2024-03-02T09:00:00Z", "2024-03-03T09:00:00Z"]
+        .iter()
+        .map(|t| t.parse().expect("Time must parse"))
+        .collect();
+    for (index, time) in times.iter().enumerate() {
+        let path = series_dir.join(format!("shot-{}.png", index + 1));
+        fs::write(&path, format!("content {}", index)).expect("Series file must be written");
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified((*time).into()))
+            .expect("File time must be set");
+    }
+
+    let repo_paths = RepositoryPaths::from_versioned_file_path(repo.dir().join("imported.png"));
+    let pattern = series_dir.join("shot-*.png");
+    let result = import_series(&repo.env, &repo_paths, &pattern.to_string_lossy(), SeriesOrder::Number, repo.env.hash_algorithm).expect("Import must succeed");
+    assert!(
+        matches!(
+            result,
+            ImportSeriesResult::Ok {
+                imported_count: 3,
+                skipped_count: 0
+            }
+        ),
+        "Import must succeed"
+    );
+
+    let repo_data = read_data(&repo_paths);
+    let creation_times: Vec<_> = repo_data.iter_head_and_ancestors().map(|v| v.creation_time).collect();
+    assert_eq!(creation_times, times.into_iter().rev().collect::<Vec<_>>());
+}
+
+// The data file is written last, so when it cannot be, head stays where it was. The versioned file then has the content
+// checked out, which is stored, so nothing is lost.
+#[test]
+fn check_out_keeps_head_when_the_data_file_cannot_be_written() {
+    let mut repo = TestRepository::new(b"root");
+    let versions = repo.build(&[DagStep::Commit(b"first".to_vec())]);
+    let (root_id, root_content) = &versions[0];
+    let previous_head = repo.data.head.clone();
+
+    // Where the data file is staged before being renamed into place
+    fs::create_dir(repo.repo_paths.file_path("data.tmp")).expect("Directory must be created");
+
+    let result = check_out(&repo.env, &repo.repo_paths, &mut repo.data, &root_id.bs58(), false);
+
+    assert!(result.is_err(), "Check out must fail");
+    assert_eq!(repo.data.head, previous_head);
+    assert_eq!(repo.data_on_disk().head, previous_head);
+    assert_eq!(&fs::read(&repo.repo_paths.versioned_file).expect("Versioned file must be read"), root_content);
+}
+
+// Repair restores data.json from a backup, which is of no use once the blobs it refers to are gone
+#[test]
+fn reset_and_gc_keep_the_objects_of_data_backups() {
+    let mut repo = TestRepository::new(b"root");
+    let versions = repo.build(&[DagStep::Commit(b"first".to_vec()), DagStep::Commit(b"second".to_vec())]);
+    let (root_id, _) = &versions[0];
+
+    // Backups rotate at most every few seconds, so the data from before the reset is made the freshest one this way
+    for backup_path in repository_io::data_backup_paths(&repo.repo_paths) {
+        let _ = fs::remove_file(backup_path);
+    }
+
+    let result = reset(&repo.env, &repo.repo_paths, &mut repo.data, &root_id.bs58(), false, false).expect("Reset must not fail");
+    assert!(matches!(result, ResetResult::Ok), "Reset must succeed");
+    gc(&repo.env, &repo.repo_paths, &mut repo.data, 1).expect("Gc must succeed");
+
+    let backup = repair::freshest_valid_backup(&repo.repo_paths)
+        .expect("Backups must be read")
+        .expect("The data from before the reset must be backed up");
+    assert_eq!(backup.repo_data.versions.len(), 3);
+    for version in &backup.repo_data.versions {
+        for blob_file_name in version.blob_file_names() {
+            assert!(
+                repo.repo_paths.blob_path(blob_file_name).exists(),
+                "Blob {} of a backed up version must be kept",
+                blob_file_name
+            );
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn restore_gives_back_committed_content(initial_content in content(), steps in dag_steps(12)) {
+        let mut repo = TestRepository::new(&initial_content);
+        let versions = repo.build(&steps);
+
+        assert_restores(&repo, &versions);
+        assert_no_problems(&repo);
+    }
+
+    #[test]
+    fn restore_to_temporary_file_stays_in_temp_dir(initial_content in content(), steps in dag_steps(6)) {
+        let mut repo = TestRepository::new(&initial_content);
+        let versions = repo.build(&steps);
+        let (version_id, content) = versions.last().expect("The initial version must be built");
+
+        let RestoreToTemporaryFileResult::Ok(path) = restore_to_temporary_file(&repo.env, &repo.repo_paths, &repo.data, &version_id.bs58()).expect("Restore must succeed") else {
+            panic!("Restore must succeed");
+        };
+
+        prop_assert!(path.starts_with(repo.dir()));
+        prop_assert_eq!(&fs::read(&path)?, content);
+    }
+
+    #[test]
+    fn reset_never_orphans(
+        initial_content in content(),
+        steps in dag_steps(12),
+        branch in any::<prop::sample::Index>(),
+        target in any::<prop::sample::Index>(),
+    ) {
+        let mut repo = TestRepository::new(&initial_content);
+        let versions = repo.build(&steps);
+
+        let mut branches: Vec<String> = repo.data.branches.keys().cloned().collect();
+        branches.sort();
+        let branch = &branches[branch.index(branches.len())];
+        check_out(&repo.env, &repo.repo_paths, &mut repo.data, branch, false).expect("Check out must succeed");
+
+        let (target_id, _) = &versions[target.index(versions.len())];
+        let result = reset(&repo.env, &repo.repo_paths, &mut repo.data, &target_id.bs58(), false, false).expect("Reset must not fail");
+
+        if let ResetResult::Ok = result {
+            prop_assert_eq!(repo.data.branches[branch], *target_id);
+        }
+        assert_no_problems(&repo);
+
+        // Blobs of erased versions are deleted, those of the remaining ones must not be
+        let remaining_versions: Vec<_> = versions.into_iter().filter(|(id, _)| repo.data.version(*id).is_some()).collect();
+        assert_restores(&repo, &remaining_versions);
+    }
+
+    #[test]
+    fn edits_are_stored_as_patches(initial_content in prop::collection::vec(any::<u8>(), 256..1024), edit in edit()) {
+        let mut repo = TestRepository::new(&initial_content);
+        let versions = repo.build(&[edit]);
+        prop_assume!(versions.len() == 2);
+
+        let (edited_id, _) = &versions[1];
+        let edited = repo.data.version(*edited_id).expect("Edited version must exist");
+        prop_assert!(is_patch(edited));
+        assert_restores(&repo, &versions);
+        assert_no_problems(&repo);
+    }
+
+    // A cache entry of the right length but the wrong content is reconstructed again rather than restored
+    #[test]
+    fn damaged_reconstruction_cache_entries_are_not_restored(initial_content in prop::collection::vec(any::<u8>(), 256..1024), edit in edit()) {
+        let mut repo = TestRepository::new(&initial_content);
+        repo.env.restore_cache_size = 1024 * 1024;
+        let versions = repo.build(&[edit]);
+        prop_assume!(versions.len() == 2);
+
+        assert_restores(&repo, &versions);
+        let cache_entries = reconstruction_cache::entries(&repo.repo_paths).expect("Cache entries must be listed");
+        prop_assert!(!cache_entries.is_empty());
+
+        for (cache_entry_path, size) in cache_entries {
+            fs::write(&cache_entry_path, vec![0; size as usize]).expect("Cache entry must be overwritten");
+        }
+        assert_restores(&repo, &versions);
+    }
+
+    // Committing content again reuses the blobs stored for it, the patch and the preview alike
+    #[test]
+    fn content_committed_again_shares_blobs(
+        initial_content in prop::collection::vec(any::<u8>(), 256..1024),
+        edit in edit(),
+        other_content in content(),
+    ) {
+        let mut repo = TestRepository::new(&initial_content);
+        let mut versions = repo.build(&[edit]);
+        prop_assume!(versions.len() == 2);
+
+        let (edited_id, edited_content) = versions[1].clone();
+        versions.extend(repo.build(&[DagStep::Commit(other_content), DagStep::Commit(edited_content)]).into_iter().skip(1));
+        prop_assume!(versions.len() == 4);
+
+        let (again_id, _) = &versions[3];
+        let edited = repo.data.version(edited_id).expect("Edited version must exist");
+        let again = repo.data.version(*again_id).expect("Version committed again must exist");
+        prop_assert!(is_patch(edited));
+        prop_assert_eq!(again.blob_file_names(), edited.blob_file_names());
+        assert_restores(&repo, &versions);
+        assert_no_problems(&repo);
+    }
+
+    #[test]
+    fn versions_with_the_same_content_look_the_same(initial_content in content(), steps in dag_steps(12)) {
+        let mut repo = TestRepository::new(&initial_content);
+        repo.build(&steps);
+
+        for version in &repo.data.versions {
+            prop_assert!(version.preview_blob_file_name.is_some());
+            prop_assert!(version.perceptual_hash.is_some());
+
+            let SimilarResult::Ok(similar) = similar_versions(&repo.repo_paths, &repo.data, &version.id.bs58(), 0) else {
+                panic!("Every version must have a preview");
+            };
+            for same_content in repo.data.versions.iter().filter(|v| v.id != version.id && v.versioned_file_hash == version.versioned_file_hash) {
+                prop_assert!(similar.iter().any(|(v, _)| v.id == same_content.id));
+            }
+        }
+    }
+
+    #[test]
+    fn versions_keep_the_full_digest_of_their_content(initial_content in content(), steps in dag_steps(8)) {
+        let mut repo = TestRepository::new(&initial_content);
+        let versions = repo.build(&steps);
+
+        for (version_id, content) in &versions {
+            let version = repo.data.version(*version_id).expect("Built version must exist");
+            prop_assert_eq!(version.versioned_file_digest, Some(Digest(*blake3::hash(content).as_bytes())));
+            prop_assert_eq!(version.versioned_file_digest.map(|d| d.first_half()), Some(version.versioned_file_hash));
+        }
+    }
+
+    #[test]
+    fn data_file_matches_data_in_memory(initial_content in content(), steps in dag_steps(12)) {
+        let mut repo = TestRepository::new(&initial_content);
+        repo.build(&steps);
+
+        let data_on_disk = repo.data_on_disk();
+
+        // Field by field, the maps serialize in no particular order
+        prop_assert_eq!(&data_on_disk.head, &repo.data.head);
+        prop_assert_eq!(&data_on_disk.branches, &repo.data.branches);
+        prop_assert_eq!(&data_on_disk.blob_checksums, &repo.data.blob_checksums);
+        prop_assert_eq!(version_graph(&data_on_disk), version_graph(&repo.data));
+    }
+}
@@ -1,18 +1,75 @@
+use crate::env::Env;
+use crate::object_store;
 use std::ffi::OsString;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
+use uuid::Uuid;
+
+const REPOSITORY_DIR_SUFFIX: &str = ".biver";
+
+// Windows refuses paths longer than MAX_PATH unless they are absolute and prefixed with \\?\, which also turns off
+// the normalization of separators, dots and trailing spaces, so the path is made absolute first
+#[cfg(windows)]
+pub fn extended_length(path: PathBuf) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    const MAX_PATH: usize = 260;
+    // Room for what is appended inside the repository directory, such as objects/ab/ and a blob file name
+    const APPENDED_LENGTH: usize = 100;
+
+    if path.as_os_str().len() + APPENDED_LENGTH < MAX_PATH {
+        return path;
+    }
+
+    let Ok(absolute_path) = std::path::absolute(&path) else {
+        return path;
+    };
+
+    let mut components = absolute_path.components();
+    let mut extended_path = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => {
+                let mut extended_prefix = OsString::from(r"\\?\");
+                extended_prefix.push(prefix.as_os_str());
+                PathBuf::from(extended_prefix)
+            }
+            Prefix::UNC(server, share) => {
+                let mut extended_prefix = OsString::from(r"\\?\UNC\");
+                extended_prefix.push(server);
+                extended_prefix.push(r"\");
+                extended_prefix.push(share);
+                PathBuf::from(extended_prefix)
+            }
+            // Already extended or a device path
+            _ => return absolute_path,
+        },
+        _ => return absolute_path,
+    };
+
+    extended_path.extend(components);
+    extended_path
+}
+
+#[cfg(not(windows))]
+pub fn extended_length(path: PathBuf) -> PathBuf {
+    path
+}
 
 pub struct RepositoryPaths {
     pub versioned_file: PathBuf,
     pub repository_dir: PathBuf,
     pub data_file: PathBuf,
+    pub objects_dir: PathBuf,
 }
 
 impl RepositoryPaths {
     pub fn from_versioned_file_path(versioned_file_path: PathBuf) -> Self {
+        let versioned_file_path = extended_length(versioned_file_path);
+
         let extension = match versioned_file_path.extension() {
             Some(extension) => {
                 let mut extension = OsString::from(extension);
-                extension.push(".biver");
+                extension.push(REPOSITORY_DIR_SUFFIX);
                 extension
             }
             None => OsString::from("biver"),
@@ -21,15 +78,59 @@ impl RepositoryPaths {
         let repository_dir_path = versioned_file_path.with_extension(extension);
 
         let data_file_path = repository_dir_path.join("data.json");
+        let objects_dir_path = repository_dir_path.join("objects");
 
         RepositoryPaths {
             versioned_file: versioned_file_path,
             repository_dir: repository_dir_path,
             data_file: data_file_path,
+            objects_dir: objects_dir_path,
+        }
+    }
+
+    // The name is not required to be valid Unicode, only its .biver extension is compared
+    pub fn from_repository_dir(repository_dir: &Path) -> Option<Self> {
+        if repository_dir.extension()? != REPOSITORY_DIR_SUFFIX.trim_start_matches('.') {
+            return None;
         }
+
+        Some(Self::from_versioned_file_path(repository_dir.with_extension("")))
+    }
+
+    pub fn versioned_file_name(&self) -> Option<String> {
+        self.versioned_file.file_name().map(|n| n.to_string_lossy().to_string())
     }
 
     pub fn file_path(&self, file_name: &str) -> PathBuf {
-        self.repository_dir.join(&file_name)
+        self.repository_dir.join(file_name)
+    }
+
+    // For files that are deleted once read, rather than moved into the repository
+    pub fn temp_file_path(&self, env: &Env, file_name: &str) -> PathBuf {
+        if env.read_only {
+            env.temp_dir.join(format!("biver-{}-{}", process::id(), file_name))
+        } else {
+            self.file_path(file_name)
+        }
+    }
+
+    // For temporary files that two processes working on the repository at once would otherwise both write
+    pub fn unique_temp_file_path(&self, env: &Env, file_name: &str) -> PathBuf {
+        self.temp_file_path(env, &format!("{}-{}", Uuid::new_v4().simple(), file_name))
+    }
+
+    // Repositories from before the objects directory keep their blobs next to data.json until a command writes to
+    // them, so those are read from there in the meantime
+    pub fn blob_path(&self, blob_file_name: &str) -> PathBuf {
+        let object_path = object_store::object_path(&self.objects_dir, blob_file_name);
+
+        if !object_path.exists()
+            && let Some(legacy_path) = object_store::legacy_object_path(&self.repository_dir, blob_file_name)
+            && legacy_path.is_file()
+        {
+            return legacy_path;
+        }
+
+        object_path
     }
 }
@@ -0,0 +1,46 @@
+use crate::repository_data::RepositoryData;
+use crate::version_id::VersionId;
+use chrono::{DateTime, Datelike, Duration, Local, Utc};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+pub struct RetentionPolicy {
+    pub keep_all_days: u32,
+    pub keep_daily_days: u32,
+}
+
+pub fn versions_to_prune(repo_data: &RepositoryData, policy: &RetentionPolicy, now: DateTime<Utc>) -> Vec<VersionId> {
+    let mut protected_ids: HashSet<VersionId> = repo_data.branches.values().copied().collect();
+    protected_ids.insert(repo_data.head_version().id);
+    protected_ids.extend(repo_data.versions.iter().filter(|v| v.is_root()).map(|v| v.id));
+    protected_ids.extend(repo_data.pinned_versions.iter().copied());
+
+    let keep_all_since = now - Duration::days(policy.keep_all_days as i64);
+    let keep_daily_since = now - Duration::days(policy.keep_daily_days as i64);
+
+    let mut versions: Vec<_> = repo_data.versions.iter().collect();
+    versions.sort_by_key(|v| Reverse(v.creation_time));
+
+    let mut kept_days = HashSet::new();
+    let mut kept_weeks = HashSet::new();
+    let mut pruned = Vec::new();
+
+    for version in versions {
+        let creation_date = version.creation_time.with_timezone(&Local).date_naive();
+
+        let keep = if version.creation_time >= keep_all_since {
+            true
+        } else if version.creation_time >= keep_daily_since {
+            kept_days.insert(creation_date)
+        } else {
+            let week = creation_date.iso_week();
+            kept_weeks.insert((week.year(), week.week()))
+        };
+
+        if !keep && !protected_ids.contains(&version.id) {
+            pruned.push(version.id);
+        }
+    }
+
+    pruned
+}
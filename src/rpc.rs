@@ -0,0 +1,255 @@
+use crate::biver_result::{BiverError, BiverErrorCategory, BiverErrorSeverity, BiverResult, categorized_error, error};
+use crate::daemon::repository_cache::RepositoryCache;
+use crate::env::Env;
+use crate::formatting;
+use crate::repository_data::RepositoryData;
+use crate::repository_operations;
+use crate::repository_operations::{CommitResult, ListVersionsResult, PreviewResult, RestoreResult, VersionOrder, VersionQuery, VersionResult};
+use crate::repository_paths::RepositoryPaths;
+use colored::Colorize;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+// JSON-RPC 2.0 codes. Failures of biver itself share one code and carry their category and exit code as data.
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const BIVER_ERROR: i32 = -32000;
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    // None when the request is a notification, which gets no response. An id of null is still an id.
+    #[serde(default, deserialize_with = "present")]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: RpcParams,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RpcParams {
+    file: Option<PathBuf>,
+    target: Option<String>,
+    branch: Option<String>,
+    author: Option<String>,
+    limit: Option<usize>,
+    description: Option<String>,
+    allow_duplicate: bool,
+    steal: bool,
+    output: Option<PathBuf>,
+}
+
+// One JSON-RPC 2.0 request per line on standard input, one response per line on standard output for each request with an
+// id, until standard input is closed. Requests are handled in order, so a client may send several without waiting.
+pub fn run(env: &Env, default_versioned_file_path: Option<&Path>) -> BiverResult<()> {
+    let mut repository_cache = RepositoryCache::new(env);
+    let mut stdout = io::stdout().lock();
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<RpcRequest>(&line) {
+            Err(e) => error_response(Value::Null, PARSE_ERROR, &e.to_string(), None),
+            Ok(request) => {
+                let id = request.id.clone();
+                let result = handle(env, &mut repository_cache, default_versioned_file_path, request);
+                repository_cache.apply_own_changes();
+
+                let Some(id) = id else {
+                    report_webhook_failures(env);
+                    continue;
+                };
+
+                match result {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(RpcError::MethodNotFound(method)) => error_response(id, METHOD_NOT_FOUND, &format!("Unknown method: {}", method), None),
+                    Err(RpcError::InvalidParams(message)) => error_response(id, INVALID_PARAMS, message, None),
+                    Err(RpcError::Biver(e)) => {
+                        let data = json!({
+                            "severity": e.severity,
+                            "category": e.category,
+                            "exit_code": match e.severity {
+                                BiverErrorSeverity::Warning => 0,
+                                BiverErrorSeverity::Error => e.category.exit_code(),
+                            },
+                        });
+                        error_response(id, BIVER_ERROR, &e.error_message, Some(data))
+                    }
+                }
+            }
+        };
+
+        writeln!(stdout, "{}", response)?;
+        stdout.flush()?;
+        report_webhook_failures(env);
+    }
+
+    Ok(())
+}
+
+// Waited for after the response is out, so the client is not kept waiting on them. Standard output is for responses only.
+fn report_webhook_failures(env: &Env) {
+    for failure in env.webhook_deliveries.wait() {
+        eprintln!("{}", failure.yellow());
+    }
+}
+
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+enum RpcError {
+    MethodNotFound(String),
+    InvalidParams(&'static str),
+    Biver(BiverError),
+}
+
+impl From<BiverError> for RpcError {
+    fn from(value: BiverError) -> Self {
+        RpcError::Biver(value)
+    }
+}
+
+fn handle(env: &Env, repository_cache: &mut RepositoryCache, default_versioned_file_path: Option<&Path>, request: RpcRequest) -> Result<Value, RpcError> {
+    let params = request.params;
+
+    let Some(versioned_file_path) = params.file.as_deref().or(default_versioned_file_path) else {
+        return Err(RpcError::InvalidParams("Missing parameter: file"));
+    };
+
+    let repo_paths = RepositoryPaths::from_versioned_file_path(versioned_file_path.to_path_buf());
+
+    let Some(repo_data) = repository_cache.data(&repo_paths).map_err(BiverError::from)? else {
+        return Err(RpcError::Biver(BiverError {
+            error_message: "Not initialized".to_string(),
+            severity: BiverErrorSeverity::Error,
+            category: BiverErrorCategory::NotInitialized,
+        }));
+    };
+
+    let result = match request.method.as_str() {
+        "list" => list(repo_data, &params),
+        "status" => status(env, &repo_paths, repo_data),
+        "commit" => commit(env, &repo_paths, repo_data, &params),
+        "restore" => restore(env, &repo_paths, repo_data, &params),
+        "preview_path" => preview_path(&repo_paths, repo_data, &params),
+        _ => return Err(RpcError::MethodNotFound(request.method)),
+    };
+
+    Ok(result?)
+}
+
+fn list(repo_data: &RepositoryData, params: &RpcParams) -> BiverResult<Value> {
+    let query = VersionQuery {
+        branch: params.branch.as_deref(),
+        since: None,
+        until: None,
+        author: params.author.as_deref(),
+        attributes: &[],
+        order: VersionOrder::NewestFirst,
+        limit: params.limit,
+    };
+
+    match repository_operations::list_versions(repo_data, &query) {
+        ListVersionsResult::BranchDoesNotExist => error("Branch does not exist"),
+        ListVersionsResult::Ok(versions) => Ok(json!(formatting::version_list_entries(repo_data, &versions))),
+    }
+}
+
+fn status(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData) -> BiverResult<Value> {
+    let has_uncommitted_changes = repository_operations::has_uncommitted_changes(env, repo_paths, repo_data, true)?;
+    let head_version = repo_data.head_version();
+
+    let branches: HashMap<&str, String> = repo_data.branches.iter().map(|(name, id)| (name.as_str(), id.bs58())).collect();
+
+    Ok(json!({
+        "head_branch": repo_data.head.branch(),
+        "head_version": formatting::version_list_entries(repo_data, &[head_version]).pop(),
+        "uncommitted_changes": has_uncommitted_changes,
+        "branches": branches,
+        "version_count": repo_data.versions.len(),
+    }))
+}
+
+fn commit(env: &Env, repo_paths: &RepositoryPaths, repo_data: &mut RepositoryData, params: &RpcParams) -> BiverResult<Value> {
+    // The same steps as a commit from the command line, so the editing lock holds and the new head is announced
+    let stolen_lock = crate::check_editing_lock(env, repo_paths, params.steal)?;
+
+    let result = crate::commit_version(
+        env,
+        repo_paths,
+        repo_data,
+        params.description.as_deref(),
+        params.branch.as_deref(),
+        false,
+        params.allow_duplicate,
+    )?;
+
+    match result {
+        CommitResult::Ok => {
+            // Taken over only once the commit went through
+            let lock_taken_over = stolen_lock.is_some() && repository_operations::take_over_editing_lock(env, repo_paths)?;
+            Ok(json!({
+                "result": "ok",
+                "version": formatting::version_list_entries(repo_data, &[repo_data.head_version()]).pop(),
+                "lock_taken_over": lock_taken_over,
+            }))
+        }
+        CommitResult::NothingToCommit => Ok(json!({ "result": "nothing_to_commit" })),
+        CommitResult::Duplicate(version_id) => Ok(json!({
+            "result": "duplicate",
+            "version": repo_data.version(version_id).and_then(|v| formatting::version_list_entries(repo_data, &[v]).pop()),
+        })),
+        CommitResult::HeadMustBeOnBranch => error("Head must be on a branch"),
+        CommitResult::BranchAlreadyExists => error("Branch already exists"),
+        CommitResult::InvalidBranchName => error("Invalid branch name"),
+        CommitResult::BranchProtected => error("Head branch is protected"),
+    }
+}
+
+fn restore(env: &Env, repo_paths: &RepositoryPaths, repo_data: &RepositoryData, params: &RpcParams) -> BiverResult<Value> {
+    let Some(target) = params.target.as_deref() else {
+        return error("Missing parameter: target");
+    };
+
+    let result = repository_operations::restore(env, repo_paths, repo_data, target, params.output.as_deref())?;
+
+    match result {
+        RestoreResult::Ok => Ok(json!({ "result": "ok" })),
+        RestoreResult::BlockedByUncommittedChanges => categorized_error(BiverErrorCategory::UncommittedChanges, "Blocked by uncommitted changes"),
+        RestoreResult::InvalidTarget => categorized_error(BiverErrorCategory::InvalidTarget, "Invalid target"),
+    }
+}
+
+fn preview_path(repo_paths: &RepositoryPaths, repo_data: &RepositoryData, params: &RpcParams) -> BiverResult<Value> {
+    let version = match params.target.as_deref() {
+        None => repo_data.head_version(),
+        Some(target) => match repository_operations::version(repo_data, target) {
+            VersionResult::InvalidTarget => return categorized_error(BiverErrorCategory::InvalidTarget, "Invalid target"),
+            VersionResult::Ok(version) => version,
+        },
+    };
+
+    match repository_operations::preview(repo_paths, version) {
+        PreviewResult::NoPreviewAvailable => Ok(json!({ "path": null })),
+        // The client's working directory may differ from biver's
+        PreviewResult::Ok(preview_file_path) => Ok(json!({ "path": std::path::absolute(preview_file_path)? })),
+    }
+}
+
+fn error_response(id: Value, code: i32, message: &str, data: Option<Value>) -> Value {
+    let mut error = json!({ "code": code, "message": message });
+    if let Some(data) = data {
+        error["data"] = data;
+    }
+
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
@@ -0,0 +1,211 @@
+use crate::command_line_arguments::CommandLineArguments;
+use crate::config::Config;
+use crate::env::Env;
+use crate::logging::LoggedCommand;
+use crate::tools::image_magick::{ImageMagickEnv, Invocation};
+use crate::tools::xdelta3::{Codec, XDelta3Env};
+use crate::tools::{Detection, Tool, image_magick, xdelta3};
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::{env, fs, io};
+
+// The tools setup offers to install or locate, the others are only needed for particular commands
+pub enum SetupTool {
+    XDelta3,
+    ImageMagick,
+}
+
+pub const SETUP_TOOLS: [SetupTool; 2] = [SetupTool::XDelta3, SetupTool::ImageMagick];
+
+// A path being tried, looked at afresh rather than as found for the configured one
+struct ToolPath<'a> {
+    path: Option<&'a Path>,
+    xdelta3_codec: OnceLock<Codec>,
+    image_magick_invocation: OnceLock<Option<Invocation>>,
+}
+
+impl<'a> ToolPath<'a> {
+    fn new(path: Option<&'a Path>) -> Self {
+        ToolPath {
+            path,
+            xdelta3_codec: OnceLock::new(),
+            image_magick_invocation: OnceLock::new(),
+        }
+    }
+}
+
+impl XDelta3Env for ToolPath<'_> {
+    fn xdelta3_path(&self) -> Option<&Path> {
+        self.path
+    }
+
+    fn xdelta3_codec(&self) -> &OnceLock<Codec> {
+        &self.xdelta3_codec
+    }
+}
+
+impl ImageMagickEnv for ToolPath<'_> {
+    fn image_magick_path(&self) -> Option<&Path> {
+        self.path
+    }
+
+    fn image_magick_invocation(&self) -> &OnceLock<Option<Invocation>> {
+        &self.image_magick_invocation
+    }
+}
+
+struct PackageManager {
+    program: &'static str,
+    // Run before the package names
+    install_arguments: &'static [&'static str],
+    xdelta3_package: Option<&'static str>,
+    image_magick_package: &'static str,
+}
+
+const PACKAGE_MANAGERS: &[PackageManager] = if cfg!(target_os = "windows") {
+    &[PackageManager {
+        program: "winget",
+        install_arguments: &["install", "--exact", "--id"],
+        xdelta3_package: None,
+        image_magick_package: "ImageMagick.ImageMagick",
+    }]
+} else if cfg!(target_os = "macos") {
+    &[PackageManager {
+        program: "brew",
+        install_arguments: &["install"],
+        xdelta3_package: Some("xdelta"),
+        image_magick_package: "imagemagick",
+    }]
+} else {
+    &[
+        PackageManager {
+            program: "apt-get",
+            install_arguments: &["install", "--yes"],
+            xdelta3_package: Some("xdelta3"),
+            image_magick_package: "imagemagick",
+        },
+        PackageManager {
+            program: "dnf",
+            install_arguments: &["install", "--assumeyes"],
+            xdelta3_package: Some("xdelta"),
+            image_magick_package: "ImageMagick",
+        },
+        PackageManager {
+            program: "pacman",
+            install_arguments: &["--sync", "--noconfirm"],
+            xdelta3_package: Some("xdelta3"),
+            image_magick_package: "imagemagick",
+        },
+    ]
+};
+
+impl SetupTool {
+    pub fn tool(&self) -> &'static Tool {
+        match self {
+            SetupTool::XDelta3 => &xdelta3::TOOL,
+            SetupTool::ImageMagick => &image_magick::TOOL,
+        }
+    }
+
+    pub fn configured_path<'e>(&self, env: &'e Env) -> Option<&'e Path> {
+        match self {
+            SetupTool::XDelta3 => env.xdelta3_path.as_deref(),
+            SetupTool::ImageMagick => env.image_magick_path.as_deref(),
+        }
+    }
+
+    pub fn config_path<'c>(&self, config: &'c mut Config) -> &'c mut Option<PathBuf> {
+        match self {
+            SetupTool::XDelta3 => &mut config.xdelta3_path,
+            SetupTool::ImageMagick => &mut config.image_magick_path,
+        }
+    }
+
+    // Without a path, the tool is searched in PATH
+    pub fn detect(&self, path: Option<&Path>) -> Detection {
+        match self {
+            SetupTool::XDelta3 => xdelta3::detect(&ToolPath::new(path)),
+            SetupTool::ImageMagick => image_magick::detect(&ToolPath::new(path)),
+        }
+    }
+
+    pub fn download_url(&self) -> &'static str {
+        match self {
+            SetupTool::XDelta3 => "https://github.com/jmacd/xdelta-gpl/releases",
+            SetupTool::ImageMagick => "https://imagemagick.org/script/download.php",
+        }
+    }
+
+    // The command line of the first package manager found that has the tool. System package managers on Linux
+    // need root, so they are run through sudo.
+    pub fn install_command(&self) -> Option<Vec<&'static str>> {
+        PACKAGE_MANAGERS.iter().filter(|m| program_exists(m.program)).find_map(|manager| {
+            let package = match self {
+                SetupTool::XDelta3 => manager.xdelta3_package?,
+                SetupTool::ImageMagick => manager.image_magick_package,
+            };
+
+            let mut command = Vec::new();
+            if cfg!(all(unix, not(target_os = "macos"))) {
+                command.push("sudo");
+            }
+            command.push(manager.program);
+            command.extend(manager.install_arguments);
+            command.push(package);
+            Some(command)
+        })
+    }
+}
+
+fn program_exists(program: &str) -> bool {
+    Command::new(program)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+// The package manager may ask for a password or a confirmation, so it gets the terminal
+pub fn run_install_command(install_command: &[&str]) -> io::Result<bool> {
+    let (program, arguments) = install_command.split_first().expect("Install command must have a program");
+    Ok(Command::new(program).args(arguments).logged_status()?.success())
+}
+
+// Where the shell loads completions from by itself. None for shells that need their profile edited.
+pub fn completions_path(shell: Shell) -> Option<PathBuf> {
+    let home = PathBuf::from(env::var_os("HOME")?);
+
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".local").join("share"));
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".config"));
+
+    match shell {
+        Shell::Bash => Some(data_home.join("bash-completion").join("completions").join("biver")),
+        Shell::Fish => Some(config_home.join("fish").join("completions").join("biver.fish")),
+        _ => None,
+    }
+}
+
+pub fn write_completions(shell: Shell, output: &mut impl io::Write) {
+    clap_complete::generate(shell, &mut CommandLineArguments::command(), "biver", output);
+}
+
+pub fn install_completions(shell: Shell, completions_path: &Path) -> io::Result<()> {
+    if let Some(completions_dir) = completions_path.parent() {
+        fs::create_dir_all(completions_dir)?;
+    }
+
+    let mut completions = Vec::new();
+    write_completions(shell, &mut completions);
+    fs::write(completions_path, completions)
+}
@@ -0,0 +1,96 @@
+use super::SHELL_ACTIONS;
+use std::fmt::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+// KDE service menus (Dolphin) and Nautilus scripts cover the two most common file managers
+pub fn install(biver_path: &Path) -> io::Result<Vec<String>> {
+    let mut installed = Vec::new();
+
+    let service_menu_path = service_menu_path()?;
+    write_executable(&service_menu_path, &service_menu(biver_path))?;
+    installed.push(service_menu_path.display().to_string());
+
+    for action in &SHELL_ACTIONS {
+        let script_path = nautilus_scripts_dir()?.join(action.label);
+        write_executable(&script_path, &nautilus_script(biver_path, action.arguments))?;
+        installed.push(script_path.display().to_string());
+    }
+
+    Ok(installed)
+}
+
+pub fn uninstall() -> io::Result<Vec<String>> {
+    let mut removed = Vec::new();
+
+    let mut paths = vec![service_menu_path()?];
+    for action in &SHELL_ACTIONS {
+        paths.push(nautilus_scripts_dir()?.join(action.label));
+    }
+
+    for path in paths {
+        if fs::exists(&path)? {
+            fs::remove_file(&path)?;
+            removed.push(path.display().to_string());
+        }
+    }
+
+    Ok(removed)
+}
+
+fn service_menu(biver_path: &Path) -> String {
+    let action_ids: Vec<String> = SHELL_ACTIONS.iter().map(|a| format!("biver{};", a.id)).collect();
+
+    let mut desktop_entry = String::new();
+    let _ = writeln!(desktop_entry, "[Desktop Entry]\nType=Service\nMimeType=all/allfiles;\nActions={}", action_ids.concat());
+
+    for action in &SHELL_ACTIONS {
+        let _ = writeln!(
+            desktop_entry,
+            "\n[Desktop Action biver{}]\nName={}\nExec=konsole --hold -e \"{}\" {} -f %f",
+            action.id,
+            action.label,
+            biver_path.display(),
+            action.arguments.join(" ")
+        );
+    }
+
+    desktop_entry
+}
+
+fn nautilus_script(biver_path: &Path, arguments: &[&str]) -> String {
+    format!(
+        "#!/bin/sh\nfor file in \"$@\"; do\n    gnome-terminal -- sh -c '\"$0\" {} -f \"$1\"; echo; echo \"Press Enter to close\"; read -r _' '{}' \"$file\"\ndone\n",
+        arguments.join(" "),
+        biver_path.display().to_string().replace('\'', "'\\''")
+    )
+}
+
+fn write_executable(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, contents)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+
+    Ok(())
+}
+
+fn service_menu_path() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("kio").join("servicemenus").join("biver.desktop"))
+}
+
+fn nautilus_scripts_dir() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("nautilus").join("scripts"))
+}
+
+fn data_dir() -> io::Result<PathBuf> {
+    if let Some(data_home) = env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(data_home));
+    }
+
+    let home = env::var_os("HOME").ok_or_else(|| io::Error::other("HOME is not set"))?;
+    Ok(PathBuf::from(home).join(".local").join("share"))
+}
@@ -0,0 +1,221 @@
+use super::{SHELL_ACTIONS, ShellAction};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+// Each action is an Automator Quick Action, which Finder lists under Quick Actions and Services
+pub fn install(biver_path: &Path) -> io::Result<Vec<String>> {
+    let mut installed = Vec::new();
+
+    for action in &SHELL_ACTIONS {
+        let workflow_path = workflow_path(action)?;
+        let contents_dir = workflow_path.join("Contents");
+        fs::create_dir_all(&contents_dir)?;
+
+        fs::write(contents_dir.join("Info.plist"), info_plist(action))?;
+        fs::write(contents_dir.join("document.wflow"), document_wflow(&shell_script(biver_path, action.arguments)))?;
+
+        installed.push(workflow_path.display().to_string());
+    }
+
+    Ok(installed)
+}
+
+pub fn uninstall() -> io::Result<Vec<String>> {
+    let mut removed = Vec::new();
+
+    for action in &SHELL_ACTIONS {
+        let workflow_path = workflow_path(action)?;
+
+        if fs::exists(&workflow_path)? {
+            fs::remove_dir_all(&workflow_path)?;
+            removed.push(workflow_path.display().to_string());
+        }
+    }
+
+    Ok(removed)
+}
+
+fn workflow_path(action: &ShellAction) -> io::Result<PathBuf> {
+    let home = env::var_os("HOME").ok_or_else(|| io::Error::other("HOME is not set"))?;
+    Ok(PathBuf::from(home).join("Library").join("Services").join(format!("BiVer {}.workflow", action.id)))
+}
+
+// Opens a Terminal window running biver on each selected file
+fn shell_script(biver_path: &Path, arguments: &[&str]) -> String {
+    format!(
+        "for f in \"$@\"; do\n    osascript -e 'on run argv' -e 'tell application \"Terminal\" to do script (quoted form of item 1 of argv & \" {} -f \" & quoted form of item 2 of argv)' -e 'tell application \"Terminal\" to activate' -e 'end run' '{}' \"$f\"\ndone",
+        arguments.join(" "),
+        biver_path.display().to_string().replace('\'', "'\\''")
+    )
+}
+
+fn info_plist(action: &ShellAction) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>{}</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSRequiredContext</key>
+			<dict>
+				<key>NSApplicationIdentifier</key>
+				<string>com.apple.finder</string>
+			</dict>
+			<key>NSSendFileTypes</key>
+			<array>
+				<string>public.item</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#,
+        xml_escape(action.label)
+    )
+}
+
+fn document_wflow(shell_script: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>523</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMAccepts</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Optional</key>
+					<true/>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>AMApplication</key>
+				<array>
+					<string>Automator</string>
+				</array>
+				<key>AMParameterProperties</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<dict/>
+					<key>CheckedForUserDefaultShell</key>
+					<dict/>
+					<key>inputMethod</key>
+					<dict/>
+					<key>shell</key>
+					<dict/>
+					<key>source</key>
+					<dict/>
+				</dict>
+				<key>AMProvides</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>{}</string>
+					<key>CheckedForUserDefaultShell</key>
+					<true/>
+					<key>inputMethod</key>
+					<integer>1</integer>
+					<key>shell</key>
+					<string>/bin/sh</string>
+					<key>source</key>
+					<string></string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+				<key>CFBundleVersion</key>
+				<string>2.0.3</string>
+				<key>CanShowSelectedItemsWhenRun</key>
+				<false/>
+				<key>CanShowWhenRun</key>
+				<true/>
+				<key>Category</key>
+				<array>
+					<string>AMCategoryUtilities</string>
+				</array>
+				<key>Class Name</key>
+				<string>RunShellScriptAction</string>
+				<key>InputUUID</key>
+				<string>6B4B3F7A-1C1E-4C45-9C65-0B1E1E0A0001</string>
+				<key>Keywords</key>
+				<array>
+					<string>Shell</string>
+					<string>Script</string>
+				</array>
+				<key>OutputUUID</key>
+				<string>6B4B3F7A-1C1E-4C45-9C65-0B1E1E0A0002</string>
+				<key>UUID</key>
+				<string>6B4B3F7A-1C1E-4C45-9C65-0B1E1E0A0003</string>
+				<key>UnlocalizedApplications</key>
+				<array>
+					<string>Automator</string>
+				</array>
+				<key>arguments</key>
+				<dict/>
+				<key>isViewVisible</key>
+				<integer>1</integer>
+			</dict>
+			<key>isViewVisible</key>
+			<integer>1</integer>
+		</dict>
+	</array>
+	<key>connectors</key>
+	<dict/>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>serviceApplicationBundleID</key>
+		<string>com.apple.finder</string>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject</string>
+		<key>serviceOutputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>serviceProcessesInput</key>
+		<integer>0</integer>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#,
+        xml_escape(shell_script)
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,50 @@
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "macos")]
+use macos as platform;
+
+#[cfg(target_os = "windows")]
+mod windows;
+#[cfg(target_os = "windows")]
+use windows as platform;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod linux;
+#[cfg(all(unix, not(target_os = "macos")))]
+use linux as platform;
+
+use std::io;
+use std::path::Path;
+
+pub struct ShellAction {
+    pub id: &'static str,
+    pub label: &'static str,
+    pub arguments: &'static [&'static str],
+}
+
+// Each action runs `biver <arguments> -f <selected file>` in a terminal window
+pub const SHELL_ACTIONS: [ShellAction; 3] = [
+    ShellAction {
+        id: "Commit",
+        label: "BiVer: Commit",
+        arguments: &["commit"],
+    },
+    ShellAction {
+        id: "Status",
+        label: "BiVer: Status",
+        arguments: &["status"],
+    },
+    ShellAction {
+        id: "History",
+        label: "BiVer: History",
+        arguments: &["status", "--all"],
+    },
+];
+
+pub fn install(biver_path: &Path) -> io::Result<Vec<String>> {
+    platform::install(biver_path)
+}
+
+pub fn uninstall() -> io::Result<Vec<String>> {
+    platform::uninstall()
+}
@@ -0,0 +1,55 @@
+use super::SHELL_ACTIONS;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// Per-user registration, so no administrator rights are needed
+const SHELL_KEY: &str = r"HKCU\Software\Classes\*\shell";
+
+pub fn install(biver_path: &Path) -> io::Result<Vec<String>> {
+    let mut installed = Vec::new();
+
+    for action in &SHELL_ACTIONS {
+        let key = action_key(action.id);
+        let command = format!(r#"cmd.exe /k ""{}" {} -f "%1"""#, biver_path.display(), action.arguments.join(" "));
+
+        run_reg(&["add", &key, "/ve", "/d", action.label, "/f"])?;
+        run_reg(&["add", &format!(r"{}\command", key), "/ve", "/d", &command, "/f"])?;
+
+        installed.push(key);
+    }
+
+    Ok(installed)
+}
+
+pub fn uninstall() -> io::Result<Vec<String>> {
+    let mut removed = Vec::new();
+
+    for action in &SHELL_ACTIONS {
+        let key = action_key(action.id);
+
+        let exists = Command::new("reg").args(["query", &key]).stdout(Stdio::null()).stderr(Stdio::null()).status()?.success();
+        if !exists {
+            continue;
+        }
+
+        run_reg(&["delete", &key, "/f"])?;
+        removed.push(key);
+    }
+
+    Ok(removed)
+}
+
+fn action_key(action_id: &str) -> String {
+    format!(r"{}\BiVer.{}", SHELL_KEY, action_id)
+}
+
+fn run_reg(args: &[&str]) -> io::Result<()> {
+    let output = Command::new("reg").args(args).output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+
+    Ok(())
+}
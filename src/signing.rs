@@ -0,0 +1,138 @@
+use crate::biver_result::{BiverErrorCategory, BiverResult, categorized_error};
+use crate::env::Env;
+use crate::repository_data::{Version, VersionSignature};
+use crate::repository_paths::RepositoryPaths;
+use crate::temporary_file::TemporaryFile;
+use crate::tools::minisign;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::fs;
+use std::path::Path;
+
+pub enum SignatureStatus {
+    Unsigned,
+    Valid { key_id: String, trusted: bool },
+    // A valid signature from before versions kept their full digest, which only covers the 128-bit content hash
+    Legacy { key_id: String },
+    Invalid { key_id: String },
+}
+
+// Returns None when no signing key is configured. Nothing is signed in a dry run, which would only ask for the key password.
+pub fn sign(env: &Env, repo_paths: &RepositoryPaths, version: &Version) -> BiverResult<Option<VersionSignature>> {
+    let Some(secret_key) = env.signing_key.as_deref() else {
+        return Ok(None);
+    };
+
+    if env.dry_run {
+        return Ok(None);
+    }
+
+    if !minisign::ready(env) {
+        return categorized_error(
+            BiverErrorCategory::DependencyMissing,
+            "minisign is required to sign versions with --signing-key but was not found",
+        );
+    }
+
+    let Some(payload) = signed_payload(version) else {
+        return categorized_error(
+            BiverErrorCategory::General,
+            "Only versions with a full BLAKE3 digest can be signed. Use a repository created with the blake3 hash algorithm.",
+        );
+    };
+
+    let public_key_path = secret_key.with_extension("pub");
+    let Some(public_key) = read_public_key(&public_key_path)? else {
+        return categorized_error(
+            BiverErrorCategory::General,
+            format!("{} is not a minisign public key. It is expected next to the signing key.", public_key_path.display()),
+        );
+    };
+
+    let payload_file = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "signature_payload.tmp"));
+    let signature_file = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "signature.tmp"));
+
+    fs::write(payload_file.path(), payload)?;
+    minisign::sign(env, secret_key, payload_file.path(), signature_file.path())?;
+    let signature = fs::read_to_string(signature_file.path())?;
+
+    Ok(Some(VersionSignature { public_key, signature }))
+}
+
+pub fn verify(env: &Env, repo_paths: &RepositoryPaths, version: &Version, trusted_key_ids: &[String]) -> BiverResult<SignatureStatus> {
+    let Some(version_signature) = &version.signature else {
+        return Ok(SignatureStatus::Unsigned);
+    };
+
+    let key_id = key_id(&version_signature.public_key).unwrap_or_else(|| "unknown key".to_string());
+
+    let payload_file = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "signature_payload.tmp"));
+    let signature_file = TemporaryFile::new(repo_paths.unique_temp_file_path(env, "signature.tmp"));
+
+    fs::write(signature_file.path(), &version_signature.signature)?;
+
+    if let Some(payload) = signed_payload(version) {
+        fs::write(payload_file.path(), payload)?;
+        if minisign::verify(env, &version_signature.public_key, payload_file.path(), signature_file.path())? {
+            // The public key travels with the signature, so a valid signature alone says nothing about who made it
+            let trusted = trusted_key_ids.contains(&key_id);
+            return Ok(SignatureStatus::Valid { key_id, trusted });
+        }
+    }
+
+    fs::write(payload_file.path(), legacy_signed_payload(version))?;
+    if minisign::verify(env, &version_signature.public_key, payload_file.path(), signature_file.path())? {
+        return Ok(SignatureStatus::Legacy { key_id });
+    }
+
+    Ok(SignatureStatus::Invalid { key_id })
+}
+
+// A minisign public key file is an untrusted comment line followed by the base64 key
+pub fn read_public_key(path: &Path) -> BiverResult<Option<String>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let public_key = contents.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"));
+
+    Ok(public_key.filter(|key| key_id(key).is_some()).map(str::to_string))
+}
+
+// The key ID as minisign prints it: the 8 bytes after the algorithm, little endian, in hex
+pub fn key_id(public_key: &str) -> Option<String> {
+    let bytes = STANDARD.decode(public_key).ok()?;
+    let key_id_bytes: [u8; 8] = bytes.get(2..10)?.try_into().ok()?;
+    Some(format!("{:016X}", u64::from_le_bytes(key_id_bytes)))
+}
+
+// The parent is left out since prune reparents versions, and the description, attributes, notes and reviews since they may be changed by anyone later.
+// The content is covered by its full BLAKE3 digest, so versions without one cannot be signed.
+fn signed_payload(version: &Version) -> Option<String> {
+    let digest = version.versioned_file_digest?;
+
+    Some(format!(
+        "biver version signature v2\nid {}\ncreation_time {}\nauthor {}\nnickname {}\nlength {}\nblake3 {}\n",
+        version.id.bs58(),
+        version.creation_time.to_rfc3339(),
+        version.author,
+        version.nickname,
+        version.versioned_file_length,
+        digest
+    ))
+}
+
+// Signatures made before v2 cover the 128-bit content hash, labelled xxh3_128 whatever the algorithm
+fn legacy_signed_payload(version: &Version) -> String {
+    format!(
+        "biver version signature v1\nid {}\ncreation_time {}\nauthor {}\nnickname {}\nlength {}\nxxh3_128 {:032x}\n",
+        version.id.bs58(),
+        version.creation_time.to_rfc3339(),
+        version.author,
+        version.nickname,
+        version.versioned_file_length,
+        version.versioned_file_hash
+    )
+}
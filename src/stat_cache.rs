@@ -0,0 +1,77 @@
+use crate::effects;
+use crate::env::Env;
+use crate::hash;
+use crate::hash::{Digest, FileHash, HashAlgorithm};
+use crate::repository_paths::RepositoryPaths;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, Metadata};
+use std::time::{Duration, SystemTime};
+use std::{fs, io};
+
+pub const STAT_CACHE_FILE_NAME: &str = "stat_cache.json";
+
+// A file modified this recently may change again within the same mtime tick without the change being visible,
+// so its hash is not cached.
+const RACY_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize)]
+struct StatRecord {
+    modified: SystemTime,
+    length: u64,
+    // Records written before repositories had a choice of algorithm are xxh3
+    #[serde(default)]
+    algorithm: HashAlgorithm,
+    #[serde(rename = "xxh3_128")]
+    hash: u128,
+    // Records written before versions kept the full BLAKE3 digest have none, and are not used for BLAKE3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    digest: Option<Digest>,
+}
+
+pub fn versioned_file_hash(env: &Env, repo_paths: &RepositoryPaths, algorithm: HashAlgorithm, trust_cache: bool) -> io::Result<FileHash> {
+    let metadata = fs::metadata(&repo_paths.versioned_file)?;
+
+    if trust_cache
+        && let Some(record) = read_record(repo_paths)
+        && record.algorithm == algorithm
+        && record.digest.is_some() != algorithm.is_xxh3()
+        && record.length == metadata.len()
+        && record.modified == metadata.modified()?
+    {
+        return Ok(FileHash {
+            hash: record.hash,
+            digest: record.digest,
+        });
+    }
+
+    let file_hash = hash::file_hashes(env, algorithm, &File::open(&repo_paths.versioned_file)?)?;
+
+    write_record(env, repo_paths, &metadata, algorithm, file_hash)?;
+
+    Ok(file_hash)
+}
+
+fn read_record(repo_paths: &RepositoryPaths) -> Option<StatRecord> {
+    let contents = fs::read(repo_paths.file_path(STAT_CACHE_FILE_NAME)).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+fn write_record(env: &Env, repo_paths: &RepositoryPaths, metadata: &Metadata, algorithm: HashAlgorithm, file_hash: FileHash) -> io::Result<()> {
+    let modified = metadata.modified()?;
+
+    if !effects::update_caches(env) || modified + RACY_INTERVAL > SystemTime::now() || !fs::exists(&repo_paths.repository_dir)? {
+        return Ok(());
+    }
+
+    let record = StatRecord {
+        modified,
+        length: metadata.len(),
+        algorithm,
+        hash: file_hash.hash,
+        digest: file_hash.digest,
+    };
+
+    fs::write(repo_paths.file_path(STAT_CACHE_FILE_NAME), serde_json::to_string_pretty(&record)?)?;
+
+    Ok(())
+}
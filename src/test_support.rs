@@ -0,0 +1,211 @@
+use crate::effects::Effects;
+use crate::env::Env;
+use crate::events::Events;
+use crate::hash::HashAlgorithm;
+use crate::notifications::WebhookDeliveries;
+use crate::repository_data::{Head, RepositoryData};
+use crate::repository_io;
+use crate::repository_io::RepositoryDataResult;
+use crate::repository_operations;
+use crate::repository_operations::{CheckOutResult, CommitResult, CreateBranchResult, InitResult};
+use crate::repository_paths::RepositoryPaths;
+use crate::tools::xdelta3::Codec;
+use crate::trash::TrashMode;
+use crate::version_id::VersionId;
+use proptest::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{fs, io};
+
+// An image type, so that versions get previews, which the image crate cannot decode, so that they are what diffs compare
+const VERSIONED_FILE_NAME: &str = "file.psd";
+
+static NEXT_FIXTURE_ID: AtomicUsize = AtomicUsize::new(0);
+
+// Operations get everything from the env, so a test env is a plain one with nothing taken from the machine. xdelta3 and
+// ImageMagick are replaced by stand-ins: the bundled VCDIFF codec and a preview writer of the image crate, so patches
+// and previews are made whatever is installed. The other tools point at a path that does not exist, which is how they
+// look when not installed.
+pub fn test_env(dir: &Path) -> Env {
+    let missing_tool = dir.join("missing-tool");
+
+    Env {
+        xdelta3_path: None,
+        image_magick_path: None,
+        git_path: Some(missing_tool.clone()),
+        rclone_path: Some(missing_tool.clone()),
+        minisign_path: Some(missing_tool),
+        signing_key: None,
+        nickname_words: None,
+        editor: None,
+        pager: None,
+        hash_chunk_size: 64 * 1024,
+        restore_cache_size: 0,
+        temp_dir: dir.to_path_buf(),
+        hash_algorithm: HashAlgorithm::Blake3,
+        trash: TrashMode::Off,
+        webhooks: Vec::new(),
+        desktop_notifications: false,
+        verbose: false,
+        author: "test".to_string(),
+        preserve_times: false,
+        dry_run: false,
+        json: false,
+        read_only: false,
+        xdelta3_codec: OnceLock::from(Codec::Bundled),
+        image_magick_invocation: OnceLock::from(None),
+        preview_stand_in: Some(write_stand_in_preview),
+        effects: Effects::default(),
+        events: Events::default(),
+        webhook_deliveries: WebhookDeliveries::default(),
+    }
+}
+
+// The bytes of the input as rows of a grey JPEG, so that different content gives different previews and perceptual hashes
+fn write_stand_in_preview(input: &Path, preview: &Path) -> io::Result<()> {
+    const WIDTH: usize = 16;
+
+    let mut pixels = fs::read(input)?;
+    let height = pixels.len().div_ceil(WIDTH).max(1);
+    pixels.resize(WIDTH * height, 0);
+
+    let image = image::GrayImage::from_raw(WIDTH as u32, height as u32, pixels).expect("Pixels must fill the image");
+    image.save_with_format(preview, image::ImageFormat::Jpeg).map_err(io::Error::other)
+}
+
+// An initialized repository in a directory of its own, removed when dropped
+pub struct TestRepository {
+    pub env: Env,
+    pub repo_paths: RepositoryPaths,
+    pub data: RepositoryData,
+    dir: PathBuf,
+}
+
+impl TestRepository {
+    pub fn new(initial_content: &[u8]) -> Self {
+        let fixture_id = NEXT_FIXTURE_ID.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("biver-test-{}-{}", process::id(), fixture_id));
+        fs::create_dir_all(&dir).expect("Fixture directory must be created");
+
+        let env = test_env(&dir);
+        let repo_paths = RepositoryPaths::from_versioned_file_path(dir.join(VERSIONED_FILE_NAME));
+        fs::write(&repo_paths.versioned_file, initial_content).expect("Versioned file must be written");
+
+        let init_result = repository_operations::init(&env, &repo_paths, None, None, None, env.hash_algorithm).expect("Init must succeed");
+        assert!(matches!(init_result, InitResult::Ok), "Init must succeed");
+
+        let data = read_data(&repo_paths);
+
+        TestRepository { env, repo_paths, data, dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Of the data file, to check what an operation wrote rather than what it left in memory
+    pub fn data_on_disk(&self) -> RepositoryData {
+        read_data(&self.repo_paths)
+    }
+
+    pub fn write_versioned_file(&self, content: &[u8]) {
+        fs::write(&self.repo_paths.versioned_file, content).expect("Versioned file must be written");
+    }
+
+    // Builds the version graph the steps describe, returning every version with the content committed as it
+    pub fn build(&mut self, steps: &[DagStep]) -> Vec<(VersionId, Vec<u8>)> {
+        let root = self.data.head_version();
+        let mut versions = vec![(root.id, fs::read(&self.repo_paths.versioned_file).expect("Versioned file must be read"))];
+        let mut branch_count = 0;
+
+        for step in steps {
+            match step {
+                DagStep::Commit(content) => self.commit(content.clone(), &mut versions),
+                DagStep::Edit { at, bytes } => {
+                    let mut content = fs::read(&self.repo_paths.versioned_file).expect("Versioned file must be read");
+                    let start = at.index(content.len());
+                    let end = (start + bytes.len()).min(content.len());
+                    content.splice(start..end, bytes.iter().copied());
+
+                    self.commit(content, &mut versions);
+                }
+                DagStep::Branch(index) => {
+                    let (target, _) = &versions[index.index(versions.len())];
+                    branch_count += 1;
+                    let name = format!("branch-{}", branch_count);
+
+                    let result =
+                        repository_operations::create_branch(&self.env, &self.repo_paths, &mut self.data, &name, Some(&target.bs58()), true).expect("Branch creation must succeed");
+                    assert!(matches!(result, CreateBranchResult::Ok), "Branch creation must succeed");
+                }
+                DagStep::CheckOut(index) => {
+                    let mut branches: Vec<String> = self.data.branches.keys().cloned().collect();
+                    branches.sort();
+                    let branch = &branches[index.index(branches.len())];
+
+                    let result = repository_operations::check_out(&self.env, &self.repo_paths, &mut self.data, branch, false).expect("Check out must succeed");
+                    assert!(matches!(result, CheckOutResult::Ok { .. }), "Check out must succeed");
+                    assert_eq!(self.data.head, Head::Branch(branch.clone()));
+                }
+            }
+        }
+
+        versions
+    }
+
+    fn commit(&mut self, content: Vec<u8>, versions: &mut Vec<(VersionId, Vec<u8>)>) {
+        self.write_versioned_file(&content);
+        let result = repository_operations::commit_version(&self.env, &self.repo_paths, &mut self.data, None, None, true, true).expect("Commit must succeed");
+
+        match result {
+            CommitResult::Ok => versions.push((self.data.head_version().id, content)),
+            // The content of the head version again
+            CommitResult::NothingToCommit => {}
+            _ => panic!("Commit on a branch must succeed"),
+        }
+    }
+}
+
+impl Drop for TestRepository {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// One step of building a version graph. Branches start at and check out any version committed so far, so commits after
+// them fork the graph. Edits overwrite a few bytes of the versioned file, so unlike new content they are stored as patches.
+#[derive(Debug, Clone)]
+pub enum DagStep {
+    Commit(Vec<u8>),
+    Edit { at: prop::sample::Index, bytes: Vec<u8> },
+    Branch(prop::sample::Index),
+    CheckOut(prop::sample::Index),
+}
+
+pub fn content() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<u8>(), 1..512)
+}
+
+pub fn edit() -> impl Strategy<Value = DagStep> {
+    (any::<prop::sample::Index>(), prop::collection::vec(any::<u8>(), 1..8)).prop_map(|(at, bytes)| DagStep::Edit { at, bytes })
+}
+
+pub fn dag_steps(max_len: usize) -> impl Strategy<Value = Vec<DagStep>> {
+    let step = prop_oneof![
+        2 => content().prop_map(DagStep::Commit),
+        2 => edit(),
+        1 => any::<prop::sample::Index>().prop_map(DagStep::Branch),
+        1 => any::<prop::sample::Index>().prop_map(DagStep::CheckOut),
+    ];
+
+    prop::collection::vec(step, 0..max_len)
+}
+
+pub fn read_data(repo_paths: &RepositoryPaths) -> RepositoryData {
+    match repository_io::read_data(repo_paths).expect("Data file must be read") {
+        RepositoryDataResult::Initialized(data) => *data,
+        RepositoryDataResult::NotInitialized { .. } => panic!("Repository must be initialized"),
+    }
+}
@@ -0,0 +1,100 @@
+use crate::logging::{LoggedChild, LoggedCommand};
+use crate::tools;
+use crate::tools::{Detection, Tool, ToolVersion};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::{fs, io};
+
+pub trait GitEnv {
+    fn git_path(&self) -> Option<&Path>;
+}
+
+// rev-parse --absolute-git-dir appeared in git 2.13
+pub const TOOL: Tool = Tool {
+    name: "git",
+    purpose: "Used for exporting version history to git",
+    version_args: &["--version"],
+    minimum_version: ToolVersion(2, 13, 0),
+    capabilities: &[],
+};
+
+pub fn ready(env: &impl GitEnv) -> bool {
+    tools::ready(&TOOL, git_command(env))
+}
+
+pub fn detect(env: &impl GitEnv) -> Detection {
+    tools::detect(&TOOL, || git_command(env))
+}
+
+pub fn init_bare(env: &impl GitEnv, repo: &Path) -> io::Result<()> {
+    fs::create_dir_all(repo)?;
+    run(git_command(env).arg("init").arg("--quiet").arg("--bare").arg(repo)).map(|_| ())
+}
+
+pub fn absolute_git_dir(env: &impl GitEnv, repo: &Path) -> io::Result<Option<PathBuf>> {
+    let output = git_command(env)
+        .arg("-C")
+        .arg(repo)
+        .arg("rev-parse")
+        .arg("--absolute-git-dir")
+        .stderr(Stdio::null())
+        .logged_output()?;
+
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    Ok(Some(PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())))
+}
+
+pub fn is_bare(env: &impl GitEnv, repo: &Path) -> io::Result<bool> {
+    let output = run(git_command(env).arg("-C").arg(repo).arg("rev-parse").arg("--is-bare-repository"))?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "true")
+}
+
+pub fn spawn_fast_import(env: &impl GitEnv, repo: &Path, export_marks: &Path) -> io::Result<Child> {
+    let mut export_marks_argument = OsString::from("--export-marks=");
+    export_marks_argument.push(export_marks);
+
+    git_command(env)
+        .arg("-C")
+        .arg(repo)
+        .arg("fast-import")
+        .arg("--quiet")
+        .arg(export_marks_argument)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .logged_spawn()
+}
+
+pub fn wait_for_fast_import(child: &mut Child) -> io::Result<()> {
+    let status = child.logged_wait()?;
+    if status.success() { Ok(()) } else { Err(io::Error::other("git fast-import failed.")) }
+}
+
+pub fn delete_ref(env: &impl GitEnv, repo: &Path, reference: &str) -> io::Result<()> {
+    run(git_command(env).arg("-C").arg(repo).arg("update-ref").arg("-d").arg(reference)).map(|_| ())
+}
+
+pub fn set_head(env: &impl GitEnv, repo: &Path, reference: &str) -> io::Result<()> {
+    run(git_command(env).arg("-C").arg(repo).arg("symbolic-ref").arg("HEAD").arg(reference)).map(|_| ())
+}
+
+fn run(command: &mut Command) -> io::Result<Output> {
+    let output = command.logged_output()?;
+
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::other(format!("git failed: {}", message)));
+    }
+
+    Ok(output)
+}
+
+fn git_command(env: &impl GitEnv) -> Command {
+    let mut git_path = env.git_path();
+    let git_path = git_path.get_or_insert_with(|| Path::new("git"));
+
+    Command::new(git_path)
+}
@@ -0,0 +1,176 @@
+use crate::background_process::Worker;
+use crate::logging::{LoggedChild, LoggedCommand};
+use crate::tools;
+use crate::tools::{Capability, Detection, Tool, ToolVersion};
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::OnceLock;
+
+pub trait ImageMagickEnv {
+    fn image_magick_path(&self) -> Option<&Path>;
+    // Set on first use, since looking for ImageMagick spawns up to three processes
+    fn image_magick_invocation(&self) -> &OnceLock<Option<Invocation>>;
+
+    // Writes previews in place of ImageMagick, from the input path to the preview path. Test environments use one, so
+    // that previews are made whatever is installed.
+    fn preview_stand_in(&self) -> Option<PreviewStandIn> {
+        None
+    }
+}
+
+pub type PreviewStandIn = fn(&Path, &Path) -> io::Result<()>;
+
+// ImageMagick 6 is still what many Linux distributions ship, 6.9 being the oldest line that is maintained
+pub const TOOL: Tool = Tool {
+    name: "ImageMagick",
+    purpose: "Used for creating version previews for image files",
+    version_args: &["-version"],
+    minimum_version: ToolVersion(6, 9, 0),
+    capabilities: &[
+        Capability {
+            probe_args: &["-list", "format"],
+            is_present: |formats| supports_format(formats, "PSD"),
+            missing_warning: "Built without PSD support, previews of .psd files cannot be created",
+        },
+        Capability {
+            probe_args: &["-list", "format"],
+            is_present: |formats| supports_format(formats, "PNG"),
+            missing_warning: "Built without PNG support, previews of .png files cannot be created",
+        },
+        Capability {
+            probe_args: &["-list", "format"],
+            is_present: |formats| supports_format(formats, "JPEG"),
+            missing_warning: "Built without JPEG support, no previews can be created since they are stored as JPEG",
+        },
+    ],
+};
+
+// The magick command replaced convert in ImageMagick 7, which still accepts convert as a subcommand.
+// ImageMagick 6 only has convert. All of them take the input file first, followed by operators and the output file.
+// Windows has a convert of its own that converts FAT volumes to NTFS, so a convert is only used if it says it is
+// ImageMagick.
+const INVOCATIONS: [Invocation; 3] = [
+    Invocation {
+        program: "magick",
+        leading_args: &[],
+    },
+    Invocation {
+        program: "magick",
+        leading_args: &["convert"],
+    },
+    Invocation {
+        program: "convert",
+        leading_args: &[],
+    },
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct Invocation {
+    program: &'static str,
+    leading_args: &'static [&'static str],
+}
+
+pub fn ready(env: &impl ImageMagickEnv) -> bool {
+    env.preview_stand_in().is_some() || resolve_invocation(env).is_some()
+}
+
+pub fn detect(env: &impl ImageMagickEnv) -> Detection {
+    match resolve_invocation(env) {
+        Some(invocation) => tools::detect(&TOOL, || image_magick_command(env, invocation)),
+        None => Detection::Missing,
+    }
+}
+
+// Lines of "magick -list format" start with the format name, marked with * when it is handled natively, e.g. "      PSD* PSD       rw+   Adobe Photoshop bitmap"
+fn supports_format(formats: &str, format: &str) -> bool {
+    formats
+        .lines()
+        .any(|line| line.split_whitespace().next().map(|name| name.trim_end_matches('*')) == Some(format))
+}
+
+pub fn spawn_create_preview(env: &impl ImageMagickEnv, input: &Path, preview: &Path) -> io::Result<Worker> {
+    if let Some(stand_in) = env.preview_stand_in() {
+        let (input, preview) = (input.to_path_buf(), preview.to_path_buf());
+        return Ok(Worker::Thread(std::thread::spawn(move || stand_in(&input, &preview))));
+    }
+
+    let mut preview_with_prefix = OsString::from("jpg:");
+    preview_with_prefix.push(tools::path_argument(preview));
+
+    let Some(invocation) = resolve_invocation(env) else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "ImageMagick not found."));
+    };
+
+    image_magick_command(env, invocation)
+        .arg(tools::path_argument(input))
+        .arg("-flatten")
+        .arg("-thumbnail")
+        .arg("1024x1024>")
+        .arg(preview_with_prefix)
+        .logged_spawn()
+        .map(Worker::Process)
+}
+
+// Flattened like previews, but at full size and lossless
+pub fn convert_to_png(env: &impl ImageMagickEnv, input: &Path, output: &Path) -> io::Result<()> {
+    let mut output_with_prefix = OsString::from("png:");
+    output_with_prefix.push(tools::path_argument(output));
+
+    let Some(invocation) = resolve_invocation(env) else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "ImageMagick not found."));
+    };
+
+    map_image_magick_status(
+        image_magick_command(env, invocation)
+            .arg(tools::path_argument(input))
+            .arg("-flatten")
+            .arg(output_with_prefix)
+            .logged_status(),
+    )
+}
+
+pub fn wait_for_preview(child: &mut Child) -> io::Result<()> {
+    map_image_magick_status(child.logged_wait())
+}
+
+fn map_image_magick_status(status_result: io::Result<ExitStatus>) -> io::Result<()> {
+    status_result.and_then(|status| if status.success() { Ok(()) } else { Err(io::Error::other("ImageMagick failed.")) })
+}
+
+fn resolve_invocation(env: &impl ImageMagickEnv) -> Option<Invocation> {
+    *env.image_magick_invocation().get_or_init(|| {
+        let invocation = INVOCATIONS
+            .into_iter()
+            .find(|&invocation| tools::version_output(&TOOL, image_magick_command(env, invocation)).is_some_and(|output| output.contains("ImageMagick")));
+        match invocation {
+            Some(invocation) => tracing::debug!("Using ImageMagick as {}", invocation_display(env, invocation)),
+            None => tracing::debug!("ImageMagick not found"),
+        }
+
+        invocation
+    })
+}
+
+fn invocation_display(env: &impl ImageMagickEnv, invocation: Invocation) -> String {
+    let program = program_path(env, invocation).display().to_string();
+    [program]
+        .into_iter()
+        .chain(invocation.leading_args.iter().map(|arg| arg.to_string()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// A configured path replaces the program name but keeps the subcommand, it may point to either magick or convert
+fn program_path(env: &impl ImageMagickEnv, invocation: Invocation) -> &Path {
+    env.image_magick_path().unwrap_or(Path::new(invocation.program))
+}
+
+fn image_magick_command(env: &impl ImageMagickEnv, invocation: Invocation) -> Command {
+    let mut command = Command::new(program_path(env, invocation));
+    command.args(invocation.leading_args);
+    command.stdout(Stdio::null());
+    command.stderr(Stdio::null());
+    command
+}
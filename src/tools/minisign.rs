@@ -0,0 +1,70 @@
+use crate::logging::LoggedCommand;
+use crate::tools;
+use crate::tools::{Detection, Tool, ToolVersion};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub trait MinisignEnv {
+    fn minisign_path(&self) -> Option<&Path>;
+}
+
+// Quiet verification (-q) appeared in minisign 0.8
+pub const TOOL: Tool = Tool {
+    name: "minisign",
+    purpose: "Used for signing versions and verifying their signatures",
+    version_args: &["-v"],
+    minimum_version: ToolVersion(0, 8, 0),
+    capabilities: &[],
+};
+
+pub fn ready(env: &impl MinisignEnv) -> bool {
+    tools::ready(&TOOL, minisign_command(env))
+}
+
+pub fn detect(env: &impl MinisignEnv) -> Detection {
+    tools::detect(&TOOL, || minisign_command(env))
+}
+
+// An encrypted secret key makes minisign ask for its password on the terminal, so stdin is left attached
+pub fn sign(env: &impl MinisignEnv, secret_key: &Path, message: &Path, signature: &Path) -> io::Result<()> {
+    let status = minisign_command(env)
+        .arg("-S")
+        .arg("-s")
+        .arg(secret_key)
+        .arg("-m")
+        .arg(message)
+        .arg("-x")
+        .arg(signature)
+        .stderr(Stdio::inherit())
+        .logged_status()?;
+
+    if status.success() { Ok(()) } else { Err(io::Error::other("minisign failed to sign.")) }
+}
+
+// A signature that does not match is not a failure to run minisign, so it is reported as false rather than an error
+pub fn verify(env: &impl MinisignEnv, public_key: &str, message: &Path, signature: &Path) -> io::Result<bool> {
+    let status = minisign_command(env)
+        .arg("-V")
+        .arg("-q")
+        .arg("-P")
+        .arg(public_key)
+        .arg("-m")
+        .arg(message)
+        .arg("-x")
+        .arg(signature)
+        .stdin(Stdio::null())
+        .logged_status()?;
+
+    Ok(status.success())
+}
+
+fn minisign_command(env: &impl MinisignEnv) -> Command {
+    let mut minisign_path = env.minisign_path();
+    let minisign_path = minisign_path.get_or_insert_with(|| Path::new("minisign"));
+
+    let mut command = Command::new(minisign_path);
+    command.stdout(Stdio::null());
+    command.stderr(Stdio::null());
+    command
+}
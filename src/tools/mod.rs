@@ -0,0 +1,160 @@
+pub mod git;
+pub mod image_magick;
+pub mod minisign;
+pub mod rclone;
+mod vcdiff;
+pub mod xdelta3;
+
+use crate::logging::LoggedCommand;
+use crate::repository_paths;
+use git::GitEnv;
+use image_magick::ImageMagickEnv;
+use minisign::MinisignEnv;
+use rclone::RcloneEnv;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use xdelta3::XDelta3Env;
+
+// What biver needs to know about an external tool to tell whether it is usable, declared once per tool in its own module
+pub struct Tool {
+    pub name: &'static str,
+    pub purpose: &'static str,
+    pub version_args: &'static [&'static str],
+    pub minimum_version: ToolVersion,
+    pub capabilities: &'static [Capability],
+}
+
+// Something a tool may have been built without, such as an ImageMagick delegate
+pub struct Capability {
+    pub probe_args: &'static [&'static str],
+    pub is_present: fn(&str) -> bool,
+    pub missing_warning: &'static str,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ToolVersion(pub u32, pub u32, pub u32);
+
+impl Display for ToolVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+pub enum Detection {
+    Missing,
+    Found { version: Option<ToolVersion>, warnings: Vec<String> },
+}
+
+pub struct DetectedTool {
+    pub tool: &'static Tool,
+    pub detection: Detection,
+}
+
+pub fn detect_all(env: &(impl XDelta3Env + ImageMagickEnv + GitEnv + RcloneEnv + MinisignEnv)) -> Vec<DetectedTool> {
+    vec![
+        DetectedTool {
+            tool: &xdelta3::TOOL,
+            detection: xdelta3::detect(env),
+        },
+        DetectedTool {
+            tool: &image_magick::TOOL,
+            detection: image_magick::detect(env),
+        },
+        DetectedTool {
+            tool: &git::TOOL,
+            detection: git::detect(env),
+        },
+        DetectedTool {
+            tool: &rclone::TOOL,
+            detection: rclone::detect(env),
+        },
+        DetectedTool {
+            tool: &minisign::TOOL,
+            detection: minisign::detect(env),
+        },
+    ]
+}
+
+pub fn ready(tool: &Tool, mut command: Command) -> bool {
+    let status = command
+        .args(tool.version_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .logged_status();
+    match status {
+        Ok(status) => status.success(),
+        Err(_) => false,
+    }
+}
+
+pub fn detect(tool: &Tool, command: impl Fn() -> Command) -> Detection {
+    let Some(version_output) = run_probe(command(), tool.version_args) else {
+        return Detection::Missing;
+    };
+
+    let version = parse_version(&version_output);
+    let mut warnings = Vec::new();
+
+    if let Some(version) = version
+        && version < tool.minimum_version
+    {
+        warnings.push(format!("Version {} is older than the supported minimum {}", version, tool.minimum_version));
+    }
+
+    // Capabilities often share a probe, such as the list of supported formats, which is then run only once
+    let mut probe_outputs: HashMap<&[&str], Option<String>> = HashMap::new();
+
+    // A probe that fails says nothing about the capability, only a successful one without it is worth a warning
+    for capability in tool.capabilities {
+        let output = probe_outputs.entry(capability.probe_args).or_insert_with(|| run_probe(command(), capability.probe_args));
+
+        if let Some(output) = output
+            && !(capability.is_present)(output)
+        {
+            warnings.push(capability.missing_warning.to_string());
+        }
+    }
+
+    Detection::Found { version, warnings }
+}
+
+// Some tools print their version to stderr, so both are searched
+// Of the version arguments, when the program runs successfully with them
+pub fn version_output(tool: &Tool, command: Command) -> Option<String> {
+    run_probe(command, tool.version_args)
+}
+
+fn run_probe(mut command: Command, args: &[&str]) -> Option<String> {
+    let output = command.args(args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).logged_output().ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push('\n');
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Some(text)
+}
+
+// The first dotted number in the output, e.g. "git version 2.43.0", "Version: ImageMagick 7.1.1-29 Q16" or "rclone v1.66.0"
+fn parse_version(output: &str) -> Option<ToolVersion> {
+    output.split(|c: char| !c.is_ascii_digit() && c != '.').find_map(|word| {
+        let mut parts = word.split('.').filter(|part| !part.is_empty()).map(|part| part.parse::<u32>().ok());
+        let major = parts.next()??;
+        let minor = parts.next()??;
+        let patch = parts.next().flatten().unwrap_or(0);
+        Some(ToolVersion(major, minor, patch))
+    })
+}
+
+// Tools get absolute paths, so that a file name starting with a dash is not taken for an option and a long path can be
+// given in the extended-length form on Windows. Paths are passed as they are, without a round trip through a string.
+pub fn path_argument(path: &Path) -> PathBuf {
+    let absolute_path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    repository_paths::extended_length(absolute_path)
+}
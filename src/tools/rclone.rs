@@ -0,0 +1,111 @@
+use crate::logging::{LoggedChild, LoggedCommand};
+use crate::tools;
+use crate::tools::{Detection, Tool, ToolVersion};
+use std::collections::HashSet;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+// rclone exit codes for a missing directory and a missing file
+const DIRECTORY_NOT_FOUND_EXIT_CODE: i32 = 3;
+const FILE_NOT_FOUND_EXIT_CODE: i32 = 4;
+
+pub trait RcloneEnv {
+    fn rclone_path(&self) -> Option<&Path>;
+}
+
+// lsf, which listing remote objects relies on, appeared in rclone 1.40
+pub const TOOL: Tool = Tool {
+    name: "rclone",
+    purpose: "Used for syncing with rclone remotes",
+    version_args: &["version"],
+    minimum_version: ToolVersion(1, 40, 0),
+    capabilities: &[],
+};
+
+pub fn ready(env: &impl RcloneEnv) -> bool {
+    tools::ready(&TOOL, rclone_command(env))
+}
+
+pub fn detect(env: &impl RcloneEnv) -> Detection {
+    tools::detect(&TOOL, || rclone_command(env))
+}
+
+pub fn join(remote_path: &str, name: &str) -> String {
+    if remote_path.ends_with(':') || remote_path.ends_with('/') {
+        format!("{}{}", remote_path, name)
+    } else {
+        format!("{}/{}", remote_path, name)
+    }
+}
+
+pub fn list_file_names(env: &impl RcloneEnv, remote_dir: &str) -> io::Result<HashSet<String>> {
+    let output = rclone_command(env).arg("lsf").arg("--files-only").arg(remote_dir).logged_output()?;
+
+    if output.status.code() == Some(DIRECTORY_NOT_FOUND_EXIT_CODE) {
+        return Ok(HashSet::new());
+    }
+
+    let output = check_output(output)?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect())
+}
+
+pub fn copy_to(env: &impl RcloneEnv, source: &Path, remote_file: &str) -> io::Result<()> {
+    let output = rclone_command(env).arg("copyto").arg(source).arg(remote_file).logged_output()?;
+    check_output(output).map(|_| ())
+}
+
+pub fn copy_from(env: &impl RcloneEnv, remote_file: &str, destination: &Path) -> io::Result<()> {
+    let output = rclone_command(env).arg("copyto").arg(remote_file).arg(destination).logged_output()?;
+    check_output(output).map(|_| ())
+}
+
+pub fn delete_file(env: &impl RcloneEnv, remote_file: &str) -> io::Result<()> {
+    let output = rclone_command(env).arg("deletefile").arg(remote_file).logged_output()?;
+    check_output(output).map(|_| ())
+}
+
+pub fn read_file(env: &impl RcloneEnv, remote_file: &str) -> io::Result<Option<Vec<u8>>> {
+    let output = rclone_command(env).arg("cat").arg(remote_file).logged_output()?;
+
+    if matches!(output.status.code(), Some(DIRECTORY_NOT_FOUND_EXIT_CODE | FILE_NOT_FOUND_EXIT_CODE)) {
+        return Ok(None);
+    }
+
+    Ok(Some(check_output(output)?.stdout))
+}
+
+pub fn write_file(env: &impl RcloneEnv, remote_file: &str, contents: &[u8]) -> io::Result<()> {
+    let mut child = rclone_command(env)
+        .arg("rcat")
+        .arg(remote_file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .logged_spawn()?;
+
+    // Dropping stdin closes it, which tells rclone the upload is complete
+    child.stdin.take().expect("stdin is piped").write_all(contents)?;
+
+    check_output(child.logged_wait_with_output()?).map(|_| ())
+}
+
+fn check_output(output: Output) -> io::Result<Output> {
+    if output.status.success() {
+        return Ok(output);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("").trim();
+
+    Err(io::Error::other(format!("rclone failed. {}", message)))
+}
+
+fn rclone_command(env: &impl RcloneEnv) -> Command {
+    let mut rclone_path = env.rclone_path();
+    let rclone_path = rclone_path.get_or_insert_with(|| Path::new("rclone"));
+
+    Command::new(rclone_path)
+}
@@ -0,0 +1,619 @@
+// VCDIFF (RFC 3284) encoding and decoding in process, for when the xdelta3 executable is not available.
+// Patches are readable by xdelta3 and the patches xdelta3 makes without secondary compression (its default)
+// are readable here, including its application header and window checksum extensions. Those made with
+// secondary compression (-S djw, lzma) or a custom code table are not, see is_supported.
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Deref;
+use std::path::Path;
+use std::{fs, io};
+
+const MAGIC: [u8; 4] = [0xD6, 0xC3, 0xC4, 0x00];
+
+const VCD_DECOMPRESS: u8 = 0x01;
+const VCD_CODETABLE: u8 = 0x02;
+const VCD_APPHEADER: u8 = 0x04;
+
+const VCD_SOURCE: u8 = 0x01;
+const VCD_TARGET: u8 = 0x02;
+// xdelta3 extension: Adler-32 of the target window after the section lengths
+const VCD_ADLER32: u8 = 0x04;
+
+const NEAR_CACHE_SIZE: usize = 4;
+const SAME_CACHE_SIZE: usize = 3;
+
+const TARGET_WINDOW_SIZE: usize = 8 * 1024 * 1024;
+const MIN_BLOCK_SIZE: usize = 16;
+// Larger sources are indexed with larger blocks, which keeps the index within a few tens of megabytes
+const MAX_INDEXED_BLOCKS: usize = 1 << 20;
+const MIN_RUN_LENGTH: usize = 32;
+
+#[derive(Clone, Copy, PartialEq)]
+enum InstructionType {
+    NoOp,
+    Add,
+    Run,
+    Copy,
+}
+
+#[derive(Clone, Copy)]
+struct Instruction {
+    instruction_type: InstructionType,
+    // 0 means the size follows in the instruction section
+    size: u8,
+    mode: u8,
+}
+
+const NO_OP: Instruction = Instruction {
+    instruction_type: InstructionType::NoOp,
+    size: 0,
+    mode: 0,
+};
+
+// The default code table of RFC 3284 section 5.6
+fn default_code_table() -> [(Instruction, Instruction); 256] {
+    let mut table = [(NO_OP, NO_OP); 256];
+    let instruction = |instruction_type, size, mode| Instruction { instruction_type, size, mode };
+
+    let mut index = 0;
+    table[index].0 = instruction(InstructionType::Run, 0, 0);
+    index += 1;
+
+    for size in 0..=17 {
+        table[index].0 = instruction(InstructionType::Add, size, 0);
+        index += 1;
+    }
+
+    for mode in 0..9 {
+        table[index].0 = instruction(InstructionType::Copy, 0, mode);
+        index += 1;
+        for size in 4..=18 {
+            table[index].0 = instruction(InstructionType::Copy, size, mode);
+            index += 1;
+        }
+    }
+
+    for mode in 0..6 {
+        for add_size in 1..=4 {
+            for copy_size in 4..=6 {
+                table[index] = (instruction(InstructionType::Add, add_size, 0), instruction(InstructionType::Copy, copy_size, mode));
+                index += 1;
+            }
+        }
+    }
+
+    for mode in 6..9 {
+        for add_size in 1..=4 {
+            table[index] = (instruction(InstructionType::Add, add_size, 0), instruction(InstructionType::Copy, 4, mode));
+            index += 1;
+        }
+    }
+
+    for mode in 0..9 {
+        table[index] = (instruction(InstructionType::Copy, 4, mode), instruction(InstructionType::Add, 1, 0));
+        index += 1;
+    }
+
+    table
+}
+
+// Mapped to avoid copying large files, except empty ones, which cannot be mapped
+enum Input {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Input::Mapped(mmap) => mmap,
+            Input::Read(bytes) => bytes,
+        }
+    }
+}
+
+fn read_input(path: &Path) -> io::Result<Input> {
+    let file = File::open(path)?;
+
+    // SAFETY: the mapping is only read. Like hashing, this relies on the file not being truncated meanwhile.
+    match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => Ok(Input::Mapped(mmap)),
+        Err(_) => Ok(Input::Read(fs::read(path)?)),
+    }
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+
+    // Sums of up to 5552 bytes cannot overflow before the modulo
+    for chunk in bytes.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+
+    (b << 16) | a
+}
+
+fn write_integer(output: &mut Vec<u8>, mut value: u64) {
+    let mut bytes = [0u8; 10];
+    let mut start = bytes.len() - 1;
+    bytes[start] = (value & 0x7F) as u8;
+    value >>= 7;
+
+    while value > 0 {
+        start -= 1;
+        bytes[start] = 0x80 | (value & 0x7F) as u8;
+        value >>= 7;
+    }
+
+    output.extend_from_slice(&bytes[start..]);
+}
+
+pub fn encode_file(source_path: &Path, target_path: &Path, patch_path: &Path) -> io::Result<()> {
+    let source = read_input(source_path)?;
+    let target = read_input(target_path)?;
+
+    let mut output = BufWriter::new(File::create(patch_path)?);
+    encode(&source, &target, &mut output)?;
+    output.flush()
+}
+
+// Checks the file header only, which is where xdelta3 declares secondary compression and custom code tables
+pub fn is_supported(patch_path: &Path) -> io::Result<bool> {
+    let mut header = [0u8; 5];
+    File::open(patch_path)?.read_exact(&mut header)?;
+
+    Ok(header[..4] == MAGIC && header[4] & (VCD_DECOMPRESS | VCD_CODETABLE) == 0)
+}
+
+pub fn decode_file(source_path: &Path, patch_path: &Path, target_path: &Path) -> io::Result<()> {
+    let source = read_input(source_path)?;
+    let patch = read_input(patch_path)?;
+
+    // Opened for reading too, for windows that copy from earlier ones
+    let target_file = File::options().read(true).write(true).create(true).truncate(true).open(target_path)?;
+    let mut output = BufWriter::new(target_file);
+    decode(&source, &patch, &mut output)?;
+    output.flush()
+}
+
+// Decoded in memory, since windows copying from earlier ones need to read them back, and then written out whole
+pub fn decode_to_writer(source_path: &Path, patch_path: &Path, output: &mut impl Write) -> io::Result<()> {
+    let source = read_input(source_path)?;
+    let patch = read_input(patch_path)?;
+
+    let mut target = Vec::new();
+    decode(&source, &patch, &mut target)?;
+    output.write_all(&target)
+}
+
+// Decoded windows are written out as they are completed. The few patches with windows copying from earlier ones
+// (VCD_TARGET) read those parts back from the output.
+trait DecodedOutput: Write {
+    fn read_back(&mut self, position: usize, length: usize) -> io::Result<Vec<u8>>;
+}
+
+impl DecodedOutput for Vec<u8> {
+    fn read_back(&mut self, position: usize, length: usize) -> io::Result<Vec<u8>> {
+        let end = position.checked_add(length).ok_or_else(|| invalid("segment overflow"))?;
+        self.get(position..end).map(<[u8]>::to_vec).ok_or_else(|| invalid("target segment out of range"))
+    }
+}
+
+impl DecodedOutput for BufWriter<File> {
+    fn read_back(&mut self, position: usize, length: usize) -> io::Result<Vec<u8>> {
+        self.flush()?;
+
+        let file = self.get_mut();
+        let written = file.stream_position()?;
+        if position.checked_add(length).is_none_or(|end| end as u64 > written) {
+            return Err(invalid("target segment out of range"));
+        }
+
+        let mut segment = vec![0; length];
+        file.seek(SeekFrom::Start(position as u64))?;
+        file.read_exact(&mut segment)?;
+        file.seek(SeekFrom::Start(written))?;
+        Ok(segment)
+    }
+}
+
+struct SourceIndex {
+    block_size: usize,
+    // Rolling hash of a block to the offset of its first occurrence
+    blocks: HashMap<u64, usize>,
+    // HASH_BASE to the power of the block size, for removing the byte leaving the rolling window
+    leaving_factor: u64,
+}
+
+const HASH_BASE: u64 = 0x100000001B3;
+
+fn block_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |hash, byte| hash.wrapping_mul(HASH_BASE).wrapping_add(*byte as u64 + 1))
+}
+
+impl SourceIndex {
+    fn new(source: &[u8]) -> Self {
+        let block_size = (source.len() / MAX_INDEXED_BLOCKS).next_power_of_two().max(MIN_BLOCK_SIZE);
+
+        let mut blocks = HashMap::new();
+        for (index, block) in source.chunks_exact(block_size).enumerate() {
+            blocks.entry(block_hash(block)).or_insert(index * block_size);
+        }
+
+        let leaving_factor = (0..block_size).fold(1u64, |factor, _| factor.wrapping_mul(HASH_BASE));
+
+        Self {
+            block_size,
+            blocks,
+            leaving_factor,
+        }
+    }
+
+    fn roll(&self, hash: u64, leaving: u8, entering: u8) -> u64 {
+        hash.wrapping_mul(HASH_BASE)
+            .wrapping_add(entering as u64 + 1)
+            .wrapping_sub(self.leaving_factor.wrapping_mul(leaving as u64 + 1))
+    }
+}
+
+// Copies from the source only, with explicit addresses, so windows do not depend on each other
+struct WindowEncoder {
+    data: Vec<u8>,
+    instructions: Vec<u8>,
+    addresses: Vec<u8>,
+}
+
+impl WindowEncoder {
+    fn add(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        if bytes.len() <= 17 {
+            self.instructions.push(1 + bytes.len() as u8);
+        } else {
+            self.instructions.push(1);
+            write_integer(&mut self.instructions, bytes.len() as u64);
+        }
+        self.data.extend_from_slice(bytes);
+    }
+
+    fn run(&mut self, byte: u8, length: usize) {
+        self.instructions.push(0);
+        write_integer(&mut self.instructions, length as u64);
+        self.data.push(byte);
+    }
+
+    fn copy(&mut self, address: usize, length: usize) {
+        // Mode 0 (VCD_SELF) entries start at 19, with the variable size first and then sizes 4 to 18
+        if (4..=18).contains(&length) {
+            self.instructions.push(20 + (length - 4) as u8);
+        } else {
+            self.instructions.push(19);
+            write_integer(&mut self.instructions, length as u64);
+        }
+        write_integer(&mut self.addresses, address as u64);
+    }
+}
+
+fn encode(source: &[u8], target: &[u8], output: &mut impl Write) -> io::Result<()> {
+    output.write_all(&MAGIC)?;
+    output.write_all(&[0])?;
+
+    let index = SourceIndex::new(source);
+
+    for window_start in (0..target.len()).step_by(TARGET_WINDOW_SIZE) {
+        let window = &target[window_start..(window_start + TARGET_WINDOW_SIZE).min(target.len())];
+        write_window(output, source, window, &encode_window(&index, source, window))?;
+    }
+
+    Ok(())
+}
+
+fn encode_window(index: &SourceIndex, source: &[u8], window: &[u8]) -> WindowEncoder {
+    let mut encoder = WindowEncoder {
+        data: Vec::new(),
+        instructions: Vec::new(),
+        addresses: Vec::new(),
+    };
+
+    let block_size = index.block_size;
+    let mut pending_start = 0;
+    let mut position = 0;
+    let mut hash = None;
+
+    while position + block_size <= window.len() {
+        let current_hash = match hash {
+            Some(hash) => hash,
+            None => block_hash(&window[position..position + block_size]),
+        };
+
+        let source_match = index
+            .blocks
+            .get(&current_hash)
+            .copied()
+            .filter(|&offset| source[offset..offset + block_size] == window[position..position + block_size]);
+
+        if let Some(source_offset) = source_match {
+            // Extended backwards into the bytes not encoded yet and forwards as far as the bytes agree
+            let backward = (1..=(position - pending_start).min(source_offset))
+                .take_while(|&back| source[source_offset - back] == window[position - back])
+                .last()
+                .unwrap_or(0);
+            let forward = source[source_offset + block_size..]
+                .iter()
+                .zip(&window[position + block_size..])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            let match_start = position - backward;
+            encoder.add(&window[pending_start..match_start]);
+            encoder.copy(source_offset - backward, backward + block_size + forward);
+
+            position += block_size + forward;
+            pending_start = position;
+            hash = None;
+            continue;
+        }
+
+        let run_length = window[position..].iter().take_while(|b| **b == window[position]).count();
+        if run_length >= MIN_RUN_LENGTH {
+            encoder.add(&window[pending_start..position]);
+            encoder.run(window[position], run_length);
+
+            position += run_length;
+            pending_start = position;
+            hash = None;
+            continue;
+        }
+
+        hash = window.get(position + block_size).map(|entering| index.roll(current_hash, window[position], *entering));
+        position += 1;
+    }
+
+    encoder.add(&window[pending_start..]);
+    encoder
+}
+
+fn write_window(output: &mut impl Write, source: &[u8], window: &[u8], encoder: &WindowEncoder) -> io::Result<()> {
+    let mut header = Vec::new();
+
+    if source.is_empty() {
+        header.push(VCD_ADLER32);
+    } else {
+        header.push(VCD_SOURCE | VCD_ADLER32);
+        write_integer(&mut header, source.len() as u64);
+        write_integer(&mut header, 0);
+    }
+
+    let mut delta = Vec::new();
+    write_integer(&mut delta, window.len() as u64);
+    delta.push(0);
+    write_integer(&mut delta, encoder.data.len() as u64);
+    write_integer(&mut delta, encoder.instructions.len() as u64);
+    write_integer(&mut delta, encoder.addresses.len() as u64);
+    delta.extend_from_slice(&adler32(window).to_be_bytes());
+
+    let delta_length = delta.len() + encoder.data.len() + encoder.instructions.len() + encoder.addresses.len();
+    write_integer(&mut header, delta_length as u64);
+
+    output.write_all(&header)?;
+    output.write_all(&delta)?;
+    output.write_all(&encoder.data)?;
+    output.write_all(&encoder.instructions)?;
+    output.write_all(&encoder.addresses)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid VCDIFF patch: {}", message))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        let byte = *self.bytes.get(self.position).ok_or_else(|| invalid("unexpected end"))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid("unexpected end"))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn integer(&mut self) -> io::Result<usize> {
+        let mut value: u64 = 0;
+
+        loop {
+            let byte = self.byte()?;
+            if value > u64::MAX >> 7 {
+                return Err(invalid("integer overflow"));
+            }
+            value = (value << 7) | (byte & 0x7F) as u64;
+
+            if byte & 0x80 == 0 {
+                return usize::try_from(value).map_err(|_| invalid("integer overflow"));
+            }
+        }
+    }
+}
+
+struct AddressCache {
+    near: [usize; NEAR_CACHE_SIZE],
+    next_near_slot: usize,
+    same: [usize; SAME_CACHE_SIZE * 256],
+}
+
+impl AddressCache {
+    fn new() -> Self {
+        Self {
+            near: [0; NEAR_CACHE_SIZE],
+            next_near_slot: 0,
+            same: [0; SAME_CACHE_SIZE * 256],
+        }
+    }
+
+    fn decode(&mut self, addresses: &mut Reader, here: usize, mode: u8) -> io::Result<usize> {
+        let mode = mode as usize;
+
+        let address = if mode == 0 {
+            addresses.integer()?
+        } else if mode == 1 {
+            here.checked_sub(addresses.integer()?).ok_or_else(|| invalid("address before the start"))?
+        } else if mode < 2 + NEAR_CACHE_SIZE {
+            self.near[mode - 2].checked_add(addresses.integer()?).ok_or_else(|| invalid("address overflow"))?
+        } else {
+            self.same[(mode - 2 - NEAR_CACHE_SIZE) * 256 + addresses.byte()? as usize]
+        };
+
+        self.near[self.next_near_slot] = address;
+        self.next_near_slot = (self.next_near_slot + 1) % NEAR_CACHE_SIZE;
+        self.same[address % (SAME_CACHE_SIZE * 256)] = address;
+
+        Ok(address)
+    }
+}
+
+fn decode(source: &[u8], patch: &[u8], output: &mut impl DecodedOutput) -> io::Result<()> {
+    let mut reader = Reader::new(patch);
+
+    if reader.bytes(4)? != MAGIC {
+        return Err(invalid("not a VCDIFF patch"));
+    }
+
+    let header_indicator = reader.byte()?;
+    if header_indicator & VCD_DECOMPRESS != 0 {
+        return Err(invalid("secondary compression is not supported, the xdelta3 executable is needed"));
+    }
+    if header_indicator & VCD_CODETABLE != 0 {
+        return Err(invalid("custom code tables are not supported, the xdelta3 executable is needed"));
+    }
+    if header_indicator & VCD_APPHEADER != 0 {
+        let length = reader.integer()?;
+        reader.bytes(length)?;
+    }
+
+    let code_table = default_code_table();
+    let mut window = Vec::new();
+
+    while !reader.is_empty() {
+        let window_indicator = reader.byte()?;
+
+        let (segment_length, segment_position) = if window_indicator & (VCD_SOURCE | VCD_TARGET) != 0 {
+            (reader.integer()?, reader.integer()?)
+        } else {
+            (0, 0)
+        };
+
+        let segment_end = segment_position.checked_add(segment_length).ok_or_else(|| invalid("segment overflow"))?;
+        let segment: Cow<[u8]> = if window_indicator & VCD_SOURCE != 0 {
+            Cow::Borrowed(source.get(segment_position..segment_end).ok_or_else(|| invalid("source segment out of range"))?)
+        } else if window_indicator & VCD_TARGET != 0 {
+            Cow::Owned(output.read_back(segment_position, segment_length)?)
+        } else {
+            Cow::Borrowed(&[])
+        };
+
+        let _delta_length = reader.integer()?;
+        let window_length = reader.integer()?;
+        if reader.byte()? != 0 {
+            return Err(invalid("compressed sections are not supported, the xdelta3 executable is needed"));
+        }
+
+        let data_length = reader.integer()?;
+        let instructions_length = reader.integer()?;
+        let addresses_length = reader.integer()?;
+
+        let checksum = if window_indicator & VCD_ADLER32 != 0 {
+            Some(u32::from_be_bytes(reader.bytes(4)?.try_into().expect("Checksum must be 4 bytes")))
+        } else {
+            None
+        };
+
+        let mut data = Reader::new(reader.bytes(data_length)?);
+        let mut instructions = Reader::new(reader.bytes(instructions_length)?);
+        let mut addresses = Reader::new(reader.bytes(addresses_length)?);
+
+        window.clear();
+        let mut address_cache = AddressCache::new();
+
+        while !instructions.is_empty() {
+            let (first, second) = code_table[instructions.byte()? as usize];
+
+            for instruction in [first, second] {
+                if instruction.instruction_type == InstructionType::NoOp {
+                    continue;
+                }
+
+                let size = if instruction.size == 0 { instructions.integer()? } else { instruction.size as usize };
+
+                match instruction.instruction_type {
+                    InstructionType::NoOp => {}
+                    InstructionType::Add => window.extend_from_slice(data.bytes(size)?),
+                    InstructionType::Run => {
+                        let byte = data.byte()?;
+                        window.resize(window.len() + size, byte);
+                    }
+                    InstructionType::Copy => {
+                        let here = segment.len() + window.len();
+                        let address = address_cache.decode(&mut addresses, here, instruction.mode)?;
+
+                        // Copies from the target window may overlap what they produce, so they go byte by byte
+                        for offset in 0..size {
+                            let address = address + offset;
+                            let byte = if address < segment.len() {
+                                segment[address]
+                            } else {
+                                *window.get(address - segment.len()).ok_or_else(|| invalid("copy out of range"))?
+                            };
+                            window.push(byte);
+                        }
+                    }
+                }
+            }
+        }
+
+        if window.len() != window_length {
+            return Err(invalid("window length mismatch"));
+        }
+        if checksum.is_some_and(|checksum| checksum != adler32(&window)) {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        output.write_all(&window)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
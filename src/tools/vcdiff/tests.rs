@@ -0,0 +1,121 @@
+use super::*;
+use crate::test_support::content;
+use proptest::prelude::*;
+
+fn round_trip(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = Vec::new();
+    encode(source, target, &mut patch).expect("Encoding must succeed");
+
+    let mut decoded = Vec::new();
+    decode(source, &patch, &mut decoded).expect("Decoding must succeed");
+    decoded
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    // Edits of the source, so the patches copy from it rather than only add
+    #[test]
+    fn decode_reverses_encode_of_edited_source(
+        source in prop::collection::vec(any::<u8>(), 0..4096),
+        cut in any::<prop::sample::Index>(),
+        inserted in content(),
+        repeat in 1..4usize,
+    ) {
+        let cut = if source.is_empty() { 0 } else { cut.index(source.len()) };
+        let mut target = source[cut..].repeat(repeat);
+        target.extend_from_slice(&inserted);
+        target.extend_from_slice(&source[..cut]);
+
+        prop_assert_eq!(round_trip(&source, &target), target);
+    }
+
+    #[test]
+    fn decode_reverses_encode_of_unrelated_content(source in content(), target in prop::collection::vec(any::<u8>(), 0..4096)) {
+        prop_assert_eq!(round_trip(&source, &target), target);
+    }
+}
+
+#[test]
+fn is_supported_rejects_secondary_compression() {
+    let mut patch = Vec::new();
+    encode(b"source", b"target", &mut patch).expect("Encoding must succeed");
+
+    let path = std::env::temp_dir().join(format!("biver-test-{}-vcdiff-header", std::process::id()));
+    fs::write(&path, &patch).expect("Patch must be written");
+    let encoded_supported = is_supported(&path).expect("Header must be read");
+
+    patch[4] |= VCD_DECOMPRESS;
+    fs::write(&path, &patch).expect("Patch must be written");
+    let compressed_supported = is_supported(&path).expect("Header must be read");
+
+    let _ = fs::remove_file(&path);
+    assert!(encoded_supported);
+    assert!(!compressed_supported);
+}
+
+fn target_window(segment: Option<(usize, usize)>, window_length: usize, encoder: &WindowEncoder) -> Vec<u8> {
+    let mut window = Vec::new();
+    match segment {
+        Some((length, position)) => {
+            window.push(VCD_TARGET);
+            write_integer(&mut window, length as u64);
+            write_integer(&mut window, position as u64);
+        }
+        None => window.push(0),
+    }
+
+    let mut delta = Vec::new();
+    write_integer(&mut delta, window_length as u64);
+    delta.push(0);
+    write_integer(&mut delta, encoder.data.len() as u64);
+    write_integer(&mut delta, encoder.instructions.len() as u64);
+    write_integer(&mut delta, encoder.addresses.len() as u64);
+    delta.extend_from_slice(&encoder.data);
+    delta.extend_from_slice(&encoder.instructions);
+    delta.extend_from_slice(&encoder.addresses);
+
+    write_integer(&mut window, delta.len() as u64);
+    window.extend_from_slice(&delta);
+    window
+}
+
+// The encoder never copies from earlier windows, xdelta3 may
+#[test]
+fn decode_file_copies_from_earlier_target_windows() {
+    let mut first = WindowEncoder {
+        data: Vec::new(),
+        instructions: Vec::new(),
+        addresses: Vec::new(),
+    };
+    first.add(b"hello world");
+
+    let mut second = WindowEncoder {
+        data: Vec::new(),
+        instructions: Vec::new(),
+        addresses: Vec::new(),
+    };
+    second.copy(6, 5);
+    second.add(b"!");
+
+    let mut patch = MAGIC.to_vec();
+    patch.push(0);
+    patch.extend(target_window(None, 11, &first));
+    patch.extend(target_window(Some((11, 0)), 6, &second));
+
+    let mut decoded = Vec::new();
+    decode(&[], &patch, &mut decoded).expect("Decoding must succeed");
+
+    let directory = std::env::temp_dir().join(format!("biver-test-{}-vcdiff-target", std::process::id()));
+    fs::create_dir_all(&directory).expect("Directory must be created");
+    let (source_path, patch_path, target_path) = (directory.join("source"), directory.join("patch"), directory.join("target"));
+    fs::write(&source_path, b"").expect("Source must be written");
+    fs::write(&patch_path, &patch).expect("Patch must be written");
+    let decode_file_result = decode_file(&source_path, &patch_path, &target_path);
+    let decoded_file = fs::read(&target_path);
+
+    let _ = fs::remove_dir_all(&directory);
+    assert_eq!(decoded, b"hello worldworld!");
+    decode_file_result.expect("Decoding must succeed");
+    assert_eq!(decoded_file.expect("Target must be written"), b"hello worldworld!");
+}
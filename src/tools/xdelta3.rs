@@ -0,0 +1,168 @@
+use crate::background_process::Worker;
+use crate::logging::{LoggedChild, LoggedCommand};
+use crate::tools;
+use crate::tools::vcdiff;
+use crate::tools::{Detection, Tool, ToolVersion};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::OnceLock;
+use std::{fs, io};
+
+pub trait XDelta3Env {
+    fn xdelta3_path(&self) -> Option<&Path>;
+    // Set on first use, so that xdelta3 is looked for once rather than for every patch
+    fn xdelta3_codec(&self) -> &OnceLock<Codec>;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Codec {
+    External,
+    // The in-process one of vcdiff. Test environments set it up front, so that they do not depend on what is installed.
+    Bundled,
+}
+
+// 3.0 is the first release with VCDIFF patches that later versions can still apply
+pub const TOOL: Tool = Tool {
+    name: "xdelta3",
+    purpose: "Used for storing version file content as patches, which reduces repository size on disk",
+    version_args: &["-V"],
+    minimum_version: ToolVersion(3, 0, 0),
+    capabilities: &[],
+};
+
+pub fn ready(env: &impl XDelta3Env) -> bool {
+    match codec(env) {
+        Codec::Bundled => true,
+        Codec::External => tools::ready(&TOOL, xdelta3_command(env)),
+    }
+}
+
+pub fn detect(env: &impl XDelta3Env) -> Detection {
+    match codec(env) {
+        Codec::Bundled => Detection::Found {
+            version: None,
+            warnings: vec!["Not found, the bundled encoder is used instead, which makes larger patches".to_string()],
+        },
+        Codec::External => tools::detect(&TOOL, || xdelta3_command(env)),
+    }
+}
+
+pub fn spawn_create_patch(env: &impl XDelta3Env, old: &Path, new: &Path, patch: &Path) -> io::Result<Worker> {
+    if let Codec::Bundled = codec(env) {
+        let (old, new, patch) = (old.to_path_buf(), new.to_path_buf(), patch.to_path_buf());
+        return Ok(Worker::Thread(std::thread::spawn(move || vcdiff::encode_file(&old, &new, &patch))));
+    }
+
+    let child = xdelta3_command(env)
+        .arg("-e") // compress
+        .arg("-s") // source
+        .arg(tools::path_argument(old))
+        .arg(tools::path_argument(new))
+        .arg(tools::path_argument(patch))
+        .logged_spawn()?;
+
+    Ok(Worker::Process(child))
+}
+
+// Only the bundled codec reads a subset of what xdelta3 writes, see vcdiff
+pub fn can_apply(env: &impl XDelta3Env, patch: &Path) -> io::Result<bool> {
+    match codec(env) {
+        Codec::Bundled => vcdiff::is_supported(patch),
+        Codec::External => Ok(true),
+    }
+}
+
+pub fn wait_for_patch(child: &mut Child) -> io::Result<()> {
+    map_xdelta3_status(child.logged_wait())
+}
+
+// xdelta3 refuses to overwrite its output, so it writes next to it and the result replaces the output only on success.
+// A failed application leaves an existing file as it was.
+pub fn apply_patch(env: &impl XDelta3Env, old: &Path, patch: &Path, new: &Path) -> io::Result<()> {
+    let mut partial_file_name = new.file_name().unwrap_or_default().to_os_string();
+    partial_file_name.push(".partial");
+    let partial = new.with_file_name(partial_file_name);
+
+    if fs::exists(&partial)? {
+        fs::remove_file(&partial)?;
+    }
+
+    match decode(env, old, patch, &partial) {
+        Ok(()) => fs::rename(&partial, new),
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+// xdelta3 writes to standard output, which is copied to the output as it comes
+pub fn apply_patch_to_writer(env: &impl XDelta3Env, old: &Path, patch: &Path, output: &mut impl Write) -> io::Result<()> {
+    if let Codec::Bundled = codec(env) {
+        return vcdiff::decode_to_writer(old, patch, output);
+    }
+
+    let mut child = xdelta3_command(env)
+        .arg("-d") // decompress
+        .arg("-c") // to standard output
+        .arg("-s") // source
+        .arg(tools::path_argument(old))
+        .arg(tools::path_argument(patch))
+        .stdout(Stdio::piped())
+        .logged_spawn()?;
+
+    let copied = io::copy(&mut child.stdout.take().expect("Standard output of xdelta3 is piped"), output);
+    let status = child.logged_wait();
+    copied?;
+
+    map_xdelta3_status(status)
+}
+
+fn decode(env: &impl XDelta3Env, old: &Path, patch: &Path, new: &Path) -> io::Result<()> {
+    if let Codec::Bundled = codec(env) {
+        return vcdiff::decode_file(old, patch, new);
+    }
+
+    let status = xdelta3_command(env)
+        .arg("-d") // decompress
+        .arg("-s") // source
+        .arg(tools::path_argument(old))
+        .arg(tools::path_argument(patch))
+        .arg(tools::path_argument(new))
+        .logged_status();
+
+    map_xdelta3_status(status)
+}
+
+fn map_xdelta3_status(status_result: io::Result<ExitStatus>) -> io::Result<()> {
+    status_result.and_then(|status| if status.success() { Ok(()) } else { Err(io::Error::other("xdelta3 failed.")) })
+}
+
+fn xdelta3_command(env: &impl XDelta3Env) -> Command {
+    let mut command = match env.xdelta3_path() {
+        Some(xdelta3_path) => Command::new(xdelta3_path),
+        None => default_command(),
+    };
+
+    command.stdout(Stdio::null());
+    command.stderr(Stdio::null());
+    command
+}
+
+fn default_command() -> Command {
+    Command::new("xdelta3")
+}
+
+// A configured path is always used, so a wrong one is reported rather than silently replaced. Without the bundled-vcdiff
+// feature xdelta3 is the only codec. The bundled one runs in process, on a thread of its own when creating patches in
+// the background.
+fn codec(env: &impl XDelta3Env) -> Codec {
+    *env.xdelta3_codec().get_or_init(|| {
+        if cfg!(feature = "bundled-vcdiff") && env.xdelta3_path().is_none() && !tools::ready(&TOOL, default_command()) {
+            Codec::Bundled
+        } else {
+            Codec::External
+        }
+    })
+}
@@ -0,0 +1,75 @@
+use crate::file_replacement;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+// Files written by a multi-step operation, such as the blobs of a new version or the versioned file replaced by checkout.
+// New files are tracked as they are written, files that replace others are written next to them first. Committing moves
+// the latter into place, dropping the transaction without committing deletes both, so an operation failing halfway
+// leaves neither stray blobs nor a half-written versioned file behind. The data file is written right before committing.
+#[derive(Default)]
+pub struct Transaction {
+    created_paths: Vec<PathBuf>,
+    staged_paths: Vec<StagedPath>,
+}
+
+struct StagedPath {
+    staged_path: PathBuf,
+    destination_path: PathBuf,
+    keep_permissions: bool,
+}
+
+impl Transaction {
+    // A file that already existed is left alone on rollback
+    pub fn create(&mut self, path: PathBuf, write: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+        let existed = fs::exists(&path)?;
+        write()?;
+
+        if !existed {
+            self.created_paths.push(path);
+        }
+
+        Ok(())
+    }
+
+    // Where to write what replaces the destination when the transaction is committed. Unless the staged file is given
+    // permissions of its own, it takes those of the destination.
+    pub fn stage(&mut self, destination_path: &Path, keep_permissions: bool) -> PathBuf {
+        let mut staged_file_name = OsString::from(".");
+        staged_file_name.push(destination_path.file_name().unwrap_or_default());
+        staged_file_name.push(".staged");
+
+        let staged_path = destination_path.with_file_name(staged_file_name);
+        self.staged_paths.push(StagedPath {
+            staged_path: staged_path.clone(),
+            destination_path: destination_path.to_path_buf(),
+            keep_permissions,
+        });
+        staged_path
+    }
+
+    // The data file referencing the created files has been written by now, so they stay whatever happens next
+    pub fn commit(mut self) -> io::Result<()> {
+        self.created_paths.clear();
+
+        while let Some(staged) = self.staged_paths.first() {
+            file_replacement::replace(&staged.staged_path, &staged.destination_path, staged.keep_permissions)?;
+            tracing::trace!("Moved {} to {}", staged.staged_path.display(), staged.destination_path.display());
+            self.staged_paths.remove(0);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        let staged_paths = self.staged_paths.iter().map(|staged| &staged.staged_path);
+
+        for path in self.created_paths.iter().chain(staged_paths) {
+            if fs::remove_file(path).is_ok() {
+                tracing::debug!("Rolled back {}", path.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::env::Env;
+use crate::repository_paths::RepositoryPaths;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+const TRASH_DIR_NAME: &str = "trash";
+
+// Where content that discard, reset --hard and delete branch would otherwise lose is kept
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashMode {
+    Off,
+    // The trash or recycle bin of the operating system
+    System,
+    // The trash directory inside the repository directory, emptied by hand
+    Repository,
+}
+
+// The current versioned file, before it is overwritten. A hard link costs nothing, since the versioned file is replaced
+// rather than written in place. Returns where the file went, if anywhere.
+pub fn keep_versioned_file(env: &Env, repo_paths: &RepositoryPaths) -> io::Result<Option<PathBuf>> {
+    keep(env, repo_paths, |trashed_path| {
+        if fs::hard_link(&repo_paths.versioned_file, trashed_path).is_err() {
+            fs::copy(&repo_paths.versioned_file, trashed_path)?;
+        }
+        Ok(())
+    })
+}
+
+// Content the repository no longer has after the operation, written by the caller
+pub fn keep(env: &Env, repo_paths: &RepositoryPaths, write: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<Option<PathBuf>> {
+    let trash_dir = match env.trash {
+        TrashMode::Off => return Ok(None),
+        _ if env.dry_run => return Ok(None),
+        TrashMode::System => repo_paths.repository_dir.clone(),
+        TrashMode::Repository => repo_paths.file_path(TRASH_DIR_NAME),
+    };
+
+    fs::create_dir_all(&trash_dir)?;
+    let trashed_path = unused_path(&trash_dir, repo_paths)?;
+    write(&trashed_path)?;
+
+    if let TrashMode::System = env.trash
+        && let Err(e) = trash::delete(&trashed_path)
+    {
+        let _ = fs::remove_file(&trashed_path);
+        return Err(io::Error::other(format!("Could not move {} to the trash: {}", trashed_path.display(), e)));
+    }
+
+    tracing::debug!("Kept the previous content as {}", trashed_path.display());
+
+    Ok(Some(trashed_path))
+}
+
+// Files in the repository trash directory with their sizes
+pub fn entries(repo_paths: &RepositoryPaths) -> io::Result<Vec<(PathBuf, u64)>> {
+    let trash_dir = repo_paths.file_path(TRASH_DIR_NAME);
+    if !fs::exists(&trash_dir)? {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for dir_entry in fs::read_dir(&trash_dir)? {
+        let dir_entry = dir_entry?;
+        entries.push((dir_entry.path(), dir_entry.metadata()?.len()));
+    }
+
+    entries.sort();
+
+    Ok(entries)
+}
+
+// The name of the versioned file with the time before the extension, e.g. drawing 2024-05-01 14-30-05.psd, so the
+// trashed files sort by time and still open in the right program. Files trashed within the same second get a number.
+fn unused_path(trash_dir: &Path, repo_paths: &RepositoryPaths) -> io::Result<PathBuf> {
+    let timestamp = Local::now().format("%Y-%m-%d %H-%M-%S").to_string();
+    let stem = repo_paths.versioned_file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = repo_paths.versioned_file.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+
+    let mut number = 1;
+    loop {
+        let suffix = if number == 1 { timestamp.clone() } else { format!("{} ({})", timestamp, number) };
+        let path = trash_dir.join(format!("{} {}{}", stem, suffix, extension));
+
+        if !fs::exists(&path)? {
+            return Ok(path);
+        }
+
+        number += 1;
+    }
+}
@@ -1,10 +1,25 @@
+use crate::animated_webp::AnimatedWebPEncoder;
 use crate::biver_result::BiverResult;
+use crate::temporary_file::TemporaryFile;
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont, point};
 use eframe::{CreationContext, Frame, NativeOptions};
-use egui::{ColorImage, Context, Image, Key, Rect, TextureHandle, TextureOptions, ViewportBuilder, ViewportCommand, pos2};
-use image::ImageFormat;
+use egui::FontDefinitions;
+use egui::{
+    Align2, Color32, ColorImage, Context, FontId, Id, Image, Key, Painter, Pos2, Rect, Sense, Shape, Spinner, Stroke, TextureHandle, TextureOptions, Vec2, ViewportBuilder,
+    ViewportCommand, pos2, vec2,
+};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::FilterType;
+use image::{Delay, DynamicImage, ImageReader, Rgb, RgbImage, imageops};
 use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{fs, io};
+use uuid::Uuid;
 
 pub fn show_preview(image_path: &Path) -> BiverResult<()> {
     let image = egui_image_from_file(image_path)?;
@@ -14,23 +29,196 @@ pub fn show_preview(image_path: &Path) -> BiverResult<()> {
     Ok(())
 }
 
-pub fn show_comparison(image_path1: &Path, description1: &str, image_path2: &Path, description2: &str) -> BiverResult<()> {
-    let image1 = egui_image_from_file(&image_path1)?;
-    let image2 = egui_image_from_file(&image_path2)?;
-
+// Up to four previews, shown one at a time or side by side in a grid.
+// The window opens right away and the previews are decoded in the background.
+pub fn show_comparison(previews: &[(&Path, &str)], blink: bool, blink_interval: Duration) -> BiverResult<()> {
     eframe::run_native(
-        &description1,
+        previews[0].1,
         egui_options(),
-        Box::new(|cc| Ok(Box::new(ComparerApp::new(cc, image1, &description1, image2, description2)))),
+        Box::new(|cc| Ok(Box::new(ComparerApp::new(cc, previews, blink, blink_interval)))),
     )?;
 
     Ok(())
 }
 
+const EXPORT_LABEL_HEIGHT: u32 = 32;
+const EXPORT_PADDING: u32 = 8;
+const EXPORT_MIN_PANE_WIDTH: u32 = 480;
+
+// The same grid as the comparison window, written to an image file instead, each preview with its description above it.
+// The format follows the file extension.
+pub fn write_comparison(previews: &[(&Path, &str)], output: &Path) -> BiverResult<()> {
+    let images = previews
+        .iter()
+        .map(|(path, _)| Ok(decode_preview(path)?.to_rgb8()))
+        .collect::<BiverResult<Vec<RgbImage>>>()?;
+
+    let columns = previews.len().min(2) as u32;
+    let rows = (previews.len() as u32).div_ceil(columns);
+    let pane_width = images.iter().map(|i| i.width()).max().unwrap_or_default().max(EXPORT_MIN_PANE_WIDTH) + 2 * EXPORT_PADDING;
+    let pane_height = images.iter().map(|i| i.height()).max().unwrap_or_default() + EXPORT_LABEL_HEIGHT + 2 * EXPORT_PADDING;
+
+    let mut canvas = RgbImage::from_pixel(columns * pane_width, rows * pane_height, Rgb([255, 255, 255]));
+
+    let font = label_font()?;
+
+    for (i, (image, (_, description))) in images.iter().zip(previews).enumerate() {
+        let pane_x = (i as u32 % columns) * pane_width;
+        let pane_y = (i as u32 / columns) * pane_height;
+
+        draw_label(
+            &mut canvas,
+            &font,
+            description,
+            pane_x + EXPORT_PADDING,
+            pane_y + EXPORT_PADDING,
+            pane_width - 2 * EXPORT_PADDING,
+        );
+
+        let image_x = pane_x + (pane_width - image.width()) / 2;
+        let image_y = pane_y + EXPORT_PADDING + EXPORT_LABEL_HEIGHT;
+        imageops::replace(&mut canvas, image, image_x as i64, image_y as i64);
+    }
+
+    canvas.save(output)?;
+
+    Ok(())
+}
+
+// Previews along the history as an animated GIF or WebP, following the file extension, scaled down to fit max_size,
+// each frame stamped with its label if given. The output only appears once every frame is written.
+pub fn write_timelapse(frames: &[(&Path, Option<&str>)], output: &Path, frames_per_second: u32, max_size: u32) -> BiverResult<()> {
+    let labeled = frames.iter().any(|(_, label)| label.is_some());
+    let label_strip_height = if labeled { EXPORT_LABEL_HEIGHT + EXPORT_PADDING } else { 0 };
+
+    // Decoded twice, since holding every frame of a long history in memory is worse than decoding it again
+    let mut frame_width = 0;
+    let mut frame_height = 0;
+    for (path, _) in frames {
+        let image = fit_within(decode_preview(path)?, max_size);
+        frame_width = frame_width.max(image.width());
+        frame_height = frame_height.max(image.height());
+    }
+
+    let output_name = output.file_name().unwrap_or_default().to_string_lossy();
+    let staged_output = TemporaryFile::new(output.with_file_name(format!(".{}-{}.tmp", Uuid::new_v4().simple(), output_name)));
+
+    let mut staged_writer = BufWriter::new(File::create(staged_output.path())?);
+    {
+        let mut encoder = if output.extension().is_some_and(|e| e.eq_ignore_ascii_case("webp")) {
+            TimelapseEncoder::WebP(AnimatedWebPEncoder::new(&mut staged_writer, frame_width, frame_height + label_strip_height)?)
+        } else {
+            let mut encoder = GifEncoder::new_with_speed(&mut staged_writer, 10);
+            encoder.set_repeat(Repeat::Infinite)?;
+            TimelapseEncoder::Gif(encoder)
+        };
+
+        let font = label_font()?;
+        let frame_duration_ms = 1000 / frames_per_second;
+
+        for (path, label) in frames {
+            let image = fit_within(decode_preview(path)?, max_size).to_rgb8();
+            let mut canvas = RgbImage::from_pixel(frame_width, frame_height + label_strip_height, Rgb([255, 255, 255]));
+
+            if let Some(label) = label {
+                draw_label(&mut canvas, &font, label, EXPORT_PADDING, EXPORT_PADDING, frame_width.saturating_sub(2 * EXPORT_PADDING));
+            }
+
+            let image_x = (frame_width - image.width()) / 2;
+            let image_y = label_strip_height + (frame_height - image.height()) / 2;
+            imageops::replace(&mut canvas, &image, image_x as i64, image_y as i64);
+
+            match &mut encoder {
+                TimelapseEncoder::Gif(encoder) => {
+                    let delay = Delay::from_numer_denom_ms(1000, frames_per_second);
+                    encoder.encode_frame(image::Frame::from_parts(DynamicImage::ImageRgb8(canvas).to_rgba8(), 0, 0, delay))?;
+                }
+                TimelapseEncoder::WebP(encoder) => encoder.encode_frame(&canvas, frame_duration_ms)?,
+            }
+        }
+
+        // A GIF gets its trailer when the encoder is dropped, at the end of this block
+        if let TimelapseEncoder::WebP(encoder) = encoder {
+            encoder.finish()?;
+        }
+    }
+
+    staged_writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+
+    fs::rename(staged_output.path(), output)?;
+
+    Ok(())
+}
+
+enum TimelapseEncoder<'a> {
+    Gif(GifEncoder<&'a mut BufWriter<File>>),
+    WebP(AnimatedWebPEncoder<&'a mut BufWriter<File>>),
+}
+
+fn decode_preview(path: &Path) -> BiverResult<DynamicImage> {
+    Ok(ImageReader::new(BufReader::new(File::open(path)?)).with_guessed_format()?.decode()?)
+}
+
+fn fit_within(image: DynamicImage, max_size: u32) -> DynamicImage {
+    if image.width() <= max_size && image.height() <= max_size {
+        return image;
+    }
+
+    image.resize(max_size, max_size, FilterType::Triangle)
+}
+
+// The font egui draws its interface with, so no font has to be installed
+fn label_font() -> io::Result<FontArc> {
+    let font_definitions = FontDefinitions::default();
+    let font_data = font_definitions.font_data.get("Ubuntu-Light").expect("egui includes its default fonts");
+    FontArc::try_from_vec(font_data.font.to_vec()).map_err(io::Error::other)
+}
+
+// Text that does not fit is cut off at the edge of the pane
+fn draw_label(canvas: &mut RgbImage, font: &FontArc, text: &str, x: u32, y: u32, max_width: u32) {
+    let scale = PxScale::from(EXPORT_LABEL_HEIGHT as f32 * 0.6);
+    let scaled_font = font.as_scaled(scale);
+    let baseline = y as f32 + (EXPORT_LABEL_HEIGHT as f32 + scaled_font.ascent() + scaled_font.descent()) / 2.0;
+
+    let mut caret = x as f32;
+    let mut previous_glyph_id = None;
+
+    for c in text.chars() {
+        let glyph_id = scaled_font.glyph_id(c);
+        if let Some(previous_glyph_id) = previous_glyph_id {
+            caret += scaled_font.kern(previous_glyph_id, glyph_id);
+        }
+        previous_glyph_id = Some(glyph_id);
+
+        let glyph = glyph_id.with_scale_and_position(scale, point(caret, baseline));
+        caret += scaled_font.h_advance(glyph_id);
+
+        let Some(outlined_glyph) = font.outline_glyph(glyph) else {
+            continue;
+        };
+
+        let bounds = outlined_glyph.px_bounds();
+        outlined_glyph.draw(|glyph_x, glyph_y, coverage| {
+            let pixel_x = bounds.min.x as i64 + glyph_x as i64;
+            let pixel_y = bounds.min.y as i64 + glyph_y as i64;
+
+            if pixel_x < x as i64 || pixel_x >= (x + max_width) as i64 || pixel_y < 0 || pixel_y >= canvas.height() as i64 {
+                return;
+            }
+
+            let pixel = canvas.get_pixel_mut(pixel_x as u32, pixel_y as u32);
+            for channel in pixel.0.iter_mut() {
+                *channel = (*channel as f32 * (1.0 - coverage.min(1.0))) as u8;
+            }
+        });
+    }
+}
+
+// Previews created by biver are JPEG, but externally regenerated ones may be PNG, WebP and so on, so the format comes from the file header
 fn egui_image_from_file(path: &Path) -> BiverResult<ColorImage> {
     let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let image = image::load(reader, ImageFormat::Jpeg)?;
+    let reader = ImageReader::new(BufReader::new(file)).with_guessed_format()?;
+    let image = reader.decode()?;
     let size = [image.width() as usize, image.height() as usize];
     let buffer = image.to_rgba8();
     let pixels = buffer.into_flat_samples();
@@ -80,42 +268,253 @@ impl eframe::App for PreviewApp {
     }
 }
 
-enum SelectedImage {
-    Image1,
-    Image2,
+#[derive(PartialEq)]
+enum Layout {
+    Single,
+    Grid,
+}
+
+// Decoded previews are kept as textures up to this many bytes, the least recently shown ones are dropped first
+const TEXTURE_CACHE_BYTES: usize = 512 * 1024 * 1024;
+
+enum PreviewState {
+    NotLoaded,
+    Loading,
+    Loaded(TextureHandle),
+    Failed(String),
+}
+
+struct ComparedPreview<'a> {
+    path: PathBuf,
+    description: &'a str,
+    state: PreviewState,
+    // Kept when the texture is dropped from the cache, it is tiny compared to the image
+    stats: Option<ImageStats>,
+    // Frame in which the preview was last shown or wanted, for evicting the least recently used texture
+    last_used: u64,
 }
 
+type LoadedPreview = (usize, BiverResult<(ColorImage, ImageStats)>);
+
 struct ComparerApp<'a> {
-    image1_texture: TextureHandle,
-    image2_texture: TextureHandle,
-    description1: &'a str,
-    description2: &'a str,
-    selected_image: SelectedImage,
+    previews: Vec<ComparedPreview<'a>>,
+    loaded_sender: Sender<LoadedPreview>,
+    loaded_receiver: Receiver<LoadedPreview>,
+    frame: u64,
+    selected_image: usize,
+    layout: Layout,
     flipped: bool,
+    // The visible part of every image in texture coordinates, shared so zooming or panning one pane moves them all
+    view: Rect,
+    blink_interval: Duration,
+    // When the current image was shown while blinking
+    last_blink: Option<Instant>,
+    show_stats: bool,
 }
 
 impl<'a> ComparerApp<'a> {
-    fn new(cc: &CreationContext, image1: ColorImage, description1: &'a str, image2: ColorImage, description2: &'a str) -> Self {
-        Self {
-            image1_texture: cc.egui_ctx.load_texture("image1", image1, TextureOptions::default()),
-            image2_texture: cc.egui_ctx.load_texture("image2", image2, TextureOptions::default()),
-            description1,
-            description2,
-            selected_image: SelectedImage::Image1,
+    fn new(cc: &CreationContext, previews: &[(&Path, &'a str)], blink: bool, blink_interval: Duration) -> Self {
+        let previews: Vec<ComparedPreview> = previews
+            .iter()
+            .map(|(path, description)| ComparedPreview {
+                path: path.to_path_buf(),
+                description,
+                state: PreviewState::NotLoaded,
+                stats: None,
+                last_used: 0,
+            })
+            .collect();
+
+        // Flipping between two images shows differences best, more are easier to tell apart side by side
+        let layout = if previews.len() > 2 && !blink { Layout::Grid } else { Layout::Single };
+
+        let (loaded_sender, loaded_receiver) = mpsc::channel();
+
+        let mut app = Self {
+            previews,
+            loaded_sender,
+            loaded_receiver,
+            frame: 0,
+            selected_image: 0,
+            layout,
             flipped: false,
+            view: FULL_VIEW,
+            blink_interval,
+            last_blink: blink.then(Instant::now),
+            show_stats: false,
+        };
+
+        // All of them are started at once, so flipping does not wait for decoding once they are in
+        for index in 0..app.previews.len() {
+            app.load(&cc.egui_ctx, index);
+        }
+
+        app
+    }
+
+    fn load(&mut self, ctx: &Context, index: usize) {
+        let preview = &mut self.previews[index];
+        preview.state = PreviewState::Loading;
+
+        let path = preview.path.clone();
+        let sender = self.loaded_sender.clone();
+        let ctx = ctx.clone();
+
+        thread::spawn(move || {
+            let loaded = egui_image_from_file(&path).map(|image| {
+                let stats = ImageStats::of(&image);
+                (image, stats)
+            });
+
+            // The window may have been closed in the meantime, nobody is waiting then
+            let _ = sender.send((index, loaded));
+            ctx.request_repaint();
+        });
+    }
+
+    fn receive_loaded(&mut self, ctx: &Context) {
+        while let Ok((index, loaded)) = self.loaded_receiver.try_recv() {
+            let preview = &mut self.previews[index];
+
+            match loaded {
+                Ok((image, stats)) => {
+                    preview.state = PreviewState::Loaded(ctx.load_texture(format!("image{}", index + 1), image, TextureOptions::default()));
+                    preview.stats = Some(stats);
+                }
+                Err(err) => preview.state = PreviewState::Failed(err.error_message),
+            }
+        }
+    }
+
+    // Shown previews and the next one in line are loaded if missing and never evicted
+    fn wanted_images(&self) -> Vec<usize> {
+        let image_count = self.previews.len();
+
+        match self.layout {
+            Layout::Single => vec![self.selected_image, (self.selected_image + 1) % image_count],
+            Layout::Grid => (0..image_count).collect(),
+        }
+    }
+
+    fn update_cache(&mut self, ctx: &Context) {
+        self.frame += 1;
+
+        for index in self.wanted_images() {
+            self.previews[index].last_used = self.frame;
+
+            if matches!(self.previews[index].state, PreviewState::NotLoaded) {
+                self.load(ctx, index);
+            }
+        }
+
+        let texture_bytes = |preview: &ComparedPreview| match &preview.state {
+            PreviewState::Loaded(texture) => texture.byte_size(),
+            _ => 0,
+        };
+
+        let mut cached_bytes: usize = self.previews.iter().map(texture_bytes).sum();
+
+        while cached_bytes > TEXTURE_CACHE_BYTES {
+            let least_recently_used = self
+                .previews
+                .iter_mut()
+                .filter(|preview| preview.last_used < self.frame && matches!(preview.state, PreviewState::Loaded(_)))
+                .min_by_key(|preview| preview.last_used);
+
+            let Some(preview) = least_recently_used else {
+                break;
+            };
+
+            cached_bytes -= texture_bytes(preview);
+            preview.state = PreviewState::NotLoaded;
+        }
+    }
+
+    fn title(&self) -> String {
+        let mut title = String::new();
+
+        if self.last_blink.is_some() {
+            title.push_str("(blinking) ");
+        }
+
+        if self.flipped {
+            title.push_str("(flipped) ");
+        }
+
+        match self.layout {
+            Layout::Single => title.push_str(self.previews[self.selected_image].description),
+            Layout::Grid => title.push_str(&format!("{} versions", self.previews.len())),
+        }
+
+        title
+    }
+
+    fn pane_rects(&self, area: Rect) -> Vec<Rect> {
+        if self.layout == Layout::Single {
+            return vec![area];
+        }
+
+        let columns = 2;
+        let rows = self.previews.len().div_ceil(columns);
+        let pane_size = vec2(area.width() / columns as f32, area.height() / rows as f32);
+
+        (0..self.previews.len())
+            .map(|i| {
+                let min = area.min + vec2((i % columns) as f32 * pane_size.x, (i / columns) as f32 * pane_size.y);
+                Rect::from_min_size(min, pane_size).shrink(PANE_SPACING / 2.0)
+            })
+            .collect()
+    }
+
+    fn zoom(&mut self, pane: Rect, pointer: Pos2, factor: f32) {
+        let anchor = self.view_position(pane, pointer);
+        let size = (self.view.size() / factor).min(vec2(1.0, 1.0)).max(vec2(MIN_VIEW_SIZE, MIN_VIEW_SIZE));
+        let fraction = (pointer - pane.min) / pane.size();
+        let fraction = vec2(if self.flipped { 1.0 - fraction.x } else { fraction.x }, fraction.y);
+
+        self.view = clamp_view(Rect::from_min_size(anchor - fraction * size, size));
+    }
+
+    fn pan(&mut self, pane: Rect, delta: Vec2) {
+        let delta = delta / pane.size() * self.view.size();
+        let delta = vec2(if self.flipped { -delta.x } else { delta.x }, delta.y);
+
+        self.view = clamp_view(self.view.translate(-delta));
+    }
+
+    fn view_position(&self, pane: Rect, pointer: Pos2) -> Pos2 {
+        let fraction = (pointer - pane.min) / pane.size();
+        let fraction = vec2(if self.flipped { 1.0 - fraction.x } else { fraction.x }, fraction.y);
+        self.view.min + fraction * self.view.size()
+    }
+
+    fn uv(&self) -> Rect {
+        if self.flipped {
+            Rect::from_min_max(pos2(self.view.max.x, self.view.min.y), pos2(self.view.min.x, self.view.max.y))
+        } else {
+            self.view
         }
     }
 }
 
 impl<'a> eframe::App for ComparerApp<'a> {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
-        let (q_pressed, k_pressed, j_pressed, space_pressed, f_pressed) = ctx.input(|i| {
+        let image_count = self.previews.len();
+
+        self.receive_loaded(ctx);
+
+        let (q_pressed, k_pressed, j_pressed, space_pressed, f_pressed, g_pressed, b_pressed, h_pressed, r_pressed, number_pressed) = ctx.input(|i| {
             (
                 i.key_pressed(Key::Q),
                 i.key_pressed(Key::K),
                 i.key_pressed(Key::J),
                 i.key_pressed(Key::Space),
                 i.key_pressed(Key::F),
+                i.key_pressed(Key::G),
+                i.key_pressed(Key::B),
+                i.key_pressed(Key::H),
+                i.key_pressed(Key::R),
+                [Key::Num1, Key::Num2, Key::Num3, Key::Num4].iter().position(|key| i.key_pressed(*key)),
             )
         });
 
@@ -126,26 +525,48 @@ impl<'a> eframe::App for ComparerApp<'a> {
         let mut title_should_be_updated = false;
 
         if k_pressed {
-            self.selected_image = SelectedImage::Image1;
+            self.selected_image = (self.selected_image + image_count - 1) % image_count;
             title_should_be_updated = true;
         }
 
-        if j_pressed {
-            self.selected_image = SelectedImage::Image2;
+        if j_pressed || space_pressed {
+            self.selected_image = (self.selected_image + 1) % image_count;
             title_should_be_updated = true;
         }
 
-        if space_pressed {
-            match self.selected_image {
-                SelectedImage::Image1 => {
-                    self.selected_image = SelectedImage::Image2;
-                    title_should_be_updated = true;
-                }
-                SelectedImage::Image2 => {
-                    self.selected_image = SelectedImage::Image1;
-                    title_should_be_updated = true;
-                }
-            };
+        if let Some(number) = number_pressed
+            && number < image_count
+        {
+            self.selected_image = number;
+            self.layout = Layout::Single;
+            self.last_blink = None;
+            title_should_be_updated = true;
+        }
+
+        if g_pressed {
+            self.layout = if self.layout == Layout::Grid { Layout::Single } else { Layout::Grid };
+            self.last_blink = None;
+            title_should_be_updated = true;
+        }
+
+        // Blinking needs all versions at the same position, so it always shows them one at a time
+        if b_pressed {
+            self.last_blink = if self.last_blink.is_some() { None } else { Some(Instant::now()) };
+            self.layout = Layout::Single;
+            title_should_be_updated = true;
+        }
+
+        if let Some(last_blink) = self.last_blink {
+            let elapsed = last_blink.elapsed();
+
+            if elapsed >= self.blink_interval {
+                self.selected_image = (self.selected_image + 1) % image_count;
+                self.last_blink = Some(Instant::now());
+                title_should_be_updated = true;
+                ctx.request_repaint_after(self.blink_interval);
+            } else {
+                ctx.request_repaint_after(self.blink_interval - elapsed);
+            }
         }
 
         if f_pressed {
@@ -153,36 +574,165 @@ impl<'a> eframe::App for ComparerApp<'a> {
             title_should_be_updated = true;
         }
 
-        if title_should_be_updated {
-            let description = match self.selected_image {
-                SelectedImage::Image1 => self.description1,
-                SelectedImage::Image2 => self.description2,
-            };
+        if r_pressed {
+            self.view = FULL_VIEW;
+        }
 
-            let description = if self.flipped {
-                let mut s = String::from("(flipped) ");
-                s.push_str(description);
-                s
-            } else {
-                description.to_string()
-            };
+        if h_pressed {
+            self.show_stats = !self.show_stats;
+        }
 
-            ctx.send_viewport_cmd(ViewportCommand::Title(description));
+        if title_should_be_updated {
+            ctx.send_viewport_cmd(ViewportCommand::Title(self.title()));
         }
 
-        let image_texture = match self.selected_image {
-            SelectedImage::Image1 => &self.image1_texture,
-            SelectedImage::Image2 => &self.image2_texture,
-        };
+        self.update_cache(ctx);
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            let ui_size = ui.available_size();
+            let pane_rects = self.pane_rects(ui.available_rect_before_wrap());
+
+            let (scroll, hover_pos) = ctx.input(|i| (i.smooth_scroll_delta.y, i.pointer.hover_pos()));
+
+            for (i, pane) in pane_rects.iter().enumerate() {
+                let response = ui.interact(*pane, Id::new("pane").with(i), Sense::drag());
+
+                if response.dragged() {
+                    self.pan(*pane, response.drag_delta());
+                }
+
+                if scroll != 0.0
+                    && let Some(hover_pos) = hover_pos
+                    && pane.contains(hover_pos)
+                {
+                    self.zoom(*pane, hover_pos, (scroll * ZOOM_SPEED).exp());
+                }
+            }
+
+            let uv = self.uv();
+
+            for (i, pane) in pane_rects.iter().enumerate() {
+                let image_index = if self.layout == Layout::Single { self.selected_image } else { i };
+
+                let preview = &self.previews[image_index];
+
+                match &preview.state {
+                    PreviewState::Loaded(texture) => {
+                        ui.put(*pane, Image::new(texture).fit_to_exact_size(pane.size()).uv(uv));
+                    }
+                    PreviewState::NotLoaded | PreviewState::Loading => {
+                        ui.put(*pane, Spinner::new().size(32.0));
+                    }
+                    PreviewState::Failed(message) => {
+                        ui.painter().text(pane.center(), Align2::CENTER_CENTER, message, FontId::monospace(12.0), Color32::RED);
+                    }
+                }
+
+                if self.layout == Layout::Grid {
+                    let label = format!("{} {}", image_index + 1, preview.description);
+                    ui.painter()
+                        .text(pane.left_top() + vec2(4.0, 4.0), Align2::LEFT_TOP, label, FontId::monospace(12.0), Color32::WHITE);
+                }
 
-            ui.add(Image::new(image_texture).fit_to_exact_size(ui_size).uv(uv_rect(self.flipped)));
+                if self.show_stats
+                    && let Some(stats) = &preview.stats
+                {
+                    paint_stats(ui.painter(), *pane, stats);
+                }
+            }
         });
     }
 }
 
+// Statistics of the whole decoded image, independent of zoom
+struct ImageStats {
+    // Pixel counts per value of the red, green and blue channels
+    histograms: [[u32; 256]; 3],
+    mean_brightness: f32,
+    contrast: f32,
+}
+
+impl ImageStats {
+    // Brightness is Rec. 709 luma, contrast its standard deviation (RMS contrast)
+    fn of(image: &ColorImage) -> Self {
+        let mut histograms = [[0u32; 256]; 3];
+        let mut luma_sum = 0.0f64;
+        let mut luma_square_sum = 0.0f64;
+
+        for pixel in &image.pixels {
+            histograms[0][pixel.r() as usize] += 1;
+            histograms[1][pixel.g() as usize] += 1;
+            histograms[2][pixel.b() as usize] += 1;
+
+            let luma = 0.2126 * pixel.r() as f64 + 0.7152 * pixel.g() as f64 + 0.0722 * pixel.b() as f64;
+            luma_sum += luma;
+            luma_square_sum += luma * luma;
+        }
+
+        let pixel_count = image.pixels.len().max(1) as f64;
+        let mean = luma_sum / pixel_count;
+        let variance = (luma_square_sum / pixel_count - mean * mean).max(0.0);
+
+        Self {
+            histograms,
+            mean_brightness: mean as f32,
+            contrast: variance.sqrt() as f32,
+        }
+    }
+}
+
+fn paint_stats(painter: &Painter, pane: Rect, stats: &ImageStats) {
+    const HISTOGRAM_SIZE: Vec2 = vec2(256.0, 96.0);
+    const TEXT_HEIGHT: f32 = 16.0;
+    const MARGIN: f32 = 8.0;
+
+    let overlay = Rect::from_min_size(
+        pane.left_bottom() + vec2(MARGIN, -MARGIN - HISTOGRAM_SIZE.y - TEXT_HEIGHT - 2.0 * MARGIN),
+        HISTOGRAM_SIZE + vec2(2.0 * MARGIN, TEXT_HEIGHT + 2.0 * MARGIN),
+    );
+    painter.rect_filled(overlay, 4.0, Color32::from_black_alpha(180));
+
+    let histogram_rect = Rect::from_min_size(overlay.min + vec2(MARGIN, MARGIN), HISTOGRAM_SIZE);
+    let max_count = stats.histograms.iter().flatten().copied().max().unwrap_or(0).max(1) as f32;
+    let channel_colors = [Color32::from_rgb(255, 80, 80), Color32::from_rgb(80, 255, 80), Color32::from_rgb(80, 140, 255)];
+
+    for (histogram, color) in stats.histograms.iter().zip(channel_colors) {
+        let points = histogram
+            .iter()
+            .enumerate()
+            .map(|(value, count)| {
+                let x = histogram_rect.min.x + value as f32 / 255.0 * histogram_rect.width();
+                let y = histogram_rect.max.y - *count as f32 / max_count * histogram_rect.height();
+                pos2(x, y)
+            })
+            .collect();
+        painter.add(Shape::line(points, Stroke::new(1.0, color)));
+    }
+
+    let text = format!("mean brightness {:.1}  contrast {:.1}", stats.mean_brightness, stats.contrast);
+    painter.text(
+        pos2(histogram_rect.min.x, histogram_rect.max.y + MARGIN / 2.0),
+        Align2::LEFT_TOP,
+        text,
+        FontId::monospace(12.0),
+        Color32::WHITE,
+    );
+}
+
+const FULL_VIEW: Rect = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+
+// Zooming in further than 1/64 of the image only shows blurry pixels
+const MIN_VIEW_SIZE: f32 = 1.0 / 64.0;
+
+const ZOOM_SPEED: f32 = 0.002;
+
+const PANE_SPACING: f32 = 4.0;
+
+// Keeps the view inside the image, so zooming out near an edge does not reveal empty space
+fn clamp_view(view: Rect) -> Rect {
+    let offset = vec2((-view.min.x).max(0.0) + (1.0 - view.max.x).min(0.0), (-view.min.y).max(0.0) + (1.0 - view.max.y).min(0.0));
+    view.translate(offset)
+}
+
 fn uv_rect(flipped: bool) -> Rect {
     let p1_x = if flipped { 1.0 } else { 0.0 };
     let p2_x = if flipped { 0.0 } else { 1.0 };
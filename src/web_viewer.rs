@@ -0,0 +1,298 @@
+use crate::biver_result::{BiverError, BiverErrorCategory, BiverErrorSeverity, BiverResult};
+use crate::daemon::repository_cache::RepositoryCache;
+use crate::env::Env;
+use crate::known_file_types;
+use crate::repository_data::{RepositoryData, ReviewState, Version};
+use crate::repository_io;
+use crate::repository_io::RepositoryDataResult;
+use crate::repository_operations;
+use crate::repository_operations::{ListVersionsResult, PreviewResult, RestoreToTemporaryFileResult, VersionOrder, VersionQuery};
+use crate::repository_paths::RepositoryPaths;
+use crate::version_id::VersionId;
+use chrono::Local;
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use std::fmt::Write;
+use std::fs;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const DOWNLOAD_WORKER_COUNT: usize = 4;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 960px; color: #222; }
+nav a { margin-right: 0.8em; }
+nav a.current { font-weight: bold; text-decoration: none; color: #222; }
+.version { display: flex; gap: 1em; padding: 1em 0; border-bottom: 1px solid #ddd; }
+.version img { width: 160px; height: 160px; object-fit: contain; background: #f4f4f4; }
+.thumbnail-missing { width: 160px; height: 160px; background: #f4f4f4; }
+.meta { color: #777; font-size: 0.9em; }
+.nickname { font-weight: bold; }
+.badge { font-size: 0.8em; padding: 0.1em 0.4em; border-radius: 0.3em; background: #e8eef8; margin-left: 0.3em; }
+.approved { background: #d8f5d8; }
+.rejected { background: #f8d8d8; }
+.submitted { background: #f8f0c8; }
+.description { white-space: pre-wrap; margin: 0.5em 0; }
+.note { font-size: 0.9em; color: #555; white-space: pre-wrap; }
+";
+
+enum Page {
+    Html(String),
+    Preview(Vec<u8>),
+    // Handed to a download worker, which restores the version
+    PendingDownload(VersionId),
+    // Restored to a temporary file, which is deleted once sent
+    Download { path: PathBuf, file_name: String },
+    Error { status_code: u16, message: &'static str },
+}
+
+// Read-only: the repository may be on a shared drive others commit to, so the command opens it with --read-only and
+// nothing is written to it. Requests are handled one at a time, like the daemon's, except downloads. Restoring and
+// sending a large version takes long, so those go to worker threads, which read the data file themselves.
+pub fn run(env: &Env, repo_paths: &RepositoryPaths, address: &str) -> BiverResult<()> {
+    let server = Server::http(address).map_err(|e| BiverError {
+        error_message: format!("Could not listen on {}: {}", address, e),
+        severity: BiverErrorSeverity::Error,
+        category: BiverErrorCategory::Io,
+    })?;
+
+    println!("Serving {} on http://{}", repo_paths.versioned_file.display(), address);
+
+    let mut repository_cache = RepositoryCache::new(env);
+    let (download_sender, download_receiver) = mpsc::channel::<(Request, VersionId)>();
+    let download_receiver = Mutex::new(download_receiver);
+
+    thread::scope(|scope| {
+        for _ in 0..DOWNLOAD_WORKER_COUNT {
+            scope.spawn(|| {
+                loop {
+                    let Ok((request, version_id)) = download_receiver.lock().expect("Download queue must not be poisoned").recv() else {
+                        return;
+                    };
+                    respond(request, download(env, repo_paths, version_id));
+                }
+            });
+        }
+
+        for request in server.incoming_requests() {
+            let page = match repository_cache.data(repo_paths) {
+                Err(e) => Err(e.into()),
+                Ok(None) => Ok(not_found("Not initialized")),
+                Ok(Some(repo_data)) if *request.method() == Method::Get => handle(repo_paths, repo_data, request.url()),
+                Ok(Some(_)) => Ok(Page::Error {
+                    status_code: 405,
+                    message: "Only GET requests are served",
+                }),
+            };
+
+            if env.verbose {
+                eprintln!("{} {}", request.method(), request.url());
+            }
+
+            match page {
+                Ok(Page::PendingDownload(version_id)) => download_sender.send((request, version_id)).expect("Download workers must be running"),
+                page => respond(request, page),
+            }
+        }
+
+        drop(download_sender);
+    });
+
+    Ok(())
+}
+
+fn handle(repo_paths: &RepositoryPaths, repo_data: &RepositoryData, url: &str) -> BiverResult<Page> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    if path == "/" {
+        let branch = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("branch="))
+            .map(|branch| percent_decode_str(branch).decode_utf8_lossy().to_string());
+
+        return Ok(timeline_page(repo_paths, repo_data, branch.as_deref()));
+    }
+
+    let version = |id: &str| VersionId::from_bs58(id).and_then(|id| repo_data.version(id));
+
+    if let Some(id) = path.strip_prefix("/preview/") {
+        let Some(version) = version(id) else {
+            return Ok(not_found("Version not found"));
+        };
+
+        return match repository_operations::preview(repo_paths, version) {
+            PreviewResult::NoPreviewAvailable => Ok(not_found("No preview available")),
+            PreviewResult::Ok(preview_path) => Ok(Page::Preview(fs::read(preview_path)?)),
+        };
+    }
+
+    if let Some(id) = path.strip_prefix("/download/") {
+        let Some(version) = version(id) else {
+            return Ok(not_found("Version not found"));
+        };
+
+        return Ok(Page::PendingDownload(version.id));
+    }
+
+    Ok(not_found("Page not found"))
+}
+
+// The data file is read again, since the cache belongs to the thread taking requests
+fn download(env: &Env, repo_paths: &RepositoryPaths, version_id: VersionId) -> BiverResult<Page> {
+    let RepositoryDataResult::Initialized(repo_data) = repository_io::read_data(repo_paths)? else {
+        return Ok(not_found("Not initialized"));
+    };
+
+    match repository_operations::restore_to_temporary_file(env, repo_paths, &repo_data, &version_id.bs58())? {
+        RestoreToTemporaryFileResult::InvalidTarget => Ok(not_found("Version not found")),
+        RestoreToTemporaryFileResult::Ok(path) => {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            Ok(Page::Download { path, file_name })
+        }
+    }
+}
+
+fn respond(request: Request, page: BiverResult<Page>) {
+    let header = |name: &str, value: &str| Header::from_bytes(name, value).expect("Header must be valid");
+
+    // A client that disconnected before reading the response is not the server's problem
+    let _ = match page {
+        Ok(Page::Html(html)) => request.respond(Response::from_string(html).with_header(header("Content-Type", "text/html; charset=utf-8"))),
+        Ok(Page::Preview(preview)) => {
+            let content_type = known_file_types::preview_mime_type(&preview);
+            request.respond(Response::from_data(preview).with_header(header("Content-Type", content_type)))
+        }
+        Ok(Page::Download { path, file_name }) => {
+            let sent = match File::open(&path) {
+                Ok(file) => {
+                    // Non-ASCII names go in filename*, which browsers prefer when present
+                    let encoded_file_name = utf8_percent_encode(&file_name, NON_ALPHANUMERIC).to_string();
+                    let ascii_file_name: String = file_name.chars().map(|c| if c.is_ascii_graphic() && c != '"' { c } else { '_' }).collect();
+                    let content_disposition = format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", ascii_file_name, encoded_file_name);
+
+                    request.respond(
+                        Response::from_file(file)
+                            .with_header(header("Content-Type", "application/octet-stream"))
+                            .with_header(header("Content-Disposition", &content_disposition)),
+                    )
+                }
+                Err(e) => request.respond(Response::from_string(format!("io failure: {}", e)).with_status_code(500)),
+            };
+
+            let _ = repository_operations::remove_restored_temporary_file(&path);
+            sent
+        }
+        Ok(Page::PendingDownload(_)) => unreachable!("Downloads are handed to a worker before responding"),
+        Ok(Page::Error { status_code, message }) => request.respond(Response::from_string(message).with_status_code(status_code)),
+        Err(e) => request.respond(Response::from_string(e.error_message).with_status_code(500)),
+    };
+}
+
+fn timeline_page(repo_paths: &RepositoryPaths, repo_data: &RepositoryData, branch: Option<&str>) -> Page {
+    let query = VersionQuery {
+        branch,
+        since: None,
+        until: None,
+        author: None,
+        attributes: &[],
+        order: VersionOrder::NewestFirst,
+        limit: None,
+    };
+
+    let versions = match repository_operations::list_versions(repo_data, &query) {
+        ListVersionsResult::BranchDoesNotExist => {
+            return not_found("Branch does not exist");
+        }
+        ListVersionsResult::Ok(versions) => versions,
+    };
+
+    let title = escape(&repo_paths.versioned_file_name().unwrap_or_default());
+
+    let mut branches: Vec<&str> = repo_data.branches.keys().map(String::as_str).collect();
+    branches.sort();
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{} - biver</title><style>{}</style></head><body><h1>{}</h1><nav>",
+        title, STYLE, title
+    );
+
+    let _ = write!(html, "<a href=\"/\"{}>All branches</a>", if branch.is_none() { " class=\"current\"" } else { "" });
+    for b in branches {
+        let _ = write!(
+            html,
+            "<a href=\"/?branch={}\"{}>{}</a>",
+            utf8_percent_encode(b, NON_ALPHANUMERIC),
+            if Some(b) == branch { " class=\"current\"" } else { "" },
+            escape(b)
+        );
+    }
+    html.push_str("</nav>");
+
+    for version in versions {
+        write_version(&mut html, repo_data, version);
+    }
+
+    html.push_str("</body></html>");
+
+    Page::Html(html)
+}
+
+fn write_version(html: &mut String, repo_data: &RepositoryData, version: &Version) {
+    let id = version.id.bs58();
+    let creation_time = version.creation_time.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+
+    html.push_str("<div class=\"version\">");
+
+    if version.preview_blob_file_name.is_some() {
+        let _ = write!(html, "<a href=\"/preview/{id}\"><img src=\"/preview/{id}\" loading=\"lazy\" alt=\"\"></a>", id = id);
+    } else {
+        html.push_str("<div class=\"thumbnail-missing\"></div>");
+    }
+
+    let _ = write!(html, "<div><span class=\"nickname\">{}</span>", escape(&version.nickname));
+
+    let mut branches: Vec<&str> = repo_data.branches.iter().filter(|(_, leaf)| **leaf == version.id).map(|(b, _)| b.as_str()).collect();
+    branches.sort();
+    for branch in branches {
+        let _ = write!(html, "<span class=\"badge\">{}</span>", escape(branch));
+    }
+    if let Some(review) = version.review() {
+        let class = match review.state {
+            ReviewState::Submitted => "submitted",
+            ReviewState::Approved => "approved",
+            ReviewState::Rejected => "rejected",
+        };
+        let title = format!("{} {}", review.reviewer, review.note);
+        let _ = write!(html, "<span class=\"badge {}\" title=\"{}\">{}</span>", class, escape(title.trim()), review.state);
+    }
+    for (key, value) in &version.attributes {
+        let _ = write!(html, "<span class=\"badge\">{}={}</span>", escape(key), escape(value));
+    }
+
+    let _ = write!(html, "<div class=\"meta\">{} &middot; {}", creation_time, &id[..id.len().min(8)]);
+    if !version.author.is_empty() {
+        let _ = write!(html, " &middot; {}", escape(&version.author));
+    }
+    let _ = write!(html, " &middot; <a href=\"/download/{}\">Download</a></div>", id);
+
+    if !version.description.is_empty() {
+        let _ = write!(html, "<div class=\"description\">{}</div>", escape(&version.description));
+    }
+    for note in &version.notes {
+        let _ = write!(html, "<div class=\"note\">{}: {}</div>", escape(&note.author), escape(&note.text));
+    }
+
+    html.push_str("</div></div>");
+}
+
+fn not_found(message: &'static str) -> Page {
+    Page::Error { status_code: 404, message }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
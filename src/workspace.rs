@@ -0,0 +1,120 @@
+use crate::biver_result::{BiverError, BiverErrorSeverity, BiverResult, error};
+use crate::formatting;
+use crate::repository_paths::RepositoryPaths;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+pub const WORKSPACE_FILE_NAME: &str = "biver.workspace.toml";
+
+// Lists versioned files relative to the manifest, e.g. files = ["cover.psd", "chapters/one.kra"]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    files: Vec<PathBuf>,
+}
+
+pub struct Workspace {
+    pub root: PathBuf,
+    pub versioned_files: Vec<PathBuf>,
+}
+
+pub enum FileOutcome {
+    Ok,
+    Warning,
+    Error,
+}
+
+// Like a git repository, a workspace is found by looking in the current directory and then in its parents
+pub fn find_current() -> BiverResult<Workspace> {
+    let current_dir = env::current_dir()?;
+
+    let Some(manifest_path) = current_dir.ancestors().map(|dir| dir.join(WORKSPACE_FILE_NAME)).find(|path| path.is_file()) else {
+        return error(format!("No {} found in the current directory or its parents", WORKSPACE_FILE_NAME));
+    };
+
+    read(&manifest_path)
+}
+
+fn read(manifest_path: &Path) -> BiverResult<Workspace> {
+    let contents = fs::read_to_string(manifest_path)?;
+
+    let manifest: Manifest = match toml::from_str(&contents) {
+        Ok(manifest) => manifest,
+        Err(e) => return error(format!("Invalid {}: {}", manifest_path.display(), e.message())),
+    };
+
+    let root = manifest_path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let versioned_files = manifest.files.iter().map(|file| root.join(file)).collect();
+
+    Ok(Workspace { root, versioned_files })
+}
+
+// Versioned files of the repositories in the directory and those listed by a workspace manifest in it, relative to it
+pub fn discover_versioned_files(dir: &Path) -> BiverResult<Vec<PathBuf>> {
+    let mut versioned_files = Vec::new();
+
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        if !dir_entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        if let Some(repo_paths) = RepositoryPaths::from_repository_dir(Path::new(&dir_entry.file_name())) {
+            versioned_files.push(repo_paths.versioned_file);
+        }
+    }
+
+    let manifest_path = dir.join(WORKSPACE_FILE_NAME);
+    if manifest_path.is_file() {
+        for versioned_file in read(&manifest_path)?.versioned_files {
+            versioned_files.push(versioned_file.strip_prefix(dir).map(Path::to_path_buf).unwrap_or(versioned_file));
+        }
+    }
+
+    versioned_files.sort();
+    versioned_files.dedup();
+
+    Ok(versioned_files)
+}
+
+// Runs the command for every file even if some of them fail, so one broken repository does not hold up the rest
+pub fn run_for_each_file(workspace: &Workspace, mut command: impl FnMut(RepositoryPaths) -> BiverResult<()>) -> BiverResult<()> {
+    let mut outcomes = Vec::new();
+
+    for versioned_file in &workspace.versioned_files {
+        let display_path = versioned_file.strip_prefix(&workspace.root).unwrap_or(versioned_file);
+        formatting::print_workspace_file_header(display_path);
+
+        let outcome = match command(RepositoryPaths::from_versioned_file_path(versioned_file.clone())) {
+            Ok(()) => FileOutcome::Ok,
+            Err(BiverError {
+                error_message,
+                severity: BiverErrorSeverity::Warning,
+                ..
+            }) => {
+                formatting::print_workspace_file_warning(&error_message);
+                FileOutcome::Warning
+            }
+            Err(BiverError {
+                error_message,
+                severity: BiverErrorSeverity::Error,
+                ..
+            }) => {
+                formatting::print_workspace_file_error(&error_message);
+                FileOutcome::Error
+            }
+        };
+
+        outcomes.push(outcome);
+    }
+
+    formatting::print_workspace_summary(&outcomes);
+
+    let error_count = outcomes.iter().filter(|o| matches!(o, FileOutcome::Error)).count();
+    if error_count > 0 {
+        return error(format!("Failed for {} of {} files", error_count, outcomes.len()));
+    }
+
+    Ok(())
+}